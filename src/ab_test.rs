@@ -0,0 +1,280 @@
+//! Prompt A/B testing: runs two prompt variants against a set of inputs and
+//! lets the user grade the answers side by side.
+
+use crate::widgets::{GeminiModel, Settings};
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+/// Replaced with the test input in prompt variants
+const INPUT_VARIABLE: &str = "{{input}}";
+const MAX_GRADE: u8 = 5;
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TestResult {
+    pub output: String,
+    pub error: Option<String>,
+    pub grade: Option<u8>,
+    #[serde(skip)]
+    pub is_running: bool,
+}
+
+type SharedResults = Arc<Mutex<Vec<Vec<TestResult>>>>;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AbWorkspace {
+    variants: [String; 2],
+    inputs: Vec<String>,
+    model: GeminiModel,
+    /// Indexed by input, then variant
+    results: SharedResults,
+}
+
+impl Default for AbWorkspace {
+    fn default() -> Self {
+        Self {
+            variants: [
+                format!("Summarize this in one sentence:\n\n{INPUT_VARIABLE}"),
+                format!("You are an editor. Write a one-line TL;DR of:\n\n{INPUT_VARIABLE}"),
+            ],
+            inputs: vec![String::new()],
+            model: GeminiModel::default(),
+            results: SharedResults::default(),
+        }
+    }
+}
+
+/// Fills in the test input, or appends it if the variant has no `{{input}}`
+fn render_prompt(variant: &str, input: &str) -> String {
+    if variant.contains(INPUT_VARIABLE) {
+        variant.replace(INPUT_VARIABLE, input)
+    } else {
+        format!("{variant}\n\n{input}")
+    }
+}
+
+impl AbWorkspace {
+    fn is_running(&self) -> bool {
+        self.results
+            .lock()
+            .is_ok_and(|results| results.iter().flatten().any(|r| r.is_running))
+    }
+
+    /// Sends every combination of variant and input, previous results are discarded
+    fn run(&mut self, ctx: &egui::Context, settings: &Settings) {
+        let mut model_picker = settings.model_picker.clone();
+        settings
+            .model_profiles
+            .select(&mut model_picker, self.model);
+        model_picker.auto_route = false;
+
+        let Ok(mut results) = self.results.lock() else {
+            return;
+        };
+        *results = vec![vec![TestResult::default(); 2]; self.inputs.len()];
+        log::info!(
+            "running A/B test with {} inputs on {}",
+            self.inputs.len(),
+            self.model
+        );
+
+        for (input_idx, input) in self.inputs.iter().enumerate() {
+            for (variant_idx, variant) in self.variants.iter().enumerate() {
+                results[input_idx][variant_idx].is_running = true;
+                let prompt = render_prompt(variant, input);
+                let settings = settings.clone();
+                let model_picker = model_picker.clone();
+                let shared = self.results.clone();
+                let ctx = ctx.clone();
+                tokio::spawn(async move {
+                    let update = |f: &mut dyn FnMut(&mut TestResult)| {
+                        if let Some(result) = shared
+                            .lock()
+                            .ok()
+                            .as_mut()
+                            .and_then(|r| r.get_mut(input_idx)?.get_mut(variant_idx))
+                        {
+                            f(result);
+                        }
+                        ctx.request_repaint();
+                    };
+                    let outcome = crate::chat::ask_headless(
+                        &settings,
+                        model_picker,
+                        prompt,
+                        Vec::new(),
+                        |text, is_thought| {
+                            if !is_thought {
+                                update(&mut |r| r.output.push_str(text));
+                            }
+                        },
+                    )
+                    .await;
+                    update(&mut |r| {
+                        r.is_running = false;
+                        r.error = outcome.clone().err();
+                    });
+                });
+            }
+        }
+    }
+
+    /// Average grade of each variant over the graded results
+    fn average_grades(&self) -> [Option<f32>; 2] {
+        let Ok(results) = self.results.lock() else {
+            return [None; 2];
+        };
+        std::array::from_fn(|variant| {
+            let grades: Vec<u8> = results
+                .iter()
+                .filter_map(|row| row.get(variant)?.grade)
+                .collect();
+            (!grades.is_empty())
+                .then(|| grades.iter().map(|&g| g as f32).sum::<f32>() / grades.len() as f32)
+        })
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Prompt A/B Testing");
+        ui.label(format!(
+            "Write two prompt variants, using {INPUT_VARIABLE} where the test input goes, \
+            and run them against every input."
+        ));
+        ui.add_space(8.0);
+
+        let is_running = self.is_running();
+        ui.add_enabled_ui(!is_running, |ui| {
+            ui.columns(2, |columns| {
+                for (i, ui) in columns.iter_mut().enumerate() {
+                    ui.strong(if i == 0 { "Variant A" } else { "Variant B" });
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.variants[i])
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(4),
+                    );
+                }
+            });
+
+            ui.add_space(8.0);
+            ui.strong("Test inputs");
+            let mut to_remove = None;
+            for (i, input) in self.inputs.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                        to_remove = Some(i);
+                    }
+                    ui.add(
+                        egui::TextEdit::multiline(input)
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(1)
+                            .hint_text(format!("Input {}", i + 1)),
+                    );
+                });
+            }
+            if let Some(i) = to_remove {
+                self.inputs.remove(i);
+                if let Ok(mut results) = self.results.lock() {
+                    if i < results.len() {
+                        results.remove(i);
+                    }
+                }
+            }
+            if ui.button("➕ Add input").clicked() {
+                self.inputs.push(String::new());
+            }
+
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("ab_test_model")
+                    .selected_text(self.model.to_string())
+                    .show_ui(ui, |ui| {
+                        for model in enum_iterator::all::<GeminiModel>() {
+                            ui.selectable_value(&mut self.model, model, model.to_string());
+                        }
+                    });
+                if ui
+                    .add_enabled(!self.inputs.is_empty(), egui::Button::new("▶ Run all"))
+                    .on_hover_text("Previous results and grades are discarded")
+                    .clicked()
+                {
+                    self.run(ui.ctx(), settings);
+                }
+            });
+        });
+        if is_running {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak("Running…");
+            });
+        }
+
+        ui.separator();
+        self.show_results(ui);
+    }
+
+    fn show_results(&mut self, ui: &mut egui::Ui) {
+        let averages = self.average_grades();
+        let Ok(mut results) = self.results.lock() else {
+            return;
+        };
+        if results.is_empty() {
+            ui.weak("No results yet");
+            return;
+        }
+
+        egui::Grid::new("ab_test_results")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Input");
+                for (name, average) in ["A", "B"].iter().zip(averages) {
+                    match average {
+                        Some(average) => ui.strong(format!("Variant {name} (avg {average:.1})")),
+                        None => ui.strong(format!("Variant {name}")),
+                    };
+                }
+                ui.end_row();
+
+                let column_width = (ui.available_width() / 2.5).max(200.0);
+                for (input, row) in self.inputs.iter().zip(results.iter_mut()) {
+                    ui.add(egui::Label::new(input.as_str()).truncate())
+                        .on_hover_text(input.as_str());
+                    for result in row.iter_mut() {
+                        ui.vertical(|ui| {
+                            ui.set_max_width(column_width);
+                            if let Some(error) = &result.error {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            } else if result.is_running && result.output.is_empty() {
+                                ui.spinner();
+                            } else {
+                                ui.label(&result.output);
+                            }
+                            if !result.is_running {
+                                ui.horizontal(|ui| {
+                                    for grade in 1..=MAX_GRADE {
+                                        let filled = result.grade.is_some_and(|g| g >= grade);
+                                        if ui
+                                            .add(
+                                                egui::Button::new(if filled {
+                                                    "★"
+                                                } else {
+                                                    "☆"
+                                                })
+                                                .frame(false),
+                                            )
+                                            .clicked()
+                                        {
+                                            result.grade =
+                                                (result.grade != Some(grade)).then_some(grade);
+                                        }
+                                    }
+                                });
+                            }
+                        });
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
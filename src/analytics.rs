@@ -0,0 +1,159 @@
+//! Local-only usage reports, exported by the user to a file. Nothing here
+//! touches the network.
+
+use crate::structured::csv_field;
+use anyhow::{Context, Result};
+use std::{collections::BTreeMap, io::Write};
+
+/// One message in the usage report
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UsageRecord {
+    pub chat: String,
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub role: &'static str,
+    pub model: String,
+    pub estimated_tokens: usize,
+    pub response_time_secs: Option<f64>,
+}
+
+/// Average silent reading speed of adults, in words per minute
+const READING_WPM: usize = 238;
+
+/// Rough token count, about 4 characters per token for English text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Estimated reading time of `words`, like "2 min" or "40 s"
+pub fn reading_time(words: usize) -> String {
+    let secs = (words * 60).div_ceil(READING_WPM);
+    if secs < 60 {
+        format!("{secs} s")
+    } else {
+        format!("{} min", secs.div_ceil(60))
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+struct ModelSummary {
+    messages: usize,
+    responses: usize,
+    estimated_tokens: usize,
+    avg_response_time_secs: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+struct UsageReport<'a> {
+    generated_at: chrono::DateTime<chrono::Utc>,
+    models: BTreeMap<&'a str, ModelSummary>,
+    messages: &'a [UsageRecord],
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum UsageReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl std::fmt::Display for UsageReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl UsageReportFormat {
+    pub const ALL: [Self; 2] = [Self::Json, Self::Csv];
+
+    #[inline]
+    pub const fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Json => &["json"],
+            Self::Csv => &["csv"],
+        }
+    }
+}
+
+fn summarize(records: &[UsageRecord]) -> BTreeMap<&str, ModelSummary> {
+    let mut models: BTreeMap<&str, ModelSummary> = BTreeMap::new();
+    let mut total_times: BTreeMap<&str, f64> = BTreeMap::new();
+    for record in records {
+        let summary = models.entry(record.model.as_str()).or_default();
+        summary.messages += 1;
+        summary.estimated_tokens += record.estimated_tokens;
+        if let Some(secs) = record.response_time_secs {
+            summary.responses += 1;
+            *total_times.entry(record.model.as_str()).or_default() += secs;
+        }
+    }
+    for (model, total) in total_times {
+        if let Some(summary) = models.get_mut(model) {
+            summary.avg_response_time_secs = Some(total / summary.responses as f64);
+        }
+    }
+    models
+}
+
+pub async fn export_usage_report(
+    records: Vec<UsageRecord>,
+    format: UsageReportFormat,
+    task: impl std::future::Future<Output = Option<rfd::FileHandle>>,
+) -> Result<egui_notify::Toast> {
+    let Some(file) = task.await else {
+        log::info!("usage report export cancelled");
+        return Ok(egui_notify::Toast::info("Export cancelled"));
+    };
+    let _writing = crate::shutdown::begin_write();
+    log::info!(
+        "exporting usage report with {} records to {file:?} (format: {format:?})...",
+        records.len()
+    );
+
+    let f = std::fs::File::create(file.path())?;
+    let mut f = std::io::BufWriter::new(f);
+
+    match format {
+        UsageReportFormat::Json => {
+            let report = UsageReport {
+                generated_at: chrono::Utc::now(),
+                models: summarize(&records),
+                messages: &records,
+            };
+            serde_json::to_writer_pretty(&mut f, &report)?;
+        }
+        UsageReportFormat::Csv => {
+            writeln!(
+                f,
+                "chat,time,role,model,estimated_tokens,response_time_secs"
+            )?;
+            for record in &records {
+                writeln!(
+                    f,
+                    "{},{},{},{},{},{}",
+                    csv_field(&record.chat),
+                    record.time.to_rfc3339(),
+                    record.role,
+                    record.model,
+                    record.estimated_tokens,
+                    record
+                        .response_time_secs
+                        .map(|secs| format!("{secs:.2}"))
+                        .unwrap_or_default()
+                )?;
+            }
+        }
+    }
+
+    f.flush().context("failed to flush writer")?;
+
+    log::info!("usage report export complete");
+    Ok(egui_notify::Toast::success(format!(
+        "Exported usage of {} messages to {}",
+        records.len(),
+        file.file_name(),
+    )))
+}
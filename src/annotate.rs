@@ -0,0 +1,422 @@
+//! A small annotation editor for image attachments: arrows, boxes, freehand
+//! strokes and cropping, to point the model at the part of the image the
+//! question is about.
+
+use anyhow::{anyhow, Result};
+use eframe::egui::{self, pos2, vec2, Color32, Pos2, Rect, Sense, Stroke, Vec2};
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Longest side of the image shown in the editor
+const PREVIEW_SIZE: u32 = 1024;
+/// Stroke width as a fraction of the longest side of the image
+const STROKE_FRACTION: f32 = 1.0 / 200.0;
+const MIN_STROKE: f32 = 3.0;
+const COLORS: [Color32; 5] = [
+    Color32::from_rgb(235, 45, 45),
+    Color32::from_rgb(255, 200, 0),
+    Color32::from_rgb(40, 200, 80),
+    Color32::from_rgb(30, 130, 255),
+    Color32::WHITE,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Arrow,
+    Box,
+    Freehand,
+    Crop,
+}
+
+impl Tool {
+    const ALL: [Self; 4] = [Self::Arrow, Self::Box, Self::Freehand, Self::Crop];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Arrow => "↗ Arrow",
+            Self::Box => "⬜ Box",
+            Self::Freehand => "✏ Freehand",
+            Self::Crop => "✂ Crop",
+        }
+    }
+}
+
+/// An edit, with points as fractions of the image size
+#[derive(Clone)]
+enum Edit {
+    Arrow(Pos2, Pos2, Color32),
+    Box(Rect, Color32),
+    Freehand(Vec<Pos2>, Color32),
+    /// Only the last crop counts
+    Crop(Rect),
+}
+
+impl Edit {
+    fn from_drag(tool: Tool, points: &[Pos2], color: Color32) -> Option<Self> {
+        let (&start, &end) = (points.first()?, points.last()?);
+        let rect = Rect::from_two_pos(start, end);
+        match tool {
+            Tool::Arrow if start.distance(end) > 0.01 => Some(Self::Arrow(start, end, color)),
+            Tool::Box if rect.area() > 0.0001 => Some(Self::Box(rect, color)),
+            Tool::Freehand if points.len() > 1 => Some(Self::Freehand(points.to_vec(), color)),
+            Tool::Crop if rect.area() > 0.0001 => Some(Self::Crop(rect)),
+            _ => None,
+        }
+    }
+
+    /// Line segments to draw, in the space `to_space` maps the fractions into
+    fn segments(&self, to_space: impl Fn(Pos2) -> Pos2, width: f32) -> Vec<[Pos2; 2]> {
+        match self {
+            Self::Arrow(from, to, _) => {
+                let (from, to) = (to_space(*from), to_space(*to));
+                let back = (from - to).normalized() * (width * 5.0).min(from.distance(to) / 2.0);
+                let wing = |angle: f32| to + egui::emath::Rot2::from_angle(angle) * back;
+                vec![[from, to], [to, wing(0.5)], [to, wing(-0.5)]]
+            }
+            Self::Box(rect, _) => {
+                let (min, max) = (to_space(rect.min), to_space(rect.max));
+                let corners = [min, pos2(max.x, min.y), max, pos2(min.x, max.y)];
+                (0..4).map(|i| [corners[i], corners[(i + 1) % 4]]).collect()
+            }
+            Self::Freehand(points, _) => points
+                .windows(2)
+                .map(|pair| [to_space(pair[0]), to_space(pair[1])])
+                .collect(),
+            Self::Crop(_) => Vec::new(),
+        }
+    }
+
+    fn color(&self) -> Color32 {
+        match self {
+            Self::Arrow(.., color) | Self::Box(_, color) | Self::Freehand(_, color) => *color,
+            Self::Crop(_) => Color32::TRANSPARENT,
+        }
+    }
+}
+
+fn crop_of(edits: &[Edit]) -> Option<Rect> {
+    edits.iter().rev().find_map(|edit| match edit {
+        Edit::Crop(rect) => Some(*rect),
+        _ => None,
+    })
+}
+
+fn stroke_width(size: Vec2) -> f32 {
+    (size.max_elem() * STROKE_FRACTION).max(MIN_STROKE)
+}
+
+/// Draws a line with round caps by stamping discs along it
+fn draw_line(img: &mut RgbaImage, [from, to]: [Pos2; 2], width: f32, color: Color32) {
+    let radius = width / 2.0;
+    let pixel = Rgba([color.r(), color.g(), color.b(), 255]);
+    let steps = (from.distance(to) / (radius / 2.0).max(0.5)).ceil() as usize;
+    for step in 0..=steps {
+        let center = from.lerp(to, step as f32 / steps.max(1) as f32);
+        let min_x = (center.x - radius).floor().max(0.0) as u32;
+        let min_y = (center.y - radius).floor().max(0.0) as u32;
+        let max_x = ((center.x + radius).ceil() as u32).min(img.width());
+        let max_y = ((center.y + radius).ceil() as u32).min(img.height());
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                if pos2(x as f32 + 0.5, y as f32 + 0.5).distance(center) <= radius {
+                    img.put_pixel(x, y, pixel);
+                }
+            }
+        }
+    }
+}
+
+/// Saves an annotated copy of the image to the attachment store
+fn save_annotated(path: &Path, edits: &[Edit]) -> Result<PathBuf> {
+    let mut img = image::open(path)?.into_rgba8();
+    let size = vec2(img.width() as f32, img.height() as f32);
+    let width = stroke_width(size);
+    let to_pixels = |p: Pos2| (p.to_vec2() * size).to_pos2();
+    for edit in edits {
+        for segment in edit.segments(to_pixels, width) {
+            draw_line(&mut img, segment, width, edit.color());
+        }
+    }
+    if let Some(crop) = crop_of(edits) {
+        let (min, max) = (to_pixels(crop.min), to_pixels(crop.max));
+        let (x, y) = (min.x as u32, min.y as u32);
+        let w = ((max.x - min.x) as u32).clamp(1, img.width() - x);
+        let h = ((max.y - min.y) as u32).clamp(1, img.height() - y);
+        img = image::imageops::crop_imm(&img, x, y, w, h).to_image();
+    }
+
+    let dir = crate::file_handler::attachments_dir();
+    std::fs::create_dir_all(&dir)?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let output_path = dir.join(format!(
+        "{stem}-annotated-{}.png",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    img.save_with_format(&output_path, ImageFormat::Png)?;
+    log::info!(
+        "annotated {} with {} edits: `{}`",
+        path.display(),
+        edits.len(),
+        output_path.display()
+    );
+    Ok(output_path)
+}
+
+enum AnnotateStatus {
+    Editing,
+    Saving,
+    Done(PathBuf),
+    Failed(String),
+}
+
+struct AnnotateState {
+    source: PathBuf,
+    /// Preview texture and the size of the full image, `None` while it's being loaded
+    texture: Option<Result<(egui::TextureHandle, Vec2), String>>,
+    edits: Vec<Edit>,
+    tool: Tool,
+    color: Color32,
+    /// Points of the stroke being dragged, as fractions of the image size
+    drag: Vec<Pos2>,
+    status: AnnotateStatus,
+}
+
+/// The annotation editor, only one image can be edited at a time
+static ANNOTATE: Mutex<Option<AnnotateState>> = Mutex::new(None);
+
+/// Opens the annotation editor for an image attachment
+pub fn open_annotate_dialog(ctx: &egui::Context, path: &Path) {
+    let Ok(mut annotate) = ANNOTATE.lock() else {
+        return;
+    };
+    *annotate = Some(AnnotateState {
+        source: path.to_path_buf(),
+        texture: None,
+        edits: Vec::new(),
+        tool: Tool::Arrow,
+        color: COLORS[0],
+        drag: Vec::new(),
+        status: AnnotateStatus::Editing,
+    });
+
+    let ctx = ctx.clone();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let texture = image::open(&path)
+            .map_err(|e| anyhow!(e))
+            .map(|img| {
+                let size = vec2(img.width() as f32, img.height() as f32);
+                let img = img.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE).into_rgba8();
+                let texture = ctx.load_texture(
+                    format!("annotate://{}", path.display()),
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [img.width() as usize, img.height() as usize],
+                        img.as_raw(),
+                    ),
+                    egui::TextureOptions::LINEAR,
+                );
+                (texture, size)
+            })
+            .map_err(|e| e.to_string());
+        if let Ok(mut annotate) = ANNOTATE.lock() {
+            if let Some(state) = annotate.as_mut().filter(|s| s.source == path) {
+                state.texture = Some(texture);
+            }
+        }
+        ctx.request_repaint();
+    });
+}
+
+fn show_canvas(
+    ui: &mut egui::Ui,
+    state: &mut AnnotateState,
+    texture: &egui::TextureHandle,
+    size: Vec2,
+) {
+    let max = vec2(640.0, 480.0);
+    let preview = texture.size_vec2();
+    let scale = (max.x / preview.x).min(max.y / preview.y).min(1.0);
+    let (rect, response) = ui.allocate_exact_size(preview * scale, Sense::drag());
+    let painter = ui.painter_at(rect);
+    painter.image(
+        texture.id(),
+        rect,
+        Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+        Color32::WHITE,
+    );
+
+    let to_fraction = |pos: Pos2| {
+        let p = (pos - rect.min) / rect.size();
+        pos2(p.x.clamp(0.0, 1.0), p.y.clamp(0.0, 1.0))
+    };
+    let to_screen = |p: Pos2| rect.min + p.to_vec2() * rect.size();
+    if let Some(pos) = response.interact_pointer_pos().map(to_fraction) {
+        if response.drag_started() {
+            state.drag = vec![pos];
+        } else if !state.drag.is_empty() {
+            if state.tool == Tool::Freehand {
+                if state.drag.last() != Some(&pos) {
+                    state.drag.push(pos);
+                }
+            } else {
+                state.drag.truncate(1);
+                state.drag.push(pos);
+            }
+        }
+    }
+    let dragged = Edit::from_drag(state.tool, &state.drag, state.color);
+    if response.drag_stopped() {
+        state.drag.clear();
+        state.edits.extend(dragged.clone());
+    }
+
+    // strokes keep their size relative to the image, like in the saved copy
+    let width = stroke_width(size) * rect.width() / size.x;
+    let mut edits: Vec<&Edit> = state.edits.iter().collect();
+    edits.extend(dragged.as_ref().filter(|_| !state.drag.is_empty()));
+    for edit in &edits {
+        for segment in edit.segments(to_screen, width) {
+            painter.line_segment(segment, Stroke::new(width, edit.color()));
+        }
+    }
+    let crop = match dragged.as_ref().filter(|_| !state.drag.is_empty()) {
+        Some(Edit::Crop(rect)) => Some(*rect),
+        _ => crop_of(&state.edits),
+    };
+    if let Some(crop) = crop {
+        let crop = Rect::from_min_max(to_screen(crop.min), to_screen(crop.max));
+        let shade = Color32::from_black_alpha(160);
+        for outside in [
+            Rect::from_min_max(rect.min, pos2(rect.max.x, crop.min.y)),
+            Rect::from_min_max(pos2(rect.min.x, crop.max.y), rect.max),
+            Rect::from_min_max(pos2(rect.min.x, crop.min.y), pos2(crop.min.x, crop.max.y)),
+            Rect::from_min_max(pos2(crop.max.x, crop.min.y), pos2(rect.max.x, crop.max.y)),
+        ] {
+            painter.rect_filled(outside, 0.0, shade);
+        }
+        painter.rect_stroke(
+            crop,
+            0.0,
+            Stroke::new(1.0, Color32::WHITE),
+            egui::StrokeKind::Outside,
+        );
+    }
+}
+
+/// Shows the annotation editor if it's open for `file_path`, and swaps the
+/// attachment for the annotated copy once it's saved
+pub fn show_annotate_dialog(ctx: &egui::Context, file_path: &mut PathBuf) {
+    let Ok(mut annotate) = ANNOTATE.lock() else {
+        return;
+    };
+    let Some(state) = annotate.as_mut().filter(|s| s.source == *file_path) else {
+        return;
+    };
+    if let AnnotateStatus::Done(annotated) = &state.status {
+        *file_path = annotated.clone();
+        *annotate = None;
+        return;
+    }
+
+    let mut open = true;
+    let mut start_save = false;
+    egui::Window::new("Annotate image")
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for tool in Tool::ALL {
+                    ui.selectable_value(&mut state.tool, tool, tool.label());
+                }
+                ui.separator();
+                for color in COLORS {
+                    let (rect, response) = ui.allocate_exact_size(vec2(18.0, 18.0), Sense::click());
+                    ui.painter().circle_filled(rect.center(), 7.0, color);
+                    if state.color == color {
+                        ui.painter().circle_stroke(
+                            rect.center(),
+                            9.0,
+                            ui.visuals().selection.stroke,
+                        );
+                    }
+                    if response.clicked() {
+                        state.color = color;
+                    }
+                }
+            });
+            match &state.texture {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Loading image…");
+                    });
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                Some(Ok((texture, size))) => {
+                    let (texture, size) = (texture.clone(), *size);
+                    show_canvas(ui, state, &texture, size);
+                }
+            }
+
+            match &state.status {
+                AnnotateStatus::Saving => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Saving…");
+                    });
+                }
+                AnnotateStatus::Failed(e) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                _ => (),
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!state.edits.is_empty(), egui::Button::new("↩ Undo"))
+                    .clicked()
+                {
+                    state.edits.pop();
+                }
+                let can_save =
+                    !state.edits.is_empty() && !matches!(state.status, AnnotateStatus::Saving);
+                if ui
+                    .add_enabled(can_save, egui::Button::new("✔ Apply"))
+                    .on_hover_text(
+                        "The annotated copy replaces the attachment, the original is kept",
+                    )
+                    .clicked()
+                {
+                    start_save = true;
+                }
+            });
+        });
+
+    if !open {
+        *annotate = None;
+        return;
+    }
+    if start_save {
+        state.status = AnnotateStatus::Saving;
+        let (source, edits) = (state.source.clone(), state.edits.clone());
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let status = match save_annotated(&source, &edits) {
+                Ok(annotated) => AnnotateStatus::Done(annotated),
+                Err(e) => AnnotateStatus::Failed(e.to_string()),
+            };
+            if let Ok(mut annotate) = ANNOTATE.lock() {
+                if let Some(state) = annotate.as_mut().filter(|s| s.source == source) {
+                    state.status = status;
+                }
+            }
+            ctx.request_repaint();
+        });
+    }
+}
@@ -0,0 +1,158 @@
+//! Artifacts: a long document or code file from a reply, opened in a side
+//! panel for editing and for asking the model for changes to just that file.
+
+use eframe::egui;
+
+/// Replies with fewer lines stay in the chat
+const MIN_ARTIFACT_LINES: usize = 30;
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Artifact {
+    /// Language of the code block, `md` for documents
+    pub language: String,
+    pub content: String,
+    /// Change request being typed
+    #[serde(skip)]
+    instruction: String,
+    /// Waiting for the model to reply with an updated version
+    #[serde(skip)]
+    pub pending: bool,
+}
+
+pub enum ArtifactAction {
+    None,
+    Close,
+    /// Send this prompt to the chat
+    RequestChanges(String),
+}
+
+/// Returns the language and the code block (or the whole document) if a reply
+/// is mostly one long file
+pub fn detect(content: &str) -> Option<(String, String)> {
+    let mut blocks = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let Some(language) = line.trim_start().strip_prefix("```") else {
+            continue;
+        };
+        let body: Vec<&str> = lines
+            .by_ref()
+            .take_while(|l| !l.trim_start().starts_with("```"))
+            .collect();
+        blocks.push((language.trim().to_owned(), body.join("\n")));
+    }
+
+    match blocks.as_slice() {
+        [] if content.lines().count() >= MIN_ARTIFACT_LINES => {
+            Some(("md".to_owned(), content.to_owned()))
+        }
+        [(language, body)]
+            if body.lines().count() >= MIN_ARTIFACT_LINES
+                && body.len() * 10 >= content.len() * 6 =>
+        {
+            Some((language.clone(), body.clone()))
+        }
+        _ => None,
+    }
+}
+
+impl Artifact {
+    pub fn new(language: String, content: String) -> Self {
+        Self {
+            language,
+            content,
+            ..Default::default()
+        }
+    }
+
+    /// Takes the updated version from the reply to a change request
+    pub fn apply_reply(&mut self, reply: &str) {
+        self.pending = false;
+        match detect(reply) {
+            Some((_, content)) => self.content = content,
+            None => log::warn!("the reply doesn't contain an updated artifact"),
+        }
+    }
+
+    fn change_request(&self) -> String {
+        format!(
+            "{}\n\nApply this to the following file and reply with the complete \
+            updated version in a single code block:\n\n```{}\n{}\n```",
+            self.instruction.trim(),
+            self.language,
+            self.content
+        )
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, is_generating: bool) -> ArtifactAction {
+        let mut action = ArtifactAction::None;
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            ui.heading("Artifact");
+            ui.weak(&self.language);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .add(egui::Button::new("❌").frame(false))
+                    .on_hover_text("Close")
+                    .clicked()
+                {
+                    action = ArtifactAction::Close;
+                }
+                if ui.button("🗐").on_hover_text("Copy").clicked() {
+                    ui.ctx().copy_text(self.content.clone());
+                }
+            });
+        });
+
+        ui.horizontal(|ui| {
+            let can_send = !is_generating && !self.instruction.trim().is_empty();
+            let send = ui
+                .add_enabled(can_send, egui::Button::new("Apply"))
+                .on_hover_text("Ask the model to change the artifact");
+            let edit = ui.add_sized(
+                [ui.available_width(), 0.0],
+                egui::TextEdit::singleline(&mut self.instruction)
+                    .hint_text("Ask for changes to this file…"),
+            );
+            let entered = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if send.clicked() || (entered && can_send) {
+                action = ArtifactAction::RequestChanges(self.change_request());
+                self.instruction.clear();
+                self.pending = true;
+            }
+        });
+        if self.pending {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak("Waiting for the updated version…");
+            });
+        }
+        ui.separator();
+
+        let theme = egui_extras::syntax_highlighting::CodeTheme::from_memory(ui.ctx(), ui.style());
+        let language = self.language.clone();
+        let mut layouter = |ui: &egui::Ui, code: &str, wrap_width: f32| {
+            let mut layout_job = egui_extras::syntax_highlighting::highlight(
+                ui.ctx(),
+                ui.style(),
+                &theme,
+                code,
+                &language,
+            );
+            layout_job.wrap.max_width = wrap_width;
+            ui.fonts(|f| f.layout_job(layout_job))
+        };
+        egui::ScrollArea::both().auto_shrink(false).show(ui, |ui| {
+            ui.add_enabled(
+                !self.pending,
+                egui::TextEdit::multiline(&mut self.content)
+                    .code_editor()
+                    .desired_width(f32::INFINITY)
+                    .layouter(&mut layouter),
+            );
+        });
+
+        action
+    }
+}
@@ -0,0 +1,105 @@
+//! Inline playback of audio attachments. Only one file plays at a time.
+
+use eframe::egui;
+use rodio::{OutputStream, Sink, Source};
+use std::{
+    cell::RefCell,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+struct Player {
+    path: PathBuf,
+    // dropping the stream stops the playback
+    _stream: OutputStream,
+    sink: Sink,
+    duration: Option<Duration>,
+}
+
+impl Player {
+    fn start(path: &Path) -> anyhow::Result<Self> {
+        let (stream, handle) = OutputStream::try_default()?;
+        let sink = Sink::try_new(&handle)?;
+        let source = rodio::Decoder::new(BufReader::new(std::fs::File::open(path)?))?;
+        let duration = source.total_duration();
+        sink.append(source);
+        Ok(Self {
+            path: path.to_path_buf(),
+            _stream: stream,
+            sink,
+            duration,
+        })
+    }
+
+    #[inline]
+    fn is_playing(&self) -> bool {
+        !self.sink.is_paused() && !self.sink.empty()
+    }
+}
+
+thread_local! {
+    // the output stream can't leave the UI thread
+    static PLAYER: RefCell<Option<Player>> = const { RefCell::new(None) };
+}
+
+fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Play/pause button, seek bar and position of an audio file
+pub fn show_player(ui: &mut egui::Ui, path: &Path) {
+    PLAYER.with_borrow_mut(|player| {
+        let is_current = player.as_ref().is_some_and(|p| p.path == path);
+        let is_playing = is_current && player.as_ref().is_some_and(Player::is_playing);
+
+        ui.horizontal(|ui| {
+            let button = ui
+                .button(if is_playing { "⏸" } else { "▶" })
+                .on_hover_text(if is_playing { "Pause" } else { "Play" });
+            if button.clicked() {
+                if let Some(p) = player.as_ref().filter(|p| is_current && !p.sink.empty()) {
+                    if is_playing {
+                        p.sink.pause();
+                    } else {
+                        p.sink.play();
+                    }
+                } else {
+                    match Player::start(path) {
+                        Ok(p) => *player = Some(p),
+                        Err(e) => log::error!("failed to play {}: {e}", path.display()),
+                    }
+                }
+            }
+
+            let current = player.as_ref().filter(|p| p.path == path);
+            let position = current.map(|p| p.sink.get_pos()).unwrap_or_default();
+            let duration = current.and_then(|p| p.duration);
+            ui.weak(format!(
+                "{} / {}",
+                format_time(position),
+                duration.map_or("--:--".to_owned(), format_time)
+            ));
+        });
+
+        let Some(current) = player.as_ref().filter(|p| p.path == path) else {
+            return;
+        };
+        if let Some(duration) = current.duration {
+            let mut position = current.sink.get_pos().as_secs_f32();
+            ui.spacing_mut().slider_width = ui.available_width();
+            let seek = ui.add(
+                egui::Slider::new(&mut position, 0.0..=duration.as_secs_f32()).show_value(false),
+            );
+            if seek.changed() {
+                if let Err(e) = current.sink.try_seek(Duration::from_secs_f32(position)) {
+                    log::error!("failed to seek: {e}");
+                }
+            }
+        }
+        if current.is_playing() {
+            ui.ctx().request_repaint_after(Duration::from_millis(250));
+        }
+    });
+}
@@ -0,0 +1,101 @@
+//! Registering the app to launch on login, per platform.
+
+use anyhow::{Context, Result};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::path::PathBuf;
+
+const APP_ID: &str = "gemini-gui";
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn current_exe() -> Result<String> {
+    Ok(std::env::current_exe()
+        .context("failed to locate the current executable")?
+        .display()
+        .to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn entry_path() -> Result<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .context("could not determine the config directory")?;
+    Ok(config.join("autostart").join(format!("{APP_ID}.desktop")))
+}
+
+#[cfg(target_os = "linux")]
+fn entry_contents() -> Result<String> {
+    Ok(format!(
+        "[Desktop Entry]\nType=Application\nName={}\nExec=\"{}\"\nX-GNOME-Autostart-enabled=true\n",
+        crate::TITLE,
+        current_exe()?
+    ))
+}
+
+#[cfg(target_os = "macos")]
+fn entry_path() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("could not determine the home directory")?;
+    Ok(PathBuf::from(home)
+        .join("Library/LaunchAgents")
+        .join(format!("dev.lavashik.{APP_ID}.plist")))
+}
+
+#[cfg(target_os = "macos")]
+fn entry_contents() -> Result<String> {
+    Ok(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>dev.lavashik.{APP_ID}</string>
+    <key>ProgramArguments</key>
+    <array><string>{}</string></array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        current_exe()?
+    ))
+}
+
+/// Adds or removes the app from the list of programs started on login.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    let path = entry_path()?;
+    if enabled {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, entry_contents()?)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+        log::info!("registered autostart entry at `{}`", path.display());
+    } else if path.exists() {
+        std::fs::remove_file(&path)
+            .with_context(|| format!("failed to remove `{}`", path.display()))?;
+        log::info!("removed autostart entry `{}`", path.display());
+    }
+    Ok(())
+}
+
+/// Adds or removes the app from the list of programs started on login.
+#[cfg(target_os = "windows")]
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+    let mut cmd = std::process::Command::new("reg");
+    if enabled {
+        cmd.args(["add", RUN_KEY, "/v", APP_ID, "/t", "REG_SZ", "/f", "/d"])
+            .arg(format!("\"{}\"", current_exe()?));
+    } else {
+        cmd.args(["delete", RUN_KEY, "/v", APP_ID, "/f"]);
+    }
+    let status = cmd.status().context("failed to run `reg`")?;
+    anyhow::ensure!(status.success(), "`reg` exited with {status}");
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn set_enabled(_enabled: bool) -> Result<()> {
+    anyhow::bail!("autostart is not supported on this platform")
+}
@@ -0,0 +1,171 @@
+//! Custom avatars for the user and for each model, shown in message headers
+//! and the sidebar instead of the default 👤 and ✨.
+
+use crate::widgets::GeminiModel;
+use eframe::egui::{self, vec2};
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+const USER_EMOJI: &str = "👤";
+const MODEL_EMOJI: &str = "✨";
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Avatar {
+    Emoji(String),
+    Image(PathBuf),
+}
+
+impl Avatar {
+    fn emoji(emoji: &str) -> Self {
+        Self::Emoji(emoji.to_owned())
+    }
+
+    /// Shows the avatar at the height of a line of text. Images that are
+    /// loading or failed to load show `fallback` instead.
+    pub fn show(&self, ui: &mut egui::Ui, fallback: &str) -> egui::Response {
+        match self {
+            Self::Emoji(emoji) if !emoji.trim().is_empty() => ui.label(emoji),
+            Self::Emoji(_) => ui.label(fallback),
+            Self::Image(path) => match crate::file_handler::thumbnail(ui.ctx(), path) {
+                Some(Ok(texture)) => {
+                    let size = ui.text_style_height(&egui::TextStyle::Body);
+                    ui.add(
+                        egui::Image::new(&texture)
+                            .fit_to_exact_size(vec2(size, size))
+                            .corner_radius(size / 2.0),
+                    )
+                    .on_hover_text(path.display().to_string())
+                }
+                _ => ui.label(fallback),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    User,
+    Model(GeminiModel),
+}
+
+/// Image picked for an avatar, applied to the settings on the next frame
+static PICKED_IMAGE: Mutex<Option<(Target, PathBuf)>> = Mutex::new(None);
+
+fn pick_image(target: Target) {
+    let task = rfd::AsyncFileDialog::new()
+        .add_filter("Image", &["png", "jpg", "jpeg", "webp", "gif", "bmp"])
+        .pick_file();
+    tokio::spawn(async move {
+        let Some(file) = task.await else {
+            return;
+        };
+        if let Ok(mut picked) = PICKED_IMAGE.lock() {
+            *picked = Some((target, file.path().to_path_buf()));
+        }
+    });
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Avatars {
+    /// `None` for the default 👤
+    pub user: Option<Avatar>,
+    /// Keyed by model name, models without an entry use ✨
+    pub models: BTreeMap<String, Avatar>,
+}
+
+impl Avatars {
+    pub fn show_user(&self, ui: &mut egui::Ui) -> egui::Response {
+        match &self.user {
+            Some(avatar) => avatar.show(ui, USER_EMOJI),
+            None => ui.label(USER_EMOJI),
+        }
+    }
+
+    pub fn show_model(&self, ui: &mut egui::Ui, model: GeminiModel) -> egui::Response {
+        match self.models.get(&model.to_string()) {
+            Some(avatar) => avatar.show(ui, MODEL_EMOJI),
+            None => ui.label(MODEL_EMOJI),
+        }
+    }
+
+    /// Whether the model has an avatar other than the default
+    pub fn has_model_avatar(&self, model: GeminiModel) -> bool {
+        self.models.contains_key(&model.to_string())
+    }
+
+    fn get_mut(&mut self, target: Target) -> Option<&mut Avatar> {
+        match target {
+            Target::User => self.user.as_mut(),
+            Target::Model(model) => self.models.get_mut(&model.to_string()),
+        }
+    }
+
+    fn set(&mut self, target: Target, avatar: Option<Avatar>) {
+        match (target, avatar) {
+            (Target::User, avatar) => self.user = avatar,
+            (Target::Model(model), Some(avatar)) => {
+                self.models.insert(model.to_string(), avatar);
+            }
+            (Target::Model(model), None) => {
+                self.models.remove(&model.to_string());
+            }
+        }
+    }
+
+    fn show_row(&mut self, ui: &mut egui::Ui, target: Target, name: &str, default: &str) {
+        ui.label(name);
+        ui.horizontal(|ui| {
+            match self.get_mut(target) {
+                Some(Avatar::Emoji(emoji)) => {
+                    ui.add(
+                        egui::TextEdit::singleline(emoji)
+                            .desired_width(40.0)
+                            .char_limit(8)
+                            .hint_text(default),
+                    );
+                }
+                Some(avatar @ Avatar::Image(_)) => {
+                    avatar.show(ui, default);
+                }
+                None => {
+                    ui.label(default);
+                }
+            }
+            if ui
+                .small_button("😀")
+                .on_hover_text("Use an emoji")
+                .clicked()
+            {
+                self.set(target, Some(Avatar::emoji(default)));
+            }
+            if ui.small_button("🖼").on_hover_text("Use an image").clicked() {
+                pick_image(target);
+            }
+            if self.get_mut(target).is_some()
+                && ui
+                    .small_button("↺")
+                    .on_hover_text("Reset to default")
+                    .clicked()
+            {
+                self.set(target, None);
+            }
+        });
+        ui.end_row();
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if let Some((target, path)) = PICKED_IMAGE.lock().ok().and_then(|mut p| p.take()) {
+            self.set(target, Some(Avatar::Image(path)));
+        }
+
+        egui::Grid::new("avatars_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                self.show_row(ui, Target::User, "You", USER_EMOJI);
+                for model in enum_iterator::all::<GeminiModel>() {
+                    self.show_row(ui, Target::Model(model), &model.to_string(), MODEL_EMOJI);
+                }
+            });
+    }
+}
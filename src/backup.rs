@@ -0,0 +1,846 @@
+//! Encrypted backups of all chats to a WebDAV server or an S3-compatible
+//! bucket, made on a schedule or by hand, and restored from the Backups tool.
+
+use crate::{chat::Chat, widgets::Settings};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use eframe::egui;
+use openssl::{
+    hash::MessageDigest,
+    pkey::PKey,
+    sign::Signer,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// Start of every backup file, followed by the salt, nonce, tag and ciphertext
+const MAGIC: &[u8] = b"EGBK1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KDF_ITERATIONS: usize = 200_000;
+const FILE_PREFIX: &str = "egeminui-backup-";
+const FILE_EXTENSION: &str = ".egbk";
+const NAME_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+/// A stalled server fails the backup instead of blocking the next ones
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+const READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum BackupTarget {
+    #[default]
+    WebDav,
+    S3,
+}
+
+impl fmt::Display for BackupTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WebDav => "WebDAV",
+            Self::S3 => "S3-compatible",
+        })
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Back up on a schedule
+    pub scheduled: bool,
+    pub interval_hours: u32,
+    /// Older backups are deleted from the remote
+    pub keep: usize,
+    pub target: BackupTarget,
+    /// Folder URL, e.g. `https://cloud.example.com/remote.php/dav/files/me/backups/`
+    pub webdav_url: String,
+    pub webdav_user: String,
+    pub webdav_password: String,
+    /// E.g. `https://s3.eu-central-1.amazonaws.com`, buckets are addressed by path
+    pub s3_endpoint: String,
+    pub s3_region: String,
+    pub s3_bucket: String,
+    pub s3_prefix: String,
+    pub s3_access_key: String,
+    pub s3_secret_key: String,
+    /// Backups are encrypted with a key derived from it
+    pub passphrase: String,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            scheduled: false,
+            interval_hours: 24,
+            keep: 10,
+            target: BackupTarget::default(),
+            webdav_url: String::new(),
+            webdav_user: String::new(),
+            webdav_password: String::new(),
+            s3_endpoint: String::new(),
+            s3_region: "us-east-1".to_owned(),
+            s3_bucket: String::new(),
+            s3_prefix: String::new(),
+            s3_access_key: String::new(),
+            s3_secret_key: String::new(),
+            passphrase: String::new(),
+        }
+    }
+}
+
+impl BackupSettings {
+    /// Labeled secrets, for leaving them out of exported settings
+    pub fn secrets_mut(&mut self) -> [(&'static str, &mut String); 3] {
+        [
+            ("WebDAV password", &mut self.webdav_password),
+            ("S3 secret key", &mut self.s3_secret_key),
+            ("Backup passphrase", &mut self.passphrase),
+        ]
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.passphrase.is_empty()
+            && match self.target {
+                BackupTarget::WebDav => !self.webdav_url.trim().is_empty(),
+                BackupTarget::S3 => {
+                    !self.s3_endpoint.trim().is_empty() && !self.s3_bucket.trim().is_empty()
+                }
+            }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Target")
+            .selected_text(self.target.to_string())
+            .show_ui(ui, |ui| {
+                for target in [BackupTarget::WebDav, BackupTarget::S3] {
+                    ui.selectable_value(&mut self.target, target, target.to_string());
+                }
+            });
+        let field = |ui: &mut egui::Ui, label: &str, value: &mut String, hint: &str, secret| {
+            ui.label(label);
+            ui.add(
+                egui::TextEdit::singleline(value)
+                    .hint_text(hint)
+                    .password(secret),
+            );
+            ui.end_row();
+        };
+        egui::Grid::new("backup_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                match self.target {
+                    BackupTarget::WebDav => {
+                        field(
+                            ui,
+                            "Folder URL",
+                            &mut self.webdav_url,
+                            "https://cloud.example.com/remote.php/dav/files/me/backups/",
+                            false,
+                        );
+                        field(ui, "User", &mut self.webdav_user, "", false);
+                        field(ui, "Password", &mut self.webdav_password, "", true);
+                    }
+                    BackupTarget::S3 => {
+                        field(
+                            ui,
+                            "Endpoint",
+                            &mut self.s3_endpoint,
+                            "https://s3.us-east-1.amazonaws.com",
+                            false,
+                        );
+                        field(ui, "Region", &mut self.s3_region, "us-east-1", false);
+                        field(ui, "Bucket", &mut self.s3_bucket, "", false);
+                        field(ui, "Path prefix", &mut self.s3_prefix, "egeminui/", false);
+                        field(ui, "Access key", &mut self.s3_access_key, "", false);
+                        field(ui, "Secret key", &mut self.s3_secret_key, "", true);
+                    }
+                }
+                field(
+                    ui,
+                    "Passphrase",
+                    &mut self.passphrase,
+                    "Needed to restore, keep it safe",
+                    true,
+                );
+            });
+        ui.add_enabled_ui(self.scheduled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Back up every");
+                ui.add(
+                    egui::DragValue::new(&mut self.interval_hours)
+                        .range(1..=24 * 30)
+                        .suffix(" h"),
+                );
+            });
+        });
+        ui.add(egui::Slider::new(&mut self.keep, 1..=100).text("Backups to keep"));
+        if !self.is_configured() {
+            ui.weak("Fill in the target and a passphrase to enable backups");
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0; 32];
+    openssl::pkcs5::pbkdf2_hmac(
+        passphrase.as_bytes(),
+        salt,
+        KDF_ITERATIONS,
+        MessageDigest::sha256(),
+        &mut key,
+    )?;
+    Ok(key)
+}
+
+/// AES-256-GCM with a key derived from the passphrase by PBKDF2
+fn encrypt(passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    openssl::rand::rand_bytes(&mut salt)?;
+    openssl::rand::rand_bytes(&mut nonce)?;
+    let key = derive_key(passphrase, &salt)?;
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(&nonce[..]),
+        MAGIC,
+        plaintext,
+        &mut tag,
+    )?;
+    Ok([MAGIC, &salt[..], &nonce[..], &tag[..], &ciphertext[..]].concat())
+}
+
+fn decrypt(passphrase: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let Some(data) = data.strip_prefix(MAGIC) else {
+        bail!("not a backup file");
+    };
+    if data.len() < SALT_LEN + NONCE_LEN + TAG_LEN {
+        bail!("the backup file is truncated");
+    }
+    let (salt, data) = data.split_at(SALT_LEN);
+    let (nonce, data) = data.split_at(NONCE_LEN);
+    let (tag, ciphertext) = data.split_at(TAG_LEN);
+    let key = derive_key(passphrase, salt)?;
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(nonce),
+        MAGIC,
+        ciphertext,
+        tag,
+    )
+    .context("wrong passphrase or damaged backup")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(data)?;
+    Ok(signer.sign_to_vec()?)
+}
+
+/// Percent-encodes everything but unreserved characters, as SigV4 wants
+fn uri_encode(s: &str, keep_slash: bool) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' if keep_slash => "/".to_owned(),
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// A request to an S3-compatible API, signed with AWS Signature Version 4
+fn s3_request(
+    client: &reqwest::Client,
+    settings: &BackupSettings,
+    method: reqwest::Method,
+    key: &str,
+    query: &[(&str, &str)],
+    body: Vec<u8>,
+) -> Result<reqwest::RequestBuilder> {
+    let endpoint = url::Url::parse(settings.s3_endpoint.trim())?;
+    let host = match (endpoint.host_str(), endpoint.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => bail!("the S3 endpoint has no host"),
+    };
+    let path = format!(
+        "{}/{}/{}",
+        endpoint.path().trim_end_matches('/'),
+        uri_encode(settings.s3_bucket.trim(), false),
+        uri_encode(key, true)
+    );
+    let mut query: Vec<(String, String)> = query
+        .iter()
+        .map(|(k, v)| (uri_encode(k, false), uri_encode(v, false)))
+        .collect();
+    query.sort();
+    let query = query
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let region = settings.s3_region.trim();
+    let payload_hash = hex(&openssl::sha::sha256(&body));
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{method}\n{path}\n{query}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}"
+    );
+    let scope = format!("{date}/{region}/s3/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&openssl::sha::sha256(canonical_request.as_bytes()))
+    );
+    let mut signing_key = hmac(
+        format!("AWS4{}", settings.s3_secret_key).as_bytes(),
+        date.as_bytes(),
+    )?;
+    for part in [region, "s3", "aws4_request"] {
+        signing_key = hmac(&signing_key, part.as_bytes())?;
+    }
+    let signature = hex(&hmac(&signing_key, string_to_sign.as_bytes())?);
+
+    let mut url = endpoint.clone();
+    url.set_path(&path);
+    url.set_query((!query.is_empty()).then_some(query.as_str()));
+    Ok(client
+        .request(method, url)
+        .header("x-amz-date", amz_date)
+        .header("x-amz-content-sha256", payload_hash)
+        .header(
+            reqwest::header::AUTHORIZATION,
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                settings.s3_access_key.trim()
+            ),
+        )
+        .body(body))
+}
+
+fn webdav_request(
+    client: &reqwest::Client,
+    settings: &BackupSettings,
+    method: reqwest::Method,
+    name: &str,
+) -> reqwest::RequestBuilder {
+    let folder = settings.webdav_url.trim().trim_end_matches('/');
+    let request = client.request(method, format!("{folder}/{name}"));
+    if settings.webdav_user.is_empty() {
+        request
+    } else {
+        request.basic_auth(&settings.webdav_user, Some(&settings.webdav_password))
+    }
+}
+
+/// Text between `<tag>` and `</tag>`, ignoring namespace prefixes like `d:`
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let name = &rest[..end];
+        let local = name.rsplit(':').next().unwrap_or(name);
+        rest = &rest[end + 1..];
+        if local == tag {
+            if let Some(close) = rest.find("</") {
+                values.push(rest[..close].trim().to_owned());
+            }
+        }
+    }
+    values
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("the server returned {status}: {}", body.trim());
+    }
+    Ok(response)
+}
+
+#[derive(Debug, Clone)]
+struct RemoteBackup {
+    name: String,
+    created: Option<DateTime<Utc>>,
+}
+
+impl RemoteBackup {
+    fn new(name: String) -> Self {
+        let created = name
+            .strip_prefix(FILE_PREFIX)
+            .and_then(|s| s.strip_suffix(FILE_EXTENSION))
+            .and_then(|s| NaiveDateTime::parse_from_str(s, NAME_TIME_FORMAT).ok())
+            .map(|t| t.and_utc());
+        Self { name, created }
+    }
+}
+
+async fn list(client: &reqwest::Client, settings: &BackupSettings) -> Result<Vec<RemoteBackup>> {
+    let names = match settings.target {
+        BackupTarget::WebDav => {
+            let propfind = reqwest::Method::from_bytes(b"PROPFIND")?;
+            let xml = send(webdav_request(client, settings, propfind, "").header("Depth", "1"))
+                .await?
+                .text()
+                .await?;
+            xml_values(&xml, "href")
+                .into_iter()
+                .filter_map(|href| {
+                    href.trim_end_matches('/')
+                        .rsplit('/')
+                        .next()
+                        .map(str::to_owned)
+                })
+                .collect::<Vec<_>>()
+        }
+        BackupTarget::S3 => {
+            let prefix = format!("{}{FILE_PREFIX}", settings.s3_prefix.trim());
+            let request = s3_request(
+                client,
+                settings,
+                reqwest::Method::GET,
+                "",
+                &[("list-type", "2"), ("prefix", &prefix)],
+                Vec::new(),
+            )?;
+            let xml = send(request).await?.text().await?;
+            xml_values(&xml, "Key")
+                .into_iter()
+                .filter_map(|key| key.rsplit('/').next().map(str::to_owned))
+                .collect()
+        }
+    };
+    let mut backups: Vec<RemoteBackup> = names
+        .into_iter()
+        .filter(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_EXTENSION))
+        .map(RemoteBackup::new)
+        .collect();
+    backups.sort_by(|a, b| b.created.cmp(&a.created));
+    Ok(backups)
+}
+
+fn s3_key(settings: &BackupSettings, name: &str) -> String {
+    format!("{}{name}", settings.s3_prefix.trim())
+}
+
+async fn upload(
+    client: &reqwest::Client,
+    settings: &BackupSettings,
+    name: &str,
+    data: Vec<u8>,
+) -> Result<()> {
+    match settings.target {
+        BackupTarget::WebDav => {
+            // creates the folder, fails harmlessly if it exists
+            let mkcol = reqwest::Method::from_bytes(b"MKCOL")?;
+            let _ = webdav_request(client, settings, mkcol, "").send().await;
+            send(webdav_request(client, settings, reqwest::Method::PUT, name).body(data)).await?;
+        }
+        BackupTarget::S3 => {
+            let key = s3_key(settings, name);
+            send(s3_request(
+                client,
+                settings,
+                reqwest::Method::PUT,
+                &key,
+                &[],
+                data,
+            )?)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn download(
+    client: &reqwest::Client,
+    settings: &BackupSettings,
+    name: &str,
+) -> Result<Vec<u8>> {
+    let request = match settings.target {
+        BackupTarget::WebDav => webdav_request(client, settings, reqwest::Method::GET, name),
+        BackupTarget::S3 => {
+            let key = s3_key(settings, name);
+            s3_request(
+                client,
+                settings,
+                reqwest::Method::GET,
+                &key,
+                &[],
+                Vec::new(),
+            )?
+        }
+    };
+    Ok(send(request).await?.bytes().await?.to_vec())
+}
+
+async fn delete(client: &reqwest::Client, settings: &BackupSettings, name: &str) -> Result<()> {
+    let request = match settings.target {
+        BackupTarget::WebDav => webdav_request(client, settings, reqwest::Method::DELETE, name),
+        BackupTarget::S3 => {
+            let key = s3_key(settings, name);
+            s3_request(
+                client,
+                settings,
+                reqwest::Method::DELETE,
+                &key,
+                &[],
+                Vec::new(),
+            )?
+        }
+    };
+    send(request).await?;
+    Ok(())
+}
+
+/// Client for the backup server, through `proxy` if it's set
+fn http_client(proxy: Option<&str>) -> Result<reqwest::Client> {
+    let mut client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT);
+    if let Some(proxy) = proxy {
+        client = client.proxy(reqwest::Proxy::all(proxy).context("invalid proxy URL")?);
+    }
+    Ok(client.build()?)
+}
+
+/// Uploads a backup and deletes the ones over the limit. Returns the file name.
+async fn back_up(settings: &BackupSettings, proxy: Option<&str>, chats: Vec<u8>) -> Result<String> {
+    let data = tokio::task::spawn_blocking({
+        let passphrase = settings.passphrase.clone();
+        move || encrypt(&passphrase, &chats)
+    })
+    .await??;
+    let name = format!(
+        "{FILE_PREFIX}{}{FILE_EXTENSION}",
+        Utc::now().format(NAME_TIME_FORMAT)
+    );
+    let client = http_client(proxy)?;
+    upload(&client, settings, &name, data).await?;
+    log::info!("uploaded backup `{name}`");
+
+    for old in list(&client, settings)
+        .await?
+        .iter()
+        .skip(settings.keep.max(1))
+    {
+        match delete(&client, settings, &old.name).await {
+            Ok(()) => log::info!("deleted old backup `{}`", old.name),
+            Err(e) => log::warn!("failed to delete old backup `{}`: {e:#}", old.name),
+        }
+    }
+    Ok(name)
+}
+
+async fn restore(settings: &BackupSettings, proxy: Option<&str>, name: &str) -> Result<Vec<Chat>> {
+    let data = download(&http_client(proxy)?, settings, name).await?;
+    let passphrase = settings.passphrase.clone();
+    let json = tokio::task::spawn_blocking(move || decrypt(&passphrase, &data)).await??;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[derive(Default)]
+struct BackupState {
+    running: bool,
+    /// Result of the last backup or restore, shown in the Backups tool
+    status: Option<Result<String, String>>,
+    remote: Option<Result<Vec<RemoteBackup>, String>>,
+    restored: Option<Vec<Chat>>,
+    /// A scheduled or manual backup finished at this time
+    finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Backups {
+    last_backup: Option<DateTime<Utc>>,
+    /// Backup asked to be restored, waiting for a second click
+    #[serde(skip)]
+    confirm_restore: Option<String>,
+    #[serde(skip)]
+    state: Arc<Mutex<BackupState>>,
+}
+
+impl Default for Backups {
+    fn default() -> Self {
+        Self {
+            last_backup: None,
+            confirm_restore: None,
+            state: Arc::default(),
+        }
+    }
+}
+
+impl Backups {
+    fn start(
+        &self,
+        ctx: &egui::Context,
+        job: impl FnOnce(Arc<Mutex<BackupState>>) -> tokio::task::JoinHandle<()>,
+    ) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.running {
+                return;
+            }
+            state.running = true;
+        }
+        let state = self.state.clone();
+        let ctx = ctx.clone();
+        let handle = job(state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = handle.await {
+                log::error!("backup task failed: {e}");
+            }
+            if let Ok(mut state) = state.lock() {
+                state.running = false;
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    fn back_up_now(&self, ctx: &egui::Context, settings: &Settings, chats: &[Chat]) {
+        let chats = match serde_json::to_vec(chats) {
+            Ok(chats) => chats,
+            Err(e) => {
+                log::error!("failed to serialize chats for a backup: {e}");
+                return;
+            }
+        };
+        let proxy = settings.proxy_path.clone();
+        let settings = settings.backup.clone();
+        self.start(ctx, move |state| {
+            tokio::spawn(async move {
+                let _writing = crate::shutdown::begin_write();
+                let result = back_up(&settings, proxy.as_deref(), chats).await;
+                if let Ok(mut state) = state.lock() {
+                    state.remote = None;
+                    state.status = Some(match result {
+                        Ok(name) => {
+                            state.finished_at = Some(Utc::now());
+                            Ok(format!("Uploaded {name}"))
+                        }
+                        Err(e) => {
+                            log::error!("backup failed: {e:#}");
+                            // retried at the next interval, not every frame
+                            state.finished_at = Some(Utc::now());
+                            Err(format!("Backup failed: {e:#}"))
+                        }
+                    });
+                }
+            })
+        });
+    }
+
+    fn refresh(&self, ctx: &egui::Context, settings: &Settings) {
+        let proxy = settings.proxy_path.clone();
+        let settings = settings.backup.clone();
+        self.start(ctx, move |state| {
+            tokio::spawn(async move {
+                let result = match http_client(proxy.as_deref()) {
+                    Ok(client) => list(&client, &settings).await,
+                    Err(e) => Err(e),
+                }
+                .map_err(|e| format!("Failed to list backups: {e:#}"));
+                if let Ok(mut state) = state.lock() {
+                    state.remote = Some(result);
+                }
+            })
+        });
+    }
+
+    fn restore(&self, ctx: &egui::Context, settings: &Settings, name: String) {
+        let proxy = settings.proxy_path.clone();
+        let settings = settings.backup.clone();
+        self.start(ctx, move |state| {
+            tokio::spawn(async move {
+                let result = restore(&settings, proxy.as_deref(), &name).await;
+                if let Ok(mut state) = state.lock() {
+                    state.status = Some(match result {
+                        Ok(chats) => {
+                            let status = format!("Restored {} chats from {name}", chats.len());
+                            state.restored = Some(chats);
+                            Ok(status)
+                        }
+                        Err(e) => Err(format!("Failed to restore {name}: {e:#}")),
+                    });
+                }
+            })
+        });
+    }
+
+    /// Starts a scheduled backup when one is due, call every frame
+    pub fn poll_schedule(&mut self, ctx: &egui::Context, settings: &Settings, chats: &[Chat]) {
+        if let Some(finished) = self
+            .state
+            .lock()
+            .ok()
+            .and_then(|mut s| s.finished_at.take())
+        {
+            self.last_backup = Some(finished);
+        }
+        let backup = &settings.backup;
+        if !backup.scheduled || !backup.is_configured() {
+            return;
+        }
+        let due = self.last_backup.is_none_or(|last| {
+            Utc::now() - last >= Duration::hours(backup.interval_hours.max(1) as i64)
+        });
+        let running = self.state.lock().is_ok_and(|s| s.running);
+        if due && !running {
+            log::info!("starting a scheduled backup");
+            // not retried until it finishes, even if it fails
+            self.last_backup = Some(Utc::now());
+            self.back_up_now(ctx, settings, chats);
+        }
+    }
+
+    /// Shows the backup browser. Returns the chats of a restored backup, which
+    /// replace the current ones.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings: &Settings,
+        chats: &[Chat],
+    ) -> Option<Vec<Chat>> {
+        ui.heading("Backups");
+        ui.label(format!(
+            "Encrypted backups of all chats to {}, set up in the settings.",
+            settings.backup.target
+        ));
+        match self.last_backup {
+            Some(last) => ui.weak(format!(
+                "Last backup: {}",
+                last.with_timezone(&chrono::Local).format("%b %-d, %H:%M")
+            )),
+            None => ui.weak("No backups made yet"),
+        };
+        if !settings.backup.is_configured() {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "⚠ Set the backup target and passphrase in the settings first",
+            );
+            return None;
+        }
+
+        let Ok(mut state) = self.state.lock() else {
+            return None;
+        };
+        let running = state.running;
+        let restored = state.restored.take();
+        let status = state.status.clone();
+        let remote = state.remote.clone();
+        drop(state);
+
+        enum Action {
+            BackUp,
+            Refresh,
+            Restore(String),
+        }
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!running, egui::Button::new("⬆ Back up now"))
+                .clicked()
+            {
+                action = Some(Action::BackUp);
+            }
+            if ui
+                .add_enabled(!running, egui::Button::new("🔄 Refresh"))
+                .clicked()
+            {
+                action = Some(Action::Refresh);
+            }
+            if running {
+                ui.spinner();
+            }
+        });
+        match &status {
+            Some(Ok(status)) => {
+                ui.weak(status);
+            }
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            None => (),
+        }
+        ui.add_space(8.0);
+
+        match &remote {
+            None if !running => action = Some(Action::Refresh),
+            None => (),
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            Some(Ok(backups)) if backups.is_empty() => {
+                ui.weak("No backups on the remote yet");
+            }
+            Some(Ok(backups)) => {
+                egui::Grid::new("remote_backups")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for backup in backups {
+                            let created = match backup.created {
+                                Some(created) => created
+                                    .with_timezone(&chrono::Local)
+                                    .format("%Y-%m-%d %H:%M")
+                                    .to_string(),
+                                None => backup.name.clone(),
+                            };
+                            ui.label(created).on_hover_text(&backup.name);
+                            if self.confirm_restore.as_ref() == Some(&backup.name) {
+                                ui.horizontal(|ui| {
+                                    let replace = egui::Button::new(format!(
+                                        "Replace all {} chats",
+                                        chats.len()
+                                    ));
+                                    if ui.add_enabled(!running, replace).clicked() {
+                                        action = Some(Action::Restore(backup.name.clone()));
+                                    }
+                                    if ui.button("Cancel").clicked() {
+                                        self.confirm_restore = None;
+                                    }
+                                });
+                            } else if ui
+                                .add_enabled(!running, egui::Button::new("⬇ Restore…"))
+                                .clicked()
+                            {
+                                self.confirm_restore = Some(backup.name.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            }
+        }
+
+        match action {
+            Some(Action::BackUp) => self.back_up_now(ui.ctx(), settings, chats),
+            Some(Action::Refresh) => self.refresh(ui.ctx(), settings),
+            Some(Action::Restore(name)) => {
+                self.confirm_restore = None;
+                self.restore(ui.ctx(), settings, name);
+            }
+            None => (),
+        }
+        restored
+    }
+}
@@ -0,0 +1,434 @@
+//! Batch mode: runs a prompt template over every row of a CSV file and writes
+//! the answers to a new CSV. Rows already answered in the output file are
+//! skipped, so an interrupted run can be resumed.
+
+use crate::{
+    structured::{csv_field, parse_csv},
+    widgets::{GeminiModel, Settings},
+};
+use anyhow::{bail, Context, Result};
+use eframe::egui;
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// First column of the output, the index of the input row
+const ROW_COLUMN: &str = "row";
+
+#[derive(Debug, Default)]
+struct Progress {
+    total: usize,
+    done: usize,
+    failed: usize,
+    /// Answered in a previous run
+    skipped: usize,
+    is_running: bool,
+    error: Option<String>,
+}
+
+enum PickedFile {
+    Input(PathBuf),
+    Output(PathBuf),
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BatchRunner {
+    input_path: Option<PathBuf>,
+    output_path: Option<PathBuf>,
+    /// Prompt with `{{column}}` placeholders
+    template: String,
+    model: GeminiModel,
+    concurrency: usize,
+    requests_per_minute: u32,
+
+    #[serde(skip)]
+    header: Vec<String>,
+    #[serde(skip)]
+    rows: Vec<Vec<String>>,
+    #[serde(skip)]
+    load_error: Option<String>,
+    #[serde(skip)]
+    picked: Arc<Mutex<Option<PickedFile>>>,
+    #[serde(skip)]
+    progress: Arc<Mutex<Progress>>,
+    #[serde(skip)]
+    cancel: Arc<AtomicBool>,
+}
+
+impl Default for BatchRunner {
+    fn default() -> Self {
+        Self {
+            input_path: None,
+            output_path: None,
+            template: String::new(),
+            model: GeminiModel::default(),
+            concurrency: 2,
+            requests_per_minute: 15,
+            header: Vec::new(),
+            rows: Vec::new(),
+            load_error: None,
+            picked: Arc::default(),
+            progress: Arc::default(),
+            cancel: Arc::default(),
+        }
+    }
+}
+
+/// Fills in `{{column}}` placeholders with the fields of a row
+fn render_template(template: &str, header: &[String], row: &[String]) -> String {
+    let mut prompt = template.to_owned();
+    for (column, value) in header.iter().zip(row) {
+        prompt = prompt.replace(&format!("{{{{{column}}}}}"), value);
+    }
+    prompt
+}
+
+fn csv_line(fields: impl IntoIterator<Item = impl AsRef<str>>) -> String {
+    let fields: Vec<String> = fields.into_iter().map(|f| csv_field(f.as_ref())).collect();
+    fields.join(",")
+}
+
+/// Rows of a previous run that were answered without an error
+fn answered_rows(output: &Path) -> HashSet<usize> {
+    let Ok(text) = std::fs::read_to_string(output) else {
+        return HashSet::new();
+    };
+    parse_csv(&text)
+        .iter()
+        .skip(1)
+        .filter(|row| row.last().is_some_and(|error| error.is_empty()))
+        .filter_map(|row| row.first()?.parse().ok())
+        .collect()
+}
+
+fn load_csv(path: &Path) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let text = std::fs::read_to_string(path).context("failed to read the CSV file")?;
+    let mut rows = parse_csv(&text).into_iter();
+    let Some(header) = rows.next() else {
+        bail!("the CSV file is empty");
+    };
+    Ok((header, rows.collect()))
+}
+
+impl BatchRunner {
+    fn default_output_path(input: &Path) -> PathBuf {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        input.with_file_name(format!("{stem}_results.csv"))
+    }
+
+    fn load_input(&mut self, path: PathBuf) {
+        match load_csv(&path) {
+            Ok((header, rows)) => {
+                log::info!("loaded {} batch rows from {}", rows.len(), path.display());
+                if self.template.is_empty() {
+                    self.template = header
+                        .iter()
+                        .map(|column| format!("{column}: {{{{{column}}}}}"))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                }
+                self.header = header;
+                self.rows = rows;
+                self.load_error = None;
+            }
+            Err(e) => {
+                log::error!("failed to load batch input: {e:#}");
+                self.load_error = Some(format!("{e:#}"));
+                self.header.clear();
+                self.rows.clear();
+            }
+        }
+        self.output_path = Some(Self::default_output_path(&path));
+        self.input_path = Some(path);
+    }
+
+    fn pick_file(&self, ctx: &egui::Context, output: bool) {
+        let picked = self.picked.clone();
+        let ctx = ctx.clone();
+        let dialog = rfd::AsyncFileDialog::new().add_filter("CSV file", &["csv"]);
+        tokio::spawn(async move {
+            let file = if output {
+                dialog.save_file().await
+            } else {
+                dialog.pick_file().await
+            };
+            let Some(file) = file else {
+                return;
+            };
+            let path = file.path().to_path_buf();
+            if let Ok(mut picked) = picked.lock() {
+                *picked = Some(if output {
+                    PickedFile::Output(path)
+                } else {
+                    PickedFile::Input(path)
+                });
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    fn is_running(&self) -> bool {
+        self.progress.lock().is_ok_and(|p| p.is_running)
+    }
+
+    fn run(&mut self, ctx: &egui::Context, settings: &Settings) {
+        let Some(output) = self.output_path.clone() else {
+            return;
+        };
+        let answered = answered_rows(&output);
+        let pending: Vec<(usize, String, Vec<String>)> = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !answered.contains(i))
+            .map(|(i, row)| {
+                (
+                    i,
+                    render_template(&self.template, &self.header, row),
+                    row.clone(),
+                )
+            })
+            .collect();
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&output)
+            .and_then(|mut f| {
+                if f.metadata()?.len() == 0 {
+                    let header = std::iter::once(ROW_COLUMN)
+                        .chain(self.header.iter().map(String::as_str))
+                        .chain(["response", "error"]);
+                    writeln!(f, "{}", csv_line(header))?;
+                }
+                Ok(f)
+            });
+        let file = match file {
+            Ok(file) => Arc::new(Mutex::new(file)),
+            Err(e) => {
+                log::error!("failed to open batch output: {e}");
+                if let Ok(mut progress) = self.progress.lock() {
+                    progress.error = Some(format!("Failed to open the output file: {e}"));
+                }
+                return;
+            }
+        };
+
+        if let Ok(mut progress) = self.progress.lock() {
+            *progress = Progress {
+                total: self.rows.len(),
+                skipped: self.rows.len() - pending.len(),
+                is_running: true,
+                ..Default::default()
+            };
+        }
+        log::info!(
+            "starting batch of {} rows ({} already answered)",
+            pending.len(),
+            answered.len()
+        );
+
+        let mut model_picker = settings.model_picker.clone();
+        settings
+            .model_profiles
+            .select(&mut model_picker, self.model);
+        model_picker.auto_route = false;
+        let settings = settings.clone();
+        let concurrency = self.concurrency.max(1);
+        let interval = Duration::from_secs_f64(60.0 / self.requests_per_minute.max(1) as f64);
+        let progress = self.progress.clone();
+        let cancel = self.cancel.clone();
+        cancel.store(false, Ordering::SeqCst);
+        let ctx = ctx.clone();
+
+        tokio::spawn(async move {
+            let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrency));
+            let mut next_start = tokio::time::Instant::now();
+            let mut tasks = Vec::new();
+            for (i, prompt, row) in pending {
+                let Ok(permit) = semaphore.clone().acquire_owned().await else {
+                    break;
+                };
+                tokio::time::sleep_until(next_start).await;
+                next_start = tokio::time::Instant::now() + interval;
+                if cancel.load(Ordering::SeqCst) {
+                    log::info!("batch cancelled");
+                    break;
+                }
+
+                let settings = settings.clone();
+                let model_picker = model_picker.clone();
+                let file = file.clone();
+                let progress = progress.clone();
+                let ctx = ctx.clone();
+                tasks.push(tokio::spawn(async move {
+                    let mut response = String::new();
+                    let result = crate::chat::ask_headless(
+                        &settings,
+                        model_picker,
+                        prompt,
+                        Vec::new(),
+                        |text, is_thought| {
+                            if !is_thought {
+                                response.push_str(text);
+                            }
+                        },
+                    )
+                    .await;
+                    let error = result.as_ref().err().cloned().unwrap_or_default();
+                    let line = csv_line(
+                        std::iter::once(i.to_string())
+                            .chain(row)
+                            .chain([response, error]),
+                    );
+                    if let Ok(mut f) = file.lock() {
+                        if let Err(e) = writeln!(f, "{line}") {
+                            log::error!("failed to write batch result: {e}");
+                        }
+                    }
+                    if let Ok(mut progress) = progress.lock() {
+                        progress.done += 1;
+                        progress.failed += usize::from(result.is_err());
+                    }
+                    drop(permit);
+                    ctx.request_repaint();
+                }));
+            }
+            for task in tasks {
+                let _ = task.await;
+            }
+            if let Ok(mut progress) = progress.lock() {
+                progress.is_running = false;
+            }
+            log::info!("batch finished");
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        let picked = self.picked.lock().ok().and_then(|mut p| p.take());
+        match picked {
+            Some(PickedFile::Input(path)) => self.load_input(path),
+            Some(PickedFile::Output(path)) => self.output_path = Some(path),
+            None => (),
+        }
+        if self.rows.is_empty() && self.load_error.is_none() {
+            if let Some(path) = self.input_path.clone() {
+                self.load_input(path);
+            }
+        }
+
+        ui.heading("Batch Prompts");
+        ui.label(
+            "Run a prompt template over every row of a CSV file. Use {{column}} to insert a field. \
+            Answers are written to a new CSV, rows already answered there are skipped.",
+        );
+        ui.add_space(8.0);
+
+        let is_running = self.is_running();
+        ui.add_enabled_ui(!is_running, |ui| {
+            egui::Grid::new("batch_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Input");
+                    ui.horizontal(|ui| {
+                        if ui.button("Open…").clicked() {
+                            self.pick_file(ui.ctx(), false);
+                        }
+                        if let Some(path) = &self.input_path {
+                            ui.weak(format!("{} ({} rows)", path.display(), self.rows.len()));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Output");
+                    ui.horizontal(|ui| {
+                        if ui.button("Save as…").clicked() {
+                            self.pick_file(ui.ctx(), true);
+                        }
+                        if let Some(path) = &self.output_path {
+                            ui.weak(path.display().to_string());
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Model");
+                    egui::ComboBox::from_id_salt("batch_model")
+                        .selected_text(self.model.to_string())
+                        .show_ui(ui, |ui| {
+                            for model in enum_iterator::all::<GeminiModel>() {
+                                ui.selectable_value(&mut self.model, model, model.to_string());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Parallel requests");
+                    ui.add(egui::Slider::new(&mut self.concurrency, 1..=8));
+                    ui.end_row();
+
+                    ui.label("Requests per minute");
+                    ui.add(egui::Slider::new(&mut self.requests_per_minute, 1..=300));
+                    ui.end_row();
+                });
+
+            if let Some(error) = &self.load_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            if !self.header.is_empty() {
+                ui.weak(format!("Columns: {}", self.header.join(", ")));
+            }
+            ui.add(
+                egui::TextEdit::multiline(&mut self.template)
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(4)
+                    .hint_text("Prompt template, e.g. Translate to French: {{text}}"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            if is_running {
+                if ui.button("⏹ Stop").clicked() {
+                    self.cancel.store(true, Ordering::SeqCst);
+                }
+            } else if ui
+                .add_enabled(
+                    !self.rows.is_empty() && self.output_path.is_some(),
+                    egui::Button::new("▶ Run"),
+                )
+                .on_hover_text("Continues where the last run into this output file stopped")
+                .clicked()
+            {
+                self.run(ui.ctx(), settings);
+            }
+        });
+
+        if let Ok(progress) = self.progress.lock() {
+            if progress.total > 0 {
+                let finished = progress.skipped + progress.done;
+                ui.add(
+                    egui::ProgressBar::new(finished as f32 / progress.total as f32)
+                        .text(format!("{finished} / {}", progress.total))
+                        .animate(progress.is_running),
+                );
+                ui.weak(format!(
+                    "{} answered now, {} failed, {} from a previous run",
+                    progress.done - progress.failed,
+                    progress.failed,
+                    progress.skipped
+                ));
+            }
+            if let Some(error) = &progress.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! "Take photo": a live camera preview that captures a frame as an image
+//! attachment, for asking about things on the desk.
+
+use anyhow::{Context, Result};
+use eframe::egui::{self, vec2};
+use image::{ImageFormat, RgbImage};
+use nokhwa::{
+    pixel_format::RgbFormat,
+    utils::{CameraIndex, RequestedFormat, RequestedFormatType},
+    Camera,
+};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+struct CameraState {
+    /// Chat the photo is attached to
+    chat_id: usize,
+    /// `None` until the first frame arrives
+    preview: Option<Result<egui::TextureHandle, String>>,
+    frame: Option<RgbImage>,
+    /// Why the last capture couldn't be saved
+    error: Option<String>,
+    /// Tells the capture thread of this dialog to release the camera
+    stop: Arc<AtomicBool>,
+}
+
+/// The camera dialog, only one can be open at a time
+static CAMERA: Mutex<Option<CameraState>> = Mutex::new(None);
+
+/// Updates the dialog the capture thread belongs to, if it's still open
+fn with_state(stop: &Arc<AtomicBool>, f: impl FnOnce(&mut CameraState)) {
+    if let Ok(mut camera) = CAMERA.lock() {
+        if let Some(state) = camera.as_mut().filter(|s| Arc::ptr_eq(&s.stop, stop)) {
+            f(state);
+        }
+    }
+}
+
+fn stream_frames(ctx: &egui::Context, stop: &Arc<AtomicBool>) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    nokhwa::nokhwa_initialize(|granted| log::info!("camera access granted: {granted}"));
+
+    let format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestResolution);
+    let mut camera = Camera::new(CameraIndex::Index(0), format)?;
+    camera.open_stream()?;
+    log::info!("opened camera `{}`", camera.info().human_name());
+
+    while !stop.load(Ordering::Relaxed) {
+        let decoded = camera.frame()?.decode_image::<RgbFormat>()?;
+        let (width, height) = (decoded.width(), decoded.height());
+        let frame = RgbImage::from_raw(width, height, decoded.into_raw())
+            .context("camera returned a malformed frame")?;
+        let image = egui::ColorImage::from_rgb([width as usize, height as usize], frame.as_raw());
+        with_state(stop, |state| {
+            match &mut state.preview {
+                Some(Ok(texture)) => texture.set(image, egui::TextureOptions::LINEAR),
+                preview => {
+                    *preview = Some(Ok(ctx.load_texture(
+                        "camera://preview",
+                        image,
+                        egui::TextureOptions::LINEAR,
+                    )))
+                }
+            }
+            state.frame = Some(frame);
+        });
+        ctx.request_repaint();
+    }
+
+    camera.stop_stream()?;
+    log::info!("closed camera");
+    Ok(())
+}
+
+/// Opens the camera dialog for the chat `chat_id`
+pub fn open_camera(ctx: &egui::Context, chat_id: usize) {
+    let Ok(mut camera) = CAMERA.lock() else {
+        return;
+    };
+    if let Some(previous) = camera.take() {
+        previous.stop.store(true, Ordering::Relaxed);
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    *camera = Some(CameraState {
+        chat_id,
+        preview: None,
+        frame: None,
+        error: None,
+        stop: stop.clone(),
+    });
+
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = stream_frames(&ctx, &stop) {
+            log::error!("camera failed: {e}");
+            with_state(&stop, |state| state.preview = Some(Err(e.to_string())));
+            ctx.request_repaint();
+        }
+    });
+}
+
+/// Saves a captured frame to the attachment store
+fn save_photo(frame: &RgbImage) -> Result<PathBuf> {
+    let dir = crate::file_handler::attachments_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "photo-{}.jpg",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    frame.save_with_format(&path, ImageFormat::Jpeg)?;
+    log::info!("captured a photo: `{}`", path.display());
+    Ok(path)
+}
+
+/// Shows the camera dialog if it's open for the chat `chat_id`, and attaches
+/// the photo once it's taken
+pub fn show_camera_dialog(ctx: &egui::Context, chat_id: usize, files: &mut Vec<PathBuf>) {
+    let Ok(mut camera) = CAMERA.lock() else {
+        return;
+    };
+    let Some(state) = camera.as_mut().filter(|s| s.chat_id == chat_id) else {
+        return;
+    };
+
+    let mut open = true;
+    let mut captured = false;
+    egui::Window::new("Take photo")
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            match &state.preview {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Starting the camera…");
+                    });
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                Some(Ok(texture)) => {
+                    let max = vec2(640.0, 480.0);
+                    let size = texture.size_vec2();
+                    let scale = (max.x / size.x).min(max.y / size.y).min(1.0);
+                    ui.add(egui::Image::new(texture).fit_to_exact_size(size * scale));
+                }
+            }
+            if let Some(e) = &state.error {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            if ui
+                .add_enabled(state.frame.is_some(), egui::Button::new("📷 Capture"))
+                .clicked()
+            {
+                match state.frame.as_ref().map(save_photo) {
+                    Some(Ok(path)) => {
+                        files.push(path);
+                        captured = true;
+                    }
+                    Some(Err(e)) => {
+                        log::error!("failed to save photo: {e}");
+                        state.error = Some(format!("Failed to save the photo: {e}"));
+                    }
+                    None => (),
+                }
+            }
+        });
+
+    if !open || captured {
+        state.stop.store(true, Ordering::Relaxed);
+        *camera = None;
+    }
+}
@@ -0,0 +1,288 @@
+//! What each model can do: the inputs it reads, thinking, tools and its
+//! limits. The table is built in, and the limits are refreshed from the
+//! models API once per run.
+
+use crate::{endpoint::Endpoint, widgets::GeminiModel};
+use eframe::egui;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Set once the refresh was started, it's not repeated until the app restarts
+static REFRESH_STARTED: AtomicBool = AtomicBool::new(false);
+/// Limits reported by the models API, by model name
+static REMOTE: Mutex<BTreeMap<String, RemoteModel>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub vision: bool,
+    pub audio: bool,
+    pub video: bool,
+    pub thinking: bool,
+    /// Function calling and code execution
+    pub tools: bool,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub knowledge_cutoff: Option<&'static str>,
+}
+
+impl Capabilities {
+    const MULTIMODAL: Self = Self {
+        vision: true,
+        audio: true,
+        video: true,
+        thinking: false,
+        tools: true,
+        input_tokens: 1_048_576,
+        output_tokens: 8_192,
+        knowledge_cutoff: None,
+    };
+
+    const GEMMA: Self = Self {
+        vision: true,
+        audio: false,
+        video: false,
+        thinking: false,
+        tools: false,
+        input_tokens: 32_768,
+        output_tokens: 8_192,
+        knowledge_cutoff: Some("Aug 2024"),
+    };
+
+    const fn thinking(self) -> Self {
+        Self {
+            thinking: true,
+            output_tokens: 65_536,
+            ..self
+        }
+    }
+
+    const fn cutoff(self, cutoff: &'static str) -> Self {
+        Self {
+            knowledge_cutoff: Some(cutoff),
+            ..self
+        }
+    }
+}
+
+/// The built-in entry of the table
+fn builtin(model: GeminiModel) -> Capabilities {
+    use GeminiModel as M;
+    match model {
+        M::Gemini20Flash | M::Gemini20FlashLite => Capabilities::MULTIMODAL.cutoff("Aug 2024"),
+        M::Gemini25Pro
+        | M::Gemini25Flash
+        | M::Gemini25FlashPreview0520
+        | M::Gemini25ProPreview0325
+        | M::Gemini25ProPreview0506
+        | M::Gemini25ProPreview0605 => Capabilities::MULTIMODAL.thinking().cutoff("Jan 2025"),
+        M::Gemini15Flash | M::Gemini15Flash8b => Capabilities::MULTIMODAL,
+        M::Gemini15Pro => Capabilities {
+            input_tokens: 2_097_152,
+            ..Capabilities::MULTIMODAL
+        },
+        M::Gemini20FlashThinkingExp0121 => Capabilities {
+            audio: false,
+            video: false,
+            tools: false,
+            ..Capabilities::MULTIMODAL.thinking().cutoff("Aug 2024")
+        },
+        M::Gemini20FlashThinkingExp1219 => Capabilities {
+            audio: false,
+            video: false,
+            tools: false,
+            input_tokens: 32_767,
+            ..Capabilities::MULTIMODAL.thinking().cutoff("Aug 2024")
+        },
+        M::Gemma34bIt | M::Gemma312bIt | M::Gemma327bIt => Capabilities::GEMMA,
+        M::Gemma31bIt | M::Gemma3nE4bIt | M::Gemma3nE2bIt => Capabilities {
+            vision: false,
+            ..Capabilities::GEMMA
+        },
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteModel {
+    name: String,
+    input_token_limit: Option<u32>,
+    output_token_limit: Option<u32>,
+    thinking: Option<bool>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelList {
+    #[serde(default)]
+    models: Vec<RemoteModel>,
+}
+
+/// What `model` can do, with the limits from the models API if they were fetched
+pub fn capabilities(model: GeminiModel) -> Capabilities {
+    let mut capabilities = builtin(model);
+    let name = format!("models/{model}");
+    if let Some(remote) = REMOTE.lock().ok().and_then(|r| r.get(&name).cloned()) {
+        if let Some(tokens) = remote.input_token_limit {
+            capabilities.input_tokens = tokens;
+        }
+        if let Some(tokens) = remote.output_token_limit {
+            capabilities.output_tokens = tokens;
+        }
+        if let Some(thinking) = remote.thinking {
+            capabilities.thinking = thinking;
+        }
+    }
+    capabilities
+}
+
+async fn fetch(
+    api_key: &str,
+    proxy: Option<&str>,
+    endpoint: &Endpoint,
+) -> anyhow::Result<Vec<RemoteModel>> {
+    let list: ModelList = endpoint
+        .http_client(proxy, REFRESH_TIMEOUT, REFRESH_TIMEOUT)?
+        .get(format!("{}/v1beta/models", endpoint.base_url()))
+        .query(&[("key", api_key), ("pageSize", "1000")])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(list.models)
+}
+
+/// Fetches the model limits in the background, the first time it's called
+/// with an API key
+pub fn refresh_once(api_key: &str, proxy: Option<&str>, endpoint: &Endpoint) {
+    if api_key.is_empty() || REFRESH_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let api_key = api_key.to_owned();
+    let proxy = proxy.map(str::to_owned);
+    let endpoint = endpoint.clone();
+    tokio::spawn(async move {
+        match fetch(&api_key, proxy.as_deref(), &endpoint).await {
+            Ok(models) => {
+                log::info!("fetched the limits of {} models", models.len());
+                if let Ok(mut remote) = REMOTE.lock() {
+                    *remote = models.into_iter().map(|m| (m.name.clone(), m)).collect();
+                }
+            }
+            Err(e) => log::warn!("failed to fetch the model list, using the built-in limits: {e}"),
+        }
+    });
+}
+
+/// The kind of input an attachment is, for the ones not every model reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modality {
+    /// Images and PDFs
+    Image,
+    Audio,
+    Video,
+}
+
+impl std::fmt::Display for Modality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Image => "image and PDF",
+            Self::Audio => "audio",
+            Self::Video => "video",
+        })
+    }
+}
+
+impl Modality {
+    fn of(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        let ext = ext.as_str();
+        if crate::VIDEO_FORMATS.contains(&ext) {
+            Some(Self::Video)
+        } else if crate::MUSIC_FORMATS.contains(&ext) {
+            Some(Self::Audio)
+        } else if crate::IMAGE_FORMATS.contains(&ext) || ext == "pdf" {
+            Some(Self::Image)
+        } else {
+            None
+        }
+    }
+
+    fn supported_by(self, capabilities: &Capabilities) -> bool {
+        match self {
+            Self::Image => capabilities.vision,
+            Self::Audio => capabilities.audio,
+            Self::Video => capabilities.video,
+        }
+    }
+}
+
+/// The attachments `model` can't read, with their kind
+pub fn unsupported_files(model: GeminiModel, files: &[PathBuf]) -> Vec<(Modality, PathBuf)> {
+    let capabilities = capabilities(model);
+    files
+        .iter()
+        .filter_map(|path| Some((Modality::of(path)?, path)))
+        .filter(|(modality, _)| !modality.supported_by(&capabilities))
+        .map(|(modality, path)| (modality, path.clone()))
+        .collect()
+}
+
+/// A model that reads all of `modalities`, `preferred` if it does
+pub fn capable_model(modalities: &[Modality], preferred: GeminiModel) -> Option<GeminiModel> {
+    let reads_all = |model: &GeminiModel| {
+        let capabilities = capabilities(*model);
+        modalities.iter().all(|m| m.supported_by(&capabilities))
+    };
+    std::iter::once(preferred)
+        .chain(enum_iterator::all::<GeminiModel>())
+        .find(reads_all)
+}
+
+fn format_tokens(tokens: u32) -> String {
+    match tokens {
+        t if t >= 1_000_000 => format!("{}M", t / 1_000_000),
+        t if t >= 1_000 => format!("{}K", t / 1_000),
+        t => t.to_string(),
+    }
+}
+
+/// Small icons for what the model reads and does, with the details on hover
+pub fn show_badges(ui: &mut egui::Ui, model: GeminiModel) {
+    let caps = capabilities(model);
+    let badges = [
+        (caps.vision, "👁", "Images and PDFs"),
+        (caps.audio, "🎧", "Audio"),
+        (caps.video, "🎞", "Video"),
+        (caps.thinking, "💭", "Thinking"),
+        (
+            caps.tools,
+            "🔧",
+            "Tools: function calling and code execution",
+        ),
+    ];
+    ui.spacing_mut().item_spacing.x = 2.0;
+    for (supported, icon, name) in badges {
+        if supported {
+            ui.small(icon).on_hover_text(name);
+        }
+    }
+    let mut details = format!(
+        "Context: {} tokens in, {} out",
+        caps.input_tokens, caps.output_tokens
+    );
+    if let Some(cutoff) = caps.knowledge_cutoff {
+        details.push_str(&format!("\nKnowledge cutoff: {cutoff}"));
+    }
+    ui.weak(format_tokens(caps.input_tokens))
+        .on_hover_text(details);
+}
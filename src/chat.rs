@@ -2,9 +2,23 @@
 use crate::sessions::SharedTts;
 
 use crate::{
+    artifact::{Artifact, ArtifactAction},
+    capabilities::Modality,
+    compress::Compressor,
     easymark::MemoizedEasymarkHighlighter,
-    file_handler::convert_file_to_part,
-    widgets::{self, GeminiModel, ModelPicker, Settings},
+    file_handler::{convert_file_to_part, ConversionOptions},
+    git_context::GitContext,
+    glossary::{Glossary, Violation},
+    http_tools::ToolCaller,
+    network::NetworkStatus,
+    ocr::OcrSettings,
+    routing::Route,
+    scratchpad::Scratchpad,
+    timeouts::{timeout, RequestTimeouts, TIMED_OUT},
+    token_diet::{DietAction, TokenDiet},
+    translator::Translator,
+    watch_folder::WatchFolder,
+    widgets::{self, GeminiModel, ModelPicker, ResponseStyle, Settings},
 };
 use anyhow::{Context, Result};
 use eframe::egui::{
@@ -18,20 +32,24 @@ use flowync::{error::Compact, CompactFlower, CompactHandle};
 use gemini_client_api::gemini::{
     ask::Gemini,
     types::{
-        request::{BlockThreshold, HarmCategory, Part, SafetySetting},
+        request::{BlockThreshold, HarmCategory, Part, SafetySetting, Tool},
         sessions::Session,
     },
 };
 use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap, HashSet},
     io::Write,
+    ops::{Deref, DerefMut},
     path::PathBuf,
+    pin::Pin,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, OnceLock,
     },
     time::{Duration, Instant},
 };
-use tokio_stream::StreamExt;
+use tokio_stream::{Stream, StreamExt};
 
 const SAFETY_SETTINGS: [SafetySetting; 4] = [
     SafetySetting {
@@ -64,7 +82,8 @@ pub struct Message {
     model: GeminiModel,
     content: String,
     role: Role,
-    #[serde(skip)]
+    /// Saved too, so an answer the app was closed in the middle of can be
+    /// told apart after a restart
     is_generating: bool,
     #[serde(skip)]
     requested_at: Instant,
@@ -77,7 +96,30 @@ pub struct Message {
     is_speaking: bool,
     files: Vec<PathBuf>,
     is_prepending: bool,
+    /// The prompt is being edited, to be sent again
+    #[serde(skip)]
+    is_editing: bool,
     is_thought: bool,
+    /// When each step of a thought started, since the request
+    thought_steps: Vec<Duration>,
+    /// Error that cut the response short, the text received before it is kept
+    interrupted: Option<String>,
+    /// Kept in the chat, but not sent to the model
+    excluded: bool,
+    /// Requested answer length, for prompts
+    style: ResponseStyle,
+    /// Pages the model fetched with the URL context tool
+    retrieved_urls: Vec<String>,
+    /// The error is a timeout, retrying differently is offered
+    timed_out: bool,
+    /// Whether the reply is JSON or CSV that can be saved, checked once it's complete
+    #[serde(skip)]
+    has_structured_output: Option<bool>,
+    /// Whether the reply is mostly one long file, checked once it's complete
+    #[serde(skip)]
+    has_artifact: Option<bool>,
+    /// Messages this summary replaced, kept to be restored
+    compressed: Vec<Message>,
 }
 
 impl Default for Message {
@@ -94,8 +136,18 @@ impl Default for Message {
             model: GeminiModel::default(),
             files: Vec::new(),
             is_prepending: false,
+            is_editing: false,
             is_thought: false,
+            thought_steps: Vec::new(),
             generation_time: None,
+            interrupted: None,
+            excluded: false,
+            style: ResponseStyle::default(),
+            retrieved_urls: Vec::new(),
+            timed_out: false,
+            has_structured_output: None,
+            has_artifact: None,
+            compressed: Vec::new(),
         }
     }
 }
@@ -119,27 +171,78 @@ fn tts_control(tts: SharedTts, text: String, speak: bool) {
     });
 }
 
-fn make_short_name(_name: &str) -> String {
-    // todo stuff
-    // todo lmao
-    // let mut c = name
-    //     .split('/')
-    //     .next()
-    //     .unwrap_or(name)
-    //     .chars()
-    //     .take_while(|c| c.is_alphanumeric());
-    // match c.next() {
-    //     None => "Gemini".to_string(),
-    //     Some(f) => f.to_uppercase().collect::<String>() + c.collect::<String>().as_str(),
-    // }
-    "Gemini".to_string()
+/// Turns a model id into a short display name, e.g. `gemini-2.5-flash-preview-05-20`
+/// into "Flash 2.5 Preview" and `gemma-3-27b-it` into "Gemma 3 27B"
+pub fn make_short_name(name: &str) -> String {
+    let capitalize = |word: &str| {
+        // sizes like `8b` and `e4b`
+        if word.ends_with('b') && word[..word.len() - 1].chars().any(|c| c.is_ascii_digit()) {
+            return word.to_uppercase();
+        }
+        let mut chars = word.chars();
+        chars.next().map_or_else(String::new, |first| {
+            first.to_uppercase().chain(chars).collect()
+        })
+    };
+    let mut parts = name.split('-');
+    let (Some(family), Some(version)) = (parts.next(), parts.next()) else {
+        return name.to_owned();
+    };
+    // dates of previews and experiments, and the `it` of instruction-tuned models
+    let words = parts.filter(|w| !w.chars().all(|c| c.is_ascii_digit()) && *w != "it");
+
+    let mut short = Vec::new();
+    match family {
+        "gemini" => {
+            let mut tags = Vec::new();
+            for word in words {
+                if matches!(word, "preview" | "exp") {
+                    tags.push(capitalize(word));
+                } else {
+                    short.push(capitalize(word));
+                }
+            }
+            short.push(version.to_owned());
+            short.append(&mut tags);
+        }
+        "gemma" => {
+            short.push("Gemma".to_owned());
+            short.push(version.to_owned());
+            short.extend(words.map(capitalize));
+        }
+        _ => return name.to_owned(),
+    }
+    short.join(" ")
+}
+
+/// Short name of the model shown in message headers, the user's alias if they set one
+fn model_short_name(model: GeminiModel, aliases: &BTreeMap<String, String>) -> String {
+    let name = model.to_string();
+    match aliases.get(&name) {
+        Some(alias) if !alias.trim().is_empty() => alias.trim().to_owned(),
+        _ => make_short_name(&name),
+    }
 }
 
+/// Drag-and-drop payload of a message being moved, holds its index
+struct DraggedMessage(usize);
+
 enum MessageAction {
     None,
     Retry(usize),
     Regenerate(usize),
+    Continue(usize),
+    SaveStructured(usize),
+    OpenArtifact(usize),
+    MakeFlashcards(usize),
     Delete(usize),
+    /// Turn on streaming and retry
+    RetryStreaming(usize),
+    RetryWithModel(usize, GeminiModel),
+    /// Put the compressed messages back in place of the summary
+    RestoreCompressed(usize),
+    /// Send the edited prompt again, dropping the messages after it
+    Resend(usize),
 }
 
 impl Message {
@@ -171,6 +274,45 @@ impl Message {
         matches!(self.role, Role::User)
     }
 
+    /// When it was sent, its length and how long it takes to read
+    fn show_info(&self, ui: &mut egui::Ui) {
+        let words = crate::analytics::word_count(&self.content);
+        egui::Grid::new("message_info")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Sent");
+                ui.label(
+                    self.time
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                );
+                ui.end_row();
+                ui.label("Words");
+                ui.label(words.to_string());
+                ui.end_row();
+                ui.label("Reading time");
+                ui.label(format!("~{}", crate::analytics::reading_time(words)));
+                ui.end_row();
+                ui.label("Tokens");
+                ui.label(format!(
+                    "~{}",
+                    crate::analytics::estimate_tokens(&self.content)
+                ));
+                ui.end_row();
+                if let Some(duration) = self.generation_time {
+                    ui.label("Generated in");
+                    ui.label(format!("{:.1}s", duration.as_secs_f64()));
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// Whether both messages are from the user, or both from the same model
+    fn same_author(&self, other: &Self) -> bool {
+        self.is_user() == other.is_user() && (self.is_user() || self.model == other.model)
+    }
+
     fn show(
         &mut self,
         ui: &mut egui::Ui,
@@ -178,33 +320,60 @@ impl Message {
         #[cfg(feature = "tts")] tts: SharedTts,
         idx: usize,
         prepend_buf: &mut String,
+        settings: &Settings,
+        group: MessageGroup,
+        can_send: bool,
     ) -> MessageAction {
+        // messages continuing a group have no header, but are indented like its first one
+        let offset_id = MessageGroup::offset_id(self.is_user());
+
         // message role
-        let message_offset = ui
-            .horizontal(|ui| {
-                if self.is_user() {
-                    let f = ui.label("👤").rect.left();
+        let message_offset = if group.continues {
+            ui.data(|d| d.get_temp(offset_id))
+                .unwrap_or(ui.spacing().interact_size.y)
+        } else {
+            let header = ui.horizontal(|ui| {
+                let offset = if self.is_user() {
+                    let f = settings.avatars.show_user(ui).rect.left();
                     ui.label("You").rect.left() - f
                 } else {
-                    let f = ui.label("✨").rect.left();
+                    let f = settings.avatars.show_model(ui, self.model).rect.left();
                     let offset = ui
-                        .label(make_short_name(&self.model.to_string()))
+                        .label(model_short_name(self.model, &settings.model_aliases))
                         .on_hover_text(&self.model.to_string())
                         .rect
                         .left()
                         - f;
-                    ui.add_enabled(false, egui::Label::new(&self.model.to_string()));
                     if let Some(duration) = self.generation_time {
                         ui.weak(format!("({:.1}s)", duration.as_secs_f64()))
                             .on_hover_text("Generation time");
                     }
                     offset
+                };
+                if !self.content.is_empty() && !self.is_generating && !self.is_error {
+                    ui.weak("ℹ").on_hover_ui(|ui| self.show_info(ui));
                 }
-            })
-            .inner;
+                if self.excluded {
+                    ui.weak("(hidden from context)");
+                }
+                if !self.is_generating {
+                    ui.dnd_drag_source(
+                        egui::Id::new(("drag_message", idx)),
+                        DraggedMessage(idx),
+                        |ui| ui.weak("☰"),
+                    )
+                    .response
+                    .on_hover_text("Drag to reorder");
+                }
+                offset
+            });
+            ui.data_mut(|d| d.insert_temp(offset_id, header.inner));
+            header.inner
+        };
 
-        let is_commonmark = !self.content.is_empty() && !self.is_error && !self.is_prepending;
-        if is_commonmark && !self.is_thought {
+        let is_commonmark =
+            !self.content.is_empty() && !self.is_error && !self.is_prepending && !self.is_editing;
+        if is_commonmark && !self.is_thought && !group.continues {
             ui.add_space(-TextStyle::Body.resolve(ui.style()).size + 4.0);
         }
 
@@ -213,19 +382,12 @@ impl Message {
         ui.horizontal(|ui| {
             ui.add_space(message_offset);
             if self.content.is_empty() && self.is_generating && !self.is_error {
-                ui.horizontal(|ui| {
-                    ui.add(egui::Spinner::new());
-
-                    // show time spent waiting for response
-                    ui.add_enabled(
-                        false,
-                        egui::Label::new(format!(
-                            "{:.1}s",
-                            self.requested_at.elapsed().as_secs_f64()
-                        )),
-                    )
-                });
+                // the request state is shown under the chatbox
+                ui.add(egui::Spinner::new());
             } else if self.is_error {
+                if self.timed_out {
+                    ui.label("⏱");
+                }
                 ui.label(self.content.clone());
                 if ui
                     .button("Retry")
@@ -236,6 +398,51 @@ impl Message {
                 {
                     action = MessageAction::Retry(idx);
                 }
+                if self.timed_out
+                    && !settings.use_streaming
+                    && ui
+                        .button("Retry with streaming")
+                        .on_hover_text(
+                            "Turn on streaming: the answer is shown as it's generated, \
+                            instead of waiting for all of it",
+                        )
+                        .clicked()
+                {
+                    action = MessageAction::RetryStreaming(idx);
+                }
+                let fast_model = crate::routing::FAST_MODEL;
+                if self.timed_out
+                    && self.model != fast_model
+                    && ui
+                        .button(format!("Retry with {fast_model}"))
+                        .on_hover_text("A faster model usually answers sooner")
+                        .clicked()
+                {
+                    action = MessageAction::RetryWithModel(idx, fast_model);
+                }
+            } else if self.is_editing {
+                let textedit = ui.add(egui::TextEdit::multiline(prepend_buf).hint_text("Prompt…"));
+                let cancelled = textedit.lost_focus() && ui.input(|i| i.key_pressed(Key::Escape));
+                ui.vertical(|ui| {
+                    if ui
+                        .add_enabled(
+                            can_send && !prepend_buf.trim().is_empty(),
+                            egui::Button::new("↩ Send"),
+                        )
+                        .on_hover_text(
+                            "Replace the prompt and send it again, \
+                            the messages after it are removed",
+                        )
+                        .on_disabled_hover_text("Wait for the answer being generated")
+                        .clicked()
+                    {
+                        action = MessageAction::Resend(idx);
+                    }
+                    if cancelled || ui.button("❌ Cancel").clicked() {
+                        self.is_editing = false;
+                        prepend_buf.clear();
+                    }
+                });
             } else if self.is_prepending {
                 let textedit = ui.add(
                     egui::TextEdit::multiline(prepend_buf).hint_text("Prepend text to response…"),
@@ -272,6 +479,8 @@ impl Message {
                             .clicked()
                     {
                         self.content = prepend_buf.clone();
+                        self.has_structured_output = None;
+                        self.has_artifact = None;
                         cancel_prepend!();
                     }
                     if ui.button("❌ Cancel").clicked() {
@@ -285,9 +494,15 @@ impl Message {
                         Frame::group(ui.style())
                             .inner_margin(Margin::symmetric(8, 4))
                             .show(ui, |ui| {
+                                let heading = match crate::thoughts::current_title(&self.content) {
+                                    Some(title) if self.is_generating => {
+                                        format!("  Thoughts: {title}")
+                                    }
+                                    _ => "  Thoughts".to_owned(),
+                                };
                                 // egui::collapsing_header::CollapsingState::load_with_default_open
-                                egui::CollapsingHeader::new("  Thoughts")
-                                    .id_salt(self.time.timestamp_millis())
+                                egui::CollapsingHeader::new(heading)
+                                    .id_salt((self.time.timestamp_millis(), idx))
                                     .default_open(false)
                                     .icon(move |ui, openness, response| {
                                         widgets::thinking_icon(
@@ -298,10 +513,17 @@ impl Message {
                                         );
                                     })
                                     .show(ui, |ui| {
-                                        CommonMarkViewer::new().show(
+                                        let end = self
+                                            .generation_time
+                                            .unwrap_or_else(|| self.requested_at.elapsed());
+                                        crate::thoughts::show_steps(
                                             ui,
                                             commonmark_cache,
+                                            (self.time.timestamp_millis(), idx, "steps"),
                                             &self.content,
+                                            &self.thought_steps,
+                                            end,
+                                            self.is_generating,
                                         );
                                     });
                             });
@@ -317,6 +539,84 @@ impl Message {
             }
         });
 
+        if let Some(error) = &self.interrupted {
+            let app_closed = error == APP_CLOSED;
+            ui.horizontal(|ui| {
+                ui.add_space(message_offset);
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ Response interrupted")
+                    .on_hover_text(error);
+                if !self.is_thought
+                    && !self.content.is_empty()
+                    && ui
+                        .button("▶ Continue")
+                        .on_hover_text("Generate the rest of the response, keeping the text so far")
+                        .clicked()
+                {
+                    action = MessageAction::Continue(idx);
+                }
+                if ui
+                    .button(if app_closed {
+                        "↻ Resend from here"
+                    } else {
+                        "Retry"
+                    })
+                    .on_hover_text("Discard this response and generate it again")
+                    .clicked()
+                {
+                    action = MessageAction::Retry(idx);
+                }
+            });
+            ui.add_space(4.0);
+        }
+
+        if !self.retrieved_urls.is_empty() {
+            ui.horizontal(|ui| {
+                ui.add_space(message_offset);
+                egui::CollapsingHeader::new(format!(
+                    "🔗 Read {} page(s)",
+                    self.retrieved_urls.len()
+                ))
+                .id_salt(("retrieved_urls", idx))
+                .show(ui, |ui| {
+                    for url in &self.retrieved_urls {
+                        ui.hyperlink(url);
+                    }
+                });
+            });
+        }
+
+        if !self.compressed.is_empty() {
+            ui.horizontal(|ui| {
+                ui.add_space(message_offset);
+                egui::CollapsingHeader::new(format!(
+                    "🗜 Compressed history ({} messages)",
+                    self.compressed.len()
+                ))
+                .id_salt(("compressed_history", idx))
+                .show(ui, |ui| {
+                    if ui
+                        .button("↩ Restore")
+                        .on_hover_text("Put these messages back in place of the summary")
+                        .clicked()
+                    {
+                        action = MessageAction::RestoreCompressed(idx);
+                    }
+                    for message in self.compressed.iter().filter(|m| !m.is_thought) {
+                        ui.strong(if message.is_user() {
+                            "You".to_owned()
+                        } else {
+                            model_short_name(message.model, &settings.model_aliases)
+                        });
+                        ui.label(&message.content);
+                        if !message.files.is_empty() {
+                            ui.weak(format!("📎 {} file(s)", message.files.len()));
+                        }
+                        ui.add_space(4.0);
+                    }
+                });
+            });
+        }
+
         // files
         if !self.files.is_empty() {
             if is_commonmark {
@@ -326,14 +626,14 @@ impl Message {
                 ui.add_space(message_offset);
                 egui::ScrollArea::horizontal().id_salt(idx).show(ui, |ui| {
                     ui.horizontal(|ui| {
-                        crate::file_handler::show_files(ui, &mut self.files, false);
+                        crate::file_handler::show_files(ui, &mut self.files, false, None);
                     });
                 })
             });
             ui.add_space(8.0);
         }
 
-        if self.is_prepending {
+        if self.is_prepending || self.is_editing {
             return action;
         }
 
@@ -354,12 +654,31 @@ impl Message {
                         .on_hover_text(if self.clicked_copy {
                             "Copied!"
                         } else {
-                            "Copy message"
+                            "Copy message, right click to copy it formatted"
                         });
                     if copy.clicked() {
                         ui.ctx().copy_text(self.content.clone());
                         self.clicked_copy = true;
                     }
+                    copy.context_menu(|ui| {
+                        if ui
+                            .button("Copy as rendered")
+                            .on_hover_text("Keeps the formatting when pasted into an email or a document")
+                            .clicked()
+                        {
+                            if let Err(e) = crate::rich_copy::copy_rendered(&self.content) {
+                                log::error!("failed to copy the message as HTML: {e}");
+                                ui.ctx().copy_text(self.content.clone());
+                            }
+                            self.clicked_copy = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("Copy as markdown").clicked() {
+                            ui.ctx().copy_text(self.content.clone());
+                            self.clicked_copy = true;
+                            ui.close_menu();
+                        }
+                    });
                     self.clicked_copy = self.clicked_copy && copy.hovered();
                 }
 
@@ -387,6 +706,71 @@ impl Message {
                     }
                 }
 
+                if !self.is_user() && !self.is_thought {
+                    let has_structured_output =
+                        *self.has_structured_output.get_or_insert_with(|| {
+                            crate::structured::StructuredOutput::extract(&self.content).is_some()
+                        });
+                    if has_structured_output
+                        && ui
+                            .add(
+                                egui::Button::new("💾")
+                                    .small()
+                                    .fill(egui::Color32::TRANSPARENT),
+                            )
+                            .on_hover_text("Save structured output (JSON or CSV) to a file")
+                            .clicked()
+                    {
+                        action = MessageAction::SaveStructured(idx);
+                    }
+
+                    let has_artifact = *self
+                        .has_artifact
+                        .get_or_insert_with(|| crate::artifact::detect(&self.content).is_some());
+                    if has_artifact
+                        && ui
+                            .add(
+                                egui::Button::new("📄")
+                                    .small()
+                                    .fill(egui::Color32::TRANSPARENT),
+                            )
+                            .on_hover_text("Open in the artifact panel")
+                            .clicked()
+                    {
+                        action = MessageAction::OpenArtifact(idx);
+                    }
+
+                    if !self.content.is_empty()
+                        && ui
+                            .add(
+                                egui::Button::new("🃏")
+                                    .small()
+                                    .fill(egui::Color32::TRANSPARENT),
+                            )
+                            .on_hover_text("Make flashcards from this reply")
+                            .clicked()
+                    {
+                        action = MessageAction::MakeFlashcards(idx);
+                    }
+                }
+
+                if ui
+                    .add(
+                        egui::Button::new("👁")
+                            .small()
+                            .selected(self.excluded)
+                            .fill(egui::Color32::TRANSPARENT),
+                    )
+                    .on_hover_text(if self.excluded {
+                        "Include the message in the context again"
+                    } else {
+                        "Exclude from context: keep the message here, but don't send it to the model"
+                    })
+                    .clicked()
+                {
+                    self.excluded = !self.excluded;
+                }
+
                 if ui
                     .add(
                         egui::Button::new("🗑")
@@ -408,30 +792,371 @@ impl Message {
                                 .small()
                                 .fill(egui::Color32::TRANSPARENT),
                         )
-                        .on_hover_text("Regenerate")
+                        .on_hover_text(
+                            "Regenerate, you can type how the new answer should start",
+                        )
                         .clicked()
                 {
                     prepend_buf.clear();
                     self.is_prepending = true;
                 }
+
+                if self.is_user()
+                    && prepend_buf.is_empty()
+                    && ui
+                        .add_enabled(
+                            can_send,
+                            egui::Button::new("\u{270f}")
+                                .small()
+                                .fill(egui::Color32::TRANSPARENT),
+                        )
+                        .on_hover_text("Edit the prompt and send it again")
+                        .clicked()
+                {
+                    prepend_buf.clone_from(&self.content);
+                    self.is_editing = true;
+                }
+
+                // without a header, the drag handle moves here
+                if group.continues {
+                    ui.dnd_drag_source(
+                        egui::Id::new(("drag_message", idx)),
+                        DraggedMessage(idx),
+                        |ui| ui.weak("☰"),
+                    )
+                    .response
+                    .on_hover_text("Drag to reorder");
+                }
             });
         }
-        ui.add_space(12.0);
+        ui.add_space(if group.continued { 4.0 } else { 12.0 });
 
         action
     }
 }
 
+/// Where a message is in a run of consecutive messages from the same author,
+/// which are shown under a single header
+#[derive(Debug, Clone, Copy, Default)]
+struct MessageGroup {
+    /// Follows a message from the same author
+    continues: bool,
+    /// Followed by a message from the same author
+    continued: bool,
+}
+
+impl MessageGroup {
+    /// Where the indent of the last header is kept, for the messages under it
+    fn offset_id(is_user: bool) -> egui::Id {
+        egui::Id::new(("message_offset", is_user))
+    }
+
+    /// Draws the line connecting the messages of a group in the gutter
+    /// under the avatar
+    fn paint_connector(self, ui: &egui::Ui, rect: Rect, offset: f32) {
+        if !self.continues && !self.continued {
+            return;
+        }
+        let spacing = ui.spacing().item_spacing;
+        let x = rect.left() + (offset - spacing.x) / 2.0;
+        let top = if self.continues {
+            rect.top() - spacing.y
+        } else {
+            // below the header
+            rect.top() + ui.spacing().interact_size.y + spacing.y
+        };
+        let bottom = if self.continued {
+            rect.bottom()
+        } else {
+            rect.bottom() - 12.0
+        };
+        if bottom > top {
+            ui.painter().vline(
+                x,
+                top..=bottom,
+                Stroke::new(2.0, ui.visuals().widgets.noninteractive.bg_stroke.color),
+            );
+        }
+    }
+}
+
+/// Sent from the completion task while the response is generated
+enum CompletionEvent {
+    /// Attachments are converted and the request is being sent
+    Connecting,
+    Part(Part),
+    /// Pages fetched by the URL context tool
+    RetrievedUrls(Vec<String>),
+    /// A tool call waiting for the user to allow it
+    ConfirmToolCall(crate::http_tools::ToolConfirmation),
+}
+
+/// Where the request being generated is, shown under the chatbox
+#[derive(Debug, Clone, Copy)]
+struct RequestProgress {
+    started: Instant,
+    /// When the request was sent, after the attachments were converted
+    connected: Option<Instant>,
+    first_token: Option<Instant>,
+    /// Estimated, counted as the text arrives
+    tokens: usize,
+}
+
+impl RequestProgress {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            connected: None,
+            first_token: None,
+            tokens: 0,
+        }
+    }
+
+    fn received(&mut self, text: &str) {
+        self.first_token.get_or_insert_with(Instant::now);
+        self.tokens += crate::analytics::estimate_tokens(text);
+    }
+
+    fn status(&self) -> String {
+        let elapsed = self.started.elapsed().as_secs_f32();
+        let Some(connected) = self.connected else {
+            return format!("Queued, preparing the request… {elapsed:.1}s");
+        };
+        let Some(first_token) = self.first_token else {
+            return format!("Connecting, waiting for the first token… {elapsed:.1}s");
+        };
+        let mut status = format!(
+            "First token in {:.1}s",
+            first_token.duration_since(connected).as_secs_f32()
+        );
+        let streaming = first_token.elapsed().as_secs_f32();
+        // the rate is meaningless until a few chunks have arrived
+        if streaming >= 0.5 {
+            status.push_str(&format!(
+                " · streaming {:.0} tok/s",
+                self.tokens as f32 / streaming
+            ));
+        }
+        status.push_str(&format!(" · {} tokens · {elapsed:.1}s", self.tokens));
+        status
+    }
+}
+
 // <completion progress, final completion, error>
-type CompletionFlower = CompactFlower<(usize, Part), (usize, String), (usize, String)>;
-type CompletionFlowerHandle = CompactHandle<(usize, Part), (usize, String), (usize, String)>;
+type CompletionFlower = CompactFlower<(usize, CompletionEvent), (usize, String), (usize, String)>;
+type CompletionFlowerHandle =
+    CompactHandle<(usize, CompletionEvent), (usize, String), (usize, String)>;
+
+thread_local! {
+    /// Set while the app state is read or written, see [`with_lazy_messages`]
+    static LAZY_MESSAGES: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` with the messages of chats kept as unparsed RON until each chat is
+/// first opened. Only for the app state in eframe storage, which is RON.
+pub fn with_lazy_messages<T>(f: impl FnOnce() -> T) -> T {
+    LAZY_MESSAGES.set(true);
+    let result = f();
+    LAZY_MESSAGES.set(false);
+    result
+}
+
+/// Message history of a chat, parsed when it's first accessed, so startup
+/// doesn't pay for chats that are never opened
+#[derive(Default)]
+pub struct Messages {
+    /// The history, and why it failed to load if it did
+    parsed: OnceLock<(Vec<Message>, Option<String>)>,
+    /// The history as stored, until it's changed. Kept for good if it fails
+    /// to load, so it isn't overwritten by an empty one
+    raw: Option<Box<ron::value::RawValue>>,
+}
+
+/// Error of an answer that was still being generated when the app closed
+const APP_CLOSED: &str = "The app was closed before the response was complete";
+
+/// Marks the answers that were being generated when the messages were saved
+/// as interrupted, nothing is generating them anymore
+fn mark_unfinished(messages: &mut [Message]) {
+    for message in messages.iter_mut().filter(|m| m.is_generating) {
+        message.is_generating = false;
+        message.interrupted = Some(APP_CLOSED.to_owned());
+    }
+}
+
+impl Messages {
+    fn parse(&self) -> (Vec<Message>, Option<String>) {
+        let Some(raw) = &self.raw else {
+            return (Vec::new(), None);
+        };
+        let start = Instant::now();
+        match raw.into_rust::<Vec<Message>>() {
+            Ok(mut messages) => {
+                log::debug!(
+                    "loaded {} messages in {:?}",
+                    messages.len(),
+                    start.elapsed()
+                );
+                mark_unfinished(&mut messages);
+                (messages, None)
+            }
+            Err(e) => {
+                log::error!("failed to load chat messages: {e}");
+                (Vec::new(), Some(e.to_string()))
+            }
+        }
+    }
+
+    /// Whether the history is parsed, accessing it otherwise parses it. One
+    /// that failed to load never is.
+    pub fn is_loaded(&self) -> bool {
+        self.raw.is_none() || self.parsed.get().is_some_and(|(_, error)| error.is_none())
+    }
+
+    /// Why the stored history couldn't be parsed, it's shown as empty then
+    pub fn load_error(&self) -> Option<&str> {
+        self.raw.as_ref()?;
+        self.parsed.get_or_init(|| self.parse()).1.as_deref()
+    }
+
+    pub fn into_vec(mut self) -> Vec<Message> {
+        std::mem::take(self.deref_mut())
+    }
+}
+
+impl From<Vec<Message>> for Messages {
+    fn from(messages: Vec<Message>) -> Self {
+        Self {
+            parsed: OnceLock::from((messages, None)),
+            raw: None,
+        }
+    }
+}
+
+impl Deref for Messages {
+    type Target = Vec<Message>;
+
+    fn deref(&self) -> &Vec<Message> {
+        &self.parsed.get_or_init(|| self.parse()).0
+    }
+}
+
+impl DerefMut for Messages {
+    fn deref_mut(&mut self) -> &mut Vec<Message> {
+        if self.parsed.get().is_none() {
+            let parsed = self.parse();
+            let _ = self.parsed.set(parsed);
+        }
+        let (messages, error) = self.parsed.get_mut().expect("parsed above");
+        if error.is_none() {
+            // changed from here on, the stored copy is outdated
+            self.raw = None;
+        } else {
+            log::warn!("changing a chat history that failed to load, it won't be saved");
+        }
+        messages
+    }
+}
+
+impl serde::Serialize for Messages {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.raw {
+            // unchanged, or failed to load: written back as it was
+            Some(raw) if LAZY_MESSAGES.get() => serde::Serialize::serialize(raw, serializer),
+            _ => serde::Serialize::serialize(self.deref(), serializer),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Messages {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if LAZY_MESSAGES.get() {
+            Ok(Self {
+                parsed: OnceLock::new(),
+                raw: Some(serde::Deserialize::deserialize(deserializer)?),
+            })
+        } else {
+            let mut messages: Vec<Message> = serde::Deserialize::deserialize(deserializer)?;
+            mark_unfinished(&mut messages);
+            Ok(messages.into())
+        }
+    }
+}
+
+/// A message waiting for the answers before it
+struct QueuedPrompt {
+    text: String,
+    files: Vec<PathBuf>,
+    style: ResponseStyle,
+}
+
+/// Where a prompt held back by a dialog is, it's sent from there once answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HeldPrompt {
+    Chatbox,
+    /// The first queued prompt
+    Queue,
+    /// The prompt at this index, being edited to be sent again
+    Edited(usize),
+}
+
+/// A prompt held back because the model can't read some of its attachments
+struct BlockedSend {
+    model: GeminiModel,
+    unsupported: Vec<(Modality, PathBuf)>,
+    /// A model that reads them all
+    suggested: Option<GeminiModel>,
+    from: HeldPrompt,
+}
+
+/// A prompt held back because it's estimated to cost more than the warning threshold
+struct CostWarning {
+    cost: f64,
+    from: HeldPrompt,
+}
+
+/// A request waiting to be confirmed in the request preview
+struct PendingRequest {
+    model_picker: ModelPicker,
+    messages: Vec<Message>,
+    /// Attachments left out, by message index
+    excluded_files: HashSet<(usize, PathBuf)>,
+    include_system_prompt: bool,
+    /// The prompt came from the chatbox, and goes back there if cancelled
+    from_chatbox: bool,
+}
 
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)]
 pub struct Chat {
     chatbox: String,
-    pub messages: Vec<Message>,
+    pub messages: Messages,
+    /// Last message and when it was sent, shown in the sidebar while the
+    /// messages aren't loaded
+    preview: Option<(String, chrono::DateTime<chrono::Utc>)>,
     pub summary: String,
+    /// Name given by the user, takes precedence over the auto-generated `summary`
+    pub title: Option<String>,
+    /// Names the chat's file in the sync folder, empty until it's first synced
+    pub sync_id: String,
+    /// Emoji shown before the chat name
+    pub icon: Option<String>,
+    /// Accent color of the chat card in the sidebar
+    pub accent_color: Option<Color32>,
+    /// Document open in the side panel
+    artifact: Option<Artifact>,
+    /// Answer length for the next prompt
+    response_style: ResponseStyle,
+    /// Set when the chat is in translator mode
+    translator: Option<Translator>,
+    /// Language answers should be in, overrides the one in the settings
+    response_language: Option<String>,
+    /// Run the response filters from the settings over answers
+    pub apply_filters: bool,
+    /// Required translations of terms
+    glossary: Glossary,
+    /// Notes shown next to the conversation
+    scratchpad: Scratchpad,
     stop_generating: Arc<AtomicBool>,
     pub model_picker: ModelPicker,
     pub files: Vec<PathBuf>,
@@ -444,24 +1169,122 @@ pub struct Chat {
     #[serde(skip)]
     retry_message_idx: Option<usize>,
     #[serde(skip)]
-    virtual_list: VirtualList,
+    save_structured_idx: Option<usize>,
+    /// Deck name and text to make flashcards from
     #[serde(skip)]
-    chatbox_highlighter: MemoizedEasymarkHighlighter,
-}
-
-impl Default for Chat {
-    fn default() -> Self {
-        Self {
-            chatbox: String::new(),
-            chatbox_height: 0.0,
-            messages: Vec::new(),
-            flower: CompletionFlower::new(1),
-            retry_message_idx: None,
-            summary: String::new(),
-            chatbox_highlighter: MemoizedEasymarkHighlighter::default(),
-            stop_generating: Arc::new(AtomicBool::new(false)),
-            virtual_list: {
-                let mut list = VirtualList::new();
+    flashcards_source: Option<(String, String)>,
+    /// A timed out request is retried with streaming, which has to be turned on
+    #[serde(skip)]
+    enable_streaming: bool,
+    #[serde(skip)]
+    token_diet: TokenDiet,
+    /// Prompts sent one by one, each after the previous answer is done
+    #[serde(skip)]
+    queued: Vec<QueuedPrompt>,
+    #[serde(skip)]
+    blocked_send: Option<BlockedSend>,
+    #[serde(skip)]
+    pending_request: Option<PendingRequest>,
+    #[serde(skip)]
+    cost_warning: Option<CostWarning>,
+    #[serde(skip)]
+    tool_confirmation: Option<crate::http_tools::ToolConfirmation>,
+    /// Set when the cost warning was answered with "Send anyway"
+    #[serde(skip)]
+    cost_confirmed: bool,
+    #[serde(skip)]
+    compressor: Compressor,
+    /// Message whose answer is still being generated, waiting for the user to
+    /// confirm deleting it
+    #[serde(skip)]
+    delete_confirm: Option<usize>,
+    /// Messages deleted once the answer being generated stops, the indices
+    /// in its events would be off otherwise
+    #[serde(skip)]
+    delete_after_stop: Vec<std::ops::Range<usize>>,
+    /// History removed by "Clear messages", kept for undo until the app closes
+    #[serde(skip)]
+    cleared_messages: Option<Vec<Message>>,
+    /// Shown after reordering left the roles in an order the API may not accept
+    #[serde(skip)]
+    role_order_warning: Option<&'static str>,
+    /// Model picked for the last prompt in auto mode
+    #[serde(skip)]
+    route: Option<Route>,
+    #[serde(skip)]
+    progress: Option<RequestProgress>,
+    /// Language the pending answer was asked to be in
+    #[serde(skip)]
+    requested_language: Option<String>,
+    /// Response filters for the pending answer, from the settings when it was asked for
+    #[serde(skip)]
+    response_filters: Vec<crate::filters::ResponseFilter>,
+    /// Language the last answer came back in, and the one it should be in
+    #[serde(skip)]
+    language_mismatch: Option<(&'static str, String)>,
+    /// Glossary terms the last answer didn't translate as required
+    #[serde(skip)]
+    glossary_violations: Vec<Violation>,
+    /// An empty response came back and the request should be sent again with a nudge
+    #[serde(skip)]
+    empty_retry_pending: bool,
+    /// The last prompt was already retried after an empty response
+    #[serde(skip)]
+    empty_retried: bool,
+    #[serde(skip)]
+    virtual_list: VirtualList,
+    #[serde(skip)]
+    chatbox_highlighter: MemoizedEasymarkHighlighter,
+}
+
+impl Default for Chat {
+    fn default() -> Self {
+        Self {
+            chatbox: String::new(),
+            chatbox_height: 0.0,
+            messages: Messages::default(),
+            preview: None,
+            flower: CompletionFlower::new(1),
+            retry_message_idx: None,
+            save_structured_idx: None,
+            flashcards_source: None,
+            enable_streaming: false,
+            token_diet: TokenDiet::default(),
+            queued: Vec::new(),
+            blocked_send: None,
+            pending_request: None,
+            cost_warning: None,
+            tool_confirmation: None,
+            cost_confirmed: false,
+            compressor: Compressor::default(),
+            delete_confirm: None,
+            delete_after_stop: Vec::new(),
+            cleared_messages: None,
+            role_order_warning: None,
+            route: None,
+            progress: None,
+            requested_language: None,
+            response_filters: Vec::new(),
+            language_mismatch: None,
+            glossary_violations: Vec::new(),
+            empty_retry_pending: false,
+            empty_retried: false,
+            summary: String::new(),
+            title: None,
+            sync_id: String::new(),
+            icon: None,
+            accent_color: None,
+            artifact: None,
+            response_style: ResponseStyle::default(),
+            translator: None,
+            response_language: None,
+            apply_filters: true,
+            glossary: Glossary::default(),
+            scratchpad: Scratchpad::default(),
+            chatbox_highlighter: MemoizedEasymarkHighlighter::default(),
+            stop_generating: Arc::new(AtomicBool::new(false)),
+            virtual_list: {
+                let mut list = VirtualList::new();
                 list.check_for_resize(false);
                 list
             },
@@ -472,21 +1295,234 @@ impl Default for Chat {
     }
 }
 
+/// Widest the conversation gets in focus mode
+const FOCUS_READING_WIDTH: f32 = 760.0;
+/// Text is this much larger in focus mode
+const FOCUS_TEXT_SCALE: f32 = 1.15;
+
+/// Error returned when the model replies with no parts or only empty text
+const EMPTY_RESPONSE: &str = "The model returned an empty response.";
+
+/// Appended to the prompt when retrying after an empty response
+const EMPTY_RESPONSE_NUDGE: &str =
+    "\n\n(Your previous reply to this was empty. Please answer the message above.)";
+
+/// Sent after a partial answer to have the model write the rest of it
+const CONTINUE_INSTRUCTION: &str = "Continue your last reply exactly where it stopped. \
+    Don't repeat any of it and don't add an introduction, answer with only the text that follows.";
+
+/// How many times in a row the model may call tools before we stop answering them
+const MAX_TOOL_ROUNDS: usize = 5;
+
+/// URLs fetched by the URL context tool, listed in the candidate metadata
+fn retrieved_urls(response: &impl serde::Serialize) -> Vec<String> {
+    let Ok(value) = serde_json::to_value(response) else {
+        return Vec::new();
+    };
+    value
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|candidate| {
+            candidate
+                .pointer("/urlContextMetadata/urlMetadata")?
+                .as_array()
+        })
+        .flatten()
+        .filter(|meta| {
+            meta.get("urlRetrievalStatus")
+                .and_then(|s| s.as_str())
+                .is_none_or(|s| s == "URL_RETRIEVAL_STATUS_SUCCESS")
+        })
+        .filter_map(|meta| Some(meta.get("retrievedUrl")?.as_str()?.to_owned()))
+        .collect()
+}
+
+/// Markdown for the code the model ran with the code execution tool, or its output
+fn code_execution_markdown(part: &Part) -> Option<String> {
+    let value = serde_json::to_value(part).ok()?;
+    if let Some(code) = value.get("executableCode") {
+        let language = code
+            .get("language")
+            .and_then(|l| l.as_str())
+            .unwrap_or("python")
+            .to_lowercase();
+        let code = code.get("code")?.as_str()?;
+        Some(format!(
+            "\n\n**▶ Ran code**\n```{language}\n{code}\n```\n\n"
+        ))
+    } else if let Some(result) = value.get("codeExecutionResult") {
+        let outcome = result
+            .get("outcome")
+            .and_then(|o| o.as_str())
+            .unwrap_or_default();
+        let output = result
+            .get("output")
+            .and_then(|o| o.as_str())
+            .unwrap_or_default();
+        let heading = if outcome.is_empty() || outcome == "OUTCOME_OK" {
+            "**Output**".to_owned()
+        } else {
+            format!(
+                "**Output** ({})",
+                outcome.trim_start_matches("OUTCOME_").to_lowercase()
+            )
+        };
+        Some(format!(
+            "{heading}\n```text\n{}\n```\n\n",
+            output.trim_end()
+        ))
+    } else {
+        None
+    }
+}
+
+/// Whether a response part carries anything to show
+fn has_content(part: &Part) -> bool {
+    !matches!(part, Part::text(info) if info.text().trim().is_empty())
+}
+
+/// One turn of the conversation sent to the model
+#[derive(Debug, Clone)]
+struct Turn {
+    is_user: bool,
+    parts: Vec<Part>,
+}
+
+/// A response, or one chunk of a streamed one
+struct ResponseChunk {
+    parts: Vec<Part>,
+    /// Pages fetched by the URL context tool
+    urls: Vec<String>,
+}
+
+type ChunkStream<'a> = Pin<Box<dyn Stream<Item = Result<ResponseChunk, String>> + Send + 'a>>;
+
+/// Where completion requests are sent
+enum Provider {
+    /// The Gemini API, through the API client
+    Gemini(Gemini),
+    /// The custom base URL and headers from the settings
+    Endpoint(crate::endpoint::EndpointClient),
+    /// A recorded stream played back, for tests and the developer menu
+    Replay(crate::replay::Replay),
+}
+
+impl Provider {
+    fn session(turns: &[Turn]) -> Session {
+        let mut session = Session::new(turns.len());
+        for turn in turns {
+            if turn.is_user {
+                session.ask(turn.parts.clone());
+            } else {
+                session.reply(turn.parts.clone());
+            }
+        }
+        session
+    }
+
+    /// The turns as the `contents` of a request body
+    fn contents(turns: &[Turn]) -> serde_json::Value {
+        turns
+            .iter()
+            .map(|turn| {
+                serde_json::json!({
+                    "role": if turn.is_user { "user" } else { "model" },
+                    "parts": turn.parts,
+                })
+            })
+            .collect()
+    }
+
+    fn json_chunk(response: &serde_json::Value) -> Result<ResponseChunk, String> {
+        Ok(ResponseChunk {
+            parts: crate::endpoint::response_parts(response).map_err(|e| format!("{e:#}"))?,
+            urls: retrieved_urls(response),
+        })
+    }
+
+    async fn ask_as_stream(&self, turns: &[Turn]) -> Result<ChunkStream<'_>, String> {
+        match self {
+            Self::Gemini(gemini) => {
+                let stream = gemini
+                    .ask_as_stream(Self::session(turns))
+                    .await
+                    .map_err(|err| err.1.to_string())?;
+                Ok(Box::pin(stream.map(|res| {
+                    let res = res.map_err(|e| e.to_string())?;
+                    let mut parts = Vec::new();
+                    for part in res.get_parts() {
+                        parts.push(part.clone());
+                    }
+                    Ok(ResponseChunk {
+                        parts,
+                        urls: retrieved_urls(&res),
+                    })
+                })))
+            }
+            Self::Endpoint(client) => {
+                let stream = client.ask_as_stream(Self::contents(turns)).await?;
+                Ok(Box::pin(stream.map(|res| {
+                    res.and_then(|response| Self::json_chunk(&response))
+                })))
+            }
+            Self::Replay(replay) => {
+                let stream = replay.ask_as_stream(Self::contents(turns));
+                Ok(Box::pin(stream.map(|res| {
+                    res.map(|parts| ResponseChunk {
+                        parts,
+                        urls: Vec::new(),
+                    })
+                })))
+            }
+        }
+    }
+
+    async fn ask(&self, turns: &[Turn]) -> Result<ResponseChunk, String> {
+        match self {
+            Self::Gemini(gemini) => {
+                let response = gemini
+                    .ask(&mut Self::session(turns))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let mut parts = Vec::new();
+                for part in response.get_parts() {
+                    parts.push(part.clone());
+                }
+                Ok(ResponseChunk {
+                    parts,
+                    urls: retrieved_urls(&response),
+                })
+            }
+            Self::Endpoint(client) => Self::json_chunk(&client.ask(Self::contents(turns)).await?),
+            Self::Replay(replay) => Ok(ResponseChunk {
+                parts: replay.ask(Self::contents(turns)).await?,
+                urls: Vec::new(),
+            }),
+        }
+    }
+}
+
 async fn request_completion(
-    gemini: Gemini,
+    provider: Provider,
     messages: Vec<Message>,
     handle: &CompletionFlowerHandle,
     stop_generating: Arc<AtomicBool>,
     index: usize,
     use_streaming: bool,
+    tools: ToolCaller,
+    ocr: OcrSettings,
+    conversion: ConversionOptions,
+    timeouts: RequestTimeouts,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     log::info!(
         "requesting completion... (history length: {})",
         messages.len()
     );
 
-    // Build a gemini-client-api session from the message history
-    let mut gemini_session = Session::new(messages.len());
+    // The conversation as the model sees it, built from the message history
+    let mut turns = Vec::new();
 
     // Regenerate from a certain point if needed
     let messages_to_process = if messages.get(index).map_or(false, |m| m.is_generating) {
@@ -495,6 +1531,60 @@ async fn request_completion(
         &messages
     };
 
+    // Convert all attachments up front and concurrently, each path only once
+    let mut conversions = Vec::new();
+    let mut seen_files = HashSet::new();
+    for message in messages_to_process
+        .iter()
+        .filter(|m| !m.is_thought && !m.excluded)
+    {
+        for file_path in &message.files {
+            if seen_files.insert(file_path) {
+                let path = file_path.clone();
+                conversions.push(tokio::spawn(async move {
+                    let part = convert_file_to_part(&path, conversion)
+                        .await
+                        .map_err(|e| e.to_string());
+                    (path, part)
+                }));
+            }
+        }
+    }
+    let mut recognitions = Vec::new();
+    if ocr.enabled {
+        for path in seen_files
+            .iter()
+            .filter(|path| crate::ocr::is_image(path))
+            .map(|path| (*path).clone())
+        {
+            let languages = ocr.languages.clone();
+            recognitions.push(tokio::spawn(async move {
+                let text = crate::ocr::recognize(&path, &languages).await;
+                (path, text)
+            }));
+        }
+    }
+    let mut converted_files = HashMap::new();
+    for conversion in conversions {
+        match conversion.await {
+            Ok((path, part)) => {
+                converted_files.insert(path, part);
+            }
+            Err(e) => log::error!("file conversion task failed: {e}"),
+        }
+    }
+    let mut recognized_text = HashMap::new();
+    for recognition in recognitions {
+        match recognition.await {
+            Ok((path, Ok(text))) if !text.is_empty() => {
+                recognized_text.insert(path, text);
+            }
+            Ok((_, Ok(_))) => (),
+            Ok((path, Err(e))) => log::warn!("OCR failed for {}: {e:#}", path.display()),
+            Err(e) => log::error!("OCR task failed: {e}"),
+        }
+    }
+
     // A buffer to hold parts for the current consecutive group of messages.
     let mut parts_buffer = Vec::new();
     // Tracks the author of the current group. `None` means we're at the start.
@@ -502,7 +1592,10 @@ async fn request_completion(
 
     for message in messages_to_process {
         // Skip messages that should not be part of the conversation history.
-        if message.is_thought || (message.content.is_empty() && message.files.is_empty()) {
+        if message.is_thought
+            || message.excluded
+            || (message.content.is_empty() && message.files.is_empty())
+        {
             continue;
         }
 
@@ -517,12 +1610,11 @@ async fn request_completion(
             // Use `std::mem::take` to efficiently swap the buffer with an empty Vec.
             let completed_parts = std::mem::take(&mut parts_buffer);
             if !completed_parts.is_empty() {
-                if current_author_is_user.unwrap() {
+                turns.push(Turn {
                     // unwrap is safe here
-                    gemini_session.ask(completed_parts);
-                } else {
-                    gemini_session.reply(completed_parts);
-                }
+                    is_user: current_author_is_user.unwrap(),
+                    parts: completed_parts,
+                });
             }
         }
 
@@ -532,18 +1624,26 @@ async fn request_completion(
         current_author_is_user = Some(message_author_is_user);
 
         for file_path in &message.files {
-            match convert_file_to_part(file_path).await {
-                Ok(part) => {
-                    parts_buffer.push(Part::text(
-                        format!(
-                            "File with name: {}",
-                            file_path.file_name().unwrap_or_default().to_string_lossy()
-                        )
-                        .into(),
-                    ));
-                    parts_buffer.push(part)
+            let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+            let recognized = recognized_text.get(file_path);
+            if let Some(text) = recognized {
+                parts_buffer.push(Part::text(
+                    format!("Text recognized in {file_name}:\n{text}").into(),
+                ));
+                if ocr.text_only {
+                    continue;
+                }
+            }
+            match converted_files.get(file_path) {
+                Some(Ok(part)) => {
+                    parts_buffer.push(Part::text(format!("File with name: {file_name}").into()));
+                    parts_buffer.push(part.clone())
+                }
+                // also shown in the UI as a toast and a badge on the attachment
+                Some(Err(e)) => {
+                    log::error!("Failed to convert file {}: {}", file_path.display(), e)
                 }
-                Err(e) => log::error!("Failed to convert file {}: {}", file_path.display(), e), // todo say to ui
+                None => (),
             }
         }
 
@@ -556,90 +1656,169 @@ async fn request_completion(
     // We need to submit this final batch.
     if !parts_buffer.is_empty() {
         if let Some(is_user) = current_author_is_user {
-            if is_user {
-                gemini_session.ask(parts_buffer);
-            } else {
-                gemini_session.reply(parts_buffer);
-            }
+            turns.push(Turn {
+                is_user,
+                parts: parts_buffer,
+            });
         }
     }
 
-    dbg!(&gemini_session);
-
-    // Handle the prepended text for regeneration // TODO BROKEN!
+    // A partial answer (prepended text, or a response that was cut off) is
+    // sent as the model's last turn, and the model is asked to carry on from
+    // it. Only the continuation comes back and is appended to it.
     if let Some(msg) = messages.get(index) {
-        if !msg.content.is_empty() {
-            gemini_session.reply(vec![Part::text(msg.content.clone().into())]);
+        if msg.is_generating && !msg.content.is_empty() {
+            turns.push(Turn {
+                is_user: false,
+                parts: vec![Part::text(msg.content.clone().into())],
+            });
+            turns.push(Turn {
+                is_user: true,
+                parts: vec![Part::text(CONTINUE_INSTRUCTION.to_owned().into())],
+            });
         }
     }
 
     let mut response_text = String::new();
-    if use_streaming {
-        let mut stream = gemini
-            .ask_as_stream(gemini_session)
-            .await
-            .map_err(|err| err.1)?;
-
-        log::info!("reading response...");
-        while let Some(Ok(res)) = stream.next().await {
-            if stop_generating.load(Ordering::SeqCst) {
-                log::info!("stopping generation");
-                drop(stream);
-                stop_generating.store(false, Ordering::SeqCst);
-                break;
-            }
+    let mut received_content = false;
+    let mut tool_rounds = 0;
+    loop {
+        handle.send((index, CompletionEvent::Connecting));
+        // parts of this round, sent back along with the tool results
+        let mut model_parts = Vec::new();
+        let mut function_calls = Vec::new();
+        let mut stopped = false;
 
-            for part in res.get_parts() {
-                handle.send((index, part.clone()));
-                match part {
-                    Part::text(info) => {
+        if use_streaming {
+            let mut stream = timeout(
+                timeouts.connect(),
+                "response",
+                provider.ask_as_stream(&turns),
+            )
+            .await??;
+
+            log::info!("reading response...");
+            while let Some(res) = timeout(timeouts.read(), "new data", stream.next()).await? {
+                if stop_generating.load(Ordering::SeqCst) {
+                    log::info!("stopping generation");
+                    drop(stream);
+                    stop_generating.store(false, Ordering::SeqCst);
+                    stopped = true;
+                    break;
+                }
+                let res = match res {
+                    Ok(res) => res,
+                    Err(e) => {
+                        log::error!(
+                            "stream interrupted after {} bytes: {e}",
+                            response_text.len()
+                        );
+                        return Err(format!("The response stream was interrupted: {e}").into());
+                    }
+                };
+
+                if !res.urls.is_empty() {
+                    handle.send((index, CompletionEvent::RetrievedUrls(res.urls)));
+                }
+                for part in res.parts {
+                    received_content |= has_content(&part);
+                    handle.send((index, CompletionEvent::Part(part.clone())));
+                    if let Part::text(info) = &part {
                         response_text += info.text();
                     }
-                    _ => {}
+                    function_calls.extend(crate::http_tools::function_call(&part));
+                    model_parts.push(part);
                 }
             }
-        }
-    } else {
-        let cancellation_checker = async {
-            loop {
-                if stop_generating.load(Ordering::SeqCst) {
-                    break;
+        } else {
+            let cancellation_checker = async {
+                loop {
+                    if stop_generating.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
                 }
-                tokio::time::sleep(std::time::Duration::from_millis(300)).await;
-            }
-        };
+            };
 
-        log::info!("sending non-streaming request...");
-        tokio::select! {  // todo some working bullshit
-            biased;
+            log::info!("sending non-streaming request...");
+            tokio::select! {  // todo some working bullshit
+                biased;
 
-            _ = cancellation_checker => {
-                log::info!("non-streaming generation cancelled by user.");
-                stop_generating.store(false, Ordering::SeqCst);
-            }
+                _ = cancellation_checker => {
+                    log::info!("non-streaming generation cancelled by user.");
+                    stop_generating.store(false, Ordering::SeqCst);
+                    stopped = true;
+                }
 
-            result = gemini.ask(&mut gemini_session) => {
-                match result {
-                    Ok(response) => {
-                        log::info!("reading non-streamed response...");
-                        let mut response_text = String::new();
-                        for part in response.get_parts() {
-                            handle.send((index, part.clone()));
-                            if let Part::text(info) = part {
-                                response_text += info.text();
-                            }
+                result = timeout(
+                    timeouts.connect() + timeouts.read(),
+                    "response",
+                    provider.ask(&turns),
+                ) => {
+                    let response = result??;
+                    log::info!("reading non-streamed response...");
+                    if !response.urls.is_empty() {
+                        handle.send((index, CompletionEvent::RetrievedUrls(response.urls)));
+                    }
+                    for part in response.parts {
+                        received_content |= has_content(&part);
+                        handle.send((index, CompletionEvent::Part(part.clone())));
+                        if let Part::text(info) = &part {
+                            response_text += info.text();
                         }
-                        log::info!(
-                            "non-streaming completion request complete, response length: {}",
-                            response_text.len()
-                        );
-                        handle.success((index, response_text));
-                        return Ok(());
+                        function_calls.extend(crate::http_tools::function_call(&part));
+                        model_parts.push(part);
                     }
-                    Err(err) => return Err(err)?,
                 }
             }
         }
+
+        if stopped {
+            // stopped on purpose, an empty response is expected
+            received_content = true;
+            break;
+        }
+        if function_calls.is_empty() {
+            break;
+        }
+        if tool_rounds >= MAX_TOOL_ROUNDS {
+            log::warn!("the model kept calling tools, stopping after {tool_rounds} rounds");
+            break;
+        }
+        tool_rounds += 1;
+        // a tool may take long to answer, or wait for the user to confirm it
+        let stop_checker = async {
+            while !stop_generating.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+        };
+        let results = tokio::select! {
+            biased;
+
+            _ = stop_checker => {
+                log::info!("tool calls cancelled by user.");
+                stop_generating.store(false, Ordering::SeqCst);
+                received_content = true;
+                break;
+            }
+
+            results = tools.call_all(function_calls, |confirmation| {
+                handle.send((index, CompletionEvent::ConfirmToolCall(confirmation)));
+            }) => results,
+        };
+        turns.push(Turn {
+            is_user: false,
+            parts: model_parts,
+        });
+        turns.push(Turn {
+            is_user: true,
+            parts: results,
+        });
+    }
+
+    if !received_content {
+        log::warn!("the model returned an empty response");
+        return Err(EMPTY_RESPONSE.into());
     }
 
     log::info!(
@@ -650,22 +1829,314 @@ async fn request_completion(
     Ok(())
 }
 
+/// Appends a streamed text part to the response at the end of `messages`.
+///
+/// The model may switch between thinking and answering any number of times,
+/// every switch closes the current message and starts a new one, so the
+/// thought blocks and answers stay in the order they were generated.
+fn push_response_text(messages: &mut Vec<Message>, text: &str, thought: bool) {
+    if text.is_empty() {
+        return;
+    }
+    // we always add a placeholder message in send_message before running
+    let last = messages.last_mut().unwrap();
+    if last.is_thought == thought || (last.content.is_empty() && last.files.is_empty()) {
+        last.is_thought = thought;
+        last.content.push_str(text);
+        if thought {
+            let elapsed = last.requested_at.elapsed();
+            crate::thoughts::track_steps(&last.content, &mut last.thought_steps, elapsed);
+        }
+        return;
+    }
+
+    last.is_generating = false;
+    last.generation_time = Some(last.requested_at.elapsed());
+
+    let mut message = Message::assistant(text.to_owned(), last.model);
+    message.is_thought = thought;
+    if thought {
+        crate::thoughts::track_steps(text, &mut message.thought_steps, Duration::ZERO);
+    }
+    messages.push(message);
+}
+
+/// Attaches a file generated by the model to the response at the end of `messages`
+fn push_response_file(messages: &mut Vec<Message>, path: PathBuf) {
+    let last = messages.last_mut().unwrap();
+    if !last.is_thought {
+        last.files.push(path);
+        return;
+    }
+
+    last.is_generating = false;
+    last.generation_time = Some(last.requested_at.elapsed());
+
+    let mut message = Message::assistant(String::new(), last.model);
+    message.files.push(path);
+    messages.push(message);
+}
+
+/// Client for the custom endpoint in the settings, with the parts of the
+/// request body the API client would otherwise add
+fn endpoint_client(
+    settings: &Settings,
+    model_picker: &ModelPicker,
+    tools: &[Tool],
+    timeouts: RequestTimeouts,
+) -> Result<crate::endpoint::EndpointClient> {
+    let mut body = serde_json::Map::new();
+    if let Some(instruction) = model_picker.system_instruction() {
+        body.insert(
+            "systemInstruction".to_owned(),
+            serde_json::json!({ "parts": [{ "text": instruction }] }),
+        );
+    }
+    body.insert(
+        "generationConfig".to_owned(),
+        model_picker.get_generation_config(),
+    );
+    body.insert(
+        "safetySettings".to_owned(),
+        serde_json::to_value(SAFETY_SETTINGS)?,
+    );
+    if !tools.is_empty() {
+        body.insert("tools".to_owned(), serde_json::to_value(tools)?);
+    }
+    crate::endpoint::EndpointClient::new(
+        &settings.endpoint,
+        settings.proxy_path.as_deref(),
+        timeouts,
+        &settings.api_key,
+        &model_picker.selected.to_string(),
+        body,
+    )
+}
+
+/// Spawns a completion request for the last message of `messages`
+fn spawn_completion_task(
+    handle: CompletionFlowerHandle,
+    settings: &Settings,
+    model_picker: &ModelPicker,
+    mut messages: Vec<Message>,
+    stop_generation: Arc<AtomicBool>,
+) {
+    let index = messages.len() - 1;
+
+    if settings.include_thoughts_in_history {
+        for msg in &mut messages {
+            if msg.is_thought {
+                msg.is_thought = false;
+                msg.content.insert_str(0, "MY INNER REFLECTIONS: ");
+                msg.content
+                    .push_str(r"--- end of inner reflections ---\r\n")
+            }
+        }
+    }
+
+    // gateways may authenticate with a header instead
+    let no_api_key = settings.api_key.is_empty() && !settings.endpoint.is_custom();
+    let use_streaming = settings.use_streaming;
+    let ocr = settings.ocr.clone();
+    let conversion = settings.conversion_options();
+    let timeouts = settings.timeouts;
+    let tools = ToolCaller::new(
+        settings.http_tools.clone(),
+        settings.proxy_path.as_deref(),
+        timeouts,
+    );
+
+    // the API client's own limit is only a fallback for the timeouts below
+    let mut gemini = model_picker
+        .create_client_with_timeout(
+            &settings.api_key,
+            settings.proxy_path.clone(),
+            timeouts.connect() + timeouts.read() * 2,
+        )
+        .set_safety_settings(Some(SAFETY_SETTINGS.to_vec()));
+    let mut enabled_tools = Vec::new();
+    enabled_tools.extend(crate::http_tools::tool_declarations(&settings.http_tools));
+    for (enabled, tool) in [
+        (model_picker.code_execution, "codeExecution"),
+        (model_picker.url_context, "urlContext"),
+    ] {
+        if !enabled {
+            continue;
+        }
+        match serde_json::from_value(serde_json::json!({ tool: {} })) {
+            Ok(tool) => enabled_tools.push(tool),
+            Err(e) => log::error!("failed to enable the {tool} tool: {e}"),
+        }
+    }
+    let provider = if settings.endpoint.is_custom() {
+        endpoint_client(settings, model_picker, &enabled_tools, timeouts)
+            .map(Provider::Endpoint)
+            .map_err(|e| format!("Invalid API endpoint settings: {e:#}"))
+    } else {
+        if !enabled_tools.is_empty() {
+            gemini = gemini.set_tools(Some(enabled_tools));
+        }
+        Ok(Provider::Gemini(gemini))
+    };
+
+    tokio::spawn(async move {
+        handle.activate();
+
+        if no_api_key {
+            handle.error((index, "API key not set.".to_string()));
+            return;
+        }
+        let provider = match provider {
+            Ok(provider) => provider,
+            Err(e) => {
+                handle.error((index, e));
+                return;
+            }
+        };
+        let tools = match tools {
+            Ok(tools) => tools,
+            Err(e) => {
+                handle.error((index, format!("Invalid proxy settings: {e:#}")));
+                return;
+            }
+        };
+
+        let _ = request_completion(
+            provider,
+            messages,
+            &handle,
+            stop_generation,
+            index,
+            use_streaming,
+            tools,
+            ocr,
+            conversion,
+            timeouts,
+        )
+        .await
+        .map_err(|e| {
+            log::error!("failed to request completion: {e}");
+            handle.error((index, e.to_string()));
+        });
+    });
+}
+
+/// Runs the completion pipeline for the last message of `messages` with a
+/// recorded stream in place of the API
+fn spawn_replay_task(
+    handle: CompletionFlowerHandle,
+    messages: Vec<Message>,
+    stop_generation: Arc<AtomicBool>,
+    fixture: PathBuf,
+    delay: Duration,
+    use_streaming: bool,
+) -> Arc<std::sync::Mutex<Vec<serde_json::Value>>> {
+    let index = messages.len() - 1;
+    let requests = Arc::default();
+    let sent = Arc::clone(&requests);
+    tokio::spawn(async move {
+        handle.activate();
+        let events = match crate::replay::load(&fixture).await {
+            Ok(events) => events,
+            Err(e) => {
+                handle.error((index, format!("{e:#}")));
+                return;
+            }
+        };
+        let mut replay = crate::replay::Replay::new(events, delay);
+        replay.requests = sent;
+
+        let _ = request_completion(
+            Provider::Replay(replay),
+            messages,
+            &handle,
+            stop_generation,
+            index,
+            use_streaming,
+            ToolCaller::default(),
+            OcrSettings::default(),
+            ConversionOptions::default(),
+            RequestTimeouts::default(),
+        )
+        .await
+        .map_err(|e| handle.error((index, e.to_string())));
+    });
+    requests
+}
+
+/// Sends a single prompt outside of the GUI, calling `on_text` with every
+/// streamed text part (and whether it is a thought)
+pub async fn ask_headless(
+    settings: &Settings,
+    model_picker: ModelPicker,
+    prompt: String,
+    files: Vec<PathBuf>,
+    mut on_text: impl FnMut(&str, bool),
+) -> Result<(), String> {
+    let model = model_picker.selected;
+    let messages = vec![
+        Message::user(prompt, model, files),
+        Message::assistant(String::new(), model),
+    ];
+    let mut flower = CompletionFlower::new(1);
+    spawn_completion_task(
+        flower.handle(),
+        settings,
+        &model_picker,
+        messages,
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    loop {
+        let mut result = None;
+        flower
+            .extract(|(_, event)| {
+                if let CompletionEvent::Part(Part::text(data)) = event {
+                    on_text(data.text(), *data.thought());
+                }
+            })
+            .finalize(|res| {
+                result = Some(res.map(|_| ()).map_err(|e| match e {
+                    Compact::Panicked(e) => format!("Tokio task panicked: {e}"),
+                    Compact::Suppose((_, e)) => e,
+                }));
+            });
+        if let Some(result) = result {
+            return result;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
 pub enum ChatExportFormat {
     #[default]
     Plaintext,
     Json,
     Ron,
+    /// Jupyter notebook
+    Notebook,
+    /// `conversations` of `human` and `gpt` turns, as in LLM datasets
+    ShareGpt,
 }
 
 impl std::fmt::Display for ChatExportFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            Self::ShareGpt => f.write_str("ShareGPT"),
+            _ => write!(f, "{self:?}"),
+        }
     }
 }
 
 impl ChatExportFormat {
-    pub const ALL: [Self; 3] = [Self::Plaintext, Self::Json, Self::Ron];
+    pub const ALL: [Self; 5] = [
+        Self::Plaintext,
+        Self::Json,
+        Self::Ron,
+        Self::Notebook,
+        Self::ShareGpt,
+    ];
 
     #[inline]
     pub const fn extensions(self) -> &'static [&'static str] {
@@ -673,12 +2144,33 @@ impl ChatExportFormat {
             Self::Plaintext => &["txt"],
             Self::Json => &["json"],
             Self::Ron => &["ron"],
+            Self::Notebook => &["ipynb"],
+            Self::ShareGpt => &["json"],
         }
     }
 }
 
+/// The conversation in the ShareGPT format, a list with one conversation
+pub fn sharegpt(messages: &[Message], system_prompt: Option<&str>) -> serde_json::Value {
+    let system = system_prompt
+        .filter(|prompt| !prompt.trim().is_empty())
+        .map(|prompt| serde_json::json!({ "from": "system", "value": prompt }));
+    let turns = messages
+        .iter()
+        .filter(|m| !m.is_thought && !m.is_error && !m.excluded && !m.content.is_empty())
+        .map(|m| {
+            serde_json::json!({
+                "from": if m.is_user() { "human" } else { "gpt" },
+                "value": m.content,
+            })
+        });
+    let conversations: Vec<serde_json::Value> = system.into_iter().chain(turns).collect();
+    serde_json::json!([{ "conversations": conversations }])
+}
+
 pub async fn export_messages(
     messages: Vec<Message>,
+    system_prompt: Option<String>,
     format: ChatExportFormat,
     task: impl std::future::Future<Output = Option<rfd::FileHandle>>,
 ) -> Result<egui_notify::Toast> {
@@ -686,6 +2178,7 @@ pub async fn export_messages(
         log::info!("export cancelled");
         return Ok(egui_notify::Toast::info("Export cancelled"));
     };
+    let _writing = crate::shutdown::begin_write();
     log::info!(
         "exporting {} messages to {file:?} (format: {format:?})...",
         messages.len()
@@ -713,6 +2206,17 @@ pub async fn export_messages(
         ChatExportFormat::Ron => {
             ron::Options::default().to_io_writer_pretty(&mut f, &messages, Default::default())?;
         }
+        ChatExportFormat::Notebook => {
+            let turns: Vec<(bool, &str)> = messages
+                .iter()
+                .filter(|m| !m.is_thought && !m.is_error && !m.excluded && !m.content.is_empty())
+                .map(|m| (m.is_user(), m.content.as_str()))
+                .collect();
+            serde_json::to_writer_pretty(&mut f, &crate::notebook::notebook(&turns))?;
+        }
+        ChatExportFormat::ShareGpt => {
+            serde_json::to_writer_pretty(&mut f, &sharegpt(&messages, system_prompt.as_deref()))?;
+        }
     }
 
     f.flush().context("failed to flush writer")?;
@@ -725,6 +2229,21 @@ pub async fn export_messages(
     )))
 }
 
+/// Runs a prompt about to be sent through the plugins' slash commands and
+/// `on_send`. `None` when a command failed, it was reported
+fn run_plugins(prompt: String) -> Option<String> {
+    #[cfg(feature = "scripting")]
+    let prompt = match crate::plugins::run_command(&prompt) {
+        Some(Ok(prompt)) => crate::plugins::transform_prompt(prompt),
+        Some(Err(e)) => {
+            crate::notify::report_error("plugins", e);
+            return None;
+        }
+        None => crate::plugins::transform_prompt(prompt),
+    };
+    Some(prompt)
+}
+
 fn make_summary(prompt: &str) -> String {
     const MAX_SUMMARY_LENGTH: usize = 24;
     let mut summary = String::with_capacity(MAX_SUMMARY_LENGTH);
@@ -745,10 +2264,60 @@ fn make_summary(prompt: &str) -> String {
     summary
 }
 
+/// Emojis and the space separated words in the first prompt of a chat that
+/// pick them, the first match wins
+const TITLE_EMOJIS: &[(&str, &str)] = &[
+    ("🦀", "rust cargo crate crab crabs"),
+    ("🐍", "python django pandas numpy"),
+    ("🐛", "bug error crash panic exception debug fix"),
+    (
+        "💻",
+        "code function snippet script program javascript typescript sql regex api",
+    ),
+    ("🌐", "server website web html css http"),
+    ("🧮", "math equation calculate formula proof integral"),
+    ("🌍", "translate translation language french spanish german"),
+    ("✉", "email letter reply message"),
+    ("📝", "write essay story poem summarize summary article"),
+    ("📜", "history historical empire roman ancient war"),
+    ("🔬", "science physics chemistry biology experiment"),
+    ("🍳", "recipe cook cooking bake food dinner"),
+    ("✈", "travel trip flight vacation visit"),
+    ("💪", "workout exercise fitness diet health"),
+    ("💰", "money budget finance invest price tax"),
+    ("🎵", "music song lyrics guitar piano"),
+    ("🎁", "gift present birthday"),
+    ("😂", "joke jokes funny meme"),
+    ("🎨", "design draw image logo color art"),
+    ("💡", "idea ideas brainstorm suggest fact"),
+];
+
+/// Picks an emoji for a chat title from the words in the prompt
+fn pick_title_emoji(prompt: &str) -> Option<&'static str> {
+    let words: Vec<String> = prompt
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    TITLE_EMOJIS
+        .iter()
+        .find(|(_, keywords)| {
+            keywords
+                .split(' ')
+                .any(|keyword| words.iter().any(|word| word == keyword))
+        })
+        .map(|(emoji, _)| *emoji)
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ChatAction {
     None,
     PickFiles { id: usize },
+    PickFolder { id: usize },
+    PickGitContext { id: usize, context: GitContext },
+    SaveStructuredOutput { message_idx: usize },
+    MakeFlashcards { name: String, text: String },
+    EnableStreaming,
 }
 
 impl Chat {
@@ -766,113 +2335,1292 @@ impl Chat {
         self.flower.id()
     }
 
-    fn send_message(&mut self, settings: &Settings) {
-        if self.chatbox.is_empty() && self.files.is_empty() {
-            return;
+    /// The id that stays the same across restarts and synced copies, given
+    /// on first use
+    pub fn sync_id(&mut self) -> &str {
+        if self.sync_id.is_empty() {
+            self.sync_id = format!("{:016x}", fastrand::u64(..));
         }
+        &self.sync_id
+    }
 
-        // remove old error messages
+    /// Returns the manual title if set, otherwise the auto-generated summary
+    pub fn display_name(&self) -> &str {
+        match &self.title {
+            Some(title) if !title.is_empty() => title,
+            _ if !self.summary.is_empty() => &self.summary,
+            _ => "New Chat",
+        }
+    }
+
+    /// Returns the display name prefixed with the chat icon, if any
+    pub fn decorated_name(&self) -> String {
+        match &self.icon {
+            Some(icon) if !icon.is_empty() => format!("{icon} {}", self.display_name()),
+            _ => self.display_name().to_owned(),
+        }
+    }
+
+    /// Removes the message history, keeping the title, model settings and attachments
+    pub fn clear_messages(&mut self) {
+        self.prepend_buf.clear();
+        self.cleared_messages = Some(std::mem::take(&mut *self.messages));
+        self.virtual_list.reset();
+    }
+
+    #[inline]
+    pub fn can_undo_clear(&self) -> bool {
+        self.cleared_messages.is_some()
+    }
+
+    /// Restores the history removed by [`Chat::clear_messages`], followed by
+    /// anything sent since
+    pub fn undo_clear(&mut self) {
+        if let Some(mut messages) = self.cleared_messages.take() {
+            messages.append(&mut self.messages);
+            *self.messages = messages;
+            self.virtual_list.reset();
+        }
+    }
+
+    /// Appends the history of another chat, keeping the messages in chronological order
+    pub fn merge(&mut self, other: Chat) {
+        self.messages.extend(other.messages.into_vec());
+        self.messages.sort_by_key(|m| m.time);
+        self.virtual_list.reset();
+        self.role_order_warning = self.check_role_order();
+        for file in other.files {
+            if !self.files.contains(&file) {
+                self.files.push(file);
+            }
+        }
+    }
+
+    /// Applies finished summaries, and shows the token diet window if it's open
+    fn show_token_diet(&mut self, ctx: &egui::Context, settings: &Settings) {
+        for (idx, original_len, result) in self.token_diet.take_summaries() {
+            match result {
+                Ok(summary) if !summary.is_empty() => {
+                    // the message may have been edited or moved in the meantime
+                    match self
+                        .messages
+                        .get_mut(idx)
+                        .filter(|m| m.content.len() == original_len)
+                    {
+                        Some(message) => {
+                            message.content =
+                                format!("{}{summary}", crate::token_diet::SUMMARY_PREFIX);
+                        }
+                        None => log::warn!("message {idx} changed while it was summarized"),
+                    }
+                }
+                Ok(_) => log::warn!("the model returned an empty summary for message {idx}"),
+                Err(e) => log::error!("failed to summarize message {idx}: {e}"),
+            }
+        }
+        if !self.token_diet.open {
+            return;
+        }
+
+        let mut entries = Vec::new();
+        for (idx, message) in self.messages.iter().enumerate() {
+            if message.is_thought || message.is_error || message.excluded {
+                continue;
+            }
+            let author = if message.is_user() { "You" } else { "Model" };
+            if !message.content.is_empty() {
+                let preview: String = message.content.chars().take(80).collect();
+                entries.push(crate::token_diet::Entry {
+                    message: idx,
+                    file: None,
+                    label: format!("#{} {author}: {}", idx + 1, preview.replace('\n', " ")),
+                    tokens: crate::analytics::estimate_tokens(&message.content),
+                });
+            }
+            for (file_idx, path) in message.files.iter().enumerate() {
+                let Some(tokens) = crate::token_diet::file_tokens(path) else {
+                    continue;
+                };
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                entries.push(crate::token_diet::Entry {
+                    message: idx,
+                    file: Some(file_idx),
+                    label: format!("#{} 📎 {name}", idx + 1),
+                    tokens,
+                });
+            }
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.tokens));
+        let total = entries.iter().map(|entry| entry.tokens).sum();
+
+        match self.token_diet.show(ctx, &entries, total) {
+            Some(DietAction::Exclude(idx)) => self.messages[idx].excluded = true,
+            Some(DietAction::RemoveFile { message, file }) => {
+                self.messages[message].files.remove(file);
+            }
+            Some(DietAction::Summarize(idx)) => {
+                let text = self.messages[idx].content.clone();
+                self.token_diet.summarize(ctx, settings, idx, text);
+            }
+            None => (),
+        }
+    }
+
+    /// Summarizes the messages before the last few prompts, for `/compress`
+    fn compress_history(&mut self, ctx: &egui::Context, settings: &Settings) {
+        if self.compressor.is_pending() || self.messages.iter().any(|m| m.is_generating) {
+            crate::notify::report_error(
+                "compress",
+                "Wait for the answer or the compression in progress to finish first",
+            );
+            return;
+        }
+        let Some(count) = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.is_user() && !m.is_error && m.compressed.is_empty())
+            .map(|(idx, _)| idx)
+            .rev()
+            .nth(crate::compress::KEEP_RECENT_PROMPTS - 1)
+        else {
+            crate::notify::report_error("compress", "The chat is too short to compress");
+            return;
+        };
+        let older = &self.messages[..count];
+        if older.iter().all(|m| !m.compressed.is_empty() || m.is_error) {
+            crate::notify::report_error("compress", "The chat is too short to compress");
+            return;
+        }
+
+        let transcript = older
+            .iter()
+            .filter(|m| !m.is_thought && !m.is_error && !m.excluded && !m.content.is_empty())
+            .map(|m| {
+                let author = if m.is_user() { "User" } else { "Assistant" };
+                let mut text = format!("{author}: {}", m.content);
+                for path in &m.files {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    text.push_str(&format!("\n[attached {name}]"));
+                }
+                text
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let content_len = older.iter().map(|m| m.content.len()).sum();
+        self.compressor
+            .start(ctx, settings, count, content_len, transcript);
+    }
+
+    /// The message at `idx` with its replies, when it's a prompt
+    fn message_with_replies(&self, idx: usize) -> std::ops::Range<usize> {
+        let replies = if self.messages[idx].is_user() {
+            self.messages[idx + 1..]
+                .iter()
+                .take_while(|m| !m.is_user())
+                .count()
+        } else {
+            0
+        };
+        idx..idx + 1 + replies
+    }
+
+    /// Deletes the message at `idx`. Asks first when it's the answer being
+    /// generated or its prompt, and waits for the answer to stop otherwise
+    fn delete_message(&mut self, idx: usize) {
+        if idx >= self.messages.len() {
+            return;
+        }
+        if !self.flower_active() {
+            self.remove_messages(idx..idx + 1);
+            return;
+        }
+        let attached = self.messages[self.message_with_replies(idx)]
+            .iter()
+            .any(|m| m.is_generating);
+        if attached {
+            self.delete_confirm = Some(idx);
+        } else {
+            log::info!("deleting message {idx} once the answer is done");
+            self.delete_after_stop.push(idx..idx + 1);
+        }
+    }
+
+    fn remove_messages(&mut self, range: std::ops::Range<usize>) {
+        let range = range.start.min(self.messages.len())..range.end.min(self.messages.len());
+        log::info!("deleting messages {range:?}");
+        self.messages.drain(range);
+        // row heights are cached by index, the ones after the deleted messages are off
+        self.virtual_list.reset();
+        self.role_order_warning = self.check_role_order();
+    }
+
+    /// Deletes the messages that waited for the answer to stop
+    fn remove_messages_after_stop(&mut self) {
+        if self.delete_after_stop.is_empty() || self.flower_active() {
+            return;
+        }
+        let mut ranges = std::mem::take(&mut self.delete_after_stop);
+        // from the last one, so the indices of the ones before stay right
+        ranges.sort_by_key(|range| std::cmp::Reverse(range.start));
+        let mut deleted_from = usize::MAX;
+        for range in ranges {
+            // the same message may have been deleted twice
+            let range = range.start..range.end.min(deleted_from);
+            if !range.is_empty() {
+                deleted_from = range.start;
+                self.remove_messages(range);
+            }
+        }
+    }
+
+    /// Asks before deleting a message whose answer is being generated, and
+    /// deletes the messages waiting for the answer to stop
+    fn show_delete_confirm(&mut self, ctx: &egui::Context) {
+        self.remove_messages_after_stop();
+        let Some(idx) = self.delete_confirm else {
+            return;
+        };
+        let is_prompt = self.messages.get(idx).is_some_and(|m| m.is_user());
+        let mut delete = None;
+        egui::Window::new("Delete message?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(if is_prompt {
+                    "The answer to this message is still being generated. \
+                    It's stopped and deleted along with the message."
+                } else {
+                    "This answer is still being generated, it's stopped first."
+                });
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.button("🗑 Stop and delete").clicked() {
+                        delete = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        delete = Some(false);
+                    }
+                });
+            });
+
+        match delete {
+            Some(true) => {
+                self.delete_confirm = None;
+                if idx < self.messages.len() {
+                    let range = self.message_with_replies(idx);
+                    self.delete_after_stop.push(range);
+                    self.stop_generation();
+                }
+            }
+            Some(false) => self.delete_confirm = None,
+            None => {}
+        }
+    }
+
+    /// Replaces the compressed messages with their summary once it's ready
+    fn apply_compression(&mut self) {
+        // the answer being generated is written by index, the summary waits
+        if self.flower_active() {
+            return;
+        }
+        let Some(done) = self.compressor.take_finished() else {
+            return;
+        };
+        // messages may have been edited, moved or removed in the meantime
+        let unchanged = done.count <= self.messages.len()
+            && self.messages[..done.count]
+                .iter()
+                .map(|m| m.content.len())
+                .sum::<usize>()
+                == done.content_len;
+        if !unchanged {
+            crate::notify::report_error(
+                "compress",
+                "The chat changed while it was compressed, try again",
+            );
+            return;
+        }
+        let originals: Vec<Message> = self.messages.drain(..done.count).collect();
+        log::info!("compressed {} messages", originals.len());
+        let mut summary = Message::user(
+            format!("{}{}", crate::compress::SUMMARY_HEADING, done.summary),
+            crate::routing::FAST_MODEL,
+            Vec::new(),
+        );
+        summary.compressed = originals;
+        self.messages.insert(0, summary);
+        self.virtual_list.reset();
+        self.role_order_warning = None;
+    }
+
+    /// Moves a message so that it ends up before the message at `to`
+    fn move_message(&mut self, from: usize, to: usize) {
+        let message = self.messages.remove(from);
+        let to = if to > from { to - 1 } else { to };
+        self.messages.insert(to, message);
+        self.role_order_warning = self.check_role_order();
+    }
+
+    /// The language the last answer is in and the one it should have been in,
+    /// if they differ. Without a preference it should match the prompt.
+    fn check_answer_language(&self) -> Option<(&'static str, String)> {
+        if self.translator.is_some() {
+            return None;
+        }
+        let prompt_idx = self.messages.iter().rposition(|m| m.is_user())?;
+        let answer = self.messages[prompt_idx..]
+            .iter()
+            .rev()
+            .find(|m| !m.is_user() && !m.is_thought && !m.is_error)?;
+        let expected = match &self.requested_language {
+            Some(language) => language.clone(),
+            None => {
+                crate::translator::detect_language(&self.messages[prompt_idx].content)?.to_owned()
+            }
+        };
+        let answered = crate::translator::detect_language(&answer.content)?;
+        (answered != expected).then_some((answered, expected))
+    }
+
+    /// Glossary terms of the last prompt that the answer doesn't use
+    fn check_glossary(&self) -> Vec<Violation> {
+        let Some(prompt_idx) = self.messages.iter().rposition(|m| m.is_user()) else {
+            return Vec::new();
+        };
+        let Some(answer) = self.messages[prompt_idx..]
+            .iter()
+            .rev()
+            .find(|m| !m.is_user() && !m.is_thought && !m.is_error)
+        else {
+            return Vec::new();
+        };
+        self.glossary
+            .check(&self.messages[prompt_idx].content, &answer.content)
+    }
+
+    fn show_glossary_violations(&mut self, ui: &mut egui::Ui) {
+        if self.glossary_violations.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            let terms: Vec<String> = self
+                .glossary_violations
+                .iter()
+                .map(|v| format!("\"{}\" → \"{}\"", v.term, v.translation))
+                .collect();
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("📖 Not translated as in the glossary: {}", terms.join(", ")),
+            );
+            if ui
+                .small_button("Ask again")
+                .on_hover_text("Regenerate the answer, the glossary is added to the prompt again")
+                .clicked()
+            {
+                self.retry_message_idx = Some(self.messages.len() - 1);
+                self.glossary_violations.clear();
+            }
+            if ui.small_button("❌").on_hover_text("Dismiss").clicked() {
+                self.glossary_violations.clear();
+            }
+        });
+    }
+
+    fn check_role_order(&self) -> Option<&'static str> {
+        let roles: Vec<bool> = self
+            .messages
+            .iter()
+            .filter(|m| !m.is_thought && !m.is_error && !m.excluded)
+            .map(|m| m.is_user())
+            .collect();
+        if roles.first() == Some(&false) {
+            Some("The history starts with a model reply, which the API may reject")
+        } else if roles.windows(2).any(|w| w[0] == w[1]) {
+            Some("Some consecutive messages have the same role and will be sent as one turn")
+        } else {
+            None
+        }
+    }
+
+    /// Model a prompt would be sent to
+    fn outgoing_model(&self, prompt: &str, files: &[PathBuf]) -> GeminiModel {
+        if self.model_picker.auto_route {
+            crate::routing::route(prompt, files).model
+        } else {
+            self.model_picker.selected
+        }
+    }
+
+    /// The chatbox as the prompt it would be sent as
+    fn chatbox_prompt(&self) -> Message {
+        let model = self.outgoing_model(&self.chatbox, &self.files);
+        let mut prompt = Message::user(self.chatbox.clone(), model, self.files.clone());
+        prompt.style = self.response_style;
+        prompt
+    }
+
+    /// Estimated cost of sending `prompt` after `history`, with the input and
+    /// answer tokens it assumes. `None` when the model is free
+    fn estimate_cost(
+        &self,
+        settings: &Settings,
+        prompt: &Message,
+        history: &[Message],
+    ) -> Option<(f64, usize, usize)> {
+        let price = crate::pricing::price(prompt.model)?;
+        let file_tokens = |files: &[PathBuf]| -> usize {
+            files
+                .iter()
+                .filter_map(|path| crate::token_diet::file_tokens(path))
+                .sum()
+        };
+        let history = history
+            .iter()
+            .filter(|m| !m.is_error && !m.excluded)
+            .filter(|m| !m.is_thought || settings.include_thoughts_in_history);
+        let mut input =
+            crate::analytics::estimate_tokens(&prompt.content) + file_tokens(&prompt.files);
+        if let Some(prompt) = &self.model_picker.system_prompt {
+            input += crate::analytics::estimate_tokens(prompt);
+        }
+        for message in history.clone() {
+            input +=
+                crate::analytics::estimate_tokens(&message.content) + file_tokens(&message.files);
+        }
+
+        // answers are assumed to be as long as the earlier ones
+        let answers: Vec<usize> = history
+            .filter(|m| !m.is_user() && !m.is_thought && !m.content.is_empty())
+            .map(|m| crate::analytics::estimate_tokens(&m.content))
+            .collect();
+        let mut output = if answers.is_empty() {
+            crate::pricing::DEFAULT_ANSWER_TOKENS
+        } else {
+            answers.iter().sum::<usize>() / answers.len()
+        };
+        if let Some(max) = prompt.style.max_output_tokens() {
+            output = output.min(max as usize);
+        }
+        Some((price.cost(input, output), input, output))
+    }
+
+    /// Holds a prompt back for a dialog when the model can't read its
+    /// attachments, or it's estimated to cost more than the warning threshold.
+    /// `history` is the number of messages sent before it
+    fn hold_back(
+        &mut self,
+        settings: &Settings,
+        prompt: &Message,
+        history: usize,
+        from: HeldPrompt,
+    ) -> bool {
+        let model = prompt.model;
+        let unsupported = crate::capabilities::unsupported_files(model, &prompt.files);
+        if !unsupported.is_empty() {
+            log::info!(
+                "{model} can't read {} of the attachments",
+                unsupported.len()
+            );
+            let mut modalities: Vec<Modality> = Vec::new();
+            for (modality, _) in &unsupported {
+                if !modalities.contains(modality) {
+                    modalities.push(*modality);
+                }
+            }
+            self.blocked_send = Some(BlockedSend {
+                model,
+                unsupported,
+                suggested: crate::capabilities::capable_model(
+                    &modalities,
+                    crate::routing::FAST_MODEL,
+                ),
+                from,
+            });
+            return true;
+        }
+        if let (Some(limit), Some((cost, _, _))) = (
+            settings.cost_warning,
+            self.estimate_cost(settings, prompt, &self.messages[..history]),
+        ) {
+            if cost > limit && !std::mem::take(&mut self.cost_confirmed) {
+                log::info!("holding back a prompt estimated at ${cost:.4}");
+                self.cost_warning = Some(CostWarning { cost, from });
+                return true;
+            }
+        }
+        self.cost_confirmed = false;
+        false
+    }
+
+    /// Removes the old error messages and interruption notes before a new request
+    fn clear_errors(&mut self) {
         self.messages.retain(|m| !m.is_error);
+        for message in self.messages.iter_mut() {
+            message.interrupted = None;
+        }
+    }
 
-        let prompt = self.chatbox.trim_end().to_string();
-        let model = self.model_picker.selected;
-        self.messages
-            .push(Message::user(prompt.clone(), model, self.files.clone()));
+    fn send_message(&mut self, settings: &Settings) {
+        if (self.chatbox.is_empty() && self.files.is_empty()) || self.pending_request.is_some() {
+            return;
+        }
+
+        let prompt = self.chatbox_prompt();
+        if self.hold_back(settings, &prompt, self.messages.len(), HeldPrompt::Chatbox) {
+            return;
+        }
+        // kept in the chatbox to fix and try again
+        let Some(prompt) = run_plugins(self.chatbox.trim_end().to_string()) else {
+            return;
+        };
+        self.clear_errors();
+
+        self.route = self
+            .model_picker
+            .auto_route
+            .then(|| crate::routing::route(&prompt, &self.files));
+        let model = self
+            .route
+            .as_ref()
+            .map_or(self.model_picker.selected, |route| route.model);
+        let mut message = Message::user(prompt.clone(), model, self.files.clone());
+        message.style = self.response_style;
+        self.messages.push(message);
 
         if self.summary.is_empty() {
             self.summary = make_summary(&prompt);
+            if settings.title_emoji && self.icon.is_none() {
+                self.icon = pick_title_emoji(&prompt).map(str::to_owned);
+            }
         }
 
         self.chatbox.clear();
         self.files.clear();
+        self.empty_retried = false;
 
         self.messages.push(Message::assistant(String::new(), model));
 
         self.spawn_completion(settings);
+        if let Some(pending) = &mut self.pending_request {
+            pending.from_chatbox = true;
+        }
     }
 
-    fn spawn_completion(&self, settings: &Settings) {
-        let handle = self.flower.handle();
-        let stop_generation = self.stop_generating.clone();
-        let mut messages = self.messages.clone();
-        let index = self.messages.len() - 1;
+    /// Moves the chatbox to the end of the queue
+    fn queue_message(&mut self) {
+        let text = self.chatbox.trim_end().to_owned();
+        if text.is_empty() && self.files.is_empty() {
+            return;
+        }
+        self.chatbox.clear();
+        self.queued.push(QueuedPrompt {
+            text,
+            files: std::mem::take(&mut self.files),
+            style: self.response_style,
+        });
+    }
 
-        if settings.include_thoughts_in_history {
-            for msg in &mut messages {
-                if msg.is_thought {
-                    msg.is_thought = false;
-                    msg.content.insert_str(0, "MY INNER REFLECTIONS: ");
-                    msg.content
-                        .push_str(r"--- end of inner reflections ---\r\n")
+    /// Shows the queued prompts above the chatbox, `held` when the queue
+    /// waits for the user after a failed answer. Returns the height taken
+    fn show_queue(&mut self, ui: &mut egui::Ui, settings: &Settings, held: bool) -> f32 {
+        if self.queued.is_empty() {
+            return 0.0;
+        }
+        enum QueueAction {
+            Send(usize),
+            Edit(usize),
+            Remove(usize),
+            Swap(usize, usize),
+            Clear,
+        }
+        let mut action = None;
+        ui.add_space(8.0);
+        let count = self.queued.len();
+        let height = ui
+            .vertical(|ui| {
+                ui.horizontal(|ui| {
+                    ui.weak(if held {
+                        format!("⏸ {count} queued, waiting after the error")
+                    } else {
+                        format!("⏳ {count} queued")
+                    });
+                    if count > 1 && ui.small_button("Clear").clicked() {
+                        action = Some(QueueAction::Clear);
+                    }
+                });
+                for (i, queued) in self.queued.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(i > 0, |ui| {
+                            if ui.small_button("⏶").on_hover_text("Send earlier").clicked() {
+                                action = Some(QueueAction::Swap(i, i - 1));
+                            }
+                        });
+                        ui.add_enabled_ui(i + 1 < count, |ui| {
+                            if ui.small_button("⏷").on_hover_text("Send later").clicked() {
+                                action = Some(QueueAction::Swap(i, i + 1));
+                            }
+                        });
+                        if held && i == 0 && ui.small_button("▶ Send").clicked() {
+                            action = Some(QueueAction::Send(i));
+                        }
+                        if ui
+                            .small_button("✏")
+                            .on_hover_text("Move back into the chatbox")
+                            .clicked()
+                        {
+                            action = Some(QueueAction::Edit(i));
+                        }
+                        if ui.small_button("❌").on_hover_text("Don't send").clicked() {
+                            action = Some(QueueAction::Remove(i));
+                        }
+                        let label = if queued.files.is_empty() {
+                            format!("{}. {}", i + 1, make_summary(&queued.text))
+                        } else {
+                            format!(
+                                "{}. {} (+{} files)",
+                                i + 1,
+                                make_summary(&queued.text),
+                                queued.files.len()
+                            )
+                        };
+                        ui.add(egui::Label::new(label).truncate())
+                            .on_hover_text(&queued.text);
+                    });
                 }
+            })
+            .response
+            .rect
+            .height();
+
+        match action {
+            Some(QueueAction::Send(i)) => {
+                let queued = self.queued.remove(i);
+                self.send_queued(settings, queued);
             }
+            Some(QueueAction::Edit(i)) => self.edit_queued(i),
+            Some(QueueAction::Remove(i)) => {
+                self.queued.remove(i);
+            }
+            Some(QueueAction::Swap(a, b)) => self.queued.swap(a, b),
+            Some(QueueAction::Clear) => self.queued.clear(),
+            None => {}
         }
+        height + 8.0
+    }
 
-        let no_api_key = settings.api_key.is_empty();
-        let use_streaming = settings.use_streaming;
+    /// Moves a queued message to the chatbox, before what's written there
+    fn edit_queued(&mut self, idx: usize) {
+        let queued = self.queued.remove(idx);
+        if !self.chatbox.is_empty() {
+            self.chatbox.insert_str(0, "\n\n");
+        }
+        self.chatbox.insert_str(0, &queued.text);
+        self.files.splice(0..0, queued.files);
+        self.response_style = queued.style;
+    }
 
-        let gemini = self
-            .model_picker
-            .create_client(&settings.api_key, settings.proxy_path.clone())
-            .set_safety_settings(Some(SAFETY_SETTINGS.to_vec()));
+    /// Sends a queued message, keeping what's being written in the chatbox
+    fn send_queued(&mut self, settings: &Settings, queued: QueuedPrompt) {
+        log::info!("sending queued message");
+        self.blocked_send = None;
+        let draft = std::mem::replace(&mut self.chatbox, queued.text);
+        let draft_files = std::mem::replace(&mut self.files, queued.files);
+        let style = std::mem::replace(&mut self.response_style, queued.style);
+        self.send_message(settings);
+        let queued = QueuedPrompt {
+            text: std::mem::replace(&mut self.chatbox, draft),
+            files: std::mem::replace(&mut self.files, draft_files),
+            style: std::mem::replace(&mut self.response_style, style),
+        };
+        // back to the front of the queue until the dialog is answered
+        if let Some(blocked) = &mut self.blocked_send {
+            blocked.from = HeldPrompt::Queue;
+            self.queued.insert(0, queued);
+        } else if let Some(warning) = &mut self.cost_warning {
+            warning.from = HeldPrompt::Queue;
+            self.queued.insert(0, queued);
+        }
+    }
 
-        tokio::spawn(async move {
-            handle.activate();
+    /// Explains why a prompt wasn't sent, and offers a model that can read its attachments
+    fn show_blocked_send(&mut self, ctx: &egui::Context, settings: &Settings) {
+        let Some(blocked) = &self.blocked_send else {
+            return;
+        };
+        enum Choice {
+            Switch(GeminiModel),
+            WithoutFiles,
+            Cancel,
+        }
+        let mut choice = None;
+        egui::Window::new("Can't send the attachments")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("{} can't read these files:", blocked.model));
+                for (modality, path) in &blocked.unsupported {
+                    ui.label(format!(
+                        "• {} ({modality})",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    ));
+                }
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if let Some(model) = blocked.suggested {
+                        if ui
+                            .button(format!("Switch to {model} and send"))
+                            .on_hover_text("The chat keeps using this model afterwards")
+                            .clicked()
+                        {
+                            choice = Some(Choice::Switch(model));
+                        }
+                    }
+                    if ui.button("Send without them").clicked() {
+                        choice = Some(Choice::WithoutFiles);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(Choice::Cancel);
+                    }
+                });
+            });
 
-            if no_api_key {
-                handle.error((index, "API key not set.".to_string()));
+        let Some(choice) = choice else {
+            return;
+        };
+        let Some(blocked) = self.blocked_send.take() else {
+            return;
+        };
+        match choice {
+            Choice::Switch(model) => {
+                settings
+                    .model_profiles
+                    .select(&mut self.model_picker, model);
+                self.model_picker.auto_route = false;
+            }
+            Choice::WithoutFiles => {
+                let files = match blocked.from {
+                    HeldPrompt::Chatbox => Some(&mut self.files),
+                    HeldPrompt::Queue => self.queued.first_mut().map(|q| &mut q.files),
+                    HeldPrompt::Edited(idx) => self.messages.get_mut(idx).map(|m| &mut m.files),
+                };
+                if let Some(files) = files {
+                    files.retain(|f| !blocked.unsupported.iter().any(|(_, u)| u == f));
+                }
+            }
+            Choice::Cancel => {
+                // a queued prompt would be blocked again right away, so it's edited instead
+                if blocked.from == HeldPrompt::Queue && !self.queued.is_empty() {
+                    self.edit_queued(0);
+                }
                 return;
             }
+        }
+        self.send_held(settings, blocked.from);
+    }
+
+    /// Sends a prompt once the dialog that held it back is answered
+    fn send_held(&mut self, settings: &Settings, from: HeldPrompt) {
+        match from {
+            HeldPrompt::Chatbox => self.send_message(settings),
+            HeldPrompt::Queue if !self.queued.is_empty() => {
+                let queued = self.queued.remove(0);
+                self.send_queued(settings, queued);
+            }
+            HeldPrompt::Queue => (),
+            HeldPrompt::Edited(idx) => self.resend_edited(settings, idx),
+        }
+    }
+
+    /// Shows a tool call the model made, and runs it only if it's allowed
+    fn show_tool_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(confirmation) = &self.tool_confirmation else {
+            return;
+        };
+        let mut allow = None;
+        egui::Window::new("Run this tool?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("The model wants to call `{}`:", confirmation.tool));
+                ui.monospace(&confirmation.request);
+                ui.label("With these arguments:");
+                egui::ScrollArea::vertical()
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut confirmation.args.as_str())
+                                .code_editor()
+                                .desired_width(f32::INFINITY),
+                        );
+                    });
+                ui.weak(
+                    "Check them before running it, attached files and pages can \
+                    contain instructions aimed at the model.",
+                );
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.button("▶ Run").clicked() {
+                        allow = Some(true);
+                    }
+                    if ui.button("Don't run").clicked() {
+                        allow = Some(false);
+                    }
+                });
+            });
+
+        let Some(allow) = allow else {
+            return;
+        };
+        if let Some(confirmation) = self.tool_confirmation.take() {
+            log::info!("tool call `{}` allowed: {allow}", confirmation.tool);
+            // the request is gone if it failed meanwhile
+            let _ = confirmation.reply.send(allow);
+        }
+    }
+
+    /// Asks before sending a prompt that's estimated to cost more than the threshold
+    fn show_cost_warning(&mut self, ctx: &egui::Context, settings: &Settings) {
+        let Some(warning) = &self.cost_warning else {
+            return;
+        };
+        let mut send = None;
+        egui::Window::new("Expensive request")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "This request is estimated to cost {}, more than the {} set in the settings.",
+                    crate::pricing::format_cost(warning.cost),
+                    crate::pricing::format_cost(settings.cost_warning.unwrap_or_default()),
+                ));
+                ui.weak("Excluding messages or attachments from the history makes it cheaper.");
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Send anyway").clicked() {
+                        send = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        send = Some(false);
+                    }
+                });
+            });
+
+        let Some(send) = send else {
+            return;
+        };
+        let Some(warning) = self.cost_warning.take() else {
+            return;
+        };
+        if !send {
+            // a queued prompt would be held again right away, so it's edited instead
+            if warning.from == HeldPrompt::Queue && !self.queued.is_empty() {
+                self.edit_queued(0);
+            }
+            return;
+        }
+        self.cost_confirmed = true;
+        self.send_held(settings, warning.from);
+    }
+
+    fn spawn_completion(&mut self, settings: &Settings) {
+        self.spawn_completion_with(settings, self.messages.clone());
+    }
+
+    /// Sends the request again, asking the model not to reply with nothing this time
+    fn retry_empty_response(&mut self, settings: &Settings) {
+        log::info!("retrying after an empty response");
+        self.empty_retried = true;
+        let mut messages = self.messages.clone();
+        if let Some(prompt) = messages.iter_mut().rev().find(|m| m.is_user()) {
+            prompt.content.push_str(EMPTY_RESPONSE_NUDGE);
+        }
+        self.spawn_completion_with(settings, messages);
+    }
 
-            let _ = request_completion(
-                gemini,
+    fn spawn_completion_with(&mut self, settings: &Settings, mut messages: Vec<Message>) {
+        self.language_mismatch = None;
+        self.glossary_violations.clear();
+        let mut model_picker = self.model_picker.clone();
+        if let Some(route) = self.route.as_ref().filter(|_| model_picker.auto_route) {
+            model_picker.selected = route.model;
+        }
+        // the translator picks the languages itself
+        self.requested_language = self
+            .response_language
+            .clone()
+            .or_else(|| settings.response_language.clone())
+            .filter(|_| self.translator.is_none());
+        self.response_filters = if self.apply_filters {
+            settings.response_filters.clone()
+        } else {
+            Vec::new()
+        };
+        if let Some(language) = &self.requested_language {
+            let instruction = crate::translator::answer_instruction(language);
+            match &mut model_picker.system_prompt {
+                Some(prompt) if !prompt.is_empty() => {
+                    prompt.push_str("\n\n");
+                    prompt.push_str(&instruction);
+                }
+                prompt => *prompt = Some(instruction),
+            }
+        }
+        if let Some(prompt) = messages.iter_mut().rev().find(|m| m.is_user()) {
+            if let Some(tokens) = prompt.style.max_output_tokens() {
+                model_picker.set_max_output_tokens(tokens);
+            }
+            // detect the direction and the terms before anything is appended to the text
+            let glossary = self.glossary.instruction(&prompt.content);
+            if let Some(translator) = &self.translator {
+                let instruction = translator.instruction(&prompt.content);
+                prompt.content.push_str("\n\n");
+                prompt.content.push_str(&instruction);
+            }
+            if let Some(instruction) = glossary {
+                prompt.content.push_str("\n\n");
+                prompt.content.push_str(&instruction);
+            }
+            if let Some(instruction) = prompt.style.instruction() {
+                prompt.content.push_str("\n\n");
+                prompt.content.push_str(instruction);
+            }
+        }
+        if settings.preview_requests {
+            self.pending_request = Some(PendingRequest {
+                model_picker,
                 messages,
-                &handle,
-                stop_generation,
-                index,
-                use_streaming,
-            )
-            .await
-            .map_err(|e| {
-                log::error!("failed to request completion: {e}");
-                handle.error((index, e.to_string()));
+                excluded_files: HashSet::new(),
+                include_system_prompt: true,
+                from_chatbox: false,
+            });
+            return;
+        }
+        self.progress = Some(RequestProgress::new());
+        spawn_completion_task(
+            self.flower.handle(),
+            settings,
+            &model_picker,
+            messages,
+            self.stop_generating.clone(),
+        );
+    }
+
+    /// The request preview: what's sent, with checkboxes to leave things out
+    fn show_request_preview(&mut self, ctx: &egui::Context, settings: &Settings) {
+        let Some(pending) = &mut self.pending_request else {
+            return;
+        };
+        let mut send = None;
+        let last = pending.messages.len().saturating_sub(1);
+        egui::Window::new("Request preview")
+            .collapsible(false)
+            .default_width(460.0)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("To {}", pending.model_picker.selected));
+                let mut tokens = 0;
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        if let Some(prompt) = &pending.model_picker.system_prompt {
+                            ui.checkbox(&mut pending.include_system_prompt, "System prompt")
+                                .on_hover_text(prompt);
+                            if pending.include_system_prompt {
+                                tokens += crate::analytics::estimate_tokens(prompt);
+                            }
+                        }
+                        for (idx, message) in pending.messages[..last].iter_mut().enumerate() {
+                            let sent = !message.is_thought || settings.include_thoughts_in_history;
+                            if !sent || (message.content.is_empty() && message.files.is_empty()) {
+                                continue;
+                            }
+                            let author = if message.is_user() { "You" } else { "Model" };
+                            let preview: String = message.content.chars().take(80).collect();
+                            let mut include = !message.excluded;
+                            ui.checkbox(
+                                &mut include,
+                                format!("#{} {author}: {}", idx + 1, preview.replace('\n', " ")),
+                            )
+                            .on_hover_text(&message.content);
+                            message.excluded = !include;
+                            if !include {
+                                continue;
+                            }
+                            tokens += crate::analytics::estimate_tokens(&message.content);
+                            ui.indent(("request_preview_files", idx), |ui| {
+                                for path in &message.files {
+                                    let key = (idx, path.clone());
+                                    let mut include = !pending.excluded_files.contains(&key);
+                                    let name =
+                                        path.file_name().unwrap_or_default().to_string_lossy();
+                                    if ui.checkbox(&mut include, format!("📎 {name}")).changed() {
+                                        if include {
+                                            pending.excluded_files.remove(&key);
+                                        } else {
+                                            pending.excluded_files.insert(key);
+                                        }
+                                    }
+                                    if include {
+                                        tokens += crate::token_diet::file_tokens(path).unwrap_or(0);
+                                    }
+                                }
+                            });
+                        }
+                    });
+                ui.separator();
+                ui.weak(format!("About {tokens} tokens"));
+                ui.horizontal(|ui| {
+                    if ui.button("Send").clicked() {
+                        send = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        send = Some(false);
+                    }
+                });
             });
+
+        let Some(send) = send else {
+            return;
+        };
+        let Some(mut pending) = self.pending_request.take() else {
+            return;
+        };
+        if send {
+            if !pending.include_system_prompt {
+                pending.model_picker.system_prompt = None;
+            }
+            for (idx, path) in &pending.excluded_files {
+                if let Some(message) = pending.messages.get_mut(*idx) {
+                    message.files.retain(|f| f != path);
+                }
+            }
+            self.progress = Some(RequestProgress::new());
+            spawn_completion_task(
+                self.flower.handle(),
+                settings,
+                &pending.model_picker,
+                pending.messages,
+                self.stop_generating.clone(),
+            );
+            return;
+        }
+
+        log::info!("request cancelled in the preview");
+        if let Some(artifact) = &mut self.artifact {
+            artifact.pending = false;
+        }
+        self.messages.retain_mut(|m| {
+            let placeholder = m.is_generating && !m.is_user() && m.content.is_empty();
+            m.is_generating = false;
+            !placeholder
         });
+        if pending.from_chatbox && self.messages.last().is_some_and(|m| m.is_user()) {
+            if let Some(prompt) = self.messages.pop() {
+                if !self.chatbox.is_empty() {
+                    self.chatbox.insert_str(0, "\n\n");
+                }
+                self.chatbox.insert_str(0, &prompt.content);
+                self.files.splice(0..0, prompt.files);
+            }
+        }
+    }
+
+    /// Plays a recorded stream into this chat as if it came from the API
+    pub fn replay_fixture(&mut self, fixture: PathBuf) {
+        let model = self.model_picker.selected;
+        let name = fixture.file_name().unwrap_or_default().to_string_lossy();
+        self.messages.push(Message::user(
+            format!("Replaying `{name}`"),
+            model,
+            Vec::new(),
+        ));
+        self.messages.push(Message::assistant(String::new(), model));
+
+        self.progress = Some(RequestProgress::new());
+        spawn_replay_task(
+            self.flower.handle(),
+            self.messages.clone(),
+            self.stop_generating.clone(),
+            fixture,
+            Duration::from_millis(150),
+            true,
+        );
     }
 
     fn regenerate_response(&mut self, settings: &Settings, idx: usize) {
         // todo: regenerate works weird
         self.messages[idx].content = self.prepend_buf.clone();
+        self.messages[idx].has_structured_output = None;
+        self.messages[idx].has_artifact = None;
         self.prepend_buf.clear();
 
         self.spawn_completion(settings);
     }
 
+    /// Replaces the prompt at `idx` with the edited text and generates the
+    /// answer to it again, the messages after it are removed
+    fn resend_edited(&mut self, settings: &Settings, idx: usize) {
+        if self.flower_active() || self.pending_request.is_some() || idx >= self.messages.len() {
+            log::warn!("can't resend message {idx} while an answer is generated");
+            return;
+        }
+        let edited = &self.messages[idx];
+        let text = self.prepend_buf.trim_end();
+        // the editor was closed while a dialog held the prompt back
+        if !edited.is_editing || text.is_empty() {
+            return;
+        }
+        let mut prompt = Message::user(
+            text.to_owned(),
+            self.outgoing_model(text, &edited.files),
+            edited.files.clone(),
+        );
+        prompt.style = edited.style;
+        if self.hold_back(settings, &prompt, idx, HeldPrompt::Edited(idx)) {
+            return;
+        }
+        // kept in the editor to fix and try again
+        let Some(prompt) = run_plugins(prompt.content) else {
+            return;
+        };
+        self.prepend_buf.clear();
+        log::info!(
+            "resending edited message {idx}, dropping {} after it",
+            self.messages.len() - idx - 1
+        );
+        self.messages.truncate(idx + 1);
+        self.clear_errors();
+        let idx = self.messages.len() - 1;
+        self.virtual_list.reset();
+        self.empty_retried = false;
+
+        self.route = self
+            .model_picker
+            .auto_route
+            .then(|| crate::routing::route(&prompt, &self.messages[idx].files));
+        let model = self
+            .route
+            .as_ref()
+            .map_or(self.model_picker.selected, |route| route.model);
+        let message = &mut self.messages[idx];
+        message.content = prompt;
+        message.model = model;
+        message.is_editing = false;
+        message.time = chrono::Utc::now();
+        self.messages.push(Message::assistant(String::new(), model));
+
+        self.spawn_completion(settings);
+    }
+
+    /// Generates the rest of an answer that was cut off, the partial text is
+    /// sent along and the continuation is appended to it
+    fn continue_response(&mut self, settings: &Settings, idx: usize) {
+        // new parts are always appended to the last message
+        if self.flower_active() || idx + 1 != self.messages.len() {
+            log::warn!("can only continue the last answer, while nothing is generated");
+            return;
+        }
+        let message = &mut self.messages[idx];
+        message.interrupted = None;
+        message.has_structured_output = None;
+        message.has_artifact = None;
+        message.is_generating = true;
+        message.requested_at = Instant::now();
+
+        self.spawn_completion(settings);
+    }
+
     fn show_chatbox(
         &mut self,
         ui: &mut egui::Ui,
         is_max_height: bool,
         is_generating: bool,
         settings: &Settings,
+        watch_folder: &WatchFolder,
+        network: &NetworkStatus,
     ) -> ChatAction {
         let mut action = ChatAction::None;
+        if std::mem::take(&mut self.empty_retry_pending) {
+            self.retry_empty_response(settings);
+        }
         if let Some(idx) = self.retry_message_idx.take() {
-            self.chatbox = self.messages[idx - 1].content.clone();
-            self.files = self.messages[idx - 1].files.clone();
-            self.messages.remove(idx);
-            self.messages.remove(idx - 1);
-            self.send_message(settings);
+            // the response may be split into thoughts and an answer, so look for the prompt
+            if let Some(prompt_idx) = self.messages[..idx].iter().rposition(|m| m.is_user()) {
+                self.chatbox = self.messages[prompt_idx].content.clone();
+                self.files = self.messages[prompt_idx].files.clone();
+                self.messages.truncate(prompt_idx);
+                self.send_message(settings);
+            }
         }
 
         if is_max_height {
             ui.add_space(8.0);
         }
 
+        let offline = settings.check_connection && network.is_offline();
+        let banner_height = if offline {
+            ui.add_space(8.0);
+            let height = ui
+                .horizontal(|ui| {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "📵 No connection, messages can't be sent until it's back",
+                    );
+                    if ui.small_button("Check again").clicked() {
+                        network.recheck();
+                    }
+                })
+                .response
+                .rect
+                .height();
+            height + 8.0
+        } else {
+            0.0
+        };
+
+        // a failed answer holds the queue back, the next prompt may need rewording
+        let held = self.messages.last().is_some_and(|m| m.is_error)
+            || self.blocked_send.is_some()
+            || self.cost_warning.is_some()
+            || self.pending_request.is_some();
+        if !is_generating && !offline && !held && !self.queued.is_empty() {
+            let queued = self.queued.remove(0);
+            self.send_queued(settings, queued);
+        }
+        let queue_height = self.show_queue(ui, settings, held && !is_generating);
+
+        let tray_height = if !watch_folder.is_empty() {
+            ui.add_space(8.0);
+            let response = ui.scope(|ui| {
+                for path in watch_folder.show_tray(ui) {
+                    if !self.files.contains(&path) {
+                        self.files.push(path);
+                    }
+                }
+            });
+            response.response.rect.height() + 8.0
+        } else {
+            0.0
+        };
+
+        #[cfg(feature = "camera")]
+        crate::camera::show_camera_dialog(ui.ctx(), self.id(), &mut self.files);
+
         let images_height = if !self.files.is_empty() {
             ui.add_space(8.0);
             let height = ui
                 .horizontal(|ui| {
-                    crate::file_handler::show_files(ui, &mut self.files, true);
+                    crate::file_handler::show_files(
+                        ui,
+                        &mut self.files,
+                        true,
+                        Some(settings.conversion_options()),
+                    );
                 })
                 .response
                 .rect
@@ -883,16 +3631,87 @@ impl Chat {
         };
 
         ui.horizontal_centered(|ui| {
-            if ui
+            let id = self.id();
+            let pick_files = ui
                 .add(
                     egui::Button::new("➕")
                         .min_size(vec2(32.0, 32.0))
                         .corner_radius(CornerRadius::same(u8::MAX)),
                 )
-                .on_hover_text_at_pointer("Pick files")
-                .clicked()
-            {
-                action = ChatAction::PickFiles { id: self.id() };
+                .on_hover_text_at_pointer("Pick files, right click for more");
+            if pick_files.clicked() {
+                action = ChatAction::PickFiles { id };
+            }
+            pick_files.context_menu(|ui| {
+                if ui
+                    .button("📁 Attach folder…")
+                    .on_hover_text("Attach the text and code files of a folder as one document")
+                    .clicked()
+                {
+                    action = ChatAction::PickFolder { id };
+                    ui.close_menu();
+                }
+                #[cfg(feature = "camera")]
+                if ui
+                    .button("📷 Take photo…")
+                    .on_hover_text("Capture a photo with the camera")
+                    .clicked()
+                {
+                    crate::camera::open_camera(ui.ctx(), id);
+                    ui.close_menu();
+                }
+                ui.separator();
+                for (label, context) in [
+                    ("Attach git diff…", GitContext::Diff),
+                    ("Attach staged changes…", GitContext::Staged),
+                ] {
+                    if ui.button(label).clicked() {
+                        action = ChatAction::PickGitContext { id, context };
+                        ui.close_menu();
+                    }
+                }
+                ui.menu_button("Attach recent commits", |ui| {
+                    for count in [5, 10, 20, 50] {
+                        if ui.button(format!("Last {count}…")).clicked() {
+                            action = ChatAction::PickGitContext {
+                                id,
+                                context: GitContext::RecentCommits(count),
+                            };
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+            ui.menu_button(self.response_style.to_string(), |ui| {
+                for style in ResponseStyle::ALL {
+                    if ui
+                        .selectable_label(self.response_style == style, style.to_string())
+                        .clicked()
+                    {
+                        self.response_style = style;
+                        ui.close_menu();
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Response length for the next message");
+            if !self.chatbox.is_empty() || !self.files.is_empty() {
+                if let Some((cost, input, output)) =
+                    self.estimate_cost(settings, &self.chatbox_prompt(), &self.messages)
+                {
+                    let over = settings.cost_warning.is_some_and(|limit| cost > limit);
+                    let text = crate::pricing::format_cost(cost);
+                    let label = if over {
+                        ui.colored_label(ui.visuals().warn_fg_color, text)
+                    } else {
+                        ui.weak(text)
+                    };
+                    label.on_hover_text(format!(
+                        "Estimated cost of sending this: about {input} tokens in, \
+                        with an answer of about {output} tokens like the earlier ones. \
+                        Thinking is billed on top"
+                    ));
+                }
             }
             ui.with_layout(
                 Layout::left_to_right(Align::Center).with_main_justify(true),
@@ -907,29 +3726,130 @@ impl Chat {
                         ui.fonts(|f| f.layout_job(layout_job))
                     };
 
-                    self.chatbox_height = egui::TextEdit::multiline(&mut self.chatbox)
-                        .return_key(KeyboardShortcut::new(Modifiers::SHIFT, Key::Enter))
-                        .hint_text("Ask me anything…")
-                        .layouter(&mut layouter)
-                        .show(ui)
-                        .response
-                        .rect
-                        .height()
-                        + images_height;
-                    if !is_generating
-                        && ui.input(|i| i.key_pressed(Key::Enter) && i.modifiers.is_none())
-                    {
-                        self.send_message(settings);
-                    }
+                    let chatbox_id = ui.make_persistent_id("chatbox");
+                    settings.plain_paste.apply(ui.ctx(), chatbox_id);
+                    self.chatbox_height = egui::TextEdit::multiline(&mut self.chatbox)
+                        .id(chatbox_id)
+                        .return_key(KeyboardShortcut::new(Modifiers::COMMAND, Key::Enter))
+                        .hint_text(if is_generating {
+                            "Write the next message, it's sent when the answer is done…"
+                        } else {
+                            "Ask me anything… (Ctrl+Enter for a new line, Shift+Enter to queue)"
+                        })
+                        .layouter(&mut layouter)
+                        .show(ui)
+                        .response
+                        .rect
+                        .height()
+                        + images_height
+                        + tray_height
+                        + banner_height
+                        + queue_height;
+                    let (enter, shift_enter) = ui.input(|i| {
+                        let enter = i.key_pressed(Key::Enter);
+                        (
+                            enter && i.modifiers.is_none(),
+                            enter && i.modifiers.shift_only(),
+                        )
+                    });
+                    if enter && self.chatbox.trim() == crate::compress::COMMAND {
+                        self.chatbox.clear();
+                        self.compress_history(ui.ctx(), settings);
+                    } else if shift_enter {
+                        self.queue_message();
+                    } else if enter {
+                        // queued prompts go first
+                        if is_generating || !self.queued.is_empty() {
+                            self.queue_message();
+                        } else if !offline {
+                            self.send_message(settings);
+                        }
+                    }
+                },
+            );
+        });
+
+        if is_max_height {
+            ui.add_space(8.0);
+        }
+
+        action
+    }
+
+    fn show_header(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.horizontal(|ui| {
+            if !self.model_picker.auto_route {
+                ui.weak(self.model_picker.selected.to_string());
+            } else if let Some(route) = &self.route {
+                ui.weak(format!("🔀 Auto: {}", route.model))
+                    .on_hover_text(format!("Picked because of: {}", route.reason));
+            } else {
+                ui.weak("🔀 Auto")
+                    .on_hover_text("The model is picked for each prompt");
+            }
+            self.model_picker
+                .show_preset_picker(ui, &settings.system_presets);
+
+            let mut translating = self.translator.is_some();
+            if ui
+                .toggle_value(&mut translating, "🌐 Translator")
+                .on_hover_text("Translate every message between two languages")
+                .changed()
+            {
+                self.translator = translating.then(Translator::default);
+            }
+            if let Some(translator) = &mut self.translator {
+                translator.show_languages(ui);
+            } else {
+                let default = format!(
+                    "Default ({})",
+                    settings.response_language.as_deref().unwrap_or("any")
+                );
+                crate::translator::response_language_picker(
+                    ui,
+                    "chat_response_language",
+                    &mut self.response_language,
+                    &default,
+                )
+                .on_hover_text("Language the model answers in, in this chat");
+            }
+
+            let terms = self.glossary.entries.len();
+            ui.toggle_value(
+                &mut self.glossary.open,
+                if terms == 0 {
+                    "📖".to_owned()
+                } else {
+                    format!("📖 {terms}")
                 },
-            );
-        });
+            )
+            .on_hover_text("Glossary: terms that have to be translated a certain way");
 
-        if is_max_height {
-            ui.add_space(8.0);
-        }
+            ui.toggle_value(&mut self.scratchpad.open, "📝")
+                .on_hover_text("Notes kept with the chat, never sent to the model");
 
-        action
+            ui.toggle_value(&mut self.token_diet.open, "⚖")
+                .on_hover_text("See which messages and attachments take up the most tokens");
+            if self.compressor.is_pending() {
+                ui.spinner();
+                ui.weak("Compressing the history…");
+            }
+
+            if ui
+                .add_enabled(!self.messages.is_empty(), egui::Button::new("🃏"))
+                .on_hover_text("Make flashcards from the whole chat")
+                .clicked()
+            {
+                let text = self
+                    .messages
+                    .iter()
+                    .filter(|m| !m.is_thought && !m.is_error && !m.excluded)
+                    .map(|m| m.content.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                self.flashcards_source = Some((self.display_name().to_owned(), text));
+            }
+        });
     }
 
     #[inline]
@@ -937,58 +3857,110 @@ impl Chat {
         self.flower.is_active()
     }
 
+    /// Stops the answer being generated, if there is one
+    pub fn stop_generation(&self) {
+        if self.flower_active() {
+            log::info!("stopping the answer in chat {}", self.id());
+            self.stop_generating.store(true, Ordering::SeqCst);
+        }
+    }
+
     pub fn poll_flower(&mut self, modal: &mut Modal) {
         let mut last_processed_idx = self.messages.len().saturating_sub(1);
+        // stopped while a tool call waits, dropping it declines the call
+        if self.tool_confirmation.is_some() && self.stop_generating.load(Ordering::SeqCst) {
+            self.tool_confirmation = None;
+        }
 
         self.flower
-            .extract(|(idx, part)| {
+            .extract(|(idx, event)| {
                 last_processed_idx = idx;
                 // let model = self // todo remove?
                 //     .messages
                 //     .get(idx - 1)
                 //     .map_or(GeminiModel::default(), |m| m.model);
 
+                let part = match event {
+                    CompletionEvent::Connecting => {
+                        if let Some(progress) = &mut self.progress {
+                            progress.connected.get_or_insert_with(Instant::now);
+                        }
+                        return;
+                    }
+                    CompletionEvent::Part(part) => part,
+                    CompletionEvent::ConfirmToolCall(confirmation) => {
+                        self.tool_confirmation = Some(confirmation);
+                        return;
+                    }
+                    CompletionEvent::RetrievedUrls(urls) => {
+                        // we always add a placeholder message in send_message before running
+                        let last = self.messages.last_mut().unwrap();
+                        for url in urls {
+                            if !last.retrieved_urls.contains(&url) {
+                                last.retrieved_urls.push(url);
+                            }
+                        }
+                        return;
+                    }
+                };
                 match part {
                     Part::text(data) => {
-                        // Safely use unwrap, as we always add
-                        // a placeholder message in send_message before running.
-                        let current_response_msg = self.messages.last_mut().unwrap();
-
-                        if *data.thought() {
-                            // This is a thought
-                            if !current_response_msg.is_thought {
-                                // If this is the first part of a "thought", turn our
-                                // placeholder message into a full "thought" message.
-                                current_response_msg.is_thought = true;
-                            }
-                            // Just append the "thought" text.
-                            current_response_msg.content.push_str(data.text());
+                        if let Some(progress) = &mut self.progress {
+                            progress.received(data.text());
+                        }
+                        push_response_text(&mut self.messages, data.text(), *data.thought());
+                    }
+                    Part::inline_data(data) => {
+                        match crate::file_handler::save_inline_data(data.mime_type(), data.data()) {
+                            Ok(path) => push_response_file(&mut self.messages, path),
+                            Err(e) => log::error!("failed to save inline data: {e}"),
+                        }
+                    }
+                    other => {
+                        if let Some(markdown) = code_execution_markdown(&other) {
+                            push_response_text(&mut self.messages, &markdown, false);
+                        } else if let Some((name, _)) = crate::http_tools::function_call(&other) {
+                            push_response_text(
+                                &mut self.messages,
+                                &format!("\n\n*🔧 Called `{name}`*\n\n"),
+                                false,
+                            );
                         } else {
-                            if current_response_msg.is_thought {
-                                // "Thoughts" have just ended. Turn off the spinner for them.
-                                current_response_msg.is_generating = false;
-                                current_response_msg.generation_time =
-                                    Some(current_response_msg.requested_at.elapsed());
-
-                                // And create a NEW, separate message for the final answer.
-                                // This will keep the thought block on screen.
-                                let model = current_response_msg.model;
-                                let mut answer_message =
-                                    Message::assistant(data.text().into(), model);
-                                answer_message.is_generating = true; // It has its own spinner.
-                                self.messages.push(answer_message);
-                            } else {
-                                // Either there were no "thoughts", or this is a continuation of the answer.
-                                // Just append the text to the current last message.
-                                current_response_msg.content.push_str(data.text());
-                            }
+                            log::warn!("ignoring unsupported response part: {other:?}");
                         }
                     }
-                    _ => todo!(),
                 }
             })
             .finalize(|result| {
+                self.progress = None;
+                self.tool_confirmation = None;
                 if let Ok((_, _)) = result {
+                    if let Some(reply) = self
+                        .messages
+                        .iter_mut()
+                        .rev()
+                        .find(|m| !m.is_user() && !m.is_thought && !m.is_error)
+                    {
+                        if !self.response_filters.is_empty() {
+                            reply.content =
+                                crate::filters::apply(&self.response_filters, &reply.content);
+                        }
+                        #[cfg(feature = "scripting")]
+                        {
+                            reply.content =
+                                crate::plugins::transform_reply(std::mem::take(&mut reply.content));
+                        }
+                    }
+                    self.language_mismatch = self.check_answer_language();
+                    self.glossary_violations = self.check_glossary();
+                    if let Some(artifact) = self.artifact.as_mut().filter(|a| a.pending) {
+                        let reply = self
+                            .messages
+                            .iter()
+                            .rev()
+                            .find(|m| !m.is_user() && !m.is_thought && !m.is_error);
+                        artifact.apply_reply(reply.map_or("", |m| m.content.as_str()));
+                    }
                 } else if let Err(e) = result {
                     let (idx, msg) = match e {
                         Compact::Panicked(e) => {
@@ -1014,17 +3986,45 @@ impl Chat {
                         Err(_) => formatted_msg,
                     };
 
-                    let message = &mut self.messages[idx];
-                    message.content = final_msg.clone();
-                    message.is_error = true;
-                    modal
-                        .dialog()
-                        .with_body(final_msg)
-                        .with_title("Failed to generate completion!")
-                        .with_icon(Icon::Error)
-                        .open();
-                    message.is_generating = false;
-                    message.generation_time = Some(message.requested_at.elapsed());
+                    if final_msg == EMPTY_RESPONSE && !self.empty_retried {
+                        let message = &mut self.messages[idx];
+                        message.is_generating = true;
+                        message.requested_at = Instant::now();
+                        self.empty_retry_pending = true;
+                        return;
+                    } else if final_msg == EMPTY_RESPONSE {
+                        // shown with a retry button, there's nothing to explain in a dialog
+                        let message = &mut self.messages[idx];
+                        message.content = format!("{EMPTY_RESPONSE} Retry?");
+                        message.is_error = true;
+                        message.is_generating = false;
+                    } else if self.messages[idx..].iter().any(|m| !m.content.is_empty()) {
+                        // keep what was streamed so far, it can be continued from
+                        let message = self.messages.last_mut().unwrap();
+                        message.interrupted = Some(final_msg);
+                        message.is_generating = false;
+                        message.generation_time = Some(message.requested_at.elapsed());
+                    } else {
+                        let message = &mut self.messages[idx];
+                        message.content = final_msg.clone();
+                        message.is_error = true;
+                        // shown with ways to retry instead of a dialog
+                        message.timed_out = final_msg.starts_with(TIMED_OUT);
+                        if !message.timed_out {
+                            modal
+                                .dialog()
+                                .with_body(final_msg)
+                                .with_title("Failed to generate completion!")
+                                .with_icon(Icon::Error)
+                                .open();
+                        }
+                        message.is_generating = false;
+                        message.generation_time = Some(message.requested_at.elapsed());
+                    }
+                    // no updated version is coming, the artifact can be edited again
+                    if let Some(artifact) = &mut self.artifact {
+                        artifact.pending = false;
+                    }
                 }
 
                 if let Some(last_msg) = self.messages.last_mut() {
@@ -1036,20 +4036,117 @@ impl Chat {
             });
     }
 
-    pub fn last_message_contents(&self) -> Option<String> {
+    /// Structured output of a message and the schema to validate it against
+    pub fn structured_output(
+        &self,
+        idx: usize,
+    ) -> Option<(
+        crate::structured::StructuredOutput,
+        Option<serde_json::Value>,
+    )> {
+        let message = self.messages.get(idx)?;
+        let output = crate::structured::StructuredOutput::extract(&message.content)?;
+        Some((output, self.model_picker.response_schema()))
+    }
+
+    /// Whether the last response ended with an error, `None` while it's being
+    /// generated or about to be retried
+    pub fn last_response_failed(&self) -> Option<bool> {
+        if self.flower_active() || self.empty_retry_pending {
+            return None;
+        }
+        let last = self.messages.last()?;
+        Some(last.is_error || last.interrupted.is_some())
+    }
+
+    /// Returns the text of the latest model answer, skipping thoughts
+    pub fn last_reply(&self) -> Option<&str> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| !m.is_user() && !m.is_thought && !m.is_error)
+            .map(|m| m.content.as_str())
+    }
+
+    /// Per-message usage for the local usage report
+    pub fn usage_records(&self) -> Vec<crate::analytics::UsageRecord> {
+        self.messages
+            .iter()
+            .filter(|m| !m.is_error && !m.is_generating)
+            .map(|m| crate::analytics::UsageRecord {
+                chat: self.display_name().to_owned(),
+                time: m.time,
+                role: match m.role {
+                    Role::User => "user",
+                    Role::Assistant if m.is_thought => "thought",
+                    Role::Assistant => "assistant",
+                },
+                model: m.model.to_string(),
+                estimated_tokens: crate::analytics::estimate_tokens(&m.content),
+                response_time_secs: m.generation_time.map(|d| d.as_secs_f64()),
+            })
+            .collect()
+    }
+
+    /// Returns the last non-empty message prefixed with its role, and when it was sent
+    pub fn last_message_contents(&self) -> Option<(String, chrono::DateTime<chrono::Utc>)> {
+        if !self.messages.is_loaded() {
+            return self.preview.clone();
+        }
         for message in self.messages.iter().rev() {
-            if message.content.is_empty() {
+            if message.content.is_empty() || message.is_thought {
                 continue;
             }
-            return Some(if message.is_user() {
-                format!("You: {}", message.content)
-            } else {
-                message.content.to_string()
-            });
+            let role = if message.is_user() { "You" } else { "✨" };
+            let first_line = message.content.lines().next().unwrap_or_default();
+            return Some((format!("{role}: {first_line}"), message.time));
         }
         None
     }
 
+    /// The start of the last few messages, each prefixed with its role, for
+    /// the chat switcher
+    pub fn thumbnail(&self, messages: usize, lines: usize) -> Vec<String> {
+        if !self.messages.is_loaded() {
+            return self.preview.iter().map(|(text, _)| text.clone()).collect();
+        }
+        let mut thumbnail: Vec<String> = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|m| !m.content.is_empty() && !m.is_thought && !m.is_error)
+            .take(messages)
+            .map(|m| {
+                let role = if m.is_user() { "You" } else { "✨" };
+                let text = m.content.lines().take(lines).collect::<Vec<_>>().join("\n");
+                format!("{role}: {text}")
+            })
+            .collect();
+        thumbnail.reverse();
+        thumbnail
+    }
+
+    /// Words in the prompts and answers, without thoughts and errors
+    pub fn word_count(&self) -> usize {
+        self.messages
+            .iter()
+            .filter(|m| !m.is_thought && !m.is_error)
+            .map(|m| crate::analytics::word_count(&m.content))
+            .sum()
+    }
+
+    /// Total length of the messages, in bytes
+    pub fn text_len(&self) -> usize {
+        self.messages.iter().map(|m| m.content.len()).sum()
+    }
+
+    /// Stores the last message for the sidebar, call before saving
+    pub fn update_preview(&mut self) {
+        if self.messages.is_loaded() {
+            self.preview = self.last_message_contents();
+        }
+    }
+
     fn stop_generating_button(&self, ui: &mut egui::Ui, radius: f32, pos: Pos2) {
         let rect = Rect::from_min_max(pos + vec2(-radius, -radius), pos + vec2(radius, radius));
         let (hovered, primary_clicked) = ui.input(|i| {
@@ -1099,29 +4196,93 @@ impl Chat {
         let mut new_speaker: Option<usize> = None;
         let mut any_prepending = false;
         let mut regenerate_response_idx = None;
+        let mut continue_response_idx = None;
         let mut message_to_delete_idx: Option<usize> = None;
+        let mut message_to_restore_idx: Option<usize> = None;
+        let mut resend_idx: Option<usize> = None;
+        let mut message_to_move: Option<(usize, usize)> = None;
+        let can_reorder = !self.flower_active();
+        let can_send = can_reorder && self.pending_request.is_none();
         egui::ScrollArea::both()
             .stick_to_bottom(true)
             .auto_shrink(false)
             .show(ui, |ui| {
                 ui.add_space(16.0);
+                if let Some(warning) = self.role_order_warning {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {warning}"));
+                        if ui.small_button("❌").on_hover_text("Dismiss").clicked() {
+                            self.role_order_warning = None;
+                        }
+                    });
+                }
                 self.virtual_list
                     .ui_custom_layout(ui, self.messages.len(), |ui, index| {
+                        let same_author = |a: usize, b: usize| {
+                            settings.group_messages
+                                && matches!(
+                                    (self.messages.get(a), self.messages.get(b)),
+                                    (Some(a), Some(b)) if a.same_author(b)
+                                )
+                        };
+                        let group = MessageGroup {
+                            continues: index > 0 && same_author(index - 1, index),
+                            continued: same_author(index, index + 1),
+                        };
                         let Some(message) = self.messages.get_mut(index) else {
                             return 0;
                         };
                         let prev_speaking = message.is_speaking;
-                        if any_prepending && message.is_prepending {
+                        if any_prepending && (message.is_prepending || message.is_editing) {
                             message.is_prepending = false;
+                            message.is_editing = false;
+                        }
+                        let row = ui.scope(|ui| {
+                            if message.excluded {
+                                ui.multiply_opacity(0.5);
+                            }
+                            message.show(
+                                ui,
+                                commonmark_cache,
+                                #[cfg(feature = "tts")]
+                                tts.clone(),
+                                index,
+                                &mut self.prepend_buf,
+                                settings,
+                                group,
+                                can_send,
+                            )
+                        });
+                        let action = row.inner;
+                        let offset = ui
+                            .data(|d| d.get_temp(MessageGroup::offset_id(message.is_user())))
+                            .unwrap_or(ui.spacing().interact_size.y);
+                        group.paint_connector(ui, row.response.rect, offset);
+
+                        // drop a dragged message before or after this one
+                        let is_drop_target = can_reorder
+                            && row.response.dnd_hover_payload::<DraggedMessage>().is_some();
+                        if is_drop_target {
+                            if let Some(pointer) = ui.ctx().pointer_interact_pos() {
+                                let after = pointer.y > row.response.rect.center().y;
+                                let y = if after {
+                                    row.response.rect.bottom()
+                                } else {
+                                    row.response.rect.top()
+                                };
+                                ui.painter().hline(
+                                    row.response.rect.x_range(),
+                                    y,
+                                    ui.visuals().selection.stroke,
+                                );
+                                if let Some(dragged) =
+                                    row.response.dnd_release_payload::<DraggedMessage>()
+                                {
+                                    message_to_move =
+                                        Some((dragged.0, if after { index + 1 } else { index }));
+                                }
+                            }
                         }
-                        let action = message.show(
-                            ui,
-                            commonmark_cache,
-                            #[cfg(feature = "tts")]
-                            tts.clone(),
-                            index,
-                            &mut self.prepend_buf,
-                        );
                         match action {
                             MessageAction::None => (),
                             MessageAction::Retry(idx) => {
@@ -1130,26 +4291,176 @@ impl Chat {
                             MessageAction::Regenerate(idx) => {
                                 regenerate_response_idx = Some(idx);
                             }
+                            MessageAction::Continue(idx) => {
+                                continue_response_idx = Some(idx);
+                            }
+                            MessageAction::SaveStructured(idx) => {
+                                self.save_structured_idx = Some(idx);
+                            }
+                            MessageAction::OpenArtifact(_) => {
+                                if let Some((language, content)) =
+                                    crate::artifact::detect(&message.content)
+                                {
+                                    self.artifact = Some(Artifact::new(language, content));
+                                }
+                            }
+                            MessageAction::MakeFlashcards(_) => {
+                                self.flashcards_source =
+                                    Some((make_summary(&message.content), message.content.clone()));
+                            }
                             MessageAction::Delete(idx) => {
                                 message_to_delete_idx = Some(idx);
                             }
+                            MessageAction::RetryStreaming(idx) => {
+                                self.enable_streaming = true;
+                                self.retry_message_idx = Some(idx);
+                            }
+                            MessageAction::Resend(idx) => {
+                                resend_idx = Some(idx);
+                            }
+                            MessageAction::RestoreCompressed(idx) => {
+                                message_to_restore_idx = Some(idx);
+                            }
+                            MessageAction::RetryWithModel(idx, model) => {
+                                settings
+                                    .model_profiles
+                                    .select(&mut self.model_picker, model);
+                                self.model_picker.auto_route = false;
+                                self.retry_message_idx = Some(idx);
+                            }
                         }
-                        any_prepending |= message.is_prepending;
+                        any_prepending |= message.is_prepending || message.is_editing;
                         if !prev_speaking && message.is_speaking {
                             new_speaker = Some(index);
                         }
                         1 // 1 rendered item per row
                     });
+                if let Some((answered, expected)) = self.language_mismatch.clone() {
+                    ui.horizontal(|ui| {
+                        ui.weak(format!("🗣 The answer is in {answered}"));
+                        if ui
+                            .small_button(format!("Answer in {expected} instead"))
+                            .on_hover_text(format!(
+                                "Ask again, and answer in {expected} from now on in this chat"
+                            ))
+                            .clicked()
+                        {
+                            self.response_language = Some(expected);
+                            self.retry_message_idx = Some(self.messages.len() - 1);
+                            self.language_mismatch = None;
+                        }
+                        if ui.small_button("❌").on_hover_text("Dismiss").clicked() {
+                            self.language_mismatch = None;
+                        }
+                    });
+                }
+                self.show_glossary_violations(ui);
             });
         if let Some(regenerate_idx) = regenerate_response_idx {
             self.regenerate_response(settings, regenerate_idx);
         }
+        if let Some(idx) = continue_response_idx {
+            self.continue_response(settings, idx);
+        }
         if let Some(idx) = message_to_delete_idx {
-            self.messages.remove(idx);
+            self.delete_message(idx);
+        }
+        if let Some(idx) = resend_idx {
+            self.resend_edited(settings, idx);
+        }
+        if let Some(idx) = message_to_restore_idx {
+            let originals = std::mem::take(&mut self.messages[idx].compressed);
+            log::info!("restoring {} compressed messages", originals.len());
+            self.messages.splice(idx..=idx, originals);
+            self.virtual_list.reset();
+        }
+        if let Some((from, to)) = message_to_move {
+            if from < self.messages.len() && to <= self.messages.len() && from != to {
+                self.move_message(from, to);
+            }
         }
         new_speaker
     }
 
+    /// Translator mode: each prompt next to its translation
+    fn show_translations(&mut self, ui: &mut egui::Ui) {
+        let Some(translator) = self.translator.clone() else {
+            return;
+        };
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                ui.add_space(16.0);
+                let mut messages = self.messages.iter().filter(|m| !m.is_thought).peekable();
+                while let Some(message) = messages.next() {
+                    if !message.is_user() {
+                        // replies without a prompt, e.g. errors
+                        ui.label(&message.content);
+                        continue;
+                    }
+                    let translation = messages
+                        .next_if(|m| !m.is_user())
+                        .map_or("", |m| m.content.as_str());
+                    crate::translator::show_pair(ui, &translator, &message.content, translation);
+                    ui.add_space(8.0);
+                }
+                self.show_glossary_violations(ui);
+            });
+    }
+
+    /// Mini mode: only the latest reply, without any message controls
+    fn show_last_reply(&self, ui: &mut egui::Ui, commonmark_cache: &mut CommonMarkCache) {
+        let Some(message) = self.messages.last() else {
+            return;
+        };
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                ui.add_space(8.0);
+                if message.is_generating && (message.content.is_empty() || message.is_thought) {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Spinner::new());
+                        ui.weak(if message.is_thought {
+                            "Thinking…"
+                        } else {
+                            "Waiting for response…"
+                        });
+                    });
+                } else if !message.is_user() {
+                    CommonMarkViewer::new().max_image_width(Some(512)).show(
+                        ui,
+                        commonmark_cache,
+                        &message.content,
+                    );
+                }
+            });
+    }
+
+    /// Puts `text` into the chatbox
+    pub fn prefill(&mut self, text: &str) {
+        self.chatbox = text.to_owned();
+    }
+
+    /// Sends `text` without touching the chatbox. Returns whether the answer
+    /// started, it's held back when a dialog has to be answered first.
+    pub fn send_prompt(&mut self, settings: &Settings, text: &str) -> bool {
+        let queued = QueuedPrompt {
+            text: text.to_owned(),
+            files: Vec::new(),
+            style: self.response_style,
+        };
+        // the request preview is open, it waits in the queue
+        if self.pending_request.is_some() {
+            self.queued.push(queued);
+            return false;
+        }
+        let sent = self.messages.len();
+        self.send_queued(settings, queued);
+        self.messages.len() > sent && self.pending_request.is_none()
+    }
+
     fn send_text(&mut self, settings: &Settings, text: &str) {
         self.chatbox = text.to_owned();
         self.send_message(settings);
@@ -1171,37 +4482,25 @@ impl Chat {
                     .max_col_width((avail_width / 2.0).min(200.0))
                     .spacing(vec2(6.0, 6.0))
                     .show(ui, |ui| {
-                        // TODO change it
-                        if widgets::suggestion(ui, "Tell me a fun fact", "about the Roman empire")
-                            .clicked()
-                        {
-                            self.send_text(settings, "Tell me a fun fact about the Roman empire");
-                        }
-                        if widgets::suggestion(
-                            ui,
-                            "Show me a code snippet",
-                            "of a web server in Rust",
-                        )
-                        .clicked()
-                        {
-                            self.send_text(
-                                settings,
-                                "Show me a code snippet of a web server in Rust",
-                            );
-                        }
-                        widgets::dummy(ui);
-                        ui.end_row();
-
-                        if widgets::suggestion(ui, "Tell me a joke", "about crabs").clicked() {
-                            self.send_text(settings, "Tell me a joke about crabs");
+                        let mut clicked = None;
+                        for (i, card) in settings.suggestion_cards.iter().enumerate() {
+                            if widgets::suggestion(ui, &card.title, &card.subtitle).clicked() {
+                                clicked = Some(card);
+                            }
+                            if i % 2 == 1 {
+                                widgets::dummy(ui);
+                                ui.end_row();
+                            }
                         }
-                        if widgets::suggestion(ui, "Give me ideas", "for a birthday present")
-                            .clicked()
-                        {
-                            self.send_text(settings, "Give me ideas for a birthday present");
+                        if let Some(card) = clicked {
+                            if let Some(model) = card.model {
+                                settings
+                                    .model_profiles
+                                    .select(&mut self.model_picker, model);
+                                self.model_picker.auto_route = false;
+                            }
+                            self.send_text(settings, &card.prompt());
                         }
-                        widgets::dummy(ui);
-                        ui.end_row();
                     });
             });
         });
@@ -1214,6 +4513,10 @@ impl Chat {
         #[cfg(feature = "tts")] tts: SharedTts,
         #[cfg(feature = "tts")] stopped_speaking: bool,
         commonmark_cache: &mut CommonMarkCache,
+        watch_folder: &WatchFolder,
+        network: &NetworkStatus,
+        mini: bool,
+        focus: bool,
     ) -> ChatAction {
         let avail = ctx.available_rect();
         let max_height = avail.height() * 0.4 + 24.0;
@@ -1222,6 +4525,35 @@ impl Chat {
         let is_generating = self.flower_active();
         let mut action = ChatAction::None;
 
+        if let Some(error) = self.messages.load_error() {
+            // nothing can be sent, the stored history would be replaced
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(avail.height() * 0.3);
+                    ui.heading("This chat's history failed to load");
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                    ui.label(
+                        "It's kept as it was saved, so nothing is lost, \
+                        but the chat can't be used until it loads.",
+                    );
+                });
+            });
+            return action;
+        }
+
+        if let Some(progress) = self.progress.filter(|_| is_generating) {
+            egui::TopBottomPanel::bottom("request_progress")
+                .show_separator_line(false)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Spinner::new().size(ui.text_style_height(&TextStyle::Small)));
+                        ui.add(egui::Label::new(
+                            egui::RichText::new(progress.status()).small().weak(),
+                        ));
+                    });
+                });
+        }
+
         egui::TopBottomPanel::bottom("chatbox_panel")
             .exact_height(actual_chatbox_panel_height)
             .show(ctx, |ui| {
@@ -1231,6 +4563,8 @@ impl Chat {
                         chatbox_panel_height >= max_height,
                         is_generating,
                         settings,
+                        watch_folder,
+                        network,
                     );
                 });
             });
@@ -1238,6 +4572,44 @@ impl Chat {
         #[cfg(feature = "tts")]
         let mut new_speaker: Option<usize> = None;
 
+        if !mini && !focus {
+            egui::TopBottomPanel::top("chat_header").show(ctx, |ui| {
+                self.show_header(ui, settings);
+            });
+        }
+
+        if self.scratchpad.open && !mini && !focus {
+            egui::SidePanel::right("scratchpad_panel")
+                .resizable(true)
+                .default_width(avail.width() * 0.3)
+                .show(ctx, |ui| {
+                    self.scratchpad.show(ui, commonmark_cache);
+                });
+        }
+
+        if let Some(artifact) = self.artifact.as_mut().filter(|_| !mini && !focus) {
+            let mut artifact_action = ArtifactAction::None;
+            egui::SidePanel::right("artifact_panel")
+                .resizable(true)
+                .default_width(avail.width() * 0.45)
+                .show(ctx, |ui| {
+                    artifact_action = artifact.show(ui, is_generating);
+                });
+            match artifact_action {
+                ArtifactAction::None => (),
+                ArtifactAction::Close => self.artifact = None,
+                // sent on its own, what's being written in the chatbox stays
+                ArtifactAction::RequestChanges(prompt) => self.send_queued(
+                    settings,
+                    QueuedPrompt {
+                        text: prompt,
+                        files: Vec::new(),
+                        style: self.response_style,
+                    },
+                ),
+            }
+        }
+
         egui::CentralPanel::default()
             .frame(Frame::central_panel(&ctx.style()).inner_margin(Margin {
                 left: 16,
@@ -1247,37 +4619,79 @@ impl Chat {
             }))
             .show(ctx, |ui| {
                 // ui.ctx().set_debug_on_hover(true); // TODO DEBUG
-                if self.messages.is_empty() {
-                    self.show_suggestions(ui, settings);
-                } else {
-                    #[allow(unused_variables)]
-                    if let Some(new) = self.show_chat_scrollarea(
-                        ui,
-                        settings,
-                        commonmark_cache,
-                        #[cfg(feature = "tts")]
-                        tts,
-                    ) {
-                        #[cfg(feature = "tts")]
-                        {
-                            new_speaker = Some(new);
+                let panel = ui.available_rect_before_wrap();
+                let mut column_width = settings.max_message_width.unwrap_or(f32::INFINITY);
+                if focus {
+                    column_width = column_width.min(FOCUS_READING_WIDTH);
+                }
+                let column_width = panel.width().min(column_width);
+                let column =
+                    Rect::from_center_size(panel.center(), vec2(column_width, panel.height()));
+                ui.scope_builder(egui::UiBuilder::new().max_rect(column), |ui| {
+                    if focus {
+                        for font in ui.style_mut().text_styles.values_mut() {
+                            font.size *= FOCUS_TEXT_SCALE;
                         }
                     }
+                    // wrapped markdown is laid out in rows, so this spaces out the lines
+                    ui.spacing_mut().item_spacing.y *= settings.line_spacing;
+                    if self.messages.is_empty() {
+                        self.show_suggestions(ui, settings);
+                    } else {
+                        if mini {
+                            self.show_last_reply(ui, commonmark_cache);
+                        } else if self.translator.is_some() {
+                            self.show_translations(ui);
+                        } else {
+                            #[allow(unused_variables)]
+                            if let Some(new) = self.show_chat_scrollarea(
+                                ui,
+                                settings,
+                                commonmark_cache,
+                                #[cfg(feature = "tts")]
+                                tts,
+                            ) {
+                                #[cfg(feature = "tts")]
+                                {
+                                    new_speaker = Some(new);
+                                }
+                            }
+                        }
 
-                    // stop generating button
-                    if is_generating {
-                        self.stop_generating_button(
-                            ui,
-                            16.0,
-                            pos2(
-                                ui.cursor().max.x - 32.0,
-                                avail.height() - 32.0 - actual_chatbox_panel_height,
-                            ),
-                        );
+                        // stop generating button
+                        if is_generating {
+                            self.stop_generating_button(
+                                ui,
+                                16.0,
+                                pos2(
+                                    ui.cursor().max.x - 32.0,
+                                    avail.height() - 32.0 - actual_chatbox_panel_height,
+                                ),
+                            );
+                        }
                     }
-                }
+                });
             });
 
+        self.apply_compression();
+        self.show_delete_confirm(ctx);
+        self.show_token_diet(ctx, settings);
+        self.glossary.show(ctx);
+        self.show_blocked_send(ctx, settings);
+        self.show_request_preview(ctx, settings);
+        self.show_cost_warning(ctx, settings);
+        self.show_tool_confirmation(ctx);
+
+        if let Some(message_idx) = self.save_structured_idx.take() {
+            action = ChatAction::SaveStructuredOutput { message_idx };
+        }
+        if let Some((name, text)) = self.flashcards_source.take() {
+            action = ChatAction::MakeFlashcards { name, text };
+        }
+        if std::mem::take(&mut self.enable_streaming) {
+            action = ChatAction::EnableStreaming;
+        }
+
         #[cfg(feature = "tts")]
         {
             if let Some(new_idx) = new_speaker {
@@ -1300,3 +4714,255 @@ impl Chat {
         action
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Messages after feeding a recorded stream to the part accumulator the
+    /// way [`Chat::poll_flower`] does, and the error that ended it, if any
+    fn accumulate(fixture: &str) -> (Vec<Message>, Option<String>) {
+        let path = Path::new(crate::replay::FIXTURES_DIR).join(format!("{fixture}.sse"));
+        let events = crate::replay::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let model = GeminiModel::default();
+        let mut messages = vec![
+            Message::user("prompt".to_owned(), model, Vec::new()),
+            Message::assistant(String::new(), model),
+        ];
+        for event in events {
+            let parts = match event {
+                crate::replay::ReplayEvent::Parts(parts) => parts,
+                crate::replay::ReplayEvent::Error(e) => return (messages, Some(e)),
+            };
+            for part in parts {
+                match part {
+                    Part::text(data) => {
+                        push_response_text(&mut messages, data.text(), *data.thought())
+                    }
+                    other => {
+                        let markdown = code_execution_markdown(&other).unwrap();
+                        push_response_text(&mut messages, &markdown, false);
+                    }
+                }
+            }
+        }
+        (messages, None)
+    }
+
+    /// Whether each answer is a thought, whether it's still generating, and its text
+    fn split(messages: &[Message]) -> Vec<(bool, bool, &str)> {
+        messages[1..]
+            .iter()
+            .map(|m| (m.is_thought, m.is_generating, m.content.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn accumulates_thought_then_answer() {
+        let (messages, error) = accumulate("thought_then_answer");
+        assert_eq!(error, None);
+        assert_eq!(
+            split(&messages),
+            [
+                (
+                    true,
+                    false,
+                    "**Considering the question**\n\nThe user asks for a greeting. \
+                    A short and friendly reply will do."
+                ),
+                (false, true, "Hello! How can I help you today?"),
+            ]
+        );
+        assert!(messages[1].generation_time.is_some());
+        assert_eq!(messages[1].thought_steps.len(), 1);
+    }
+
+    #[test]
+    fn accumulates_interleaved_thoughts_in_order() {
+        let (messages, error) = accumulate("interleaved_thoughts");
+        assert_eq!(error, None);
+        assert_eq!(
+            split(&messages),
+            [
+                (true, false, "**Planning**\n\nFirst list the steps."),
+                (false, false, "1. Boil the water\n2. Add the pasta\n"),
+                (
+                    true,
+                    false,
+                    "**Checking the timing**\n\nMost pasta needs 8-10 minutes."
+                ),
+                (false, true, "3. Cook for 8-10 minutes\n4. Drain and serve"),
+            ]
+        );
+    }
+
+    #[test]
+    fn accumulates_until_interrupted() {
+        let (messages, error) = accumulate("interrupted");
+        assert_eq!(
+            error.as_deref(),
+            Some("error decoding response body: connection reset by peer")
+        );
+        // the text before the error stays in the answer being generated
+        assert_eq!(
+            split(&messages),
+            [(
+                false,
+                true,
+                "Rust's ownership model has three rules:\n\n1. Each value has an owner.\n"
+            )]
+        );
+    }
+
+    #[test]
+    fn accumulates_code_execution_into_the_answer() {
+        let (messages, error) = accumulate("code_execution");
+        assert_eq!(error, None);
+        assert_eq!(messages.len(), 2);
+        assert!(!messages[1].is_thought);
+        assert_eq!(
+            messages[1].content,
+            "Let me compute that. \n\n**▶ Ran code**\n```python\n\
+            total = sum(range(1, 101))\nprint(total)\n```\n\n\
+            **Output**\n```text\n5050\n```\n\n\
+            The sum of the numbers from 1 to 100 is **5050**."
+        );
+    }
+
+    #[test]
+    fn files_after_a_thought_start_the_answer() {
+        let (mut messages, _) = accumulate("thought_then_answer");
+        messages.truncate(2);
+        assert!(messages[1].is_thought);
+
+        push_response_file(&mut messages, PathBuf::from("plot.png"));
+        assert_eq!(messages.len(), 3);
+        assert!(!messages[1].is_generating);
+        assert!(!messages[2].is_thought);
+        assert_eq!(messages[2].files, [PathBuf::from("plot.png")]);
+
+        // text that follows goes into the same answer
+        push_response_text(&mut messages, "Here is the plot.", false);
+        push_response_file(&mut messages, PathBuf::from("data.csv"));
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2].content, "Here is the plot.");
+        assert_eq!(messages[2].files.len(), 2);
+    }
+
+    #[test]
+    fn failed_history_is_saved_as_it_was() {
+        let stored = r#"[(content: 42)]"#;
+        let mut messages: Messages = with_lazy_messages(|| ron::from_str(stored)).unwrap();
+        assert!(!messages.is_loaded());
+        assert!(messages.load_error().is_some());
+        assert!(messages.is_empty());
+
+        messages.push(Message::default());
+        let saved = with_lazy_messages(|| ron::to_string(&messages)).unwrap();
+        assert_eq!(saved, stored);
+    }
+
+    #[test]
+    fn deletions_while_generating_are_all_applied() {
+        let mut chat = Chat::default();
+        let model = chat.model_picker.selected;
+        for i in 0..6 {
+            chat.messages
+                .push(Message::user(i.to_string(), model, Vec::new()));
+        }
+        chat.delete_after_stop = vec![1..2, 4..6, 1..2, 3..5];
+        chat.remove_messages_after_stop();
+        let left: Vec<&str> = chat.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(left, ["0", "2"]);
+        assert!(chat.delete_after_stop.is_empty());
+    }
+
+    /// Chat with a prompt and the placeholder for its answer
+    fn chat_with_prompt(prompt: &str) -> Chat {
+        let mut chat = Chat::default();
+        let model = chat.model_picker.selected;
+        chat.messages
+            .push(Message::user(prompt.to_owned(), model, Vec::new()));
+        chat.messages.push(Message::assistant(String::new(), model));
+        chat
+    }
+
+    /// Runs the completion pipeline on a recorded stream and polls the chat
+    /// the way the UI does until the answer is done. Returns the `contents`
+    /// of the requests the model was sent.
+    async fn replay(chat: &mut Chat, fixture: &str, use_streaming: bool) -> Vec<serde_json::Value> {
+        let requests = spawn_replay_task(
+            chat.flower.handle(),
+            chat.messages.clone(),
+            chat.stop_generating.clone(),
+            Path::new(crate::replay::FIXTURES_DIR).join(format!("{fixture}.sse")),
+            Duration::ZERO,
+            use_streaming,
+        );
+        let ctx = egui::Context::default();
+        let mut modal = Modal::new(&ctx, "test_modal");
+        let started = Instant::now();
+        while chat.messages.iter().any(|m| m.is_generating) {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "the replay of `{fixture}` didn't finish"
+            );
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            chat.poll_flower(&mut modal);
+        }
+        let requests = requests.lock().unwrap().clone();
+        requests
+    }
+
+    #[tokio::test]
+    async fn interrupted_stream_keeps_the_text() {
+        let mut chat = chat_with_prompt("Explain ownership");
+        replay(&mut chat, "interrupted", true).await;
+        let answer = chat.messages.last().unwrap();
+        assert_eq!(
+            answer.content,
+            "Rust's ownership model has three rules:\n\n1. Each value has an owner.\n"
+        );
+        assert!(!answer.is_error);
+        let error = answer.interrupted.as_deref().unwrap();
+        assert!(error.contains("connection reset by peer"), "{error}");
+        assert_eq!(chat.last_response_failed(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn interrupted_without_streaming_is_an_error() {
+        let mut chat = chat_with_prompt("Explain ownership");
+        replay(&mut chat, "interrupted", false).await;
+        let answer = chat.messages.last().unwrap();
+        assert!(answer.is_error);
+        assert!(answer.content.contains("connection reset by peer"));
+        assert!(answer.interrupted.is_none());
+    }
+
+    #[tokio::test]
+    async fn continuing_sends_the_partial_answer() {
+        let mut chat = chat_with_prompt("Hi");
+        let partial = "Hello there, ";
+        let answer = chat.messages.last_mut().unwrap();
+        answer.content = partial.to_owned();
+        answer.is_generating = true;
+
+        let requests = replay(&mut chat, "code_execution", true).await;
+        let contents = requests[0].as_array().unwrap();
+        let roles: Vec<&str> = contents
+            .iter()
+            .map(|turn| turn["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["user", "model", "user"]);
+        assert_eq!(contents[1]["parts"][0]["text"], partial);
+        assert_eq!(contents[2]["parts"][0]["text"], CONTINUE_INSTRUCTION);
+        // the continuation is appended to the partial answer, not a new one
+        assert_eq!(chat.messages.len(), 2);
+        let answer = &chat.messages[1].content;
+        assert!(
+            answer.starts_with("Hello there, Let me compute that. "),
+            "{answer}"
+        );
+    }
+}
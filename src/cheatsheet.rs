@@ -0,0 +1,131 @@
+//! The keyboard shortcut cheat sheet: every shortcut, chatbox command and
+//! hidden click in one overlay, opened with `?` or from the settings.
+
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+pub const CHEAT_SHEET_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F1);
+
+/// Opens or closes the cheat sheet on `?` or F1, call every frame. `?` is
+/// left alone while a text field is focused, Shift is ignored for it since
+/// most layouts need it to type `?`
+pub fn handle_shortcut(ctx: &egui::Context, open: &mut bool) {
+    let typing = ctx.wants_keyboard_input();
+    let toggled = ctx.input_mut(|i| {
+        i.consume_shortcut(&CHEAT_SHEET_SHORTCUT)
+            || (!typing && i.consume_key(Modifiers::NONE, Key::Questionmark))
+    });
+    if toggled {
+        *open = !*open;
+    } else if *open && !typing && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+        *open = false;
+    }
+}
+
+fn section(ui: &mut egui::Ui, title: &str, rows: &[(String, &str)]) {
+    ui.strong(title);
+    egui::Grid::new(("cheat_sheet", title))
+        .num_columns(2)
+        .striped(true)
+        .spacing([16.0, 4.0])
+        .show(ui, |ui| {
+            for (keys, description) in rows {
+                ui.monospace(keys);
+                ui.label(*description);
+                ui.end_row();
+            }
+        });
+    ui.add_space(8.0);
+}
+
+pub fn show(ctx: &egui::Context, open: &mut bool) {
+    let shortcut = |shortcut: &KeyboardShortcut| ctx.format_shortcut(shortcut);
+
+    egui::Window::new("⌨ Shortcuts")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(480.0)
+                .show(ui, |ui| {
+                    section(
+                        ui,
+                        "Chatbox",
+                        &[
+                            (
+                                "Enter".to_owned(),
+                                "Send, or queue while an answer is generated",
+                            ),
+                            (
+                                "Shift+Enter".to_owned(),
+                                "Queue the message to send after the current answer",
+                            ),
+                            (
+                                shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Enter)),
+                                "New line",
+                            ),
+                            (
+                                shortcut(&crate::plain_text::PASTE_PLAIN_SHORTCUT),
+                                "Paste as plain text",
+                            ),
+                            (
+                                crate::compress::COMMAND.to_owned(),
+                                "Replace the older messages with a summary",
+                            ),
+                            (
+                                "Drop files".to_owned(),
+                                "Attach images, videos, audio and text files",
+                            ),
+                            (
+                                "Right click ➕".to_owned(),
+                                "Attach a folder or take a photo",
+                            ),
+                        ],
+                    );
+                    section(
+                        ui,
+                        "Window",
+                        &[
+                            (
+                                shortcut(&crate::sessions::FOCUS_MODE_SHORTCUT),
+                                "Focus mode, only the conversation",
+                            ),
+                            (
+                                shortcut(&crate::switcher::SWITCHER_SHORTCUT),
+                                "Switch to a recent chat, hold Ctrl and press Tab again to go further",
+                            ),
+                            (
+                                format!("? or {}", shortcut(&CHEAT_SHEET_SHORTCUT)),
+                                "This cheat sheet",
+                            ),
+                        ],
+                    );
+                    section(
+                    ui,
+                    "Messages and chats",
+                    &[
+                        (
+                            "🔄".to_owned(),
+                            "Regenerate, text typed in the box is kept as the start of the answer",
+                        ),
+                        ("Esc".to_owned(), "Cancel regenerating or renaming"),
+                        ("Right click 🗐".to_owned(), "Copy a message formatted or as markdown"),
+                        ("Double click".to_owned(), "Rename a chat in the sidebar"),
+                        ("Shift+click ❌".to_owned(), "Remove a chat without asking"),
+                    ],
+                );
+                    #[cfg(feature = "scripting")]
+                    {
+                        let commands = crate::plugins::commands();
+                        if !commands.is_empty() {
+                            let rows: Vec<(String, &str)> = commands
+                                .into_iter()
+                                .map(|command| (format!("{command} …"), "Plugin command"))
+                                .collect();
+                            section(ui, "Chatbox commands", &rows);
+                        }
+                    }
+                });
+        });
+}
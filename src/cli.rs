@@ -0,0 +1,123 @@
+//! Headless `ask` command, sharing settings and the request pipeline with the GUI.
+
+use crate::widgets::{GeminiModel, Settings};
+use std::{io::Write, path::PathBuf};
+
+const USAGE: &str = "\
+Usage: gemini-gui ask <PROMPT> [OPTIONS]
+
+Options:
+  -m, --model <MODEL>  Model name or part of it (e.g. `flash`, `gemini-2.5-pro`)
+  -f, --file <PATH>    Attach a file, can be repeated
+      --no-stream      Wait for the full response instead of streaming it
+  -h, --help           Print this help";
+
+pub struct AskCommand {
+    prompt: String,
+    model: Option<GeminiModel>,
+    files: Vec<PathBuf>,
+    no_stream: bool,
+}
+
+impl AskCommand {
+    /// Parses the process arguments (without the program name). Returns `None`
+    /// when the arguments are not an `ask` command, so the GUI should start.
+    pub fn parse(args: &[String]) -> Option<Result<Self, String>> {
+        let (first, rest) = args.split_first()?;
+        if first != "ask" {
+            return None;
+        }
+        Some(Self::parse_ask(rest))
+    }
+
+    fn parse_ask(args: &[String]) -> Result<Self, String> {
+        let mut prompt = None;
+        let mut model = None;
+        let mut files = Vec::new();
+        let mut no_stream = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Err(String::new()),
+                "-m" | "--model" => {
+                    let name = args.next().ok_or("missing value for `--model`")?;
+                    model = Some(
+                        GeminiModel::from_query(name)
+                            .ok_or_else(|| format!("unknown model `{name}`"))?,
+                    );
+                }
+                "-f" | "--file" => {
+                    let path = args.next().ok_or("missing value for `--file`")?;
+                    files.push(PathBuf::from(path));
+                }
+                "--no-stream" => no_stream = true,
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ if prompt.is_none() => prompt = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument `{arg}`")),
+            }
+        }
+
+        Ok(Self {
+            prompt: prompt.ok_or("missing prompt")?,
+            model,
+            files,
+            no_stream,
+        })
+    }
+}
+
+/// Prints the usage, with an optional error before it. Returns the exit code.
+pub fn print_usage(error: &str) -> i32 {
+    if error.is_empty() {
+        println!("{USAGE}");
+        0
+    } else {
+        eprintln!("error: {error}\n\n{USAGE}");
+        2
+    }
+}
+
+/// Runs the command, printing the answer to stdout and thoughts to stderr.
+/// Returns the process exit code.
+pub async fn run(command: AskCommand, mut settings: Settings) -> i32 {
+    if settings.api_key.is_empty() {
+        settings.api_key = std::env::var("GEMINI_API_KEY").unwrap_or_default();
+    }
+    if command.no_stream {
+        settings.use_streaming = false;
+    }
+
+    let mut model_picker = settings.model_picker.clone();
+    if let Some(model) = command.model {
+        settings.model_profiles.select(&mut model_picker, model);
+    }
+    log::info!("asking {} from the command line", model_picker.selected);
+
+    let result = crate::chat::ask_headless(
+        &settings,
+        model_picker,
+        command.prompt,
+        command.files,
+        |text, is_thought| {
+            if is_thought {
+                eprint!("{text}");
+            } else {
+                print!("{text}");
+                let _ = std::io::stdout().flush();
+            }
+        },
+    )
+    .await;
+
+    match result {
+        Ok(()) => {
+            println!();
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            1
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! History compression: `/compress` in the chatbox replaces the older messages
+//! of a chat with a summary written by the model. The originals are kept in
+//! the summary message, and can be restored from it.
+
+use crate::widgets::Settings;
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+/// Typed alone in the chatbox, compresses the history instead of being sent
+pub const COMMAND: &str = "/compress";
+/// Prompts kept as they are, with their answers
+pub const KEEP_RECENT_PROMPTS: usize = 2;
+/// Starts the summary message, it's sent to the model too
+pub const SUMMARY_HEADING: &str = "**Summary of the conversation so far**\n\n";
+
+const COMPRESS_PROMPT: &str = "Summarize the following conversation between a user and an AI \
+    assistant so it can continue from the summary alone. Keep the user's goals, the facts, \
+    decisions, names, numbers, code identifiers and open questions, drop the small talk. \
+    Write it as a compact list of points and reply with the summary only.\n\n";
+
+/// A finished summary of the first `count` messages, which had `content_len`
+/// bytes of text when it was requested
+pub struct Compressed {
+    pub count: usize,
+    pub content_len: usize,
+    pub summary: String,
+}
+
+#[derive(Default)]
+struct State {
+    pending: bool,
+    done: Option<Compressed>,
+}
+
+#[derive(Default)]
+pub struct Compressor {
+    state: Arc<Mutex<State>>,
+}
+
+impl Compressor {
+    pub fn is_pending(&self) -> bool {
+        self.state.lock().is_ok_and(|s| s.pending)
+    }
+
+    /// Asks a fast model to summarize `transcript`, the first `count` messages
+    pub fn start(
+        &self,
+        ctx: &egui::Context,
+        settings: &Settings,
+        count: usize,
+        content_len: usize,
+        transcript: String,
+    ) {
+        let mut model_picker = settings.model_picker.clone();
+        model_picker.selected = crate::routing::FAST_MODEL;
+        model_picker.auto_route = false;
+        model_picker.system_prompt = None;
+        let settings = settings.clone();
+        let state = self.state.clone();
+        let ctx = ctx.clone();
+        if let Ok(mut state) = state.lock() {
+            state.pending = true;
+        }
+        log::info!("compressing {count} messages ({} bytes)", transcript.len());
+
+        tokio::spawn(async move {
+            let mut summary = String::new();
+            let result = crate::chat::ask_headless(
+                &settings,
+                model_picker,
+                format!("{COMPRESS_PROMPT}{transcript}"),
+                Vec::new(),
+                |text, is_thought| {
+                    if !is_thought {
+                        summary.push_str(text);
+                    }
+                },
+            )
+            .await;
+            if let Ok(mut state) = state.lock() {
+                state.pending = false;
+                match result {
+                    Ok(()) if !summary.trim().is_empty() => {
+                        state.done = Some(Compressed {
+                            count,
+                            content_len,
+                            summary: summary.trim().to_owned(),
+                        });
+                    }
+                    Ok(()) => crate::notify::report_error(
+                        "compress",
+                        "The model returned an empty summary",
+                    ),
+                    Err(e) => crate::notify::report_error(
+                        "compress",
+                        format!("Failed to compress the history: {e}"),
+                    ),
+                }
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn take_finished(&self) -> Option<Compressed> {
+        self.state.lock().ok()?.done.take()
+    }
+}
@@ -0,0 +1,142 @@
+//! Panic capture: writes a crash report with the backtrace and recent log
+//! lines, which is offered to the user on the next launch.
+
+use std::{collections::VecDeque, io::Write, path::PathBuf, sync::Mutex};
+
+const ISSUES_URL: &str = "https://github.com/LaVashikk/Gemini-eGUI/issues/new";
+const LOG_CAPACITY: usize = 200;
+
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Forwards to `env_logger`, while also keeping the recent info-and-above lines
+/// for crash reports regardless of `RUST_LOG`.
+struct RingLogger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for RingLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= log::Level::Info || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if record.level() <= log::Level::Info {
+            let line = format!(
+                "{} {:<5} {}: {}",
+                chrono::Local::now().format("%H:%M:%S%.3f"),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            if let Ok(mut logs) = RECENT_LOGS.lock() {
+                if logs.len() >= LOG_CAPACITY {
+                    logs.pop_front();
+                }
+                logs.push_back(line);
+            }
+        }
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Sets up logging and the panic hook. Replaces `env_logger::init()`.
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    log::set_max_level(inner.filter().max(log::LevelFilter::Info));
+    if let Err(e) = log::set_boxed_logger(Box::new(RingLogger { inner })) {
+        eprintln!("failed to set up logging: {e}");
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // panics in worker threads are caught and shown in the UI instead
+        if std::thread::current().name() == Some("main") {
+            match write_report(&info.to_string()) {
+                Ok(path) => eprintln!("crash report saved to `{}`", path.display()),
+                Err(e) => eprintln!("failed to save crash report: {e}"),
+            }
+        }
+        default_hook(info);
+    }));
+}
+
+fn crash_dir() -> PathBuf {
+    eframe::storage_dir(crate::TITLE)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("crashes")
+}
+
+fn write_report(message: &str) -> std::io::Result<PathBuf> {
+    let dir = crash_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "crash-{}.txt",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    let mut f = std::fs::File::create(&path)?;
+    writeln!(f, "{} {}", crate::TITLE, env!("CARGO_PKG_VERSION"))?;
+    writeln!(
+        f,
+        "OS: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(f, "\n{message}")?;
+    writeln!(
+        f,
+        "\nBacktrace:\n{}",
+        std::backtrace::Backtrace::force_capture()
+    )?;
+    writeln!(f, "\nRecent log:")?;
+    if let Ok(logs) = RECENT_LOGS.lock() {
+        for line in logs.iter() {
+            writeln!(f, "{line}")?;
+        }
+    }
+    Ok(path)
+}
+
+/// Returns the newest crash report the user hasn't seen yet
+pub fn pending_report() -> Option<PathBuf> {
+    std::fs::read_dir(crash_dir())
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "txt")
+                && !path.to_string_lossy().ends_with(".seen.txt")
+        })
+        .max()
+}
+
+/// Marks a report as seen so it isn't offered again
+pub fn mark_seen(path: &std::path::Path) {
+    let seen = path.with_extension("seen.txt");
+    if let Err(e) = std::fs::rename(path, &seen) {
+        log::error!("failed to mark crash report as seen: {e}");
+    }
+}
+
+/// Builds a "new issue" link pre-filled with the start of the report
+pub fn issue_url(path: &std::path::Path) -> String {
+    const MAX_BODY_LEN: usize = 4000;
+    let report = std::fs::read_to_string(path).unwrap_or_default();
+    let mut body: String = report.chars().take(MAX_BODY_LEN).collect();
+    if body.len() < report.len() {
+        body.push_str("\n…(truncated, full report attached)");
+    }
+    let title = report
+        .lines()
+        .find(|l| l.starts_with("panicked at"))
+        .unwrap_or("Crash report");
+    let body = format!("```\n{body}\n```");
+    url::Url::parse_with_params(ISSUES_URL, [("title", title), ("body", body.as_str())])
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| ISSUES_URL.to_owned())
+}
@@ -0,0 +1,123 @@
+//! `egeminui://` links, e.g. `egeminui://new?prompt=Hello&model=flash`.
+
+use crate::widgets::GeminiModel;
+use anyhow::{anyhow, bail, Context, Result};
+
+pub const SCHEME: &str = "egeminui";
+
+/// What a link asks the app to do
+#[derive(Debug)]
+pub struct DeepLink {
+    /// Put into the chatbox of a new chat, for the user to send
+    pub prompt: String,
+    pub model: Option<GeminiModel>,
+}
+
+/// Returns whether a command-line argument looks like one of our links
+pub fn is_link(arg: &str) -> bool {
+    arg.starts_with(&format!("{SCHEME}:"))
+}
+
+pub fn parse(link: &str) -> Result<DeepLink> {
+    let url = url::Url::parse(link).context("invalid link")?;
+    if url.scheme() != SCHEME {
+        bail!("unsupported link scheme `{}`", url.scheme());
+    }
+    match url.host_str() {
+        Some("new") => (),
+        Some(other) => bail!("unsupported link action `{other}`"),
+        None => bail!("link has no action"),
+    }
+
+    let mut link = DeepLink {
+        prompt: String::new(),
+        model: None,
+    };
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "prompt" => link.prompt = value.into_owned(),
+            "send" => log::warn!("ignoring `send`, links only pre-fill the prompt"),
+            "model" => {
+                link.model = Some(
+                    GeminiModel::from_query(&value)
+                        .ok_or_else(|| anyhow!("unknown model `{value}`"))?,
+                )
+            }
+            _ => log::warn!("ignoring unknown link parameter `{key}`"),
+        }
+    }
+    Ok(link)
+}
+
+/// Registers the app as the handler of `egeminui://` links for the current user.
+#[cfg(target_os = "linux")]
+pub fn register() -> Result<()> {
+    const DESKTOP_FILE: &str = "gemini-gui-url-handler.desktop";
+    let exe = std::env::current_exe()?;
+    let dir = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::PathBuf::from(home).join(".local/share"))
+        })
+        .context("could not determine the data directory")?
+        .join("applications");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+        dir.join(DESKTOP_FILE),
+        format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec=\"{}\" %u\nMimeType=x-scheme-handler/{SCHEME};\nNoDisplay=true\n",
+            crate::TITLE,
+            exe.display()
+        ),
+    )?;
+    let status = std::process::Command::new("xdg-mime")
+        .args(["default", DESKTOP_FILE])
+        .arg(format!("x-scheme-handler/{SCHEME}"))
+        .status()
+        .context("failed to run `xdg-mime`")?;
+    anyhow::ensure!(status.success(), "`xdg-mime` exited with {status}");
+    Ok(())
+}
+
+/// Registers the app as the handler of `egeminui://` links for the current user.
+#[cfg(target_os = "windows")]
+pub fn register() -> Result<()> {
+    let key = format!(r"HKCU\Software\Classes\{SCHEME}");
+    let command = format!("\"{}\" \"%1\"", std::env::current_exe()?.display());
+    for args in [
+        vec![
+            key.clone(),
+            "/ve".into(),
+            "/d".into(),
+            format!("URL:{SCHEME} Protocol"),
+        ],
+        vec![
+            key.clone(),
+            "/v".into(),
+            "URL Protocol".into(),
+            "/d".into(),
+            String::new(),
+        ],
+        vec![
+            format!(r"{key}\shell\open\command"),
+            "/ve".into(),
+            "/d".into(),
+            command,
+        ],
+    ] {
+        let status = std::process::Command::new("reg")
+            .arg("add")
+            .args(args)
+            .arg("/f")
+            .status()
+            .context("failed to run `reg`")?;
+        anyhow::ensure!(status.success(), "`reg` exited with {status}");
+    }
+    Ok(())
+}
+
+/// Registers the app as the handler of `egeminui://` links for the current user.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn register() -> Result<()> {
+    bail!("link handlers are declared by the app bundle on this platform")
+}
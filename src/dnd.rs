@@ -0,0 +1,142 @@
+//! Do not disturb: holds back toasts and sound cues by hand, during quiet
+//! hours or while another app is fullscreen. Held toasts show up once it ends.
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How often the foreground app is checked for being fullscreen
+const FULLSCREEN_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+static FULLSCREEN_APP: AtomicBool = AtomicBool::new(false);
+static FULLSCREEN_CHECKED: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DoNotDisturb {
+    /// Turned on by hand from the sidebar
+    pub enabled: bool,
+    pub quiet_hours: bool,
+    /// Hour of the day quiet hours start at, they can go past midnight
+    pub start_hour: u32,
+    pub end_hour: u32,
+    pub when_fullscreen: bool,
+}
+
+impl Default for DoNotDisturb {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            quiet_hours: false,
+            start_hour: 22,
+            end_hour: 8,
+            when_fullscreen: false,
+        }
+    }
+}
+
+/// Why notifications are held back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Manual,
+    QuietHours,
+    FullscreenApp,
+}
+
+impl std::fmt::Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Manual => "Do not disturb is on",
+            Self::QuietHours => "Quiet hours",
+            Self::FullscreenApp => "An app is fullscreen",
+        })
+    }
+}
+
+impl DoNotDisturb {
+    fn in_quiet_hours(&self) -> bool {
+        let hour = chrono::Local::now().hour();
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+
+    /// Why notifications are held back right now, `None` if they aren't
+    pub fn active(&self) -> Option<Reason> {
+        if self.enabled {
+            Some(Reason::Manual)
+        } else if self.quiet_hours && self.in_quiet_hours() {
+            Some(Reason::QuietHours)
+        } else if self.when_fullscreen && fullscreen_app() {
+            Some(Reason::FullscreenApp)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether another app was fullscreen at the last check, starts a new check
+/// in the background when the last one is old
+fn fullscreen_app() -> bool {
+    if let Ok(mut checked) = FULLSCREEN_CHECKED.lock() {
+        if checked.is_none_or(|c| c.elapsed() >= FULLSCREEN_CHECK_INTERVAL) {
+            *checked = Some(Instant::now());
+            std::thread::spawn(|| {
+                FULLSCREEN_APP.store(detect_fullscreen_app(), Ordering::Relaxed);
+            });
+        }
+    }
+    FULLSCREEN_APP.load(Ordering::Relaxed)
+}
+
+#[cfg(windows)]
+fn detect_fullscreen_app() -> bool {
+    use windows_sys::Win32::UI::Shell::{
+        SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE,
+        QUNS_RUNNING_D3D_FULL_SCREEN,
+    };
+    let mut state = 0;
+    // SAFETY: the pointer is to a local that outlives the call
+    let result = unsafe { SHQueryUserNotificationState(&mut state) };
+    result >= 0
+        && matches!(
+            state,
+            QUNS_BUSY | QUNS_RUNNING_D3D_FULL_SCREEN | QUNS_PRESENTATION_MODE
+        )
+}
+
+/// Asks the X server about the active window, always false on Wayland
+#[cfg(all(unix, not(target_os = "macos")))]
+fn detect_fullscreen_app() -> bool {
+    let xprop = |args: &[&str]| {
+        std::process::Command::new("xprop")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let Some(active) = xprop(&["-root", "_NET_ACTIVE_WINDOW"]) else {
+        return false;
+    };
+    let Some(window) = active
+        .split_whitespace()
+        .last()
+        .filter(|id| id.starts_with("0x") && *id != "0x0")
+    else {
+        return false;
+    };
+    xprop(&["-id", window, "_NET_WM_STATE"])
+        .is_some_and(|state| state.contains("_NET_WM_STATE_FULLSCREEN"))
+}
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+fn detect_fullscreen_app() -> bool {
+    false
+}
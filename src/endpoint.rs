@@ -0,0 +1,255 @@
+//! Custom API endpoint: a base URL in place of the Gemini API's and extra
+//! headers sent with every request, for corporate gateways, API-compatible
+//! mirrors and header-based auth. The API client only talks to Google, so
+//! requests to a custom endpoint are made here.
+
+use crate::timeouts::RequestTimeouts;
+use anyhow::{Context, Result};
+use eframe::egui;
+use gemini_client_api::gemini::types::request::Part;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::{Map, Value};
+use std::time::Duration;
+use tokio_stream::wrappers::ReceiverStream;
+
+pub const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Endpoint {
+    /// Replaces [`DEFAULT_BASE_URL`], empty to use it
+    pub base_url: String,
+    /// Sent with every request to the API
+    pub headers: Vec<(String, String)>,
+}
+
+impl Endpoint {
+    /// Whether requests can't go through the API client
+    pub fn is_custom(&self) -> bool {
+        !self.base_url.trim().is_empty() || self.headers.iter().any(|(name, _)| !name.is_empty())
+    }
+
+    pub fn base_url(&self) -> &str {
+        match self.base_url.trim().trim_end_matches('/') {
+            "" => DEFAULT_BASE_URL,
+            url => url,
+        }
+    }
+
+    /// Host the requests go to, without a proxy
+    pub fn host(&self) -> Option<(String, u16)> {
+        let url = url::Url::parse(self.base_url()).ok()?;
+        Some((url.host_str()?.to_owned(), url.port_or_known_default()?))
+    }
+
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers.iter().filter(|(name, _)| !name.is_empty()) {
+            headers.insert(
+                HeaderName::from_bytes(name.trim().as_bytes())
+                    .with_context(|| format!("invalid header name `{name}`"))?,
+                HeaderValue::from_str(value.trim())
+                    .with_context(|| format!("invalid value of the `{name}` header"))?,
+            );
+        }
+        Ok(headers)
+    }
+
+    /// HTTP client sending the extra headers, through `proxy` if it's set.
+    /// There's no limit on the whole response, streamed ones can take long.
+    pub fn http_client(
+        &self,
+        proxy: Option<&str>,
+        connect_timeout: Duration,
+        read_timeout: Duration,
+    ) -> Result<reqwest::Client> {
+        let mut client = reqwest::Client::builder()
+            .connect_timeout(connect_timeout)
+            .read_timeout(read_timeout)
+            .default_headers(self.header_map()?);
+        if let Some(proxy) = proxy {
+            client = client.proxy(reqwest::Proxy::all(proxy).context("invalid proxy URL")?);
+        }
+        Ok(client.build()?)
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("API base URL");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.base_url)
+                    .hint_text(DEFAULT_BASE_URL)
+                    .desired_width(260.0),
+            );
+        });
+        if !self.base_url.trim().is_empty() && url::Url::parse(self.base_url()).is_err() {
+            ui.colored_label(ui.visuals().error_fg_color, "Not a valid URL");
+        }
+        ui.label("Extra request headers");
+        let mut to_remove = None;
+        for (i, (name, value)) in self.headers.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(name)
+                        .desired_width(120.0)
+                        .hint_text("Header"),
+                );
+                ui.add(egui::TextEdit::singleline(value).hint_text("Value"));
+                if ui.small_button("🗑").clicked() {
+                    to_remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = to_remove {
+            self.headers.remove(i);
+        }
+        if ui.small_button("➕ Add header").clicked() {
+            self.headers.push(Default::default());
+        }
+        if let Err(e) = self.header_map() {
+            ui.colored_label(ui.visuals().error_fg_color, format!("{e:#}"));
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Response {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+}
+
+#[derive(serde::Deserialize)]
+struct Candidate {
+    content: Option<Content>,
+}
+
+#[derive(serde::Deserialize)]
+struct Content {
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+/// Parts of the first candidates of a `generateContent` response, or one
+/// chunk of a streamed one
+pub fn response_parts(response: &Value) -> Result<Vec<Part>> {
+    let response: Response =
+        serde_json::from_value(response.clone()).context("invalid response")?;
+    Ok(response
+        .candidates
+        .into_iter()
+        .filter_map(|c| c.content)
+        .flat_map(|c| c.parts)
+        .collect())
+}
+
+/// Error message of a failed request, the API explains it in the body
+async fn status_error(response: reqwest::Response) -> String {
+    let status = response.status();
+    match response.text().await {
+        // JSON errors are shown formatted
+        Ok(body) if serde_json::from_str::<Value>(&body).is_ok() => body,
+        Ok(body) if !body.trim().is_empty() => format!("{status}: {}", body.trim()),
+        _ => status.to_string(),
+    }
+}
+
+/// Requests to a custom endpoint, with the rest of the body the API client
+/// would send (system instruction, generation config and so on)
+pub struct EndpointClient {
+    client: reqwest::Client,
+    /// `{base}/v1beta/models/{model}`
+    model_url: String,
+    api_key: String,
+    body: Map<String, Value>,
+}
+
+impl EndpointClient {
+    pub fn new(
+        endpoint: &Endpoint,
+        proxy: Option<&str>,
+        timeouts: RequestTimeouts,
+        api_key: &str,
+        model: &str,
+        body: Map<String, Value>,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: endpoint.http_client(proxy, timeouts.connect(), timeouts.read())?,
+            model_url: format!("{}/v1beta/models/{model}", endpoint.base_url()),
+            api_key: api_key.to_owned(),
+            body,
+        })
+    }
+
+    fn request(&self, method: &str, contents: Value) -> reqwest::RequestBuilder {
+        let mut body = self.body.clone();
+        body.insert("contents".to_owned(), contents);
+        let mut request = self
+            .client
+            .post(format!("{}:{method}", self.model_url))
+            .header(reqwest::header::USER_AGENT, crate::TITLE)
+            .json(&body);
+        // gateways with header auth may not need a key
+        if !self.api_key.is_empty() {
+            request = request.header("x-goog-api-key", &self.api_key);
+        }
+        request
+    }
+
+    pub async fn ask(&self, contents: Value) -> Result<Value, String> {
+        let response = self
+            .request("generateContent", contents)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(status_error(response).await);
+        }
+        response.json().await.map_err(|e| e.to_string())
+    }
+
+    /// Sends a streamed request, every `data:` event of the response is one chunk
+    pub async fn ask_as_stream(
+        &self,
+        contents: Value,
+    ) -> Result<ReceiverStream<Result<Value, String>>, String> {
+        let mut response = self
+            .request("streamGenerateContent", contents)
+            .query(&[("alt", "sse")])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(status_error(response).await);
+        }
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            loop {
+                let chunk = match response.chunk().await {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = tx.send(Err(e.to_string())).await;
+                        return;
+                    }
+                };
+                buffer.extend_from_slice(&chunk);
+                while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let Some(data) = line.trim().strip_prefix("data:") else {
+                        continue;
+                    };
+                    let event = serde_json::from_str(data.trim())
+                        .map_err(|e| format!("invalid response chunk: {e}"));
+                    // the receiver is dropped when the answer is stopped
+                    if tx.send(event).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
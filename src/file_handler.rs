@@ -1,11 +1,15 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use base64::Engine;
 use eframe::egui::{self, vec2, Color32, Rect, RichText, Stroke};
 use gemini_client_api::gemini::types::request::{InlineData, Part};
-use image::ImageFormat;
+use image::{DynamicImage, ImageFormat, RgbaImage};
 use std::{
+    collections::{BTreeMap, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
     io::Cursor,
     path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, OnceLock},
+    time::SystemTime,
 };
 
 const GEMINI_MIME: &[&str] = &[
@@ -29,18 +33,139 @@ const GEMINI_MIME: &[&str] = &[
     "text/plain",
 ];
 
-pub async fn convert_file_to_part(path: &Path) -> Result<Part> {
+/// Total size of the base64 data kept in [`PART_CACHE`]
+const MAX_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+struct CachedPart {
+    key: u64,
+    part: Part,
+    size: usize,
+}
+
+/// Converted attachments keyed by a hash of their content, so resending a chat
+/// with many files doesn't convert and encode all of them again
+static PART_CACHE: Mutex<VecDeque<CachedPart>> = Mutex::new(VecDeque::new());
+
+fn cache_key(mime_str: &str, options: ConversionOptions, bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    mime_str.hash(&mut hasher);
+    options.hash(&mut hasher);
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn cached_part(key: u64) -> Option<Part> {
+    let cache = PART_CACHE.lock().ok()?;
+    cache.iter().find(|c| c.key == key).map(|c| c.part.clone())
+}
+
+fn cache_part(key: u64, part: &Part, size: usize) {
+    let Ok(mut cache) = PART_CACHE.lock() else {
+        return;
+    };
+    let mut total: usize = cache.iter().map(|c| c.size).sum::<usize>() + size;
+    while total > MAX_CACHE_BYTES {
+        let Some(evicted) = cache.pop_front() else {
+            break;
+        };
+        total -= evicted.size;
+    }
+    if size <= MAX_CACHE_BYTES {
+        cache.push_back(CachedPart {
+            key,
+            part: part.clone(),
+            size,
+        });
+    }
+}
+
+/// State of an attachment that is being prepared for a request
+#[derive(Debug, Clone)]
+pub enum ConversionStatus {
+    InProgress,
+    Failed(String),
+}
+
+static CONVERSION_STATUS: Mutex<BTreeMap<PathBuf, ConversionStatus>> = Mutex::new(BTreeMap::new());
+
+fn set_conversion_status(path: &Path, status: Option<ConversionStatus>) {
+    if let Ok(mut statuses) = CONVERSION_STATUS.lock() {
+        match status {
+            Some(status) => statuses.insert(path.to_path_buf(), status),
+            None => statuses.remove(path),
+        };
+    }
+}
+
+pub fn conversion_status(path: &Path) -> Option<ConversionStatus> {
+    CONVERSION_STATUS.lock().ok()?.get(path).cloned()
+}
+
+/// How attachments are shrunk before upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Compression {
+    pub compress_images: bool,
+    /// Longest side of uploaded images, in pixels
+    pub max_image_dimension: u32,
+    pub jpeg_quality: u8,
+    pub reencode_audio: bool,
+    pub audio_bitrate_kbps: u32,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            compress_images: false,
+            max_image_dimension: 2048,
+            jpeg_quality: 85,
+            reencode_audio: false,
+            audio_bitrate_kbps: 48,
+        }
+    }
+}
+
+/// Everything that changes how an attachment is converted for upload
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ConversionOptions {
+    pub strip_metadata: bool,
+    pub compression: Compression,
+}
+
+pub async fn convert_file_to_part(path: &Path, options: ConversionOptions) -> Result<Part> {
+    set_conversion_status(path, Some(ConversionStatus::InProgress));
+    let result = convert(path, options).await;
+    match &result {
+        Ok(_) => set_conversion_status(path, None),
+        Err(e) => {
+            set_conversion_status(path, Some(ConversionStatus::Failed(e.to_string())));
+            crate::notify::report_error(
+                "file_handler",
+                format!(
+                    "Failed to attach {}: {e}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ),
+            );
+        }
+    }
+    result
+}
+
+async fn convert(path: &Path, options: ConversionOptions) -> Result<Part> {
+    if path.is_dir() {
+        let dir = path.to_path_buf();
+        let bundle = tokio::task::spawn_blocking(move || bundle_folder(&dir)).await??;
+        return Ok(Part::text(bundle.into()));
+    }
+
     // Asynchronously read the file into bytes
     let file_bytes = tokio::fs::read(path).await?;
 
-    // Determine the MIME type of the file
-    let mime_type = mime_guess::from_path(path).first_or_octet_stream();
-    let mut mime_str = mime_type.to_string();
-
-    if (mime_str.starts_with("application") && mime_str != "application/pdf")
-        || (mime_str.starts_with("text") && mime_str != "text/plain")
-    {
-        mime_str = "text/plain".to_string();
+    let mime_str = upload_mime(path);
+    let key = cache_key(&mime_str, options, &file_bytes);
+    if let Some(part) = cached_part(key) {
+        log::debug!("Using cached conversion of {}", path.display());
+        return Ok(part);
     }
 
     log::info!(
@@ -49,9 +174,238 @@ pub async fn convert_file_to_part(path: &Path) -> Result<Part> {
         mime_str
     );
 
+    // Decoding and encoding large files is CPU-bound, keep it off the async workers
+    let path = path.to_path_buf();
+    let (mime_str, base64) =
+        tokio::task::spawn_blocking(move || encode_file(&path, file_bytes, mime_str, options))
+            .await??;
+
+    // Create a Part for the API
+    let size = base64.len();
+    let part = Part::inline_data(InlineData::new(mime_str, base64));
+    cache_part(key, &part, size);
+    Ok(part)
+}
+
+/// Total size of the text gathered from an attached folder
+const MAX_FOLDER_BYTES: usize = 1024 * 1024;
+/// Larger files in an attached folder are skipped
+const MAX_FOLDER_FILE_BYTES: u64 = 128 * 1024;
+
+/// Concatenates the text files of a folder into one document with a header per
+/// file, skipping anything ignored by `.gitignore`
+fn bundle_folder(dir: &Path) -> Result<String> {
+    let mut bundle = String::new();
+    let mut skipped = 0;
+    let walker = ignore::WalkBuilder::new(dir)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("skipping folder entry: {e}");
+                continue;
+            }
+        };
+        let path = entry.path();
+        let is_text = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+            .is_some_and(|ext| {
+                ext != "pdf"
+                    && (crate::TEXT_FORMATS.contains(&ext.as_str())
+                        || crate::CODE_FORMATS.contains(&ext.as_str()))
+            });
+        if !is_text || !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        if entry
+            .metadata()
+            .map_or(true, |m| m.len() > MAX_FOLDER_FILE_BYTES)
+        {
+            skipped += 1;
+            continue;
+        }
+        let Ok(text) = std::fs::read_to_string(path) else {
+            skipped += 1;
+            continue;
+        };
+
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        let section = format!("=== {} ===\n{text}\n\n", relative.display());
+        if bundle.len() + section.len() > MAX_FOLDER_BYTES {
+            skipped += 1;
+            continue;
+        }
+        bundle.push_str(&section);
+    }
+
+    anyhow::ensure!(!bundle.is_empty(), "no text files found in the folder");
+    if skipped > 0 {
+        bundle.push_str(&format!(
+            "({skipped} files were left out because of size limits)\n"
+        ));
+    }
+    log::info!(
+        "bundled {} bytes of text from `{}`",
+        bundle.len(),
+        dir.display()
+    );
+    Ok(bundle)
+}
+
+/// Decodes image formats the `image` crate can't read by itself.
+/// `None` if `mime_str` isn't one of them.
+fn decode_extra_format(bytes: &[u8], mime_str: &str) -> Option<Result<DynamicImage>> {
+    match mime_str {
+        "image/svg+xml" => Some(decode_svg(bytes)),
+        "image/heic" | "image/heif" => Some(decode_heif(bytes)),
+        _ => None,
+    }
+}
+
+fn decode_svg(bytes: &[u8]) -> Result<DynamicImage> {
+    let image = egui_extras::image::load_svg_bytes(bytes).map_err(|e| anyhow!(e))?;
+    let pixels = image
+        .pixels
+        .iter()
+        .flat_map(|color| color.to_srgba_unmultiplied())
+        .collect();
+    RgbaImage::from_raw(image.width() as u32, image.height() as u32, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| anyhow!("invalid SVG image size"))
+}
+
+#[cfg(feature = "heif")]
+fn decode_heif(bytes: &[u8]) -> Result<DynamicImage> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let context = HeifContext::read_from_bytes(bytes)?;
+    let handle = context.primary_image_handle()?;
+    let image = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .ok_or_else(|| anyhow!("HEIF image has no interleaved plane"))?;
+
+    // rows may be padded, copy only the pixels
+    let row_len = plane.width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    RgbaImage::from_raw(plane.width, plane.height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| anyhow!("invalid HEIF image size"))
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heif(_bytes: &[u8]) -> Result<DynamicImage> {
+    Err(anyhow!(
+        "HEIC/HEIF support is not enabled in this build, rebuild with `--features heif`"
+    ))
+}
+
+/// The MIME type the file is sent as, before any conversion
+fn upload_mime(path: &Path) -> String {
+    let mime_str = mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string();
+    if (mime_str.starts_with("application") && mime_str != "application/pdf")
+        || (mime_str.starts_with("text") && mime_str != "text/plain")
+    {
+        "text/plain".to_string()
+    } else {
+        mime_str
+    }
+}
+
+/// Downscales the image and encodes it as JPEG, or PNG if it has transparency
+fn compress_image(img: DynamicImage, compression: &Compression) -> Result<(String, Vec<u8>)> {
+    let max = compression.max_image_dimension.max(1);
+    let img = if img.width().max(img.height()) > max {
+        img.resize(max, max, image::imageops::FilterType::Triangle)
+    } else {
+        img
+    };
+    let mut buf = Vec::new();
+    if img.color().has_alpha() {
+        img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?;
+        Ok(("image/png".to_string(), buf))
+    } else {
+        img.to_rgb8()
+            .write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(
+                &mut buf,
+                compression.jpeg_quality.clamp(1, 100),
+            ))?;
+        Ok(("image/jpeg".to_string(), buf))
+    }
+}
+
+/// Re-encodes audio to mono Opus with `ffmpeg`
+fn reencode_audio(path: &Path, bitrate_kbps: u32) -> Result<Vec<u8>> {
+    let output = std::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-vn", "-ac", "1", "-c:a", "libopus", "-b:a"])
+        .arg(format!("{bitrate_kbps}k"))
+        .args(["-f", "ogg", "-"])
+        .output()
+        .context("failed to run `ffmpeg`, is it installed?")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`ffmpeg` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(output.stdout)
+}
+
+/// Converts the file to a format Gemini accepts and applies the compression
+/// settings, returning the MIME type and the bytes to upload
+fn prepare_file(
+    path: &Path,
+    file_bytes: Vec<u8>,
+    mut mime_str: String,
+    options: ConversionOptions,
+) -> Result<(String, Vec<u8>)> {
+    let compression = &options.compression;
+    let is_image = mime_str.starts_with("image/");
+    let is_audio = mime_str.starts_with("audio/");
+
     // For images that are not PNG/JPEG, convert them to PNG for better compatibility.
     // For video and text files, we simply send them "as is".
-    let final_bytes = if mime_type.type_() == "image" {
+    let final_bytes = if is_image && compression.compress_images {
+        let img = match decode_extra_format(&file_bytes, &mime_str) {
+            Some(img) => img?,
+            None => image::load_from_memory(&file_bytes)?,
+        };
+        let (compressed_mime, bytes) = compress_image(img, compression)?;
+        log::debug!(
+            "compressed {mime_str} image from {} to {} bytes",
+            file_bytes.len(),
+            bytes.len()
+        );
+        mime_str = compressed_mime;
+        bytes
+    } else if is_audio && compression.reencode_audio {
+        let bytes = reencode_audio(path, compression.audio_bitrate_kbps)?;
+        log::debug!(
+            "re-encoded {mime_str} audio from {} to {} bytes",
+            file_bytes.len(),
+            bytes.len()
+        );
+        mime_str = "audio/ogg".to_string();
+        bytes
+    } else if let Some(img) = decode_extra_format(&file_bytes, &mime_str) {
+        log::debug!("Got {mime_str} image, converting to png");
+        mime_str = "image/png".to_string();
+        let mut buf = Vec::new();
+        img?.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?;
+        buf
+    } else if is_image {
         match image::guess_format(&file_bytes) {
             Ok(format) if !matches!(format, ImageFormat::Png | ImageFormat::Jpeg) => {
                 log::debug!("Got {format:?} image, converting to png");
@@ -62,6 +416,10 @@ pub async fn convert_file_to_part(path: &Path) -> Result<Part> {
                 img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?;
                 buf
             }
+            // re-encoding drops EXIF and other metadata
+            Ok(format) if options.strip_metadata => {
+                crate::image_privacy::strip_metadata(&file_bytes, format)?
+            }
             _ => {
                 // It's already PNG/JPEG or an unknown image format, send as is
                 file_bytes
@@ -79,6 +437,17 @@ pub async fn convert_file_to_part(path: &Path) -> Result<Part> {
             GEMINI_MIME
         ));
     }
+    Ok((mime_str, final_bytes))
+}
+
+/// Converts the file to a format Gemini accepts if needed, and encodes it in base64
+fn encode_file(
+    path: &Path,
+    file_bytes: Vec<u8>,
+    mime_str: String,
+    options: ConversionOptions,
+) -> Result<(String, String)> {
+    let (mime_str, final_bytes) = prepare_file(path, file_bytes, mime_str, options)?;
 
     // Encode the final bytes in Base64
     let base64 = base64::engine::general_purpose::STANDARD.encode(&final_bytes);
@@ -87,12 +456,278 @@ pub async fn convert_file_to_part(path: &Path) -> Result<Part> {
         base64.len(),
         mime_str
     );
+    Ok((mime_str, base64))
+}
 
-    // Create a Part for the API
-    Ok(Part::inline_data(InlineData::new(mime_str, base64)))
+/// Directory for files generated by the model, so they persist with the chat
+pub fn attachments_dir() -> PathBuf {
+    eframe::storage_dir(crate::TITLE)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("attachments")
+}
+
+/// Decodes base64 inline data from a response and saves it to the attachment store
+pub fn save_inline_data(mime_type: &str, data: &str) -> Result<PathBuf> {
+    let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
+    let ext = match mime_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        other => mime_guess::get_mime_extensions_str(other)
+            .and_then(|exts| exts.first().copied())
+            .unwrap_or("bin"),
+    };
+
+    let dir = attachments_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{}.{ext}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S-%9f")
+    ));
+    std::fs::write(&path, bytes)?;
+    log::info!(
+        "saved {} bytes of {mime_type} from the response to `{}`",
+        data.len(),
+        path.display()
+    );
+    Ok(path)
+}
+
+/// Longest side of attachment previews, in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+/// Previews kept in memory, the ones shown least recently are dropped first
+const MAX_THUMBNAILS: usize = 256;
+/// Threads loading previews, the others wait in a queue
+const THUMBNAIL_WORKERS: usize = 2;
+
+enum Thumbnail {
+    Loading,
+    Ready(egui::TextureHandle),
+    Failed,
+}
+
+struct CachedThumbnail {
+    path: PathBuf,
+    /// When the file was modified, a newer version gets a new preview
+    modified: Option<SystemTime>,
+    thumbnail: Thumbnail,
+}
+
+/// Downsized previews of image attachments and video poster frames, so they
+/// aren't kept in memory at full size. The most recently shown one is last
+static THUMBNAILS: Mutex<VecDeque<CachedThumbnail>> = Mutex::new(VecDeque::new());
+
+struct ThumbnailJob {
+    ctx: egui::Context,
+    path: PathBuf,
+    modified: Option<SystemTime>,
 }
 
-pub fn show_files(ui: &mut egui::Ui, files: &mut Vec<PathBuf>, mutate: bool) {
+fn load_thumbnail(path: &Path) -> Result<egui::ColorImage> {
+    let mime_type = mime_guess::from_path(path).first_or_octet_stream();
+    let img = if mime_type.type_() == "video" {
+        crate::video::poster_frame(path)?
+    } else {
+        let bytes = std::fs::read(path)?;
+        match decode_extra_format(&bytes, mime_type.essence_str()) {
+            Some(img) => img?,
+            None => image::load_from_memory(&bytes)?,
+        }
+    }
+    .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+    .into_rgba8();
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        [img.width() as usize, img.height() as usize],
+        img.as_raw(),
+    ))
+}
+
+fn run_thumbnail_job(job: ThumbnailJob) {
+    let thumbnail = match load_thumbnail(&job.path) {
+        Ok(image) => Thumbnail::Ready(job.ctx.load_texture(
+            format!("thumbnail://{}", job.path.display()),
+            image,
+            egui::TextureOptions::LINEAR,
+        )),
+        Err(e) => {
+            log::error!("failed to load thumbnail of {}: {e}", job.path.display());
+            Thumbnail::Failed
+        }
+    };
+    if let Ok(mut thumbnails) = THUMBNAILS.lock() {
+        // dropped meanwhile, or the file changed again
+        if let Some(cached) = thumbnails
+            .iter_mut()
+            .find(|c| c.path == job.path && c.modified == job.modified)
+        {
+            cached.thumbnail = thumbnail;
+        }
+    }
+    job.ctx.request_repaint();
+}
+
+/// Queue of the previews to load, started with its workers on first use
+fn thumbnail_queue() -> &'static Mutex<mpsc::Sender<ThumbnailJob>> {
+    static QUEUE: OnceLock<Mutex<mpsc::Sender<ThumbnailJob>>> = OnceLock::new();
+    QUEUE.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<ThumbnailJob>();
+        let rx = Arc::new(Mutex::new(rx));
+        for i in 0..THUMBNAIL_WORKERS {
+            let rx = Arc::clone(&rx);
+            let spawned = std::thread::Builder::new()
+                .name(format!("thumbnails-{i}"))
+                .spawn(move || loop {
+                    // the lock is only held while waiting for a job
+                    let job = match rx.lock() {
+                        Ok(rx) => rx.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        Ok(job) => run_thumbnail_job(job),
+                        Err(_) => return,
+                    }
+                });
+            if let Err(e) = spawned {
+                log::error!("failed to start a thumbnail worker: {e}");
+            }
+        }
+        Mutex::new(tx)
+    })
+}
+
+/// Returns the preview texture of an image, queueing it to be loaded the
+/// first time it's requested and after the file changes. `None` while loading.
+pub fn thumbnail(ctx: &egui::Context, path: &Path) -> Option<Result<egui::TextureHandle, ()>> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut thumbnails = THUMBNAILS.lock().ok()?;
+    if let Some(i) = thumbnails.iter().position(|c| c.path == path) {
+        let cached = thumbnails.remove(i)?;
+        if cached.modified == modified {
+            let result = match &cached.thumbnail {
+                Thumbnail::Ready(texture) => Some(Ok(texture.clone())),
+                Thumbnail::Failed => Some(Err(())),
+                Thumbnail::Loading => None,
+            };
+            thumbnails.push_back(cached);
+            return result;
+        }
+        log::debug!("{} changed, reloading its thumbnail", path.display());
+    }
+    if thumbnails.len() >= MAX_THUMBNAILS {
+        thumbnails.pop_front();
+    }
+    thumbnails.push_back(CachedThumbnail {
+        path: path.to_path_buf(),
+        modified,
+        thumbnail: Thumbnail::Loading,
+    });
+    drop(thumbnails);
+
+    let job = ThumbnailJob {
+        ctx: ctx.clone(),
+        path: path.to_path_buf(),
+        modified,
+    };
+    if let Ok(queue) = thumbnail_queue().lock() {
+        if queue.send(job).is_err() {
+            log::error!("no thumbnail worker is running");
+        }
+    }
+    None
+}
+
+enum SizeEstimate {
+    Pending,
+    /// Size of the file and of what is uploaded
+    Ready(u64, u64),
+    Failed,
+}
+
+/// Upload sizes of attachments with the compression settings they were estimated for
+static SIZE_ESTIMATES: Mutex<BTreeMap<PathBuf, (ConversionOptions, SizeEstimate)>> =
+    Mutex::new(BTreeMap::new());
+
+/// Returns the original and upload size of an image or audio attachment when
+/// compression applies to it, converting it on a worker thread the first time
+/// it's requested. `None` while converting.
+fn size_estimate(
+    ctx: &egui::Context,
+    path: &Path,
+    options: ConversionOptions,
+) -> Option<(u64, u64)> {
+    let mime_str = upload_mime(path);
+    let compressed = (mime_str.starts_with("image/") && options.compression.compress_images)
+        || (mime_str.starts_with("audio/") && options.compression.reencode_audio);
+    if !compressed {
+        return None;
+    }
+
+    let mut estimates = SIZE_ESTIMATES.lock().ok()?;
+    match estimates.get(path) {
+        Some((estimated, SizeEstimate::Ready(original, upload))) if *estimated == options => {
+            return Some((*original, *upload));
+        }
+        Some((estimated, _)) if *estimated == options => return None,
+        _ => (),
+    }
+    estimates.insert(path.to_path_buf(), (options, SizeEstimate::Pending));
+
+    let ctx = ctx.clone();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let estimate = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| {
+                let original = bytes.len() as u64;
+                let (_, upload) = prepare_file(&path, bytes, mime_str, options)?;
+                Ok(SizeEstimate::Ready(original, upload.len() as u64))
+            })
+            .unwrap_or_else(|e| {
+                log::warn!("failed to estimate upload size of {}: {e}", path.display());
+                SizeEstimate::Failed
+            });
+        if let Ok(mut estimates) = SIZE_ESTIMATES.lock() {
+            if estimates.get(&path).is_some_and(|(o, _)| *o == options) {
+                estimates.insert(path, (options, estimate));
+            }
+        }
+        ctx.request_repaint();
+    });
+    None
+}
+
+/// Round button on the corner of an attachment card, returns whether it was clicked
+fn corner_button(ui: &egui::Ui, center: egui::Pos2, pointer: egui::Pos2, icon: &str) -> bool {
+    let contains_pointer = Rect::from_center_size(center, vec2(16.0, 16.0)).contains(pointer);
+    ui.painter()
+        .circle_filled(center, 10.0, ui.visuals().window_fill);
+    ui.painter().circle_filled(
+        center,
+        8.0,
+        if contains_pointer {
+            ui.visuals().gray_out(ui.visuals().selection.bg_fill)
+        } else {
+            ui.visuals().selection.bg_fill
+        },
+    );
+    ui.painter().text(
+        center,
+        egui::Align2::CENTER_CENTER,
+        icon,
+        egui::FontId::proportional(11.0),
+        Color32::WHITE,
+    );
+    contains_pointer && ui.input(|i| i.pointer.primary_clicked())
+}
+
+/// Shows attachment cards. With `upload` set, the size savings of compression are shown too.
+pub fn show_files(
+    ui: &mut egui::Ui,
+    files: &mut Vec<PathBuf>,
+    mutate: bool,
+    upload: Option<ConversionOptions>,
+) {
     const MAX_PREVIEW_HEIGHT: f32 = 128.0;
     let pointer_pos = ui.input(|i| i.pointer.interact_pos());
     let mut showing_x = false;
@@ -102,10 +737,15 @@ pub fn show_files(ui: &mut egui::Ui, files: &mut Vec<PathBuf>, mutate: bool) {
         let mime_type = mime_guess::from_path(&file_path).first_or_octet_stream();
 
         let is_exist = file_path.exists();
-        let frame_color = if is_exist {
-            egui::Color32::GRAY
-        } else {
+        let status = conversion_status(file_path);
+        // the player has its own buttons, so the card doesn't open the file on click
+        let has_player = cfg!(feature = "audio") && is_exist && mime_type.type_() == "audio";
+        let frame_color = if !is_exist {
             egui::Color32::ORANGE
+        } else if matches!(status, Some(ConversionStatus::Failed(_))) {
+            ui.visuals().error_fg_color
+        } else {
+            egui::Color32::GRAY
         };
         let custom_frame =
             egui::Frame::group(ui.style()).stroke(egui::Stroke::new(1.0, frame_color));
@@ -115,13 +755,39 @@ pub fn show_files(ui: &mut egui::Ui, files: &mut Vec<PathBuf>, mutate: bool) {
                 ui.vertical(|ui| {
                     // Display preview or icon depending on the file type
                     match mime_type.type_().as_str() {
-                        "image" if is_exist => {
-                            ui.add(
-                                egui::Image::new(format!("file://{path_string}"))
-                                    .max_height(MAX_PREVIEW_HEIGHT)
-                                    .fit_to_original_size(1.0),
-                            );
-                        }
+                        "image" | "video" if is_exist => match thumbnail(ui.ctx(), file_path) {
+                            Some(Ok(texture)) => {
+                                ui.add(
+                                    egui::Image::new(&texture)
+                                        .max_height(MAX_PREVIEW_HEIGHT)
+                                        .fit_to_original_size(1.0),
+                                );
+                            }
+                            thumbnail => {
+                                let (rect, _) = ui.allocate_exact_size(
+                                    vec2(MAX_PREVIEW_HEIGHT, MAX_PREVIEW_HEIGHT),
+                                    egui::Sense::hover(),
+                                );
+                                if thumbnail.is_none() {
+                                    egui::Spinner::new().paint_at(
+                                        ui,
+                                        Rect::from_center_size(rect.center(), vec2(24.0, 24.0)),
+                                    );
+                                } else {
+                                    ui.painter().text(
+                                        rect.center(),
+                                        egui::Align2::CENTER_CENTER,
+                                        if mime_type.type_() == "video" {
+                                            "🎬"
+                                        } else {
+                                            "🖼"
+                                        },
+                                        egui::FontId::proportional(40.0),
+                                        ui.visuals().text_color(),
+                                    );
+                                }
+                            }
+                        },
                         _ => {
                             // Create a container-frame with a fixed height
                             egui::Frame::NONE.show(ui, |ui| {
@@ -130,10 +796,21 @@ pub fn show_files(ui: &mut egui::Ui, files: &mut Vec<PathBuf>, mutate: bool) {
                                 // Set the width so that the widget is not too narrow
                                 ui.set_width(MAX_PREVIEW_HEIGHT * 1.2);
 
+                                #[cfg(feature = "audio")]
+                                if has_player {
+                                    ui.vertical_centered(|ui| {
+                                        ui.label(RichText::new("🎶").size(40.0));
+                                        crate::audio::show_player(ui, file_path);
+                                    });
+                                    return;
+                                }
+
                                 // Center the icon inside this frame
                                 ui.centered_and_justified(|ui| {
                                     let icon = if !is_exist {
                                         "⚠"
+                                    } else if file_path.is_dir() {
+                                        "📁"
                                     } else {
                                         match mime_type.type_().as_str() {
                                             "video" => "🎬",
@@ -153,15 +830,61 @@ pub fn show_files(ui: &mut egui::Ui, files: &mut Vec<PathBuf>, mutate: bool) {
                     if !is_exist {
                         text.to_mut().push_str(" (FILE NOT FOUND)");
                     }
-                    ui.add(egui::Label::new(RichText::new(text).small()).truncate())
+                    ui.add(egui::Label::new(RichText::new(text).small()).truncate());
+                    if let Some((original, upload)) = upload
+                        .filter(|_| is_exist)
+                        .and_then(|options| size_estimate(ui.ctx(), file_path, options))
+                    {
+                        ui.label(
+                            RichText::new(format!(
+                                "{} → {}",
+                                bytesize::ByteSize(original),
+                                bytesize::ByteSize(upload)
+                            ))
+                            .small()
+                            .weak(),
+                        )
+                        .on_hover_text("Size before and after compression");
+                    }
                 });
             })
             .response;
 
+        // conversion badges in the bottom right corner
+        let badge_rect = Rect::from_center_size(
+            resp.rect.right_bottom() - vec2(14.0, 28.0),
+            vec2(16.0, 16.0),
+        );
+        let hover_text = match &status {
+            Some(ConversionStatus::InProgress) => {
+                egui::Spinner::new().paint_at(ui, badge_rect);
+                format!("{path_string}\nPreparing for upload…")
+            }
+            Some(ConversionStatus::Failed(e)) => {
+                ui.painter().text(
+                    badge_rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    "⚠",
+                    egui::FontId::proportional(14.0),
+                    ui.visuals().error_fg_color,
+                );
+                format!("{path_string}\nFailed to attach: {e}")
+            }
+            None => path_string.clone(),
+        };
+
         // again stupid hacks for egu
         let interact_resp = ui
-            .interact(resp.rect, resp.id.with("interact"), egui::Sense::click())
-            .on_hover_text(&path_string);
+            .interact(
+                resp.rect,
+                resp.id.with("interact"),
+                if has_player {
+                    egui::Sense::hover()
+                } else {
+                    egui::Sense::click()
+                },
+            )
+            .on_hover_text(hover_text);
         if interact_resp.hovered() {
             ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
         }
@@ -173,6 +896,22 @@ pub fn show_files(ui: &mut egui::Ui, files: &mut Vec<PathBuf>, mutate: bool) {
             }
         }
 
+        let is_video = is_exist && mime_type.type_() == "video";
+        if mutate && is_video {
+            crate::video::show_trim_dialog(ui.ctx(), file_path);
+        }
+        // formats the `image` crate can't read are converted on upload instead
+        let is_maskable = is_exist
+            && mime_type.type_() == "image"
+            && !matches!(
+                mime_type.essence_str(),
+                "image/svg+xml" | "image/heic" | "image/heif"
+            );
+        if mutate && is_maskable {
+            crate::image_privacy::show_mask_dialog(ui.ctx(), file_path);
+            crate::annotate::show_annotate_dialog(ui.ctx(), file_path);
+        }
+
         if !mutate || showing_x {
             return true;
         }
@@ -209,6 +948,19 @@ pub fn show_files(ui: &mut egui::Ui, files: &mut Vec<PathBuf>, mutate: bool) {
                 if contains_pointer && ui.input(|i| i.pointer.primary_clicked()) {
                     return false;
                 }
+
+                // and a ✂ for videos, mask and annotate buttons for images
+                if is_video && corner_button(ui, resp.rect.left_top(), pos, "✂") {
+                    crate::video::open_trim_dialog(ui.ctx(), file_path);
+                }
+                if is_maskable && corner_button(ui, resp.rect.left_top(), pos, "▩") {
+                    crate::image_privacy::open_mask_dialog(ui.ctx(), file_path);
+                }
+                if is_maskable
+                    && corner_button(ui, resp.rect.left_top() + vec2(22.0, 0.0), pos, "✏")
+                {
+                    crate::annotate::open_annotate_dialog(ui.ctx(), file_path);
+                }
             }
         }
 
@@ -0,0 +1,117 @@
+//! Response filters: regex rules that rewrite answers once they're done,
+//! like stripping a boilerplate preamble.
+
+use eframe::egui;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ResponseFilter {
+    pub enabled: bool,
+    pub name: String,
+    pub pattern: String,
+    /// `$1` or `${name}` insert capture groups
+    pub replacement: String,
+}
+
+impl Default for ResponseFilter {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            name: String::new(),
+            pattern: String::new(),
+            replacement: String::new(),
+        }
+    }
+}
+
+impl ResponseFilter {
+    fn regex(&self) -> Result<Regex, String> {
+        Regex::new(&self.pattern).map_err(|e| format!("filter \"{}\": {e}", self.name))
+    }
+}
+
+/// Runs the enabled filters over `text` in order, skipping the invalid ones
+fn rewrite(filters: &[ResponseFilter], text: &str, mut on_error: impl FnMut(String)) -> String {
+    let mut text = text.to_owned();
+    for filter in filters
+        .iter()
+        .filter(|f| f.enabled && !f.pattern.is_empty())
+    {
+        match filter.regex() {
+            Ok(regex) => text = regex.replace_all(&text, &filter.replacement).into_owned(),
+            Err(e) => on_error(e),
+        }
+    }
+    text
+}
+
+/// The answer after the filters
+pub fn apply(filters: &[ResponseFilter], text: &str) -> String {
+    rewrite(filters, text, |e| crate::notify::report_error("filters", e))
+}
+
+/// The filter list in the settings, with a box to try them on
+pub fn show(ui: &mut egui::Ui, filters: &mut Vec<ResponseFilter>) {
+    ui.label(
+        "Rewrite answers once they're done, with regular expressions. \
+        Filters run in order and can be turned off for a chat in its settings.",
+    );
+    let mut to_remove = None;
+    for (i, filter) in filters.iter_mut().enumerate() {
+        let title = if filter.name.is_empty() {
+            "Unnamed filter"
+        } else {
+            filter.name.as_str()
+        };
+        egui::CollapsingHeader::new(title)
+            .id_salt(("response_filter", i))
+            .show(ui, |ui| {
+                ui.checkbox(&mut filter.enabled, "Enabled");
+                ui.add(egui::TextEdit::singleline(&mut filter.name).hint_text("Name"));
+                ui.add(
+                    egui::TextEdit::singleline(&mut filter.pattern)
+                        .hint_text(r"(?i)^(sure|certainly)[^\n]*\n+")
+                        .code_editor(),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut filter.replacement)
+                        .hint_text("Replacement, $1 for a group")
+                        .code_editor(),
+                );
+                if !filter.pattern.is_empty() {
+                    if let Err(e) = filter.regex() {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+                }
+                if ui.button("🗑 Remove").clicked() {
+                    to_remove = Some(i);
+                }
+            });
+    }
+    if let Some(i) = to_remove {
+        filters.remove(i);
+    }
+    if ui.button("➕ Add filter").clicked() {
+        filters.push(ResponseFilter {
+            name: format!("Filter {}", filters.len() + 1),
+            ..Default::default()
+        });
+    }
+    if !filters.is_empty() {
+        ui.collapsing("Try it", |ui| {
+            let id = ui.id().with("filter_sample");
+            let mut sample: String = ui.data(|d| d.get_temp(id)).unwrap_or_default();
+            ui.add(
+                egui::TextEdit::multiline(&mut sample)
+                    .hint_text("Paste an answer here")
+                    .desired_rows(3),
+            );
+            if !sample.is_empty() {
+                ui.label(rewrite(filters, &sample, |_| {}));
+            }
+            ui.data_mut(|d| d.insert_temp(id, sample));
+        });
+    }
+}
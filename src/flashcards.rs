@@ -0,0 +1,362 @@
+//! Flashcards: the model turns a reply or a whole chat into question/answer
+//! cards, which are reviewed here with spaced repetition or exported to Anki.
+
+use crate::widgets::Settings;
+use chrono::{DateTime, Duration, Utc};
+use eframe::egui;
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+const GENERATE_PROMPT: &str = "Turn the text below into flashcards for studying. \
+    Write one card per line as the question, a tab character and the answer. \
+    Keep the answers short, and reply with the cards only.\n\n";
+
+/// Ease factor of new cards, as in SM-2
+const INITIAL_EASE: f32 = 2.5;
+const MIN_EASE: f32 = 1.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Grade {
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Grade {
+    const ALL: [Self; 4] = [Self::Again, Self::Hard, Self::Good, Self::Easy];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Again => "Again",
+            Self::Hard => "Hard",
+            Self::Good => "Good",
+            Self::Easy => "Easy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Card {
+    pub question: String,
+    pub answer: String,
+    ease: f32,
+    interval_days: u32,
+    due: DateTime<Utc>,
+}
+
+impl Default for Card {
+    fn default() -> Self {
+        Self {
+            question: String::new(),
+            answer: String::new(),
+            ease: INITIAL_EASE,
+            interval_days: 0,
+            due: Utc::now(),
+        }
+    }
+}
+
+impl Card {
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.due <= now
+    }
+
+    /// Schedules the next review, a simplified SM-2
+    fn review(&mut self, grade: Grade) {
+        let now = Utc::now();
+        match grade {
+            Grade::Again => {
+                self.interval_days = 0;
+                self.ease = (self.ease - 0.2).max(MIN_EASE);
+                self.due = now + Duration::minutes(1);
+                return;
+            }
+            Grade::Hard => {
+                self.ease = (self.ease - 0.15).max(MIN_EASE);
+                self.interval_days = ((self.interval_days as f32 * 1.2) as u32).max(1);
+            }
+            Grade::Good => {
+                self.interval_days = match self.interval_days {
+                    0 => 1,
+                    1 => 3,
+                    days => (days as f32 * self.ease).round() as u32,
+                };
+            }
+            Grade::Easy => {
+                self.ease += 0.15;
+                self.interval_days = match self.interval_days {
+                    0 => 4,
+                    days => (days as f32 * self.ease * 1.3).round() as u32,
+                };
+            }
+        }
+        self.due = now + Duration::days(self.interval_days as i64);
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Deck {
+    pub name: String,
+    pub cards: Vec<Card>,
+}
+
+/// Reads `question<TAB>answer` lines, ignoring anything else the model wrote
+fn parse_cards(text: &str) -> Vec<Card> {
+    text.lines()
+        .filter_map(|line| {
+            let (question, answer) = line.split_once('\t')?;
+            let (question, answer) = (question.trim(), answer.trim());
+            (!question.is_empty() && !answer.is_empty()).then(|| Card {
+                question: question.to_owned(),
+                answer: answer.to_owned(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Tab-separated file that Anki imports as basic notes
+fn write_anki_tsv(path: &std::path::Path, deck: &Deck) -> std::io::Result<()> {
+    let clean = |field: &str| field.replace(['\t', '\n'], " ");
+    let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(
+        f,
+        "#separator:tab\n#html:false\n#deck:{}",
+        clean(&deck.name)
+    )?;
+    for card in &deck.cards {
+        writeln!(f, "{}\t{}", clean(&card.question), clean(&card.answer))?;
+    }
+    f.flush()
+}
+
+#[derive(Debug, Default)]
+struct Generation {
+    running: usize,
+    finished: Vec<Deck>,
+    /// Result of the last generation or export, shown under the heading
+    status: Option<Result<String, String>>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Flashcards {
+    decks: Vec<Deck>,
+    selected_deck: usize,
+    #[serde(skip)]
+    show_answer: bool,
+    #[serde(skip)]
+    generation: Arc<Mutex<Generation>>,
+}
+
+impl Default for Flashcards {
+    fn default() -> Self {
+        Self {
+            decks: Vec::new(),
+            selected_deck: 0,
+            show_answer: false,
+            generation: Arc::default(),
+        }
+    }
+}
+
+impl Flashcards {
+    /// Asks the model for cards about `text`, they appear as a new deck
+    pub fn generate(&self, ctx: &egui::Context, settings: &Settings, name: String, text: String) {
+        let mut model_picker = settings.model_picker.clone();
+        model_picker.auto_route = false;
+        model_picker.system_prompt = None;
+        let settings = settings.clone();
+        let generation = self.generation.clone();
+        let ctx = ctx.clone();
+        if let Ok(mut generation) = generation.lock() {
+            generation.running += 1;
+        }
+        log::info!("generating flashcards for `{name}`");
+
+        tokio::spawn(async move {
+            let mut response = String::new();
+            let result = crate::chat::ask_headless(
+                &settings,
+                model_picker,
+                format!("{GENERATE_PROMPT}{text}"),
+                Vec::new(),
+                |text, is_thought| {
+                    if !is_thought {
+                        response.push_str(text);
+                    }
+                },
+            )
+            .await;
+            let cards = parse_cards(&response);
+            if let Ok(mut generation) = generation.lock() {
+                generation.running -= 1;
+                let status = match result {
+                    Err(e) => Err(e),
+                    Ok(()) if cards.is_empty() => Err("The model did not write any cards".into()),
+                    Ok(()) => {
+                        let status = format!("Made {} cards for `{name}`", cards.len());
+                        generation.finished.push(Deck { name, cards });
+                        Ok(status)
+                    }
+                };
+                generation.status = Some(status);
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    fn export(&self, ctx: &egui::Context) {
+        let Some(deck) = self.decks.get(self.selected_deck).cloned() else {
+            return;
+        };
+        let generation = self.generation.clone();
+        let ctx = ctx.clone();
+        let task = rfd::AsyncFileDialog::new()
+            .add_filter("Anki text file", &["txt", "tsv"])
+            .set_file_name(format!("{}.txt", deck.name))
+            .save_file();
+        tokio::spawn(async move {
+            let Some(file) = task.await else {
+                return;
+            };
+            let path = file.path();
+            let status = write_anki_tsv(path, &deck)
+                .map(|()| format!("Exported {} cards to {}", deck.cards.len(), path.display()))
+                .map_err(|e| {
+                    log::error!("failed to export flashcards: {e}");
+                    format!("Failed to export: {e}")
+                });
+            if let Ok(mut generation) = generation.lock() {
+                generation.status = Some(status);
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Flashcards");
+        ui.label(
+            "Make cards from a reply with the 🃏 button under it, or from a whole chat in its header.",
+        );
+
+        let mut running = 0;
+        if let Ok(mut generation) = self.generation.lock() {
+            if !generation.finished.is_empty() {
+                self.selected_deck = self.decks.len();
+                self.show_answer = false;
+            }
+            self.decks.append(&mut generation.finished);
+            running = generation.running;
+            match &generation.status {
+                Some(Ok(status)) => {
+                    ui.weak(status);
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                None => (),
+            }
+        }
+        if running > 0 {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak(format!("Making {running} deck(s)…"));
+            });
+        }
+        ui.add_space(8.0);
+
+        if self.decks.is_empty() {
+            ui.weak("No decks yet");
+            return;
+        }
+        self.selected_deck = self.selected_deck.min(self.decks.len() - 1);
+
+        let now = Utc::now();
+        let mut delete_deck = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("flashcard_deck")
+                .selected_text(self.decks[self.selected_deck].name.as_str())
+                .show_ui(ui, |ui| {
+                    for (i, deck) in self.decks.iter().enumerate() {
+                        let due = deck.cards.iter().filter(|c| c.is_due(now)).count();
+                        if ui
+                            .selectable_value(
+                                &mut self.selected_deck,
+                                i,
+                                format!("{} ({due} due)", deck.name),
+                            )
+                            .clicked()
+                        {
+                            self.show_answer = false;
+                        }
+                    }
+                });
+            if ui
+                .button("📤 Export for Anki")
+                .on_hover_text("Save as a tab-separated file, import it in Anki with File → Import")
+                .clicked()
+            {
+                self.export(ui.ctx());
+            }
+            if ui.button("🗑 Delete deck").clicked() {
+                delete_deck = true;
+            }
+        });
+        if delete_deck {
+            self.decks.remove(self.selected_deck);
+            self.show_answer = false;
+            return;
+        }
+
+        let deck = &mut self.decks[self.selected_deck];
+        ui.add_space(8.0);
+        let due = deck.cards.iter().filter(|c| c.is_due(now)).count();
+        ui.weak(format!("{due} of {} cards due", deck.cards.len()));
+
+        // the card due the longest goes first
+        let Some(card) = deck
+            .cards
+            .iter_mut()
+            .filter(|c| c.is_due(now))
+            .min_by_key(|c| c.due)
+        else {
+            let next = deck.cards.iter().map(|c| c.due).min();
+            ui.label("All done for now!");
+            if let Some(next) = next {
+                ui.weak(format!(
+                    "Next review on {}",
+                    next.with_timezone(&chrono::Local).format("%b %-d, %H:%M")
+                ));
+            }
+            return;
+        };
+
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            ui.add_space(8.0);
+            ui.label(egui::RichText::new(&card.question).heading());
+            ui.add_space(8.0);
+            if self.show_answer {
+                ui.separator();
+                ui.label(&card.answer);
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    for grade in Grade::ALL {
+                        if ui.button(grade.name()).clicked() {
+                            card.review(grade);
+                            self.show_answer = false;
+                        }
+                    }
+                });
+            } else if ui.button("Show answer").clicked() {
+                self.show_answer = true;
+            }
+        });
+    }
+}
@@ -0,0 +1,280 @@
+//! Optional sync of chats through a folder shared with Syncthing, Dropbox or
+//! a similar tool: every chat is written to its own file, and chats changed on
+//! other machines are merged on startup.
+
+use crate::chat::Chat;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const EXTENSION: &str = "json";
+
+/// Contents of a chat file
+#[derive(serde::Deserialize)]
+struct SyncedChat {
+    id: String,
+    /// When the chat was last written, by any machine
+    modified: DateTime<Utc>,
+    chat: Chat,
+}
+
+/// The version of a chat this machine last wrote or merged
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+struct SyncedVersion {
+    modified: DateTime<Utc>,
+    /// Hash of the chat as JSON, to notice local changes
+    hash: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    /// Chats changed both here and elsewhere, the other version is added as a copy
+    pub conflicts: usize,
+}
+
+impl ImportSummary {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.updated == 0 && self.conflicts == 0
+    }
+}
+
+/// FNV-1a, unlike `DefaultHasher` it stays the same between Rust versions
+fn hash_chat(chat: &Chat) -> Result<u64> {
+    let json = serde_json::to_vec(chat)?;
+    Ok(json.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    }))
+}
+
+fn chat_path(folder: &Path, id: &str) -> PathBuf {
+    folder.join(format!("chat-{id}.{EXTENSION}"))
+}
+
+/// Reads every chat file in the folder, including conflict copies made by the
+/// sync tool, grouped by chat with the newest version first
+fn read_folder(folder: &Path) -> Result<BTreeMap<String, Vec<(PathBuf, SyncedChat)>>> {
+    let mut chats: BTreeMap<String, Vec<(PathBuf, SyncedChat)>> = BTreeMap::new();
+    let entries = std::fs::read_dir(folder)
+        .with_context(|| format!("failed to read sync folder `{}`", folder.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != EXTENSION) {
+            continue;
+        }
+        let synced = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice::<SyncedChat>(&bytes)?));
+        match synced {
+            Ok(synced) => chats
+                .entry(synced.id.clone())
+                .or_default()
+                .push((path, synced)),
+            Err(e) => log::warn!("skipping sync file `{}`: {e}", path.display()),
+        }
+    }
+    for versions in chats.values_mut() {
+        versions.sort_by(|(_, a), (_, b)| b.modified.cmp(&a.modified));
+    }
+    Ok(chats)
+}
+
+fn remove_copy(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        log::warn!("failed to remove `{}`: {e}", path.display());
+    }
+}
+
+fn conflict_copy(mut chat: Chat, modified: DateTime<Utc>) -> Chat {
+    chat.sync_id.clear();
+    chat.title = Some(format!(
+        "{} (changed elsewhere {})",
+        chat.display_name(),
+        modified
+            .with_timezone(&chrono::Local)
+            .format("%b %-d, %H:%M")
+    ));
+    chat
+}
+
+/// Folder picked in the settings, applied on the next frame
+static PICKED_FOLDER: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn pick_folder() {
+    let task = rfd::AsyncFileDialog::new()
+        .set_title("Pick a folder to sync chats through")
+        .pick_folder();
+    tokio::spawn(async move {
+        let Some(folder) = task.await else {
+            return;
+        };
+        if let Ok(mut picked) = PICKED_FOLDER.lock() {
+            *picked = Some(folder.path().to_path_buf());
+        }
+    });
+}
+
+pub fn take_picked_folder() -> Option<PathBuf> {
+    PICKED_FOLDER.lock().ok()?.take()
+}
+
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FolderSync {
+    /// Keyed by the sync id of the chat
+    synced: BTreeMap<String, SyncedVersion>,
+}
+
+impl FolderSync {
+    /// Merges chats added or changed on other machines since the last sync
+    pub fn import(&mut self, folder: &Path, chats: &mut Vec<Chat>) -> Result<ImportSummary> {
+        let mut summary = ImportSummary::default();
+        for (id, versions) in read_folder(folder)? {
+            let last = self.synced.get(&id).copied();
+            let is_new = |modified: DateTime<Utc>| last.is_none_or(|l| modified > l.modified);
+            let local = chats.iter().position(|chat| chat.sync_id == id);
+            let mut versions = versions.into_iter();
+            let Some((newest_path, newest)) = versions.next() else {
+                continue;
+            };
+            let canonical_path = chat_path(folder, &id);
+
+            match local {
+                // deleted here since the last sync, the file is removed on export
+                None if last.is_some() => (),
+                None => {
+                    self.synced.insert(
+                        id.clone(),
+                        SyncedVersion {
+                            modified: newest.modified,
+                            hash: hash_chat(&newest.chat)?,
+                        },
+                    );
+                    chats.push(newest.chat);
+                    summary.added += 1;
+                }
+                Some(_) if !is_new(newest.modified) => (),
+                Some(i) => {
+                    // an unloaded history can't have changed since the last sync
+                    let hash = match last {
+                        Some(last) if !chats[i].messages.is_loaded() => last.hash,
+                        _ => hash_chat(&chats[i])?,
+                    };
+                    let remote_hash = hash_chat(&newest.chat)?;
+                    let changed_here = last.is_none_or(|l| l.hash != hash);
+                    if changed_here && hash != remote_hash {
+                        // keep ours, it's written back on export
+                        chats.push(conflict_copy(newest.chat, newest.modified));
+                        summary.conflicts += 1;
+                        self.synced.insert(
+                            id.clone(),
+                            SyncedVersion {
+                                modified: newest.modified,
+                                hash: last.map_or(0, |l| l.hash),
+                            },
+                        );
+                    } else {
+                        self.synced.insert(
+                            id.clone(),
+                            SyncedVersion {
+                                modified: newest.modified,
+                                hash: remote_hash,
+                            },
+                        );
+                        if hash != remote_hash {
+                            chats[i] = newest.chat;
+                            summary.updated += 1;
+                        }
+                    }
+                }
+            }
+
+            // the newest version is in a copy made by the sync tool, so the
+            // chat is written again under its usual name on export
+            if newest_path != canonical_path {
+                if let Some(version) = self.synced.get_mut(&id) {
+                    version.hash = 0;
+                }
+            }
+            // copies left by the sync tool when two machines wrote the chat at once
+            for (path, older) in versions {
+                if is_new(older.modified) {
+                    chats.push(conflict_copy(older.chat, older.modified));
+                    summary.conflicts += 1;
+                }
+                if path != canonical_path {
+                    remove_copy(&path);
+                }
+            }
+            if newest_path != canonical_path {
+                remove_copy(&newest_path);
+            }
+        }
+        log::info!("imported chats from `{}`: {summary:?}", folder.display());
+        Ok(summary)
+    }
+
+    /// Writes the chats changed since the last sync, and removes the files of
+    /// deleted chats. Returns the number of files written.
+    pub fn export(&mut self, folder: &Path, chats: &mut [Chat]) -> Result<usize> {
+        std::fs::create_dir_all(folder)?;
+        let mut written = 0;
+        for chat in chats.iter_mut().filter(|chat| !chat.flower_active()) {
+            chat.sync_id();
+            // hashing parses the history, one that isn't loaded hasn't changed
+            // and one that failed to load would be written as empty
+            if !chat.messages.is_loaded()
+                && (self.synced.contains_key(&chat.sync_id) || chat.messages.load_error().is_some())
+            {
+                continue;
+            }
+            let hash = hash_chat(chat)?;
+            if self
+                .synced
+                .get(&chat.sync_id)
+                .is_some_and(|v| v.hash == hash)
+            {
+                continue;
+            }
+            let modified = Utc::now();
+            let json = serde_json::to_vec_pretty(&serde_json::json!({
+                "id": chat.sync_id,
+                "modified": modified,
+                "chat": chat,
+            }))?;
+            // written next to it first, so the sync tool never sees half a file
+            let path = chat_path(folder, &chat.sync_id);
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, json)?;
+            std::fs::rename(&tmp, &path)?;
+            self.synced
+                .insert(chat.sync_id.clone(), SyncedVersion { modified, hash });
+            written += 1;
+        }
+
+        let deleted: Vec<String> = self
+            .synced
+            .keys()
+            .filter(|id| !chats.iter().any(|chat| chat.sync_id == **id))
+            .cloned()
+            .collect();
+        for id in deleted {
+            let path = chat_path(folder, &id);
+            match std::fs::remove_file(&path) {
+                Ok(()) => log::info!("removed sync file of deleted chat `{}`", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => {
+                    log::warn!("failed to remove `{}`: {e}", path.display());
+                    continue;
+                }
+            }
+            self.synced.remove(&id);
+        }
+        Ok(written)
+    }
+}
@@ -0,0 +1,78 @@
+//! Caps the frame rate while something keeps asking for repaints, like a
+//! streaming response or a spinner, so the app doesn't keep the GPU and a CPU
+//! core busy. Frames caused by input are never held back.
+
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+/// Polling rate of text-to-speech, which only has to notice when it stops
+const SPEECH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FramePacing {
+    /// Frame rate while a response is streamed or something is animated
+    pub max_fps: u32,
+    /// Lower frame rates for laptops on battery
+    pub power_saver: bool,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        Self {
+            max_fps: 30,
+            power_saver: false,
+        }
+    }
+}
+
+impl FramePacing {
+    fn fps(&self, focused: bool) -> u32 {
+        match (focused, self.power_saver) {
+            (true, false) => self.max_fps,
+            (true, true) => self.max_fps.min(15),
+            // only the title and the taskbar are visible
+            (false, false) => self.max_fps.min(5),
+            (false, true) => 1,
+        }
+        .max(1)
+    }
+
+    fn frame_interval(&self, focused: bool) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps(focused) as f64)
+    }
+
+    /// Schedules the next frame while a response is generated or other work is
+    /// polled, instead of repainting as fast as possible
+    pub fn request_poll(&self, ctx: &egui::Context) {
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        ctx.request_repaint_after(self.frame_interval(focused));
+    }
+
+    /// Schedules the next check of whether text-to-speech has stopped
+    pub fn request_speech_poll(&self, ctx: &egui::Context) {
+        ctx.request_repaint_after(SPEECH_POLL_INTERVAL.max(self.frame_interval(true)));
+    }
+}
+
+#[derive(Default)]
+pub struct FramePacer {
+    last_frame: Option<Instant>,
+}
+
+impl FramePacer {
+    /// Waits out the rest of the frame interval if this frame was only asked
+    /// for by a repaint request, call at the start of every frame
+    pub fn pace(&mut self, ctx: &egui::Context, pacing: &FramePacing) {
+        let (has_input, focused) =
+            ctx.input(|i| (!i.events.is_empty(), i.viewport().focused.unwrap_or(true)));
+        if let Some(last) = self.last_frame.filter(|_| !has_input) {
+            let interval = pacing.frame_interval(focused);
+            let elapsed = last.elapsed();
+            if elapsed < interval {
+                std::thread::sleep(interval - elapsed);
+            }
+        }
+        self.last_frame = Some(Instant::now());
+    }
+}
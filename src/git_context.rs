@@ -0,0 +1,81 @@
+//! Attaching the state of a git repository (diffs, recent commits) to a chat,
+//! for code reviews and commit messages. Uses the `git` command line tool.
+
+use anyhow::{Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitContext {
+    /// Changes in the working tree that aren't staged
+    Diff,
+    /// Changes staged for the next commit
+    Staged,
+    /// Messages of the last N commits
+    RecentCommits(usize),
+}
+
+impl GitContext {
+    fn args(self) -> Vec<String> {
+        match self {
+            Self::Diff => vec!["diff".into()],
+            Self::Staged => vec!["diff".into(), "--staged".into()],
+            Self::RecentCommits(count) => vec![
+                "log".into(),
+                format!("-n{count}"),
+                "--date=short".into(),
+                "--format=commit %h (%ad, %an)%n%n%B".into(),
+            ],
+        }
+    }
+
+    fn file_name(self) -> String {
+        match self {
+            Self::Diff => "diff".to_owned(),
+            Self::Staged => "staged".to_owned(),
+            Self::RecentCommits(count) => format!("last-{count}-commits"),
+        }
+    }
+}
+
+/// Runs git in `repo` and saves the output as a text attachment
+pub fn save_context(repo: &Path, context: GitContext) -> Result<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(context.args())
+        .output()
+        .context("failed to run `git`, is it installed?")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`git` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    anyhow::ensure!(
+        !output.stdout.iter().all(u8::is_ascii_whitespace),
+        "nothing to attach, the output of `git {}` is empty",
+        context.args().join(" ")
+    );
+
+    let repo_name = repo
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let dir = crate::file_handler::attachments_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{repo_name}-{}-{}.txt",
+        context.file_name(),
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::write(&path, &output.stdout)?;
+    log::info!(
+        "saved {context:?} of `{}` to `{}`",
+        repo.display(),
+        path.display()
+    );
+    Ok(path)
+}
@@ -0,0 +1,148 @@
+//! Glossary: required translations of terms in a chat, added to prompts that
+//! use them and checked in the answers.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GlossaryEntry {
+    pub term: String,
+    /// What the term has to be translated as
+    pub translation: String,
+}
+
+impl GlossaryEntry {
+    fn is_complete(&self) -> bool {
+        !self.term.trim().is_empty() && !self.translation.trim().is_empty()
+    }
+}
+
+/// A term of the prompt whose required translation is missing from the answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub term: String,
+    pub translation: String,
+}
+
+fn contains_ignore_case(text: &str, needle: &str) -> bool {
+    text.to_lowercase().contains(&needle.trim().to_lowercase())
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Glossary {
+    pub entries: Vec<GlossaryEntry>,
+    #[serde(skip)]
+    pub open: bool,
+}
+
+impl Glossary {
+    /// Entries whose term appears in `prompt`
+    fn used_in<'a>(&'a self, prompt: &'a str) -> impl Iterator<Item = &'a GlossaryEntry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.is_complete() && contains_ignore_case(prompt, &entry.term))
+    }
+
+    /// Appended to the prompt, `None` when it uses none of the terms
+    pub fn instruction(&self, prompt: &str) -> Option<String> {
+        let lines: Vec<String> = self
+            .used_in(prompt)
+            .map(|entry| {
+                format!(
+                    "- \"{}\" → \"{}\"",
+                    entry.term.trim(),
+                    entry.translation.trim()
+                )
+            })
+            .collect();
+        (!lines.is_empty()).then(|| {
+            format!(
+                "Use these translations for the following terms exactly as written:\n{}",
+                lines.join("\n")
+            )
+        })
+    }
+
+    /// Terms of `prompt` whose required translation isn't in `answer`
+    pub fn check(&self, prompt: &str, answer: &str) -> Vec<Violation> {
+        self.used_in(prompt)
+            .filter(|entry| !contains_ignore_case(answer, &entry.translation))
+            .map(|entry| Violation {
+                term: entry.term.trim().to_owned(),
+                translation: entry.translation.trim().to_owned(),
+            })
+            .collect()
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let entries = &mut self.entries;
+        egui::Window::new("📖 Glossary")
+            .open(&mut self.open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label("Terms that have to be translated a certain way in this chat.");
+                ui.weak(
+                    "The ones a prompt uses are added to it, answers missing them get a warning.",
+                );
+                ui.separator();
+                let mut to_remove = None;
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("glossary_grid")
+                            .num_columns(3)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Term");
+                                ui.strong("Translation");
+                                ui.end_row();
+                                for (i, entry) in entries.iter_mut().enumerate() {
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut entry.term)
+                                            .desired_width(140.0),
+                                    );
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut entry.translation)
+                                            .desired_width(140.0),
+                                    );
+                                    if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                                        to_remove = Some(i);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                if let Some(i) = to_remove {
+                    entries.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("➕ Add term").clicked() {
+                        entries.push(GlossaryEntry::default());
+                    }
+                    if ui
+                        .button("📋 Paste")
+                        .on_hover_text("Add the terms from the clipboard, one `term = translation` or tab separated pair per line")
+                        .clicked()
+                    {
+                        if let Ok(text) = arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+                            entries.extend(parse_pairs(&text));
+                        }
+                    }
+                });
+            });
+    }
+}
+
+/// Reads `term = translation` or tab separated pairs, one per line
+fn parse_pairs(text: &str) -> Vec<GlossaryEntry> {
+    text.lines()
+        .filter_map(|line| line.split_once('\t').or_else(|| line.split_once('=')))
+        .map(|(term, translation)| GlossaryEntry {
+            term: term.trim().to_owned(),
+            translation: translation.trim().to_owned(),
+        })
+        .filter(GlossaryEntry::is_complete)
+        .collect()
+}
@@ -0,0 +1,169 @@
+//! Completion hooks: a shell command or a URL called when an answer in a
+//! chat is done, so answers can be piped into scripts and other tools.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HookKind {
+    #[default]
+    Command,
+    Webhook,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CompletionHook {
+    pub enabled: bool,
+    pub kind: HookKind,
+    /// Run by the shell, with the payload as JSON on stdin
+    pub command: String,
+    /// The payload is POSTed here as JSON
+    pub url: String,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    chat_id: &'a str,
+    chat: &'a str,
+    model: &'a str,
+    reply: &'a str,
+    time: chrono::DateTime<chrono::Utc>,
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+fn run_command(command: &str, chat_id: &str, payload: &[u8]) -> Result<(), String> {
+    let mut child = shell(command)
+        .env("EGEMINUI_CHAT_ID", chat_id)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run hook `{command}`: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command may not read its input, that's fine
+        let _ = stdin.write_all(payload);
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to wait for hook `{command}`: {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(format!(
+        "hook `{command}` failed ({}): {}",
+        output.status,
+        stderr.lines().next().unwrap_or_default()
+    ))
+}
+
+async fn post(url: &str, payload: Vec<u8>) -> Result<(), String> {
+    reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload)
+        .timeout(WEBHOOK_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(|e| format!("webhook to {url} failed: {e}"))
+}
+
+impl CompletionHook {
+    /// Calls the hook with the finished `reply`, in the background
+    pub fn run(&self, chat_id: &str, chat: &str, model: &str, reply: &str) {
+        if !self.enabled {
+            return;
+        }
+        let payload = Payload {
+            chat_id,
+            chat,
+            model,
+            reply,
+            time: chrono::Utc::now(),
+        };
+        let payload = match serde_json::to_vec(&payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                crate::notify::report_error(
+                    "hooks",
+                    format!("failed to serialize hook payload: {e}"),
+                );
+                return;
+            }
+        };
+        match self.kind {
+            HookKind::Command if !self.command.trim().is_empty() => {
+                log::info!("running completion hook for chat {chat_id}");
+                let command = self.command.clone();
+                let chat_id = chat_id.to_owned();
+                std::thread::spawn(move || {
+                    if let Err(e) = run_command(&command, &chat_id, &payload) {
+                        crate::notify::report_error("hooks", e);
+                    }
+                });
+            }
+            HookKind::Webhook if !self.url.trim().is_empty() => {
+                log::info!("posting completion webhook for chat {chat_id}");
+                let url = self.url.trim().to_owned();
+                tokio::spawn(async move {
+                    if let Err(e) = post(&url, payload).await {
+                        crate::notify::report_error("hooks", e);
+                    }
+                });
+            }
+            _ => {}
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            "Run a command or call a URL when an answer in this chat is done. \
+            It gets the chat id, the model and the reply as JSON.",
+        );
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.kind, HookKind::Command, "Shell command");
+                ui.selectable_value(&mut self.kind, HookKind::Webhook, "Webhook");
+            });
+            match self.kind {
+                HookKind::Command => {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.command)
+                            .hint_text("jq -r .reply >> ~/answers.md")
+                            .code_editor(),
+                    )
+                    .on_hover_text("The JSON comes on stdin, the chat id is in EGEMINUI_CHAT_ID");
+                }
+                HookKind::Webhook => {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.url)
+                            .hint_text("https://example.com/hook"),
+                    )
+                    .on_hover_text("The JSON is sent in a POST request");
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,328 @@
+//! REST tools defined by the user in the settings, which the model can call
+//! through function calling.
+
+use crate::timeouts::RequestTimeouts;
+use anyhow::{bail, Context, Result};
+use gemini_client_api::gemini::types::request::{Part, Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// Longer response bodies are cut before being sent back to the model
+const MAX_BODY_LEN: usize = 32 * 1024;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HttpMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+        })
+    }
+}
+
+impl HttpMethod {
+    pub const ALL: [Self; 4] = [Self::Get, Self::Post, Self::Put, Self::Delete];
+
+    fn to_reqwest(self) -> reqwest::Method {
+        match self {
+            Self::Get => reqwest::Method::GET,
+            Self::Post => reqwest::Method::POST,
+            Self::Put => reqwest::Method::PUT,
+            Self::Delete => reqwest::Method::DELETE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpTool {
+    pub enabled: bool,
+    pub name: String,
+    pub description: String,
+    pub method: HttpMethod,
+    /// URL with `{argument}` placeholders
+    pub url: String,
+    /// Values may contain `{argument}` placeholders too
+    pub headers: Vec<(String, String)>,
+    /// JSON schema of the arguments, as JSON text
+    pub parameters: String,
+    /// Show the call and wait for the user before running it, `None` until
+    /// it's set. Asks by default, model-chosen arguments can carry chat
+    /// content to the tool's host even with GET.
+    pub ask_before_calling: Option<bool>,
+}
+
+/// Whether a header likely holds a credential, like `Authorization` or `X-Api-Key`
+pub fn is_secret_header(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["auth", "token", "key", "secret", "cookie", "password"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+impl Default for HttpTool {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            name: String::new(),
+            description: String::new(),
+            method: HttpMethod::default(),
+            url: String::new(),
+            headers: Vec::new(),
+            parameters: "{\n  \"type\": \"object\",\n  \"properties\": {}\n}".to_owned(),
+            ask_before_calling: None,
+        }
+    }
+}
+
+/// Function names may only contain letters, digits, underscores and dashes
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Replaces `{argument}` placeholders, returning the names of the arguments used
+fn fill_template(
+    template: &str,
+    args: &Map<String, Value>,
+    encode: bool,
+    used: &mut Vec<String>,
+) -> String {
+    let mut filled = template.to_owned();
+    for (key, value) in args {
+        let placeholder = format!("{{{key}}}");
+        if !filled.contains(&placeholder) {
+            continue;
+        }
+        let value = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        let value = if encode {
+            url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+        } else {
+            value
+        };
+        filled = filled.replace(&placeholder, &value);
+        used.push(key.clone());
+    }
+    filled
+}
+
+impl HttpTool {
+    pub fn asks_before_calling(&self) -> bool {
+        self.ask_before_calling.unwrap_or(true)
+    }
+
+    pub fn parsed_parameters(&self) -> Result<Value> {
+        serde_json::from_str(&self.parameters).context("invalid parameter schema")
+    }
+
+    fn declaration(&self) -> Result<Value> {
+        if !is_valid_name(&self.name) {
+            bail!("invalid tool name `{}`", self.name);
+        }
+        Ok(json!({
+            "name": self.name,
+            "description": self.description,
+            "parameters": self.parsed_parameters()?,
+        }))
+    }
+
+    async fn call(&self, client: &reqwest::Client, args: &Map<String, Value>) -> Result<Value> {
+        let mut used = Vec::new();
+        let url = fill_template(&self.url, args, true, &mut used);
+        let mut request = client
+            .request(self.method.to_reqwest(), &url)
+            .header(reqwest::header::USER_AGENT, crate::TITLE);
+        for (key, value) in &self.headers {
+            request = request.header(key, fill_template(value, args, false, &mut used));
+        }
+
+        // arguments that aren't in the URL or headers go into the body
+        let rest: Map<String, Value> = args
+            .iter()
+            .filter(|(key, _)| !used.contains(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if !rest.is_empty() {
+            if matches!(self.method, HttpMethod::Get | HttpMethod::Delete) {
+                request = request.query(
+                    &rest
+                        .iter()
+                        .map(|(key, value)| match value {
+                            Value::String(s) => (key.clone(), s.clone()),
+                            other => (key.clone(), other.to_string()),
+                        })
+                        .collect::<Vec<_>>(),
+                );
+            } else {
+                request = request.json(&rest);
+            }
+        }
+
+        log::info!("calling tool `{}`: {} {url}", self.name, self.method);
+        let response = request.send().await.context("request failed")?;
+        let status = response.status().as_u16();
+        let mut body = response
+            .text()
+            .await
+            .context("failed to read the response")?;
+        if body.len() > MAX_BODY_LEN {
+            let mut end = MAX_BODY_LEN;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            body.truncate(end);
+            body.push_str("…(truncated)");
+        }
+        let body = serde_json::from_str(&body).unwrap_or(Value::String(body));
+        Ok(json!({ "status": status, "body": body }))
+    }
+}
+
+/// The function declarations of the enabled tools, `None` if there are none
+pub fn tool_declarations(tools: &[HttpTool]) -> Option<Tool> {
+    let declarations: Vec<Value> = tools
+        .iter()
+        .filter(|tool| tool.enabled)
+        .filter_map(|tool| {
+            tool.declaration()
+                .map_err(|e| log::warn!("skipping tool `{}`: {e:#}", tool.name))
+                .ok()
+        })
+        .collect();
+    if declarations.is_empty() {
+        return None;
+    }
+    serde_json::from_value(json!({ "functionDeclarations": declarations }))
+        .map_err(|e| log::error!("failed to build tool declarations: {e}"))
+        .ok()
+}
+
+/// Name and arguments of a function call part
+pub fn function_call(part: &Part) -> Option<(String, Map<String, Value>)> {
+    let value = serde_json::to_value(part).ok()?;
+    let call = value.get("functionCall")?;
+    let name = call.get("name")?.as_str()?.to_owned();
+    let args = call
+        .get("args")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    Some((name, args))
+}
+
+/// A call of a tool set to ask first, waiting for the user
+#[derive(Debug)]
+pub struct ToolConfirmation {
+    pub tool: String,
+    /// Method and URL of the tool, before the arguments are filled in
+    pub request: String,
+    /// Arguments given by the model, as pretty JSON
+    pub args: String,
+    /// Whether the user allows the call, dropping it declines
+    pub reply: tokio::sync::oneshot::Sender<bool>,
+}
+
+/// Asks the user through `confirm` whether the call may run
+async fn confirm_call(
+    confirm: &impl Fn(ToolConfirmation),
+    tool: &HttpTool,
+    args: &Map<String, Value>,
+) -> bool {
+    let (reply, allowed) = tokio::sync::oneshot::channel();
+    confirm(ToolConfirmation {
+        tool: tool.name.clone(),
+        request: format!("{} {}", tool.method, tool.url),
+        args: serde_json::to_string_pretty(args).unwrap_or_default(),
+        reply,
+    });
+    allowed.await.unwrap_or(false)
+}
+
+/// The tools of a completion request, and the client their calls go through
+#[derive(Default)]
+pub struct ToolCaller {
+    tools: Vec<HttpTool>,
+    client: reqwest::Client,
+}
+
+impl ToolCaller {
+    /// Calls go through `proxy` if it's set, like the other requests
+    pub fn new(
+        tools: Vec<HttpTool>,
+        proxy: Option<&str>,
+        timeouts: RequestTimeouts,
+    ) -> Result<Self> {
+        let mut client = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect())
+            .read_timeout(timeouts.read());
+        if let Some(proxy) = proxy {
+            client = client.proxy(reqwest::Proxy::all(proxy).context("invalid proxy URL")?);
+        }
+        Ok(Self {
+            tools,
+            client: client.build()?,
+        })
+    }
+
+    /// Runs the function calls of the model, returning a response part for
+    /// each. Calls of tools that ask first are passed to `confirm`, and only
+    /// run if the user allows them
+    pub async fn call_all(
+        &self,
+        calls: Vec<(String, Map<String, Value>)>,
+        confirm: impl Fn(ToolConfirmation),
+    ) -> Vec<Part> {
+        let mut parts = Vec::new();
+        for (name, args) in calls {
+            parts.extend(function_response(
+                &name,
+                self.call(&name, &args, &confirm).await,
+            ));
+        }
+        parts
+    }
+
+    async fn call(
+        &self,
+        name: &str,
+        args: &Map<String, Value>,
+        confirm: &impl Fn(ToolConfirmation),
+    ) -> Value {
+        let Some(tool) = self.tools.iter().find(|t| t.enabled && t.name == name) else {
+            return json!({ "error": format!("unknown tool `{name}`") });
+        };
+        if tool.asks_before_calling() && !confirm_call(confirm, tool, args).await {
+            log::info!("the user declined calling tool `{name}`");
+            return json!({ "error": "the user declined this call" });
+        }
+        tool.call(&self.client, args).await.unwrap_or_else(|e| {
+            log::error!("tool `{name}` failed: {e:#}");
+            json!({ "error": format!("{e:#}") })
+        })
+    }
+}
+
+fn function_response(name: &str, response: Value) -> Option<Part> {
+    serde_json::from_value(json!({
+        "functionResponse": { "name": name, "response": response }
+    }))
+    .map_err(|e| log::error!("failed to build function response: {e}"))
+    .ok()
+}
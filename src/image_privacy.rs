@@ -0,0 +1,281 @@
+//! Image privacy: stripping metadata before upload, and a dialog for masking
+//! parts of an image attachment.
+
+use anyhow::{anyhow, Result};
+use eframe::egui::{self, pos2, vec2, Color32, Rect, Sense, Stroke};
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Quality of re-encoded JPEGs, high enough to not be noticeable
+const JPEG_QUALITY: u8 = 92;
+/// Longest side of the image shown in the mask dialog
+const PREVIEW_SIZE: u32 = 1024;
+/// Masked regions are pixelated into blocks this many times smaller
+const PIXELATE_FACTOR: u32 = 16;
+
+/// Re-encodes a PNG or JPEG so EXIF, GPS and other metadata are dropped
+pub fn strip_metadata(bytes: &[u8], format: ImageFormat) -> Result<Vec<u8>> {
+    let img = image::load_from_memory_with_format(bytes, format)?;
+    let mut buf = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            img.to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY))?;
+        }
+        format => img.write_to(&mut Cursor::new(&mut buf), format)?,
+    }
+    log::debug!(
+        "stripped image metadata ({} -> {} bytes)",
+        bytes.len(),
+        buf.len()
+    );
+    Ok(buf)
+}
+
+/// Pixelates the regions, given as fractions of the image size
+fn mask_regions(img: &mut DynamicImage, regions: &[Rect]) {
+    let (width, height) = (img.width(), img.height());
+    for region in regions {
+        let x = ((region.min.x * width as f32) as u32).min(width - 1);
+        let y = ((region.min.y * height as f32) as u32).min(height - 1);
+        let w = ((region.width() * width as f32) as u32).clamp(1, width - x);
+        let h = ((region.height() * height as f32) as u32).clamp(1, height - y);
+        let block = img.crop_imm(x, y, w, h);
+        let pixelated = block
+            .resize_exact(
+                (w / PIXELATE_FACTOR).max(1),
+                (h / PIXELATE_FACTOR).max(1),
+                image::imageops::FilterType::Triangle,
+            )
+            .resize_exact(w, h, image::imageops::FilterType::Nearest);
+        image::imageops::replace(img, &pixelated, x as i64, y as i64);
+    }
+}
+
+/// Saves a masked copy of the image to the attachment store
+fn save_masked(path: &Path, regions: &[Rect]) -> Result<PathBuf> {
+    let mut img = image::open(path)?;
+    mask_regions(&mut img, regions);
+
+    let dir = crate::file_handler::attachments_dir();
+    std::fs::create_dir_all(&dir)?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let output_path = dir.join(format!(
+        "{stem}-masked-{}.png",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    img.save_with_format(&output_path, ImageFormat::Png)?;
+    log::info!(
+        "masked {} regions of {}: `{}`",
+        regions.len(),
+        path.display(),
+        output_path.display()
+    );
+    Ok(output_path)
+}
+
+enum MaskStatus {
+    Editing,
+    Saving,
+    Done(PathBuf),
+    Failed(String),
+}
+
+struct MaskState {
+    source: PathBuf,
+    /// `None` while it's being loaded
+    texture: Option<Result<egui::TextureHandle, String>>,
+    /// Masked regions as fractions of the image size
+    regions: Vec<Rect>,
+    /// Start of the region being dragged
+    drag_start: Option<egui::Pos2>,
+    status: MaskStatus,
+}
+
+/// The mask dialog, only one image can be edited at a time
+static MASK: Mutex<Option<MaskState>> = Mutex::new(None);
+
+/// Opens the mask dialog for an image attachment
+pub fn open_mask_dialog(ctx: &egui::Context, path: &Path) {
+    let Ok(mut mask) = MASK.lock() else {
+        return;
+    };
+    *mask = Some(MaskState {
+        source: path.to_path_buf(),
+        texture: None,
+        regions: Vec::new(),
+        drag_start: None,
+        status: MaskStatus::Editing,
+    });
+
+    let ctx = ctx.clone();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let texture = image::open(&path)
+            .map_err(|e| anyhow!(e))
+            .map(|img| {
+                let img = img.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE).into_rgba8();
+                ctx.load_texture(
+                    format!("mask://{}", path.display()),
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [img.width() as usize, img.height() as usize],
+                        img.as_raw(),
+                    ),
+                    egui::TextureOptions::LINEAR,
+                )
+            })
+            .map_err(|e| e.to_string());
+        if let Ok(mut mask) = MASK.lock() {
+            if let Some(state) = mask.as_mut().filter(|s| s.source == path) {
+                state.texture = Some(texture);
+            }
+        }
+        ctx.request_repaint();
+    });
+}
+
+/// Shows the mask dialog if it's open for `file_path`, and swaps the
+/// attachment for the masked copy once it's saved
+pub fn show_mask_dialog(ctx: &egui::Context, file_path: &mut PathBuf) {
+    let Ok(mut mask) = MASK.lock() else {
+        return;
+    };
+    let Some(state) = mask.as_mut().filter(|s| s.source == *file_path) else {
+        return;
+    };
+    if let MaskStatus::Done(masked) = &state.status {
+        *file_path = masked.clone();
+        *mask = None;
+        return;
+    }
+
+    let mut open = true;
+    let mut start_save = false;
+    egui::Window::new("Mask image")
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.label("Drag over the parts of the image to hide, like faces or addresses.");
+            match &state.texture {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Loading image…");
+                    });
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                Some(Ok(texture)) => {
+                    let max = vec2(512.0, 512.0);
+                    let size = texture.size_vec2();
+                    let scale = (max.x / size.x).min(max.y / size.y).min(1.0);
+                    let (rect, response) = ui.allocate_exact_size(size * scale, Sense::drag());
+                    ui.painter().image(
+                        texture.id(),
+                        rect,
+                        Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+
+                    let to_fraction = |pos: egui::Pos2| {
+                        let p = (pos - rect.min) / rect.size();
+                        pos2(p.x.clamp(0.0, 1.0), p.y.clamp(0.0, 1.0))
+                    };
+                    let to_screen = |region: &Rect| {
+                        Rect::from_min_max(
+                            rect.min + region.min.to_vec2() * rect.size(),
+                            rect.min + region.max.to_vec2() * rect.size(),
+                        )
+                    };
+                    if response.drag_started() {
+                        state.drag_start = response.interact_pointer_pos().map(to_fraction);
+                    }
+                    let dragged = state
+                        .drag_start
+                        .zip(response.interact_pointer_pos().map(to_fraction));
+                    if let Some((start, end)) = dragged {
+                        let region = Rect::from_two_pos(start, end);
+                        if response.drag_stopped() {
+                            state.drag_start = None;
+                            if region.area() > 0.0001 {
+                                state.regions.push(region);
+                            }
+                        } else {
+                            ui.painter().rect_stroke(
+                                to_screen(&region),
+                                0.0,
+                                Stroke::new(2.0, ui.visuals().selection.stroke.color),
+                                egui::StrokeKind::Inside,
+                            );
+                        }
+                    }
+                    for region in &state.regions {
+                        ui.painter().rect_filled(
+                            to_screen(region),
+                            0.0,
+                            Color32::from_black_alpha(200),
+                        );
+                    }
+                }
+            }
+
+            match &state.status {
+                MaskStatus::Saving => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Saving…");
+                    });
+                }
+                MaskStatus::Failed(e) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                _ => (),
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!state.regions.is_empty(), egui::Button::new("↩ Undo"))
+                    .clicked()
+                {
+                    state.regions.pop();
+                }
+                let can_save =
+                    !state.regions.is_empty() && !matches!(state.status, MaskStatus::Saving);
+                if ui
+                    .add_enabled(can_save, egui::Button::new("✔ Apply"))
+                    .on_hover_text("The masked copy replaces the attachment, the original is kept")
+                    .clicked()
+                {
+                    start_save = true;
+                }
+            });
+        });
+
+    if !open {
+        *mask = None;
+        return;
+    }
+    if start_save {
+        state.status = MaskStatus::Saving;
+        let (source, regions) = (state.source.clone(), state.regions.clone());
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let status = match save_masked(&source, &regions) {
+                Ok(masked) => MaskStatus::Done(masked),
+                Err(e) => MaskStatus::Failed(e.to_string()),
+            };
+            if let Ok(mut mask) = MASK.lock() {
+                if let Some(state) = mask.as_mut().filter(|s| s.source == source) {
+                    state.status = status;
+                }
+            }
+            ctx.request_repaint();
+        });
+    }
+}
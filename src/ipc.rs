@@ -0,0 +1,216 @@
+//! Single-instance enforcement: the first instance listens on a local socket,
+//! later ones forward their request to it and exit.
+//!
+//! The first instance writes a random token to the storage directory, and a
+//! connection only counts once it has sent that token and got an `ok` back.
+//! Other programs on the socket can neither send requests nor be mistaken for
+//! the running instance.
+
+use eframe::egui;
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+
+/// Loopback port used instead of a socket file where unix sockets are unavailable
+#[cfg(not(unix))]
+const PORT: u16 = 47313;
+
+/// Longest wait for the other side of a connection to greet or answer
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A request sent from a newly launched instance to the running one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Bring the window to the front and start a new chat
+    NewChat,
+    /// Handle an `egeminui://` link
+    OpenLink(String),
+    /// Start a new chat with these files attached
+    AttachFiles(Vec<PathBuf>),
+}
+
+impl Request {
+    fn encode(&self) -> String {
+        match self {
+            Self::NewChat => "new-chat".to_owned(),
+            Self::OpenLink(link) => format!("open {link}"),
+            Self::AttachFiles(files) => format!(
+                "files {}",
+                serde_json::to_string(files).expect("paths should serialize")
+            ),
+        }
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        match line.trim() {
+            "new-chat" => Some(Self::NewChat),
+            other => {
+                if let Some(link) = other.strip_prefix("open ") {
+                    Some(Self::OpenLink(link.to_owned()))
+                } else if let Some(files) = other.strip_prefix("files ") {
+                    serde_json::from_str(files)
+                        .map(Self::AttachFiles)
+                        .map_err(|e| log::warn!("invalid file list in IPC request: {e}"))
+                        .ok()
+                } else {
+                    log::warn!("ignoring unknown IPC request `{other}`");
+                    None
+                }
+            }
+        }
+    }
+}
+
+pub struct Server {
+    listener: Listener,
+    token: String,
+}
+
+pub enum Instance {
+    /// This is the first instance. `None` if the socket could not be set up,
+    /// in which case we run without single-instance enforcement.
+    Primary(Option<Server>),
+    /// Another instance is running and received our request
+    Secondary,
+}
+
+fn storage_dir() -> PathBuf {
+    let dir = eframe::storage_dir(crate::TITLE).unwrap_or_else(std::env::temp_dir);
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+#[cfg(unix)]
+fn socket_path() -> PathBuf {
+    storage_dir().join("instance.sock")
+}
+
+/// Token of the running instance, readable only by the user's own programs
+fn token_path() -> PathBuf {
+    storage_dir().join("instance.token")
+}
+
+fn hello(token: &str) -> String {
+    format!("hello {token}")
+}
+
+#[cfg(unix)]
+fn bind() -> std::io::Result<Listener> {
+    Listener::bind(socket_path())
+}
+
+#[cfg(unix)]
+fn connect() -> std::io::Result<Stream> {
+    Stream::connect(socket_path())
+}
+
+#[cfg(not(unix))]
+fn bind() -> std::io::Result<Listener> {
+    Listener::bind(("127.0.0.1", PORT))
+}
+
+#[cfg(not(unix))]
+fn connect() -> std::io::Result<Stream> {
+    Stream::connect(("127.0.0.1", PORT))
+}
+
+fn send(request: &Request) -> std::io::Result<()> {
+    let token = std::fs::read_to_string(token_path())?;
+    let mut stream = connect()?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    writeln!(stream, "{}", hello(token.trim()))?;
+    let mut answer = String::new();
+    BufReader::new(&stream).read_line(&mut answer)?;
+    if answer.trim() != "ok" {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the socket is held by another program",
+        ));
+    }
+    writeln!(stream, "{}", request.encode())
+}
+
+fn serve(listener: Listener) -> Instance {
+    let token: String = std::iter::repeat_with(fastrand::alphanumeric)
+        .take(32)
+        .collect();
+    match std::fs::write(token_path(), &token) {
+        Ok(()) => Instance::Primary(Some(Server { listener, token })),
+        Err(e) => {
+            log::warn!("failed to write the single-instance token: {e}");
+            Instance::Primary(None)
+        }
+    }
+}
+
+/// Becomes the primary instance, or forwards `request` to the running one
+pub fn acquire(request: &Request) -> Instance {
+    let err = match bind() {
+        Ok(listener) => return serve(listener),
+        Err(e) => e,
+    };
+
+    match send(request) {
+        Ok(()) => {
+            log::info!("another instance is running, forwarded {request:?} to it");
+            return Instance::Secondary;
+        }
+        Err(e) => log::debug!("failed to reach the running instance: {e}"),
+    }
+
+    // nobody is listening, so the socket file was left behind by a crash
+    #[cfg(unix)]
+    if err.kind() == std::io::ErrorKind::AddrInUse {
+        let _ = std::fs::remove_file(socket_path());
+        if let Ok(listener) = bind() {
+            return serve(listener);
+        }
+    }
+
+    log::warn!("failed to set up single-instance socket: {err}");
+    Instance::Primary(None)
+}
+
+impl Server {
+    /// Listens for requests on a background thread, waking up the UI for each
+    pub fn spawn(self, ctx: egui::Context) -> Receiver<Request> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let hello = hello(&self.token);
+            for stream in self.listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).is_err() {
+                    continue;
+                }
+                let mut lines = BufReader::new(&stream).lines().map_while(Result::ok);
+                if lines.next().as_deref() != Some(hello.as_str()) {
+                    log::warn!("ignoring an IPC connection without the instance token");
+                    continue;
+                }
+                if writeln!(&stream, "ok").is_err() {
+                    continue;
+                }
+                for line in lines {
+                    let Some(request) = Request::decode(&line) else {
+                        continue;
+                    };
+                    if tx.send(request).is_err() {
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        });
+        rx
+    }
+}
@@ -3,23 +3,85 @@
 
 use eframe::egui;
 use sessions::Sessions;
+mod ab_test;
+mod analytics;
+mod annotate;
+mod artifact;
+#[cfg(feature = "audio")]
+mod audio;
+mod autostart;
+mod avatar;
+mod backup;
+mod batch;
+#[cfg(feature = "camera")]
+mod camera;
+mod capabilities;
 mod chat;
+mod cheatsheet;
+mod cli;
+mod compress;
+mod crash;
+mod deeplink;
+mod dnd;
 mod easymark;
+mod endpoint;
 mod file_handler;
+mod filters;
+mod flashcards;
+mod folder_sync;
+mod frame_pacing;
+mod git_context;
+mod glossary;
+mod hooks;
+mod http_tools;
+mod image_privacy;
+mod ipc;
+mod markdown_cache;
+mod network;
+mod notebook;
+mod notify;
+mod ocr;
+mod plain_text;
+#[cfg(feature = "scripting")]
+mod plugins;
+mod pricing;
+mod profiles;
+mod quick_ask;
+mod replay;
+mod rich_copy;
+mod routing;
+mod scratchpad;
 mod sessions;
+mod shutdown;
+mod sound;
+mod structured;
 mod style;
+mod switcher;
+mod theme;
+mod thoughts;
+mod timeouts;
+mod token_diet;
+mod translator;
+mod updates;
+mod video;
+mod watch_folder;
 mod widgets;
 
 const TITLE: &str = "Gemini GUI";
 const IMAGE_FORMATS: &[&str] = &[
     "bmp", "dds", "ff", "gif", "hdr", "ico", "jpeg", "jpg", "exr", "png", "pnm", "qoi", "tga",
-    "tiff", "webp",
+    "tiff", "webp", "svg", "heic", "heif", "avif",
 ];
 const VIDEO_FORMATS: &[&str] = &["mp4", "mpeg", "mov", "avi", "flv", "webm"];
 const TEXT_FORMATS: &[&str] = &[
     "txt", "md", "rs", "py", "js", "html", "css", "json", "toml", "yaml", "log", "csv", "xml",
     "pdf",
 ];
+/// Source files gathered from attached folders, in addition to `TEXT_FORMATS`
+const CODE_FORMATS: &[&str] = &[
+    "c", "h", "cpp", "hpp", "cs", "go", "java", "kt", "swift", "ts", "tsx", "jsx", "rb", "php",
+    "sh", "lua", "sql", "ini", "cfg", "zig", "scala", "vue", "svelte", "yml",
+];
 const MUSIC_FORMATS: &[&str] = &[
     "aac", "flac", "mp3", "m4a", "mpeg", "mpga", "opus", "pcm", "wav", "webm", "aiff", "ogg",
 ];
@@ -44,29 +106,138 @@ fn load_icon() -> egui::IconData {
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    crash::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = cli::AskCommand::parse(&args) {
+        let code = match command {
+            Ok(command) => {
+                let settings = Ellama::load_from_disk()
+                    .map(|app| app.sessions.settings)
+                    .unwrap_or_default();
+                cli::run(command, settings).await
+            }
+            Err(e) => cli::print_usage(&e),
+        };
+        std::process::exit(code);
+    }
+
+    // a link passed by the OS when the user opens `egeminui://...`, or files
+    // passed by "Open with" / the command line
+    let files: Vec<_> = args
+        .iter()
+        .filter(|arg| !deeplink::is_link(arg))
+        .map(std::path::PathBuf::from)
+        .filter(|path| path.is_file())
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+    let launch_request = args
+        .iter()
+        .find(|arg| deeplink::is_link(arg))
+        .map(|link| ipc::Request::OpenLink(link.clone()))
+        .or_else(|| (!files.is_empty()).then(|| ipc::Request::AttachFiles(files)));
+
+    let ipc_server = match ipc::acquire(launch_request.as_ref().unwrap_or(&ipc::Request::NewChat)) {
+        ipc::Instance::Primary(server) => server,
+        ipc::Instance::Secondary => return,
+    };
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_icon(load_icon()),
+        // window geometry is restored by `WindowState` instead
+        persist_window: false,
         ..Default::default()
     };
     eframe::run_native(
         TITLE,
         native_options,
-        Box::new(|cc| Ok(Box::new(Ellama::new(cc)))),
+        Box::new(|cc| Ok(Box::new(Ellama::new(cc, ipc_server, launch_request)))),
     )
     .expect("failed to run app");
 }
 
+/// Window geometry saved between runs, in points
+#[derive(Default, Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct WindowState {
+    inner_size: Option<egui::Vec2>,
+    outer_position: Option<egui::Pos2>,
+    maximized: bool,
+}
+
+impl WindowState {
+    fn restore(&self, ctx: &egui::Context) {
+        if let Some(size) = self.inner_size {
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+        }
+        if let Some(pos) = self.outer_position {
+            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(pos));
+        }
+        if self.maximized {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(true));
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context) {
+        ctx.input(|i| {
+            let viewport = i.viewport();
+            if let Some(maximized) = viewport.maximized {
+                self.maximized = maximized;
+            }
+            // keep the last "normal" geometry so un-maximizing restores it
+            if self.maximized || viewport.minimized == Some(true) {
+                return;
+            }
+            if let Some(rect) = viewport.inner_rect {
+                self.inner_size = Some(rect.size());
+            }
+            if let Some(rect) = viewport.outer_rect {
+                self.outer_position = Some(rect.min);
+            }
+        });
+    }
+}
+
 #[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
 struct Ellama {
     sessions: Sessions,
+    window: WindowState,
+    #[serde(skip)]
+    ipc: Option<std::sync::mpsc::Receiver<ipc::Request>>,
+    #[serde(skip)]
+    launch_request: Option<ipc::Request>,
+    #[serde(skip)]
+    frame_pacer: frame_pacing::FramePacer,
+    #[serde(skip)]
+    theme: theme::ThemeWatcher,
 }
 
 impl Ellama {
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    /// Reads the app state saved by eframe, without creating a window
+    fn load_from_disk() -> Option<Self> {
+        let path = eframe::storage_dir(TITLE)?.join("app.ron");
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| log::warn!("failed to read `{}`: {e}", path.display()))
+            .ok()?;
+        let kv: std::collections::HashMap<String, String> = ron::from_str(&contents)
+            .map_err(|e| log::warn!("failed to parse `{}`: {e}", path.display()))
+            .ok()?;
+        let state = kv.get(eframe::APP_KEY)?;
+        chat::with_lazy_messages(|| ron::from_str(state))
+            .map_err(|e| log::warn!("failed to decode app state: {e}"))
+            .ok()
+    }
+
+    fn new(
+        cc: &eframe::CreationContext<'_>,
+        ipc_server: Option<ipc::Server>,
+        launch_request: Option<ipc::Request>,
+    ) -> Self {
         // change visuals
         style::set_style(&cc.egui_ctx);
         egui_extras::install_image_loaders(&cc.egui_ctx);
+        let ipc = ipc_server.map(|server| server.spawn(cc.egui_ctx.clone()));
 
         // try to restore app
         log::debug!(
@@ -75,8 +246,18 @@ impl Ellama {
         );
 
         if let Some(storage) = cc.storage {
-            if let Some(app_state) = eframe::get_value::<Self>(storage, eframe::APP_KEY) {
+            // messages are parsed when each chat is first opened
+            let app_state =
+                chat::with_lazy_messages(|| eframe::get_value::<Self>(storage, eframe::APP_KEY));
+            if let Some(mut app_state) = app_state {
                 log::debug!("app state successfully restored from storage");
+                app_state.ipc = ipc;
+                app_state.launch_request = launch_request;
+                app_state.window.restore(&cc.egui_ctx);
+                if app_state.sessions.settings.start_minimized {
+                    cc.egui_ctx
+                        .send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                }
                 return app_state;
             }
         }
@@ -84,18 +265,39 @@ impl Ellama {
         log::debug!("app state is not saved in storage, using default app state");
 
         // default app
-        Self::default()
+        Self {
+            ipc,
+            launch_request,
+            ..Default::default()
+        }
     }
 }
 
 impl eframe::App for Ellama {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.frame_pacer
+            .pace(ctx, &self.sessions.settings.frame_pacing);
         ctx.set_pixels_per_point(1.2);
+        self.theme.update(ctx);
+        // the geometry to restore is the usual one, not the mini window's
+        if !self.sessions.is_mini() {
+            self.window.update(ctx);
+        }
+        if let Some(request) = self.launch_request.take() {
+            self.sessions.handle_ipc_request(ctx, request);
+        }
+        if let Some(ipc) = &self.ipc {
+            while let Ok(request) = ipc.try_recv() {
+                self.sessions.handle_ipc_request(ctx, request);
+            }
+        }
         self.sessions.show(ctx);
     }
 
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.sessions.export_to_sync_folder();
+        self.sessions.update_chat_previews();
         log::debug!("saving app state");
-        eframe::set_value(storage, eframe::APP_KEY, self);
+        chat::with_lazy_messages(|| eframe::set_value(storage, eframe::APP_KEY, self));
     }
 }
@@ -0,0 +1,83 @@
+//! CommonMark caches kept per chat, so the ones of chats that haven't been
+//! looked at in a while can be dropped instead of growing for the whole session.
+
+use egui_commonmark::CommonMarkCache;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Caches of chats not viewed for this long are dropped
+const MAX_IDLE: Duration = Duration::from_secs(10 * 60);
+/// Rough budget for the text rendered through the kept caches, the least
+/// recently viewed chats are dropped above it
+const MAX_CACHED_TEXT: usize = 4 * 1024 * 1024;
+
+struct Entry {
+    cache: CommonMarkCache,
+    last_used: Instant,
+    /// Length of the chat's messages when it was last viewed, standing in for
+    /// the size of the cache
+    text_len: usize,
+}
+
+#[derive(Default)]
+pub struct MarkdownCaches {
+    /// Keyed by chat id
+    chats: HashMap<usize, Entry>,
+    /// For text outside of chats, like release notes
+    shared: CommonMarkCache,
+}
+
+impl MarkdownCaches {
+    /// Cache for the chat `id`, whose messages are `text_len` bytes long
+    pub fn for_chat(&mut self, id: usize, text_len: usize) -> &mut CommonMarkCache {
+        let entry = self.chats.entry(id).or_insert_with(|| Entry {
+            cache: CommonMarkCache::default(),
+            last_used: Instant::now(),
+            text_len,
+        });
+        entry.last_used = Instant::now();
+        entry.text_len = text_len;
+        &mut entry.cache
+    }
+
+    pub fn shared(&mut self) -> &mut CommonMarkCache {
+        &mut self.shared
+    }
+
+    /// Drops the caches of chats not viewed for a while, then the least
+    /// recently viewed ones until the rest fit the budget. The cache of the
+    /// chat `current` is always kept.
+    pub fn evict(&mut self, current: Option<usize>) {
+        let before = self.chats.len();
+        self.chats
+            .retain(|id, entry| Some(*id) == current || entry.last_used.elapsed() < MAX_IDLE);
+
+        let mut total: usize = self.chats.values().map(|entry| entry.text_len).sum();
+        if total > MAX_CACHED_TEXT {
+            let mut oldest: Vec<(usize, Instant, usize)> = self
+                .chats
+                .iter()
+                .filter(|(id, _)| Some(**id) != current)
+                .map(|(id, entry)| (*id, entry.last_used, entry.text_len))
+                .collect();
+            oldest.sort_by_key(|(_, last_used, _)| *last_used);
+            for (id, _, text_len) in oldest {
+                if total <= MAX_CACHED_TEXT {
+                    break;
+                }
+                self.chats.remove(&id);
+                total -= text_len;
+            }
+        }
+
+        if self.chats.len() < before {
+            log::debug!(
+                "dropped {} markdown caches, {} left",
+                before - self.chats.len(),
+                self.chats.len()
+            );
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Connectivity probe: the API host (or the proxy) is checked in the
+//! background, so being offline is shown before a message fails to send.
+
+use eframe::egui;
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+const API_HOST: &str = "generativelanguage.googleapis.com";
+const ONLINE_INTERVAL: Duration = Duration::from_secs(15);
+/// Checked more often while offline, so the banner goes away soon after reconnecting
+const OFFLINE_INTERVAL: Duration = Duration::from_secs(3);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Failed probes in a row before counting as offline, one may be a hiccup
+const FAILED_PROBES: u32 = 2;
+
+#[derive(Default)]
+struct Shared {
+    /// Proxy from the settings, the probe connects to it instead of the API
+    proxy: Option<String>,
+    /// API host and port, when a custom endpoint is set
+    api: Option<(String, u16)>,
+    /// Failed probes in a row
+    failures: u32,
+}
+
+/// Host and port the requests connect to first
+fn probe_target(proxy: Option<&str>, api: Option<(String, u16)>) -> (String, u16) {
+    // reqwest picks up the proxy variables too
+    let proxy = proxy.map(str::to_owned).or_else(|| {
+        ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+            .into_iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+    });
+    proxy
+        .and_then(|proxy| url::Url::parse(&proxy).ok())
+        .and_then(|url| Some((url.host_str()?.to_owned(), url.port_or_known_default()?)))
+        .or(api)
+        .unwrap_or_else(|| (API_HOST.to_owned(), 443))
+}
+
+fn probe(host: &str, port: u16) -> bool {
+    let Ok(addrs) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok())
+}
+
+fn spawn_prober(ctx: egui::Context, shared: Arc<Mutex<Shared>>, recheck: Arc<AtomicBool>) {
+    std::thread::spawn(move || loop {
+        let Ok((proxy, api)) = shared.lock().map(|s| (s.proxy.clone(), s.api.clone())) else {
+            return;
+        };
+        let (host, port) = probe_target(proxy.as_deref(), api);
+        let online = probe(&host, port);
+
+        let Ok(mut state) = shared.lock() else {
+            return;
+        };
+        let was_offline = state.failures >= FAILED_PROBES;
+        state.failures = if online { 0 } else { state.failures + 1 };
+        let offline = state.failures >= FAILED_PROBES;
+        drop(state);
+        if offline != was_offline {
+            if offline {
+                log::warn!("can't reach {host}:{port}, offline");
+            } else {
+                log::info!("back online");
+            }
+            ctx.request_repaint();
+        }
+
+        let interval = if online {
+            ONLINE_INTERVAL
+        } else {
+            OFFLINE_INTERVAL
+        };
+        let slept = Instant::now();
+        while slept.elapsed() < interval && !recheck.swap(false, Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(250));
+        }
+    });
+}
+
+#[derive(Default)]
+pub struct NetworkStatus {
+    shared: Arc<Mutex<Shared>>,
+    recheck: Arc<AtomicBool>,
+    started: bool,
+}
+
+impl NetworkStatus {
+    /// Starts probing, with `proxy` and the `api` host from the settings, call every frame
+    pub fn update(&mut self, ctx: &egui::Context, proxy: Option<&str>, api: Option<(String, u16)>) {
+        if let Ok(mut shared) = self.shared.lock() {
+            if shared.proxy.as_deref() != proxy || shared.api != api {
+                shared.proxy = proxy.map(str::to_owned);
+                shared.api = api;
+                self.recheck.store(true, Ordering::Relaxed);
+            }
+        }
+        if !self.started {
+            self.started = true;
+            spawn_prober(ctx.clone(), self.shared.clone(), self.recheck.clone());
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.started
+            && self
+                .shared
+                .lock()
+                .is_ok_and(|shared| shared.failures >= FAILED_PROBES)
+    }
+
+    /// Probes again right away instead of waiting for the next check
+    pub fn recheck(&self) {
+        self.recheck.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,204 @@
+//! Export of a chat as a Jupyter notebook: code blocks of the answers become
+//! code cells, everything else becomes markdown cells.
+
+use serde_json::{json, Value};
+
+/// Kernel used when the answers have no code blocks in a known language
+const DEFAULT_LANGUAGE: &str = "python";
+
+/// Kernel name, display name and the fence labels of a notebook language
+const KERNELS: &[(&str, &str, &[&str])] = &[
+    (
+        "python3",
+        "Python 3",
+        &["python", "py", "python3", "ipython"],
+    ),
+    ("ir", "R", &["r"]),
+    ("julia", "Julia", &["julia", "jl"]),
+    ("javascript", "JavaScript (Node.js)", &["javascript", "js"]),
+];
+
+fn kernel_index(fence_language: &str) -> Option<usize> {
+    let language = fence_language.trim().to_lowercase();
+    KERNELS
+        .iter()
+        .position(|(_, _, labels)| labels.contains(&language.as_str()))
+}
+
+enum Segment<'a> {
+    Prose(String),
+    Code { language: &'a str, code: String },
+}
+
+/// Splits markdown into prose and fenced code blocks
+fn split_code_blocks(markdown: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut prose = String::new();
+    let mut code: Option<(&str, &str, String)> = None;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        match &mut code {
+            Some((fence, language, text)) => {
+                if trimmed.starts_with(*fence) && trimmed.trim_end() == *fence {
+                    segments.push(Segment::Code {
+                        language: *language,
+                        code: std::mem::take(text),
+                    });
+                    code = None;
+                } else {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+            }
+            None => {
+                let fence = ["```", "~~~"]
+                    .into_iter()
+                    .find(|fence| trimmed.starts_with(fence));
+                if let Some(fence) = fence {
+                    if !prose.trim().is_empty() {
+                        segments.push(Segment::Prose(std::mem::take(&mut prose)));
+                    }
+                    prose.clear();
+                    let language = trimmed[fence.len()..]
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or("");
+                    code = Some((fence, language, String::new()));
+                } else {
+                    prose.push_str(line);
+                    prose.push('\n');
+                }
+            }
+        }
+    }
+    // an unterminated block, e.g. from a response that was cut off
+    if let Some((_, language, text)) = code {
+        segments.push(Segment::Code {
+            language,
+            code: text,
+        });
+    }
+    if !prose.trim().is_empty() {
+        segments.push(Segment::Prose(prose));
+    }
+    segments
+}
+
+/// Cell source in the notebook format, a list of lines keeping their newlines
+fn source(text: &str) -> Value {
+    let text = text.trim_matches('\n');
+    Value::from(
+        text.split_inclusive('\n')
+            .map(str::to_owned)
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn markdown_cell(id: usize, text: &str) -> Value {
+    json!({
+        "cell_type": "markdown",
+        "id": format!("cell-{id}"),
+        "metadata": {},
+        "source": source(text),
+    })
+}
+
+fn code_cell(id: usize, code: &str) -> Value {
+    json!({
+        "cell_type": "code",
+        "id": format!("cell-{id}"),
+        "execution_count": null,
+        "metadata": {},
+        "outputs": [],
+        "source": source(code),
+    })
+}
+
+/// Builds an nbformat 4 notebook from the turns of a chat, given as whether
+/// the message is from the user and its text. The kernel is picked by the
+/// language most code blocks are in, code in other languages stays markdown.
+pub fn notebook(turns: &[(bool, &str)]) -> Value {
+    let segments: Vec<(bool, Vec<Segment<'_>>)> = turns
+        .iter()
+        .map(|(is_user, text)| (*is_user, split_code_blocks(text)))
+        .collect();
+
+    let mut counts = vec![0; KERNELS.len()];
+    for (is_user, segments) in &segments {
+        if *is_user {
+            continue;
+        }
+        for segment in segments {
+            if let Some(i) = match segment {
+                Segment::Code { language, .. } => kernel_index(language),
+                Segment::Prose(_) => None,
+            } {
+                counts[i] += 1;
+            }
+        }
+    }
+    let kernel = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .max_by_key(|(i, count)| (**count, std::cmp::Reverse(*i)))
+        .map(|(i, _)| i)
+        .or_else(|| kernel_index(DEFAULT_LANGUAGE))
+        .unwrap_or_default();
+    let (kernel_name, display_name, labels) = KERNELS[kernel];
+
+    let mut cells = Vec::new();
+    for (is_user, segments) in segments {
+        if is_user {
+            // prompts are quoted, so they stand apart from the answers
+            let text = segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Prose(text) => text.clone(),
+                    Segment::Code { language, code } => format!("```{language}\n{code}```\n"),
+                })
+                .collect::<String>();
+            let quoted = text
+                .trim_end()
+                .lines()
+                .map(|line| format!("> {line}\n"))
+                .collect::<String>();
+            cells.push(markdown_cell(
+                cells.len(),
+                &format!("**Prompt**\n\n{quoted}"),
+            ));
+            continue;
+        }
+        for segment in segments {
+            match segment {
+                Segment::Code { language, code }
+                    if language.is_empty()
+                        || labels.contains(&language.to_lowercase().as_str()) =>
+                {
+                    cells.push(code_cell(cells.len(), &code));
+                }
+                Segment::Code { language, code } => {
+                    cells.push(markdown_cell(
+                        cells.len(),
+                        &format!("```{language}\n{code}```"),
+                    ));
+                }
+                Segment::Prose(text) => cells.push(markdown_cell(cells.len(), &text)),
+            }
+        }
+    }
+
+    json!({
+        "cells": cells,
+        "metadata": {
+            "kernelspec": {
+                "name": kernel_name,
+                "display_name": display_name,
+                "language": labels[0],
+            },
+            "language_info": { "name": labels[0] },
+        },
+        "nbformat": 4,
+        "nbformat_minor": 5,
+    })
+}
@@ -0,0 +1,24 @@
+//! Errors of work done in the background, like converting attachments or
+//! running hooks, queued until the UI shows them as toasts.
+
+use std::sync::Mutex;
+
+/// Errors not yet shown to the user
+static ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Logs an error of `source` and queues it to be shown to the user
+pub fn report_error(source: &str, error: impl Into<String>) {
+    let error = error.into();
+    log::error!("{source}: {error}");
+    if let Ok(mut errors) = ERRORS.lock() {
+        errors.push(error);
+    }
+}
+
+/// Returns the errors reported since the last call
+pub fn take_errors() -> Vec<String> {
+    ERRORS
+        .lock()
+        .map(|mut errors| std::mem::take(&mut *errors))
+        .unwrap_or_default()
+}
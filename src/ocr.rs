@@ -0,0 +1,78 @@
+//! Local text recognition for image attachments, using the `tesseract`
+//! command-line tool when it is installed.
+
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct OcrSettings {
+    pub enabled: bool,
+    /// Tesseract language codes joined with `+`, e.g. `eng+deu`
+    pub languages: String,
+    /// Send only the recognized text instead of the image
+    pub text_only: bool,
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            languages: "eng".to_owned(),
+            text_only: false,
+        }
+    }
+}
+
+/// Recognized text keyed by path and modification time, so a chat with many
+/// images doesn't run OCR on all of them for every message
+static OCR_CACHE: Mutex<Option<HashMap<(PathBuf, SystemTime), String>>> = Mutex::new(None);
+
+/// Whether the file is an image tesseract can read
+pub fn is_image(path: &Path) -> bool {
+    mime_guess::from_path(path)
+        .first()
+        .is_some_and(|mime| mime.type_() == "image" && mime.subtype() != "svg")
+}
+
+pub async fn recognize(path: &Path, languages: &str) -> Result<String> {
+    let modified = tokio::fs::metadata(path).await?.modified()?;
+    let key = (path.to_path_buf(), modified);
+    if let Some(text) = OCR_CACHE
+        .lock()
+        .ok()
+        .and_then(|cache| cache.as_ref()?.get(&key).cloned())
+    {
+        return Ok(text);
+    }
+
+    log::info!("running OCR on {} ({languages})", path.display());
+    let mut command = tokio::process::Command::new("tesseract");
+    command.arg(path).arg("stdout");
+    if !languages.trim().is_empty() {
+        command.args(["-l", languages.trim()]);
+    }
+    let output = command
+        .output()
+        .await
+        .context("failed to run `tesseract`, is it installed?")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`tesseract` exited with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if let Ok(mut cache) = OCR_CACHE.lock() {
+        cache
+            .get_or_insert_with(HashMap::new)
+            .insert(key, text.clone());
+    }
+    Ok(text)
+}
@@ -0,0 +1,183 @@
+//! "Paste as plain text": cleans up text copied from web pages, documents and
+//! chat apps before it lands in the prompt.
+
+use eframe::egui;
+
+pub const PASTE_PLAIN_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::V,
+);
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct PlainPaste {
+    /// Clean every paste, not only the ones with Shift held
+    pub always: bool,
+    pub strip_markdown: bool,
+    /// Replace smart quotes, dashes and odd spaces with plain ASCII
+    pub normalize_punctuation: bool,
+}
+
+impl Default for PlainPaste {
+    fn default() -> Self {
+        Self {
+            always: false,
+            strip_markdown: true,
+            normalize_punctuation: true,
+        }
+    }
+}
+
+impl PlainPaste {
+    /// Cleans the text of paste events of this frame if the text edit `id` has
+    /// focus, call before showing it
+    pub fn apply(&self, ctx: &egui::Context, id: egui::Id) {
+        if !ctx.memory(|m| m.has_focus(id)) {
+            return;
+        }
+        ctx.input_mut(|i| {
+            if !self.always && !i.modifiers.matches_exact(PASTE_PLAIN_SHORTCUT.modifiers) {
+                return;
+            }
+            for event in &mut i.events {
+                if let egui::Event::Paste(text) = event {
+                    *text = self.clean(text);
+                }
+            }
+        });
+    }
+
+    pub fn clean(&self, text: &str) -> String {
+        let mut text = remove_invisible(text);
+        if self.normalize_punctuation {
+            text = normalize_punctuation(&text);
+        }
+        if self.strip_markdown {
+            text = strip_markdown(&text);
+        }
+        text
+    }
+}
+
+/// Drops zero-width characters, bidi controls and soft hyphens, which are
+/// invisible but still end up in the prompt
+fn remove_invisible(text: &str) -> String {
+    text.chars()
+        .filter(|c| {
+            !matches!(
+                c,
+                '\u{00AD}'
+                    | '\u{200B}'..='\u{200F}'
+                    | '\u{202A}'..='\u{202E}'
+                    | '\u{2060}'..='\u{2064}'
+                    | '\u{2066}'..='\u{2069}'
+                    | '\u{FEFF}'
+            )
+        })
+        .collect()
+}
+
+fn normalize_punctuation(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '‘' | '’' | '‚' | '‛' | '′' => out.push('\''),
+            '“' | '”' | '„' | '‟' | '″' | '«' | '»' => out.push('"'),
+            '‐' | '‑' | '‒' | '–' | '—' | '―' | '−' => out.push('-'),
+            '…' => out.push_str("..."),
+            '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => {
+                out.push(' ')
+            }
+            '\u{2028}' | '\u{2029}' => out.push('\n'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Removes markdown markup, keeping the text. Code blocks are kept as they are.
+fn strip_markdown(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code_block = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            lines.push(line.to_owned());
+            continue;
+        }
+        // horizontal rules
+        let rule: Vec<char> = trimmed.chars().filter(|c| *c != ' ').collect();
+        if rule.len() >= 3
+            && matches!(rule[0], '-' | '*' | '_')
+            && rule.iter().all(|c| *c == rule[0])
+        {
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        let mut content = trimmed;
+        while let Some(rest) = content.strip_prefix('>') {
+            content = rest.trim_start();
+        }
+        let heading = content.trim_start_matches('#');
+        if heading.len() < content.len() && (heading.is_empty() || heading.starts_with(' ')) {
+            content = heading.trim_start();
+        }
+        // bullets become dashes, numbered lists are kept
+        let bullet = ["* ", "+ ", "- "]
+            .iter()
+            .find_map(|bullet| content.strip_prefix(bullet));
+        let content = match bullet {
+            Some(rest) => format!("- {}", strip_inline(rest)),
+            None => strip_inline(content),
+        };
+        lines.push(format!("{indent}{content}"));
+    }
+    lines.join("\n")
+}
+
+/// Removes emphasis, inline code and link markup from a line
+fn strip_inline(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut prev = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        match c {
+            '`' => (),
+            // a lone `*` between spaces or digits is multiplication, not emphasis
+            '*' if !matches!(
+                (prev, next),
+                (Some(' '), Some(' ')) | (Some('0'..='9'), Some('0'..='9'))
+            ) => {}
+            '~' | '_' if next == Some(c) => {
+                chars.next();
+            }
+            // `[text](url)` and `![alt](url)` keep only the text
+            '!' if line[i + 1..].starts_with('[') => (),
+            '[' => match link_end(&line[i..]) {
+                Some((text, len)) => {
+                    out.push_str(&strip_inline(text));
+                    while chars.peek().is_some_and(|(j, _)| *j < i + len) {
+                        chars.next();
+                    }
+                }
+                None => out.push(c),
+            },
+            c => out.push(c),
+        }
+        prev = Some(c);
+    }
+    out
+}
+
+/// For a line starting with `[text](url)`, returns the text and the length of the link
+fn link_end(s: &str) -> Option<(&str, usize)> {
+    let close = s.find("](")?;
+    let end = s[close..].find(')')? + close;
+    Some((&s[1..close], end + 1))
+}
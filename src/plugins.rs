@@ -0,0 +1,252 @@
+//! Script plugins: Rhai scripts in the plugins folder that hook into sending
+//! and receiving messages and add slash commands.
+//!
+//! A script can define any of:
+//! - `on_send(prompt)`, returning the prompt to send instead
+//! - `on_receive(reply)`, returning the reply to show instead
+//! - `command_<name>(args)`, run by typing `/<name> args`, returning the
+//!   prompt to send
+
+use eframe::egui;
+use rhai::{Engine, Scope, AST};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const EXTENSION: &str = "rhai";
+const COMMAND_PREFIX: &str = "command_";
+/// Keeps a script with an endless loop from freezing the app
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Loaded scripts, `None` until first used
+static PLUGINS: Mutex<Option<Plugins>> = Mutex::new(None);
+
+pub fn plugins_dir() -> PathBuf {
+    eframe::storage_dir(crate::TITLE)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("plugins")
+}
+
+struct Script {
+    /// File name, also how it's turned off in the settings
+    name: String,
+    path: PathBuf,
+    /// `None` if it failed to compile
+    ast: Option<AST>,
+    error: Option<String>,
+    enabled: bool,
+    /// Hooks and commands it defines, for the plugin list
+    functions: Vec<String>,
+}
+
+impl Script {
+    fn load(engine: &Engine, path: &Path, disabled: &BTreeSet<String>) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let compiled = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| engine.compile(source).map_err(|e| e.to_string()));
+        let (ast, error) = match compiled {
+            Ok(ast) => (Some(ast), None),
+            Err(e) => {
+                log::error!("failed to load plugin `{}`: {e}", path.display());
+                (None, Some(e))
+            }
+        };
+        let functions = ast
+            .iter()
+            .flat_map(|ast| ast.iter_functions())
+            .filter(|f| {
+                f.params.len() == 1
+                    && (f.name == "on_send"
+                        || f.name == "on_receive"
+                        || f.name.starts_with(COMMAND_PREFIX))
+            })
+            .map(|f| match f.name.strip_prefix(COMMAND_PREFIX) {
+                Some(command) => format!("/{command}"),
+                None => f.name.to_owned(),
+            })
+            .collect();
+        Self {
+            enabled: !disabled.contains(&name),
+            name,
+            path: path.to_path_buf(),
+            ast,
+            error,
+            functions,
+        }
+    }
+
+    fn has(&self, function: &str) -> bool {
+        self.functions.iter().any(|f| f == function)
+    }
+
+    fn call(&self, engine: &Engine, function: &str, arg: String) -> Result<String, String> {
+        let ast = self.ast.as_ref().ok_or("the script failed to load")?;
+        engine
+            .call_fn::<String>(&mut Scope::new(), ast, function, (arg,))
+            .map_err(|e| format!("{} in plugin {}: {e}", function, self.name))
+    }
+}
+
+struct Plugins {
+    engine: Engine,
+    scripts: Vec<Script>,
+}
+
+impl Plugins {
+    fn load(disabled: &BTreeSet<String>) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| log::info!("plugin: {text}"));
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(plugins_dir())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // scripts run in name order, so it can be controlled with a number prefix
+        paths.sort();
+        let scripts: Vec<Script> = paths
+            .iter()
+            .map(|path| Script::load(&engine, path, disabled))
+            .collect();
+        log::info!("loaded {} plugins", scripts.len());
+        Self { engine, scripts }
+    }
+
+    /// Passes `text` through `function` of every enabled script that has it
+    fn transform(&self, function: &str, mut text: String) -> String {
+        for script in self.scripts.iter().filter(|s| s.enabled && s.has(function)) {
+            match script.call(&self.engine, function, text.clone()) {
+                Ok(transformed) => text = transformed,
+                Err(e) => crate::notify::report_error("plugins", e),
+            }
+        }
+        text
+    }
+}
+
+fn with_plugins<R>(f: impl FnOnce(&mut Plugins) -> R) -> Option<R> {
+    let mut plugins = PLUGINS.lock().ok()?;
+    Some(f(plugins.as_mut()?))
+}
+
+/// Loads the scripts if they aren't yet, call every frame
+pub fn ensure_loaded(disabled: &BTreeSet<String>) {
+    if let Ok(mut plugins) = PLUGINS.lock() {
+        if plugins.is_none() {
+            *plugins = Some(Plugins::load(disabled));
+        }
+    }
+}
+
+/// The prompt after the `on_send` hooks
+pub fn transform_prompt(prompt: String) -> String {
+    with_plugins(|p| p.transform("on_send", prompt.clone())).unwrap_or(prompt)
+}
+
+/// The reply after the `on_receive` hooks
+pub fn transform_reply(reply: String) -> String {
+    with_plugins(|p| p.transform("on_receive", reply.clone())).unwrap_or(reply)
+}
+
+/// Runs the slash command the prompt starts with, if a plugin defines it.
+/// Returns the prompt to send instead, or the error
+pub fn run_command(prompt: &str) -> Option<Result<String, String>> {
+    let line = prompt.strip_prefix('/')?;
+    let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    with_plugins(|p| {
+        let script = p
+            .scripts
+            .iter()
+            .find(|s| s.enabled && s.has(&format!("/{command}")))?;
+        log::info!("running /{command} from plugin {}", script.name);
+        Some(script.call(
+            &p.engine,
+            &format!("{COMMAND_PREFIX}{command}"),
+            args.trim().to_owned(),
+        ))
+    })
+    .flatten()
+}
+
+/// Slash commands of the enabled plugins, for the shortcut cheat sheet
+pub fn commands() -> Vec<String> {
+    with_plugins(|p| {
+        p.scripts
+            .iter()
+            .filter(|s| s.enabled)
+            .flat_map(|s| s.functions.iter().filter(|f| f.starts_with('/')).cloned())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// The plugin manager in the settings
+pub fn show_manager(ui: &mut egui::Ui, disabled: &mut BTreeSet<String>) {
+    ensure_loaded(disabled);
+    ui.label(format!(
+        "Rhai scripts (.{EXTENSION}) in the plugins folder can change prompts before \
+        they're sent with on_send(prompt), replies with on_receive(reply), and add slash \
+        commands with command_<name>(args)."
+    ));
+    ui.horizontal(|ui| {
+        if ui.button("📂 Open folder").clicked() {
+            let dir = plugins_dir();
+            if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| open::that(&dir)) {
+                log::error!("failed to open `{}`: {e}", dir.display());
+            }
+        }
+        if ui.button("⟳ Reload").clicked() {
+            if let Ok(mut plugins) = PLUGINS.lock() {
+                *plugins = Some(Plugins::load(disabled));
+            }
+        }
+    });
+    with_plugins(|p| {
+        if p.scripts.is_empty() {
+            ui.weak("No plugins loaded");
+            return;
+        }
+        egui::Grid::new("plugins_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for script in &mut p.scripts {
+                    if ui
+                        .checkbox(&mut script.enabled, &script.name)
+                        .on_hover_text(script.path.display().to_string())
+                        .changed()
+                    {
+                        if script.enabled {
+                            disabled.remove(&script.name);
+                        } else {
+                            disabled.insert(script.name.clone());
+                        }
+                    }
+                    match &script.error {
+                        Some(error) => {
+                            ui.colored_label(ui.visuals().error_fg_color, "Failed to load")
+                                .on_hover_text(error);
+                        }
+                        None if script.functions.is_empty() => {
+                            ui.weak("No hooks");
+                        }
+                        None => {
+                            ui.label(script.functions.join(", "));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+}
@@ -0,0 +1,92 @@
+//! Paid tier prices of the models, to estimate what a request costs before
+//! it's sent. The estimates use the rough token counts, not the API's.
+
+use crate::widgets::GeminiModel;
+
+/// Answer length assumed when the chat has no answers to go by
+pub const DEFAULT_ANSWER_TOKENS: usize = 600;
+
+/// US dollars per million tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    pub input: f64,
+    /// Thinking is billed as output too
+    pub output: f64,
+    /// Prompts longer than this many tokens cost `long_input` and `long_output`
+    pub long_context: Option<(usize, f64, f64)>,
+}
+
+impl Price {
+    const fn flat(input: f64, output: f64) -> Self {
+        Self {
+            input,
+            output,
+            long_context: None,
+        }
+    }
+
+    /// Cost in dollars of a request with `input` tokens and an answer of `output` tokens
+    pub fn cost(&self, input: usize, output: usize) -> f64 {
+        let (input_price, output_price) = match self.long_context {
+            Some((threshold, long_input, long_output)) if input > threshold => {
+                (long_input, long_output)
+            }
+            _ => (self.input, self.output),
+        };
+        (input as f64 * input_price + output as f64 * output_price) / 1_000_000.0
+    }
+}
+
+/// Price of `model`, `None` for the ones that are free to use
+pub fn price(model: GeminiModel) -> Option<Price> {
+    use GeminiModel as M;
+    match model {
+        M::Gemini25Pro
+        | M::Gemini25ProPreview0325
+        | M::Gemini25ProPreview0506
+        | M::Gemini25ProPreview0605 => Some(Price {
+            input: 1.25,
+            output: 10.0,
+            long_context: Some((200_000, 2.5, 15.0)),
+        }),
+        M::Gemini25Flash | M::Gemini25FlashPreview0520 => Some(Price::flat(0.30, 2.50)),
+        M::Gemini20Flash => Some(Price::flat(0.10, 0.40)),
+        M::Gemini20FlashLite => Some(Price::flat(0.075, 0.30)),
+        M::Gemini15Pro => Some(Price {
+            input: 1.25,
+            output: 5.0,
+            long_context: Some((128_000, 2.5, 10.0)),
+        }),
+        M::Gemini15Flash => Some(Price {
+            input: 0.075,
+            output: 0.30,
+            long_context: Some((128_000, 0.15, 0.60)),
+        }),
+        M::Gemini15Flash8b => Some(Price {
+            input: 0.0375,
+            output: 0.15,
+            long_context: Some((128_000, 0.075, 0.30)),
+        }),
+        M::Gemini20FlashThinkingExp0121
+        | M::Gemini20FlashThinkingExp1219
+        | M::Gemma34bIt
+        | M::Gemma312bIt
+        | M::Gemma327bIt
+        | M::Gemma31bIt
+        | M::Gemma3nE4bIt
+        | M::Gemma3nE2bIt => None,
+    }
+}
+
+/// Like "~$0.012", with enough digits to tell small costs apart
+pub fn format_cost(cost: f64) -> String {
+    if cost >= 1.0 {
+        format!("~${cost:.2}")
+    } else if cost >= 0.01 {
+        format!("~${cost:.3}")
+    } else if cost >= 0.0001 {
+        format!("~${cost:.4}")
+    } else {
+        "<$0.0001".to_owned()
+    }
+}
@@ -0,0 +1,141 @@
+//! Named settings profiles, e.g. for work and personal use, each with its own
+//! API key, proxy and default model.
+
+use crate::widgets::Settings;
+use eframe::egui;
+use std::collections::BTreeMap;
+
+const DEFAULT_PROFILE: &str = "Default";
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Profiles {
+    /// Name of the profile in use, its settings are the ones being edited
+    active: String,
+    /// Settings of the other profiles
+    stored: BTreeMap<String, Settings>,
+    #[serde(skip)]
+    new_name: String,
+    #[serde(skip)]
+    renaming: bool,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_owned(),
+            stored: BTreeMap::new(),
+            new_name: String::new(),
+            renaming: false,
+        }
+    }
+}
+
+impl Profiles {
+    /// Name of the profile in use, `None` if it's the only one
+    pub fn active_name(&self) -> Option<&str> {
+        (!self.stored.is_empty()).then_some(self.active.as_str())
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.active == name || self.stored.contains_key(name)
+    }
+
+    /// Stores the current settings under the active profile and loads `name`
+    fn switch(&mut self, settings: &mut Settings, name: &str) {
+        let Some(next) = self.stored.remove(name) else {
+            return;
+        };
+        let previous = std::mem::replace(settings, next);
+        let previous_name = std::mem::replace(&mut self.active, name.to_owned());
+        self.stored.insert(previous_name, previous);
+        log::info!("switched to settings profile `{name}`");
+    }
+
+    /// Shows the profile bar at the top of the settings page. Returns the name
+    /// of the profile switched to, if any.
+    pub fn show(&mut self, ui: &mut egui::Ui, settings: &mut Settings) -> Option<String> {
+        let mut switch_to = None;
+        ui.horizontal(|ui| {
+            ui.label("Profile");
+            if self.renaming {
+                let resp = ui.add(
+                    egui::TextEdit::singleline(&mut self.new_name)
+                        .hint_text(self.active.as_str())
+                        .desired_width(160.0),
+                );
+                if resp.lost_focus() {
+                    let name = self.new_name.trim();
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter))
+                        && !name.is_empty()
+                        && !self.exists(name)
+                    {
+                        self.active = name.to_owned();
+                    }
+                    self.new_name.clear();
+                    self.renaming = false;
+                } else {
+                    resp.request_focus();
+                }
+            } else {
+                egui::ComboBox::from_id_salt("settings_profile")
+                    .selected_text(self.active.as_str())
+                    .show_ui(ui, |ui| {
+                        ui.add(egui::SelectableLabel::new(true, self.active.as_str()));
+                        for name in self.stored.keys() {
+                            if ui.selectable_label(false, name).clicked() {
+                                switch_to = Some(name.clone());
+                            }
+                        }
+                    });
+                if ui
+                    .small_button("\u{270f}")
+                    .on_hover_text("Rename profile")
+                    .clicked()
+                {
+                    self.new_name = self.active.clone();
+                    self.renaming = true;
+                }
+            }
+
+            ui.menu_button("➕", |ui| {
+                ui.label("New profile, starting from the current settings");
+                let resp = ui
+                    .add(egui::TextEdit::singleline(&mut self.new_name).hint_text("Profile name"));
+                let name = self.new_name.trim().to_owned();
+                let valid = !name.is_empty() && !self.exists(&name);
+                let create = ui.add_enabled(valid, egui::Button::new("Create")).clicked()
+                    || (valid
+                        && resp.lost_focus()
+                        && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                if create {
+                    self.stored.insert(name.clone(), settings.clone());
+                    switch_to = Some(name);
+                    self.new_name.clear();
+                    ui.close_menu();
+                }
+            })
+            .response
+            .on_hover_text("New profile");
+
+            if !self.stored.is_empty()
+                && ui
+                    .small_button("🗑")
+                    .on_hover_text("Delete this profile")
+                    .clicked()
+            {
+                if let Some(next) = self.stored.keys().next().cloned() {
+                    let deleted = self.active.clone();
+                    self.switch(settings, &next);
+                    self.stored.remove(&deleted);
+                    log::info!("deleted settings profile `{deleted}`");
+                }
+            }
+        });
+
+        if let Some(name) = &switch_to {
+            self.switch(settings, name);
+        }
+        switch_to
+    }
+}
@@ -0,0 +1,101 @@
+//! Global hotkey for quick ask: pressing it anywhere sends the text on the
+//! clipboard to the quick ask chat. The clipboard is only read on a press.
+
+use eframe::egui;
+use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use std::sync::{
+    mpsc::{self, Receiver},
+    Mutex, OnceLock,
+};
+
+pub const DEFAULT_SHORTCUT: &str = "ctrl+alt+KeyG";
+
+/// Ids of pressed hotkeys. The hotkey handler is global and can only be set
+/// once, so the channel lives as long as the app.
+fn pressed_hotkeys(ctx: &egui::Context) -> &'static Mutex<Receiver<u32>> {
+    static PRESSED: OnceLock<Mutex<Receiver<u32>>> = OnceLock::new();
+    PRESSED.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+            if event.state == HotKeyState::Pressed && tx.send(event.id).is_ok() {
+                // the window may be in the background and idle
+                ctx.request_repaint();
+            }
+        }));
+        Mutex::new(rx)
+    })
+}
+
+/// A registered hotkey, unregistered when dropped
+struct Registration {
+    manager: GlobalHotKeyManager,
+    hotkey: HotKey,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Err(e) = self.manager.unregister(self.hotkey) {
+            log::warn!("failed to unregister the quick ask hotkey: {e}");
+        }
+    }
+}
+
+fn register(shortcut: &str) -> Result<Registration, String> {
+    let hotkey: HotKey = shortcut
+        .parse()
+        .map_err(|e| format!("Invalid quick ask shortcut `{shortcut}`: {e}"))?;
+    let manager =
+        GlobalHotKeyManager::new().map_err(|e| format!("Global hotkeys aren't available: {e}"))?;
+    manager
+        .register(hotkey)
+        .map_err(|e| format!("Failed to register `{shortcut}` for quick ask: {e}"))?;
+    log::info!("registered `{shortcut}` for quick ask");
+    Ok(Registration { manager, hotkey })
+}
+
+#[derive(Default)]
+pub struct QuickAskHotkey {
+    registration: Option<Registration>,
+    /// Shortcut registered, or that failed to, so it's not retried every frame
+    applied: Option<String>,
+}
+
+impl QuickAskHotkey {
+    /// Registers the shortcut while quick ask is enabled and unregisters it
+    /// otherwise. Returns whether it was pressed since the last call, or the
+    /// error if registering failed (reported once per shortcut).
+    pub fn update(&mut self, ctx: &egui::Context, shortcut: Option<&str>) -> Result<bool, String> {
+        let Some(shortcut) = shortcut.map(str::trim) else {
+            self.registration = None;
+            self.applied = None;
+            return Ok(false);
+        };
+        let pressed = pressed_hotkeys(ctx);
+        if self.applied.as_deref() != Some(shortcut) {
+            self.registration = None;
+            self.applied = Some(shortcut.to_owned());
+            self.registration = Some(register(shortcut)?);
+        }
+        let Some(registration) = &self.registration else {
+            return Ok(false);
+        };
+        let id = registration.hotkey.id();
+        let Ok(pressed) = pressed.lock() else {
+            return Ok(false);
+        };
+        // drained either way, presses of old shortcuts are dropped
+        let mut was_pressed = false;
+        for pressed_id in pressed.try_iter() {
+            was_pressed |= pressed_id == id;
+        }
+        Ok(was_pressed)
+    }
+}
+
+/// Text on the clipboard, read when the hotkey is pressed
+pub fn clipboard_text() -> Result<String, String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|e| format!("Failed to read the clipboard: {e}"))
+}
@@ -0,0 +1,128 @@
+//! Replays recorded `streamGenerateContent?alt=sse` responses from fixture
+//! files in place of the API, so the whole completion pipeline can be
+//! exercised without network.
+//!
+//! A fixture is a captured SSE stream: every `data: {...}` line is one chunk.
+//! A line starting with `error: ` makes the replay fail at that point, to
+//! simulate the connection dropping mid-stream.
+
+use anyhow::{Context, Result};
+use gemini_client_api::gemini::types::request::Part;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Directory with the fixtures shipped in the repository
+pub const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/streams");
+
+#[derive(Debug, Clone)]
+pub enum ReplayEvent {
+    Parts(Vec<Part>),
+    Error(String),
+}
+
+pub fn parse(fixture: &str) -> Result<Vec<ReplayEvent>> {
+    let mut events = Vec::new();
+    for (i, line) in fixture.lines().enumerate() {
+        let line = line.trim();
+        if let Some(data) = line.strip_prefix("data:") {
+            let chunk: serde_json::Value = serde_json::from_str(data.trim())
+                .with_context(|| format!("invalid chunk on line {}", i + 1))?;
+            let parts = crate::endpoint::response_parts(&chunk)
+                .with_context(|| format!("invalid chunk on line {}", i + 1))?;
+            events.push(ReplayEvent::Parts(parts));
+        } else if let Some(error) = line.strip_prefix("error:") {
+            events.push(ReplayEvent::Error(error.trim().to_owned()));
+        }
+    }
+    Ok(events)
+}
+
+pub async fn load(path: &Path) -> Result<Vec<ReplayEvent>> {
+    let fixture = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read fixture `{}`", path.display()))?;
+    parse(&fixture)
+}
+
+/// Lists the fixtures in [`FIXTURES_DIR`], sorted by name
+pub fn list_fixtures() -> Vec<PathBuf> {
+    let mut fixtures: Vec<PathBuf> = std::fs::read_dir(FIXTURES_DIR)
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "sse"))
+                .collect()
+        })
+        .unwrap_or_default();
+    fixtures.sort();
+    fixtures
+}
+
+/// Mock provider answering every request with the same recorded stream
+#[derive(Debug, Clone)]
+pub struct Replay {
+    events: Vec<ReplayEvent>,
+    /// Between chunks, so the UI can be watched while they arrive
+    delay: Duration,
+    /// `contents` of every request, as they would be sent to the API
+    pub requests: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl Replay {
+    pub fn new(events: Vec<ReplayEvent>, delay: Duration) -> Self {
+        Self {
+            events,
+            delay,
+            requests: Arc::default(),
+        }
+    }
+
+    fn record(&self, contents: serde_json::Value) {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(contents);
+        }
+    }
+
+    /// The chunks one by one, like a streamed response
+    pub fn ask_as_stream(
+        &self,
+        contents: serde_json::Value,
+    ) -> ReceiverStream<Result<Vec<Part>, String>> {
+        self.record(contents);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let events = self.events.clone();
+        let delay = self.delay;
+        tokio::spawn(async move {
+            for event in events {
+                tokio::time::sleep(delay).await;
+                let chunk = match event {
+                    ReplayEvent::Parts(parts) => Ok(parts),
+                    ReplayEvent::Error(e) => Err(e),
+                };
+                let failed = chunk.is_err();
+                // the receiver is dropped when the answer is stopped
+                if tx.send(chunk).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+        ReceiverStream::new(rx)
+    }
+
+    /// All the parts at once, like a response without streaming
+    pub async fn ask(&self, contents: serde_json::Value) -> Result<Vec<Part>, String> {
+        self.record(contents);
+        tokio::time::sleep(self.delay).await;
+        let mut parts = Vec::new();
+        for event in &self.events {
+            match event {
+                ReplayEvent::Parts(chunk) => parts.extend(chunk.iter().cloned()),
+                ReplayEvent::Error(e) => return Err(e.clone()),
+            }
+        }
+        Ok(parts)
+    }
+}
@@ -0,0 +1,64 @@
+//! "Copy as rendered": puts a message on the clipboard as HTML, with the
+//! markdown as the plain text alternative, so pasting into an email or a
+//! document keeps headings, lists, tables and code blocks.
+
+use anyhow::Result;
+use pulldown_cmark::{html, Options, Parser};
+use std::sync::Mutex;
+
+/// Styles are inlined, most mail clients drop `<style>` blocks
+const PRE_STYLE: &str = "background-color:#f6f8fa;padding:8px 12px;border-radius:6px;\
+    overflow-x:auto;font-family:Consolas,Menlo,monospace;font-size:90%";
+const CODE_STYLE: &str = "font-family:Consolas,Menlo,monospace;font-size:90%";
+const TABLE_STYLE: &str = "border-collapse:collapse";
+const CELL_STYLE: &str = "border:1px solid #d0d7de;padding:4px 8px";
+const QUOTE_STYLE: &str =
+    "border-left:3px solid #d0d7de;margin-left:0;padding-left:12px;color:#57606a";
+
+/// Kept alive so the copied HTML stays available, on X11 it's served by the
+/// clipboard instance that set it
+static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+pub fn markdown_to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut body = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut body, Parser::new_ext(markdown, options));
+
+    let body = body
+        .replace("<pre>", &format!("<pre style=\"{PRE_STYLE}\">"))
+        .replace("<code>", &format!("<code style=\"{CODE_STYLE}\">"))
+        .replace(
+            "<code class=",
+            &format!("<code style=\"{CODE_STYLE}\" class="),
+        )
+        .replace("<table>", &format!("<table style=\"{TABLE_STYLE}\">"))
+        .replace("<th>", &format!("<th style=\"{CELL_STYLE}\">"))
+        .replace("<td>", &format!("<td style=\"{CELL_STYLE}\">"))
+        .replace(
+            "<th style=\"text-align",
+            &format!("<th style=\"{CELL_STYLE};text-align"),
+        )
+        .replace(
+            "<td style=\"text-align",
+            &format!("<td style=\"{CELL_STYLE};text-align"),
+        )
+        .replace(
+            "<blockquote>",
+            &format!("<blockquote style=\"{QUOTE_STYLE}\">"),
+        );
+    format!("<div style=\"font-family:sans-serif;line-height:1.5\">\n{body}</div>")
+}
+
+/// Copies `markdown` rendered as HTML, apps that only take plain text get the markdown
+pub fn copy_rendered(markdown: &str) -> Result<()> {
+    let mut clipboard = CLIPBOARD.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+    if clipboard.is_none() {
+        *clipboard = Some(arboard::Clipboard::new()?);
+    }
+    let clipboard = clipboard.as_mut().expect("created above");
+    clipboard.set_html(markdown_to_html(markdown), Some(markdown))?;
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Auto model mode: picks a fast model for simple prompts and a thinking
+//! model for long, code-heavy or attachment-heavy ones.
+
+use crate::widgets::GeminiModel;
+use std::path::PathBuf;
+
+pub const FAST_MODEL: GeminiModel = GeminiModel::Gemini25Flash;
+const THINKING_MODEL: GeminiModel = GeminiModel::Gemini25Pro;
+
+/// Prompts longer than this go to the thinking model
+const LONG_PROMPT_CHARS: usize = 1500;
+
+/// Words that usually mean the prompt needs some reasoning
+const REASONING_HINTS: &[&str] = &[
+    "step by step",
+    "prove",
+    "debug",
+    "refactor",
+    "analyze",
+    "analyse",
+    "optimize",
+    "why does",
+];
+
+/// The model picked for a prompt and why
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub model: GeminiModel,
+    pub reason: String,
+}
+
+/// Counts lines that look like source code rather than prose
+fn code_lines(prompt: &str) -> usize {
+    prompt
+        .lines()
+        .map(str::trim)
+        .filter(|l| {
+            l.ends_with([';', '{', '}'])
+                || [
+                    "fn ", "def ", "class ", "import ", "#include", "let ", "const ",
+                ]
+                .iter()
+                .any(|kw| l.starts_with(kw))
+        })
+        .count()
+}
+
+pub fn route(prompt: &str, files: &[PathBuf]) -> Route {
+    let mut reasons = Vec::new();
+    if prompt.contains("```") || code_lines(prompt) >= 3 {
+        reasons.push("contains code".to_owned());
+    }
+    if prompt.chars().count() > LONG_PROMPT_CHARS {
+        reasons.push("long prompt".to_owned());
+    }
+    if !files.is_empty() {
+        reasons.push(format!("{} attachment(s)", files.len()));
+    }
+    let lowercase = prompt.to_lowercase();
+    if REASONING_HINTS.iter().any(|hint| lowercase.contains(hint)) {
+        reasons.push("asks for reasoning".to_owned());
+    }
+
+    if reasons.is_empty() {
+        Route {
+            model: FAST_MODEL,
+            reason: "short, simple prompt".to_owned(),
+        }
+    } else {
+        Route {
+            model: THINKING_MODEL,
+            reason: reasons.join(", "),
+        }
+    }
+}
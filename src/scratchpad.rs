@@ -0,0 +1,58 @@
+//! Scratchpad: markdown notes kept with a chat in a side panel, for todo
+//! items, facts pulled from the answers or drafts. They're never sent to the
+//! model.
+
+use eframe::egui;
+use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Scratchpad {
+    pub text: String,
+    pub open: bool,
+    /// Showing the rendered notes instead of the editor
+    rendered: bool,
+}
+
+impl Scratchpad {
+    pub fn show(&mut self, ui: &mut egui::Ui, commonmark_cache: &mut CommonMarkCache) {
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            ui.heading("Notes");
+            ui.weak(format!(
+                "{} words",
+                crate::analytics::word_count(&self.text)
+            ));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .add(egui::Button::new("❌").frame(false))
+                    .on_hover_text("Close, the notes are kept")
+                    .clicked()
+                {
+                    self.open = false;
+                }
+                if ui.button("🗐").on_hover_text("Copy").clicked() {
+                    ui.ctx().copy_text(self.text.clone());
+                }
+                ui.toggle_value(&mut self.rendered, "👁")
+                    .on_hover_text("Show the notes formatted");
+            });
+        });
+        ui.weak("Saved with the chat, never sent to the model.");
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                if self.rendered {
+                    CommonMarkViewer::new().show(ui, commonmark_cache, &self.text);
+                } else {
+                    ui.add_sized(
+                        ui.available_size(),
+                        egui::TextEdit::multiline(&mut self.text)
+                            .hint_text("- [ ] Todo items, facts, drafts…"),
+                    );
+                }
+            });
+    }
+}
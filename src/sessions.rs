@@ -1,9 +1,16 @@
 use crate::{
+    ab_test::AbWorkspace,
+    batch::BatchRunner,
     chat::{Chat, ChatAction, ChatExportFormat},
-    widgets::{ModelPicker, RequestInfoType, Settings},
+    flashcards::Flashcards,
+    git_context::GitContext,
+    markdown_cache::MarkdownCaches,
+    network::NetworkStatus,
+    switcher::ChatSwitcher,
+    watch_folder::WatchFolder,
+    widgets::{ModelPicker, RequestInfoType, Settings, SidebarDensity},
 };
-use eframe::egui::{self, vec2, Color32, CornerRadius, Frame, Layout, Stroke};
-use egui_commonmark::CommonMarkCache;
+use eframe::egui::{self, vec2, Color32, CornerRadius, Frame, Layout, Margin, Stroke};
 use egui_modal::{Icon, Modal};
 use egui_notify::{Toast, Toasts};
 use egui_twemoji::EmojiLabel;
@@ -13,16 +20,51 @@ use flowync::{CompactFlower, CompactHandle};
 use parking_lot::RwLock;
 #[cfg(feature = "tts")]
 use std::sync::Arc;
-use std::{cell::RefCell, path::PathBuf, rc::Rc, time::Instant};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
 #[cfg(feature = "tts")]
 use tts::Tts;
 
+/// Quick picks for the chat icon in the edit panel
+const CHAT_ICONS: &[&str] = &["💬", "💡", "💻", "📚", "📝", "🔬", "🎨", "🌍", "⭐"];
+
 #[derive(Default, PartialEq, serde::Serialize, serde::Deserialize)]
 enum SessionTab {
     #[default]
     Chats,
 }
 
+/// Views shown instead of the selected chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum ToolView {
+    AbTest,
+    Batch,
+    Flashcards,
+    Backups,
+}
+
+impl ToolView {
+    const ALL: [Self; 4] = [Self::AbTest, Self::Batch, Self::Flashcards, Self::Backups];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::AbTest => "🔬 Prompt A/B testing",
+            Self::Batch => "📋 Batch prompts from CSV",
+            Self::Flashcards => "🃏 Flashcards",
+            Self::Backups => "☁ Backups",
+        }
+    }
+}
+
+pub const FOCUS_MODE_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::F,
+);
+
 #[cfg(feature = "tts")]
 pub type SharedTts = Option<Arc<RwLock<Tts>>>;
 enum BackendResponse {
@@ -30,6 +72,7 @@ enum BackendResponse {
     Toast(Toast),
     Files { id: usize, files: Vec<PathBuf> },
     Settings(Box<Settings>),
+    Update(Box<crate::updates::Release>),
 }
 
 // <progress, response, error>
@@ -51,7 +94,11 @@ pub struct Sessions {
     #[serde(skip)]
     tts: SharedTts,
     #[serde(skip)]
-    commonmark_cache: CommonMarkCache,
+    markdown_caches: MarkdownCaches,
+    #[serde(skip)]
+    watch_folder: WatchFolder,
+    #[serde(skip)]
+    network: NetworkStatus,
     #[serde(skip)]
     flower: BackendFlower,
     #[serde(skip)]
@@ -59,11 +106,65 @@ pub struct Sessions {
     #[serde(skip)]
     virtual_list: Rc<RefCell<VirtualList>>,
     edited_chat: Option<usize>,
+    #[serde(skip)]
+    renaming_chat: Option<usize>,
+    #[serde(skip)]
+    rename_buf: String,
+    #[serde(skip)]
+    merge_source: Option<usize>,
     chat_export_format: ChatExportFormat,
     #[serde(skip)]
     toasts: Toasts,
     settings_open: bool,
+    #[serde(skip)]
+    cheat_sheet_open: bool,
+    #[serde(skip)]
+    chat_switcher: ChatSwitcher,
+    tool_view: Option<ToolView>,
+    ab_workspace: AbWorkspace,
+    batch_runner: BatchRunner,
+    flashcards: Flashcards,
+    sidebar_collapsed: bool,
+    sidebar_width: f32,
+    /// Not saved, the app always starts at its usual size
+    #[serde(skip)]
+    mini_mode: bool,
+    /// Only the conversation, in a centered column with larger text
+    focus_mode: bool,
+    #[serde(skip)]
+    size_before_mini: Option<egui::Vec2>,
+    #[serde(skip)]
+    applied_always_on_top: Option<bool>,
+    /// Responses finished while the window was in the background, shown in its title
+    #[serde(skip)]
+    unread_completions: usize,
+    #[serde(skip)]
+    applied_unread_count: Option<usize>,
+    /// Id of the chat shown in the last frame, to notice switching away from it
+    #[serde(skip)]
+    shown_chat: Option<usize>,
+    #[serde(skip)]
+    shutdown: crate::shutdown::Shutdown,
+    quick_ask_chat: Option<usize>,
+    #[serde(skip)]
+    quick_ask_pending: bool,
+    #[serde(skip)]
+    quick_ask_hotkey: crate::quick_ask::QuickAskHotkey,
+    #[serde(skip)]
+    update_checked: bool,
+    #[serde(skip)]
+    available_update: Option<crate::updates::Release>,
+    dismissed_update: Option<String>,
+    #[serde(skip)]
+    crash_checked: bool,
+    #[serde(skip)]
+    crash_report: Option<PathBuf>,
     pub settings: Settings,
+    profiles: crate::profiles::Profiles,
+    folder_sync: crate::folder_sync::FolderSync,
+    #[serde(skip)]
+    sync_imported: bool,
+    backups: crate::backup::Backups,
 }
 
 impl Default for Sessions {
@@ -81,7 +182,9 @@ impl Default for Sessions {
                 .map_err(|e| log::error!("failed to initialize TTS: {e}"))
                 .map(|tts| Arc::new(RwLock::new(tts)))
                 .ok(),
-            commonmark_cache: CommonMarkCache::default(),
+            markdown_caches: MarkdownCaches::default(),
+            watch_folder: WatchFolder::default(),
+            network: NetworkStatus::default(),
             flower: BackendFlower::new(1),
             last_request_time: now,
             virtual_list: Rc::new(RefCell::new({
@@ -90,10 +193,41 @@ impl Default for Sessions {
                 list
             })),
             edited_chat: None,
+            renaming_chat: None,
+            rename_buf: String::new(),
+            merge_source: None,
             chat_export_format: ChatExportFormat::default(),
             toasts: Toasts::default(),
             settings_open: false,
+            cheat_sheet_open: false,
+            chat_switcher: ChatSwitcher::default(),
+            tool_view: None,
+            ab_workspace: AbWorkspace::default(),
+            batch_runner: BatchRunner::default(),
+            flashcards: Flashcards::default(),
+            sidebar_collapsed: false,
+            sidebar_width: 200.0,
+            mini_mode: false,
+            focus_mode: false,
+            size_before_mini: None,
+            applied_always_on_top: None,
+            unread_completions: 0,
+            applied_unread_count: None,
+            shown_chat: None,
+            shutdown: crate::shutdown::Shutdown::default(),
+            quick_ask_chat: None,
+            quick_ask_pending: false,
+            quick_ask_hotkey: crate::quick_ask::QuickAskHotkey::default(),
+            update_checked: false,
+            available_update: None,
+            dismissed_update: None,
+            crash_checked: false,
+            crash_report: None,
             settings: Settings::default(),
+            profiles: crate::profiles::Profiles::default(),
+            folder_sync: crate::folder_sync::FolderSync::default(),
+            sync_imported: false,
+            backups: crate::backup::Backups::default(),
         }
     }
 }
@@ -129,6 +263,46 @@ async fn pick_files(id: usize, handle: &BackendFlowerHandle) {
     });
 }
 
+async fn pick_folder(id: usize, handle: &BackendFlowerHandle) {
+    let Some(folder) = rfd::AsyncFileDialog::new().pick_folder().await else {
+        handle.success(BackendResponse::Ignore);
+        return;
+    };
+
+    log::info!("selected folder `{}`", folder.path().display());
+
+    handle.success(BackendResponse::Files {
+        id,
+        files: vec![folder.path().to_path_buf()],
+    });
+}
+
+async fn pick_git_context(id: usize, context: GitContext, handle: &BackendFlowerHandle) {
+    let Some(repo) = rfd::AsyncFileDialog::new()
+        .set_title("Pick a git repository")
+        .pick_folder()
+        .await
+    else {
+        handle.success(BackendResponse::Ignore);
+        return;
+    };
+
+    let repo = repo.path().to_path_buf();
+    match tokio::task::spawn_blocking(move || crate::git_context::save_context(&repo, context))
+        .await
+    {
+        Ok(Ok(path)) => handle.success(BackendResponse::Files {
+            id,
+            files: vec![path],
+        }),
+        Ok(Err(e)) => {
+            log::error!("failed to get git context: {e}");
+            handle.success(BackendResponse::Toast(Toast::error(e.to_string())));
+        }
+        Err(e) => handle.success(BackendResponse::Toast(Toast::error(e.to_string()))),
+    }
+}
+
 async fn load_settings(handle: &BackendFlowerHandle) {
     let Some(file) = rfd::AsyncFileDialog::new()
         .add_filter("JSON file", &["json"])
@@ -156,6 +330,43 @@ async fn load_settings(handle: &BackendFlowerHandle) {
     }
 }
 
+/// Checks whether a dropped or passed file can be attached, notifying the user if not
+fn is_supported_file(path: &Path, toasts: &mut Toasts) -> bool {
+    // folders are attached as a bundle of their text files
+    if path.is_dir() {
+        return true;
+    }
+
+    let filename = path.file_name().unwrap_or_default().to_string_lossy();
+    let Some(ext) = path.extension().and_then(|s| s.to_str()) else {
+        log::warn!("file `{}` has no extension", path.display());
+        toasts.add(Toast::info(format!(
+            "Skipping file with no extension: `{filename}`"
+        )));
+        return false;
+    };
+
+    let all_formats: Vec<_> = [
+        crate::IMAGE_FORMATS,
+        crate::VIDEO_FORMATS,
+        crate::TEXT_FORMATS,
+        crate::MUSIC_FORMATS,
+    ]
+    .concat();
+
+    if !all_formats.contains(&ext.to_lowercase().as_str()) {
+        log::warn!(
+            "file `{}` has unsupported extension `{ext}`",
+            path.display()
+        );
+        toasts.add(Toast::info(format!(
+            "Skipping unsupported file type: `{filename}`"
+        )));
+        return false;
+    }
+    true
+}
+
 fn preview_files_being_dropped(ctx: &egui::Context) {
     use egui::*;
     use std::fmt::Write as _;
@@ -204,11 +415,11 @@ impl Sessions {
             };
         }
 
-        // if speaking, continuously check if stopped
+        // if speaking, keep checking if stopped
         #[cfg(feature = "tts")]
-        let mut request_repaint = self.is_speaking;
-
-        #[cfg(not(feature = "tts"))]
+        if self.is_speaking {
+            self.settings.frame_pacing.request_speech_poll(ctx);
+        }
         let mut request_repaint = false;
 
         let mut modal = Modal::new(ctx, "sessions_main_modal");
@@ -220,35 +431,208 @@ impl Sessions {
         modal.show_dialog();
         self.settings.show_modal(&settings_modal);
 
-        let avail_width = ctx.available_rect().width();
-        egui::SidePanel::left("sessions_panel")
-            .resizable(true)
-            .max_width(avail_width * 0.5)
-            .show(ctx, |ui| {
-                self.show_left_panel(ui);
-                ui.allocate_space(ui.available_size());
+        let crash_modal = Modal::new(ctx, "crash_report_modal");
+        if !self.crash_checked {
+            self.crash_checked = true;
+            self.crash_report = crate::crash::pending_report();
+            if self.crash_report.is_some() {
+                crash_modal.open();
+            }
+        }
+        crash_modal.show(|ui| {
+            self.show_crash_modal_inner(ui, &crash_modal);
+        });
+
+        if self.applied_always_on_top != Some(self.settings.always_on_top) {
+            self.applied_always_on_top = Some(self.settings.always_on_top);
+            ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
+                if self.settings.always_on_top {
+                    egui::WindowLevel::AlwaysOnTop
+                } else {
+                    egui::WindowLevel::Normal
+                },
+            ));
+        }
+
+        if !self.update_checked && self.settings.check_for_updates {
+            self.update_checked = true;
+            let handle = self.flower.handle();
+            tokio::spawn(async move {
+                handle.activate();
+                match crate::updates::check_for_update().await {
+                    Ok(Some(release)) => handle.success(BackendResponse::Update(Box::new(release))),
+                    Ok(None) => handle.success(BackendResponse::Ignore),
+                    Err(e) => {
+                        log::warn!("update check failed: {e:#}");
+                        handle.success(BackendResponse::Ignore);
+                    }
+                }
             });
+        }
+        self.show_update_banner(ctx);
+        crate::capabilities::refresh_once(
+            &self.settings.api_key,
+            self.settings.proxy_path.as_deref(),
+            &self.settings.endpoint,
+        );
+
+        if ctx.input_mut(|i| i.consume_shortcut(&FOCUS_MODE_SHORTCUT)) {
+            self.set_focus_mode(ctx, !self.focus_mode);
+        }
+        crate::cheatsheet::handle_shortcut(ctx, &mut self.cheat_sheet_open);
+        crate::cheatsheet::show(ctx, &mut self.cheat_sheet_open);
+        let switch_to = self
+            .chat_switcher
+            .handle_input(ctx, &self.chats)
+            .or_else(|| self.chat_switcher.show(ctx, &self.chats));
+        if let Some(idx) = switch_to.and_then(|id| self.chats.iter().position(|c| c.id() == id)) {
+            self.selected_chat = idx;
+            self.settings_open = false;
+            self.tool_view = None;
+            self.edited_chat = None;
+        }
+
+        let avail_width = ctx.available_rect().width();
+        if self.mini_mode {
+            self.show_mini_mode_bar(ctx);
+        } else if self.focus_mode {
+            // no sidebar
+        } else if self.sidebar_collapsed || self.settings.sidebar_density == SidebarDensity::Rail {
+            egui::SidePanel::left("sessions_rail")
+                .resizable(false)
+                .exact_width(44.0)
+                .show(ctx, |ui| {
+                    self.show_sidebar_rail(ui);
+                });
+        } else {
+            self.sidebar_width = egui::SidePanel::left("sessions_panel")
+                .resizable(true)
+                .default_width(self.sidebar_width)
+                .max_width(avail_width * 0.5)
+                .show(ctx, |ui| {
+                    egui::TopBottomPanel::bottom("sidebar_footer")
+                        .frame(egui::Frame::NONE)
+                        .show_inside(ui, |ui| {
+                            ui.add_space(4.0);
+                            self.show_dnd_toggle(ui, "Do not disturb");
+                        });
+                    self.show_left_panel(ui);
+                    ui.allocate_space(ui.available_size());
+                })
+                .response
+                .rect
+                .width();
+        }
+
+        self.poll_quick_ask(ctx);
+        if !self.sync_imported {
+            self.sync_imported = true;
+            self.import_from_sync_folder();
+        }
+        self.backups.poll_schedule(ctx, &self.settings, &self.chats);
+        self.watch_folder
+            .update(ctx, self.settings.watch_folder.as_deref());
+        if self.settings.check_connection {
+            self.network.update(
+                ctx,
+                self.settings.proxy_path.as_deref(),
+                self.settings.endpoint.host(),
+            );
+        }
 
         // poll all flowers
-        for chat in self.chats.iter_mut() {
+        let window_focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        for (i, chat) in self.chats.iter_mut().enumerate() {
             if chat.flower_active() {
                 request_repaint = true;
                 chat.poll_flower(&mut chat_modal);
+
+                if let Some(failed) = chat.last_response_failed() {
+                    if let Some(hook) = self.settings.chat_hooks.get(&chat.sync_id) {
+                        if let Some(reply) = chat.last_reply().filter(|_| !failed) {
+                            hook.run(
+                                &chat.sync_id,
+                                chat.display_name(),
+                                &chat.model_picker.selected.to_string(),
+                                reply,
+                            );
+                        }
+                    }
+                    if !window_focused {
+                        self.unread_completions += 1;
+                    }
+                    if self.settings.do_not_disturb.active().is_none() {
+                        self.settings.sound_cues.notify(
+                            if failed {
+                                crate::sound::Cue::Failed
+                            } else {
+                                crate::sound::Cue::Completed
+                            },
+                            window_focused,
+                        );
+                    }
+                }
+
+                if !chat.flower_active() && self.quick_ask_pending && self.quick_ask_chat == Some(i)
+                {
+                    self.quick_ask_pending = false;
+                    let first_line = chat
+                        .last_reply()
+                        .and_then(|reply| reply.lines().find(|l| !l.trim().is_empty()))
+                        .unwrap_or("(empty answer)");
+                    self.toasts
+                        .add(Toast::info(format!("Quick ask: {first_line}")));
+                }
             }
         }
+        if window_focused {
+            self.unread_completions = 0;
+        }
+        if self.applied_unread_count != Some(self.unread_completions) {
+            self.applied_unread_count = Some(self.unread_completions);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(
+                match self.unread_completions {
+                    0 => crate::TITLE.to_owned(),
+                    unread => format!("({unread}) {}", crate::TITLE),
+                },
+            ));
+        }
         if self.flower.is_active() {
             request_repaint = true;
             self.poll_backend_flower(&modal);
         }
+        self.handle_leaving(ctx);
+        #[cfg(feature = "scripting")]
+        crate::plugins::ensure_loaded(&self.settings.disabled_plugins);
+        for error in crate::notify::take_errors() {
+            self.toasts.add(Toast::error(error));
+        }
 
         if request_repaint {
-            ctx.request_repaint();
+            self.settings.frame_pacing.request_poll(ctx);
         }
 
-        if self.settings_open {
+        if self.settings_open && !self.mini_mode {
             self.edited_chat = None;
             egui::CentralPanel::default().show(ctx, |ui| {
                 egui::ScrollArea::both().auto_shrink(false).show(ui, |ui| {
+                    if ui
+                        .button("⌨ Keyboard shortcuts")
+                        .on_hover_text(format!(
+                            "Also opens with ? or {}",
+                            ui.ctx()
+                                .format_shortcut(&crate::cheatsheet::CHEAT_SHEET_SHORTCUT)
+                        ))
+                        .clicked()
+                    {
+                        self.cheat_sheet_open = true;
+                    }
+                    if let Some(profile) = self.profiles.show(ui, &mut self.settings) {
+                        self.toasts.add(Toast::info(format!(
+                            "Switched to the \"{profile}\" profile"
+                        )));
+                    }
+                    ui.separator();
                     self.settings.show(
                         ui,
                         &mut |typ| match typ {
@@ -259,12 +643,78 @@ impl Sessions {
                                     load_settings(&handle).await;
                                 });
                             }
+                            RequestInfoType::PropagateSystemPreset(preset) => {
+                                let mut updated = 0;
+                                for chat in self.chats.iter_mut().filter(|chat| {
+                                    chat.model_picker.system_preset.as_ref() == Some(&preset.name)
+                                }) {
+                                    chat.model_picker.apply_preset(&preset);
+                                    updated += 1;
+                                }
+                                self.toasts.add(Toast::success(format!(
+                                    "Updated {updated} chats using \"{}\"",
+                                    preset.name
+                                )));
+                            }
+                            RequestInfoType::RegisterLinkHandler => {
+                                let toast = match crate::deeplink::register() {
+                                    Ok(()) => Toast::success(
+                                        "Registered as the handler of egeminui:// links",
+                                    ),
+                                    Err(e) => {
+                                        log::error!("failed to register link handler: {e:#}");
+                                        Toast::error(format!(
+                                            "Failed to register the link handler: {e:#}"
+                                        ))
+                                    }
+                                };
+                                self.toasts.add(toast);
+                            }
+                            RequestInfoType::ExportUsageReport(format) => {
+                                let task = rfd::AsyncFileDialog::new()
+                                    .add_filter(format!("{format:?} file"), format.extensions())
+                                    .save_file();
+                                let records = self
+                                    .chats
+                                    .iter()
+                                    .flat_map(|chat| chat.usage_records())
+                                    .collect();
+                                let handle = self.flower.handle();
+                                tokio::spawn(async move {
+                                    let toast = crate::analytics::export_usage_report(
+                                        records, format, task,
+                                    )
+                                    .await
+                                    .unwrap_or_else(|e| {
+                                        log::error!("failed to export usage report: {e}");
+                                        Toast::error(e.to_string())
+                                    });
+                                    handle.activate();
+                                    handle.success(BackendResponse::Toast(toast));
+                                });
+                            }
                         },
                         &settings_modal,
                     );
                 });
             });
-        } else if let Some(edited_chat) = self.edited_chat {
+        } else if let Some(tool) = self.tool_view.filter(|_| !self.mini_mode) {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                egui::ScrollArea::both()
+                    .auto_shrink(false)
+                    .show(ui, |ui| match tool {
+                        ToolView::AbTest => self.ab_workspace.show(ui, &self.settings),
+                        ToolView::Batch => self.batch_runner.show(ui, &self.settings),
+                        ToolView::Flashcards => self.flashcards.show(ui),
+                        ToolView::Backups => {
+                            if let Some(chats) = self.backups.show(ui, &self.settings, &self.chats)
+                            {
+                                self.restore_chats(chats);
+                            }
+                        }
+                    })
+            });
+        } else if let Some(edited_chat) = self.edited_chat.filter(|_| !self.mini_mode) {
             egui::CentralPanel::default().show(ctx, |ui| {
                 egui::ScrollArea::both().auto_shrink(false).show(ui, |ui| {
                     self.show_chat_edit_panel(ui, edited_chat);
@@ -279,8 +729,264 @@ impl Sessions {
             preview_files_being_dropped(ctx);
         }
 
-        // display toast queue
-        self.toasts.show(ctx);
+        // display toast queue, held back while in do not disturb
+        if self.settings.do_not_disturb.active().is_none() {
+            self.toasts.show(ctx);
+        }
+    }
+
+    /// Replaces all chats with the ones of a restored backup
+    fn restore_chats(&mut self, chats: Vec<Chat>) {
+        if chats.is_empty() {
+            self.toasts.add(Toast::warning(
+                "The backup has no chats, nothing was restored",
+            ));
+            return;
+        }
+        let count = chats.len();
+        self.chats = chats;
+        self.selected_chat = 0;
+        self.edited_chat = None;
+        self.renaming_chat = None;
+        self.merge_source = None;
+        self.quick_ask_chat = None;
+        self.toasts.add(Toast::success(format!(
+            "Restored {count} chats from the backup"
+        )));
+    }
+
+    /// Merges chats changed on other machines, once on startup
+    fn import_from_sync_folder(&mut self) {
+        let Some(folder) = self.settings.sync_folder.clone() else {
+            return;
+        };
+        match self.folder_sync.import(&folder, &mut self.chats) {
+            Ok(summary) if summary.is_empty() => (),
+            Ok(summary) => {
+                let mut toast = format!(
+                    "Synced chats: {} new, {} updated",
+                    summary.added, summary.updated
+                );
+                if summary.conflicts > 0 {
+                    toast.push_str(&format!(
+                        ", {} changed on two machines were kept as copies",
+                        summary.conflicts
+                    ));
+                }
+                self.toasts.add(Toast::info(toast));
+            }
+            Err(e) => {
+                log::error!("failed to import chats from the sync folder: {e:#}");
+                self.toasts
+                    .add(Toast::error(format!("Failed to sync chats: {e}")));
+            }
+        }
+    }
+
+    /// Whether the window is shrunk to the last reply and the chatbox
+    pub fn is_mini(&self) -> bool {
+        self.mini_mode
+    }
+
+    /// Stores the last message of each chat for the sidebar, called when the
+    /// app state is saved
+    pub fn update_chat_previews(&mut self) {
+        for chat in &mut self.chats {
+            chat.update_preview();
+        }
+    }
+
+    /// Writes changed chats to the sync folder, called when the app state is saved
+    pub fn export_to_sync_folder(&mut self) {
+        let Some(folder) = self.settings.sync_folder.clone() else {
+            return;
+        };
+        if !self.sync_imported {
+            return;
+        }
+        match self.folder_sync.export(&folder, &mut self.chats) {
+            Ok(0) => (),
+            Ok(written) => log::info!("wrote {written} chats to `{}`", folder.display()),
+            Err(e) => log::error!("failed to write chats to the sync folder: {e:#}"),
+        }
+    }
+
+    /// Registers the quick ask hotkey while it's enabled, and sends the text
+    /// on the clipboard to the quick ask chat when it's pressed
+    fn poll_quick_ask(&mut self, ctx: &egui::Context) {
+        let shortcut = self
+            .settings
+            .quick_ask_enabled
+            .then_some(self.settings.quick_ask_hotkey.as_str());
+        match self.quick_ask_hotkey.update(ctx, shortcut) {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(e) => {
+                log::error!("{e}");
+                self.toasts.add(Toast::error(e));
+                return;
+            }
+        }
+        let text = match crate::quick_ask::clipboard_text() {
+            Ok(text) if !text.trim().is_empty() => text,
+            Ok(_) => {
+                self.toasts
+                    .add(Toast::info("Quick ask: there's no text on the clipboard"));
+                return;
+            }
+            Err(e) => {
+                log::error!("{e}");
+                self.toasts.add(Toast::error(e));
+                return;
+            }
+        };
+
+        let idx = match self.quick_ask_chat {
+            Some(idx) if idx < self.chats.len() => idx,
+            _ => {
+                self.add_default_chat();
+                let idx = self.chats.len() - 1;
+                self.chats[idx].title = Some("Quick ask".to_owned());
+                self.chats[idx].icon = Some("📋".to_owned());
+                self.quick_ask_chat = Some(idx);
+                idx
+            }
+        };
+        let chat = &mut self.chats[idx];
+        if chat.flower_active() {
+            log::warn!("quick ask chat is busy, ignoring the hotkey");
+            self.toasts
+                .add(Toast::info("Quick ask is still answering the last text"));
+            return;
+        }
+        log::info!("sending {} bytes of copied text to quick ask", text.len());
+        let prompt = format!("{}\n\n{text}", self.settings.quick_ask_instruction);
+        self.quick_ask_pending = chat.send_prompt(&self.settings, &prompt);
+        if !self.quick_ask_pending {
+            // the dialog holding it back is shown in the chat
+            self.selected_chat = idx;
+            self.toasts
+                .add(Toast::info("Quick ask is waiting for you in its chat"));
+        }
+    }
+
+    /// Stops or keeps the answers of chats being left, and puts off closing
+    /// the app until answers and exports are done
+    fn handle_leaving(&mut self, ctx: &egui::Context) {
+        let shown = self.chats.get(self.selected_chat).map(Chat::id);
+        if shown != self.shown_chat {
+            if self.settings.stop_on_chat_switch {
+                if let Some(left) = self.chats.iter().find(|c| Some(c.id()) == self.shown_chat) {
+                    left.stop_generation();
+                }
+            }
+            self.shown_chat = shown;
+            if let Some(id) = shown {
+                self.chat_switcher.visit(id);
+            }
+        }
+
+        let generating = self.chats.iter().filter(|c| c.flower_active()).count();
+        let on_close = if self.settings.finish_before_close {
+            crate::shutdown::OnClose::Finish
+        } else {
+            crate::shutdown::OnClose::Stop
+        };
+        if self.shutdown.update(ctx, generating, on_close) {
+            for chat in &self.chats {
+                chat.stop_generation();
+            }
+        }
+    }
+
+    fn show_update_banner(&mut self, ctx: &egui::Context) {
+        let Some(release) = &self.available_update else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::TopBottomPanel::top("update_banner").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "✨ {} is available (you have {})",
+                    release.name.as_deref().unwrap_or(&release.tag_name),
+                    env!("CARGO_PKG_VERSION")
+                ));
+                ui.hyperlink_to("Download", &release.html_url);
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui
+                        .add(egui::Button::new("❌").fill(Color32::TRANSPARENT))
+                        .on_hover_text("Don't show again for this version")
+                        .clicked()
+                    {
+                        dismissed = true;
+                    }
+                });
+            });
+            if !release.body.is_empty() {
+                ui.collapsing("Release notes", |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(240.0)
+                        .show(ui, |ui| {
+                            egui_commonmark::CommonMarkViewer::new().show(
+                                ui,
+                                self.markdown_caches.shared(),
+                                &release.body,
+                            );
+                        });
+                });
+            }
+        });
+        if dismissed {
+            self.dismissed_update = self.available_update.take().map(|r| r.tag_name);
+        }
+    }
+
+    /// Handles a request forwarded by another launched instance
+    pub fn handle_ipc_request(&mut self, ctx: &egui::Context, request: crate::ipc::Request) {
+        log::info!("received IPC request: {request:?}");
+        match request {
+            crate::ipc::Request::NewChat => self.open_new_chat(),
+            crate::ipc::Request::AttachFiles(files) => {
+                self.open_new_chat();
+                let chat = self.chats.last_mut().expect("a chat was just added");
+                for file in files {
+                    if is_supported_file(&file, &mut self.toasts) {
+                        chat.files.push(file);
+                    }
+                }
+            }
+            crate::ipc::Request::OpenLink(link) => match crate::deeplink::parse(&link) {
+                Ok(link) => {
+                    self.open_new_chat();
+                    let chat = self.chats.last_mut().expect("a chat was just added");
+                    if let Some(model) = link.model {
+                        self.settings
+                            .model_profiles
+                            .select(&mut chat.model_picker, model);
+                    }
+                    // never sent from a link, any page or process can open one
+                    chat.prefill(&link.prompt);
+                }
+                Err(e) => {
+                    log::error!("failed to open link `{link}`: {e:#}");
+                    self.toasts
+                        .add(Toast::error(format!("Failed to open link: {e}")));
+                }
+            },
+        }
+        // brought back while waiting to close, so stay open
+        self.shutdown.cancel();
+        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+    }
+
+    /// Creates a new chat and switches to it
+    fn open_new_chat(&mut self) {
+        self.add_default_chat();
+        self.selected_chat = self.chats.len() - 1;
+        self.edited_chat = None;
+        self.settings_open = false;
+        self.tool_view = None;
     }
 
     fn show_selected_chat(
@@ -297,38 +1003,15 @@ impl Sessions {
         ctx.input(|i| {
             for file in &i.raw.dropped_files {
                 if let Some(path) = &file.path {
-                    let filename = path.file_name().unwrap_or_default().to_string_lossy();
-                    let Some(ext) = path.extension().and_then(|s| s.to_str()) else {
-                        log::warn!("dropped file `{}` has no extension", path.display());
-                        self.toasts.add(Toast::info(format!(
-                            "Skipping file with no extension: `{filename}`"
-                        )));
-                        continue;
-                    };
-
-                    let all_formats: Vec<_> = [
-                        crate::IMAGE_FORMATS,
-                        crate::VIDEO_FORMATS,
-                        crate::TEXT_FORMATS,
-                        crate::MUSIC_FORMATS,
-                    ]
-                    .concat();
-
-                    if !all_formats.contains(&ext.to_lowercase().as_str()) {
-                        log::warn!(
-                            "dropped file `{}` has unsupported extension `{ext}`",
-                            path.display()
-                        );
-                        self.toasts.add(Toast::info(format!(
-                            "Skipping unsupported file type: `{filename}`"
-                        )));
-                        continue;
+                    if is_supported_file(path, &mut self.toasts) {
+                        chat.files.push(path.clone());
                     }
-                    chat.files.push(path.clone());
                 }
             }
         });
 
+        self.markdown_caches.evict(Some(chat.id()));
+        let commonmark_cache = self.markdown_caches.for_chat(chat.id(), chat.text_len());
         let action = chat.show(
             ctx,
             &self.settings,
@@ -336,7 +1019,11 @@ impl Sessions {
             self.tts.clone(),
             #[cfg(feature = "tts")]
             stopped_talking,
-            &mut self.commonmark_cache,
+            commonmark_cache,
+            &self.watch_folder,
+            &self.network,
+            self.mini_mode,
+            self.focus_mode && !self.mini_mode,
         );
 
         match action {
@@ -348,6 +1035,54 @@ impl Sessions {
                     pick_files(id, &handle).await;
                 });
             }
+            ChatAction::PickFolder { id } => {
+                let handle = self.flower.handle();
+                tokio::spawn(async move {
+                    handle.activate();
+                    pick_folder(id, &handle).await;
+                });
+            }
+            ChatAction::SaveStructuredOutput { message_idx } => {
+                let Some((output, schema)) = self
+                    .chats
+                    .get(self.selected_chat)
+                    .and_then(|chat| chat.structured_output(message_idx))
+                else {
+                    return;
+                };
+                let task = rfd::AsyncFileDialog::new()
+                    .add_filter(
+                        format!("{} file", output.extension().to_uppercase()),
+                        &[output.extension()],
+                    )
+                    .save_file();
+                let handle = self.flower.handle();
+                tokio::spawn(async move {
+                    let toast = crate::structured::save_structured_output(output, schema, task)
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::error!("failed to save structured output: {e}");
+                            Toast::error(e.to_string())
+                        });
+                    handle.activate();
+                    handle.success(BackendResponse::Toast(toast));
+                });
+            }
+            ChatAction::PickGitContext { id, context } => {
+                let handle = self.flower.handle();
+                tokio::spawn(async move {
+                    handle.activate();
+                    pick_git_context(id, context, &handle).await;
+                });
+            }
+            ChatAction::MakeFlashcards { name, text } => {
+                self.flashcards.generate(ctx, &self.settings, name, text);
+                self.tool_view = Some(ToolView::Flashcards);
+                self.settings_open = false;
+            }
+            ChatAction::EnableStreaming => {
+                self.settings.use_streaming = true;
+            }
         }
     }
 
@@ -368,13 +1103,7 @@ impl Sessions {
                 let summary = self
                     .chats
                     .get(self.chat_marked_for_deletion)
-                    .map(|c| {
-                        if c.summary.is_empty() {
-                            "New Chat"
-                        } else {
-                            c.summary.as_str()
-                        }
-                    })
+                    .map(|c| c.display_name())
                     .unwrap_or("New Chat");
                 if modal
                     .caution_button(ui, "Yes")
@@ -388,16 +1117,55 @@ impl Sessions {
         });
     }
 
+    fn show_crash_modal_inner(&mut self, ui: &mut egui::Ui, modal: &Modal) {
+        let Some(path) = self.crash_report.clone() else {
+            return;
+        };
+        modal.title(ui, "Crash Report");
+        modal.frame(ui, |ui| {
+            modal.body_and_icon(
+                ui,
+                format!(
+                    "The app crashed last time. A report was saved to\n{}",
+                    path.display()
+                ),
+                Icon::Error,
+            );
+        });
+        modal.buttons(ui, |ui| {
+            let mut handled = false;
+            if modal.button(ui, "Dismiss").clicked() {
+                handled = true;
+            }
+            if modal.button(ui, "Open report").clicked() {
+                if let Err(e) = open::that(&path) {
+                    log::error!("failed to open crash report: {e}");
+                }
+                handled = true;
+            }
+            if modal
+                .suggested_button(ui, "Report issue")
+                .on_hover_text("Open a pre-filled GitHub issue in the browser")
+                .clicked()
+            {
+                if let Err(e) = open::that(crate::crash::issue_url(&path)) {
+                    log::error!("failed to open browser: {e}");
+                }
+                handled = true;
+            }
+            if handled {
+                crate::crash::mark_seen(&path);
+                self.crash_report = None;
+            }
+        });
+    }
+
     fn show_chat_edit_panel(&mut self, ui: &mut egui::Ui, chat_idx: usize) {
         ui.horizontal(|ui| {
             let Some(chat) = self.chats.get(chat_idx) else {
                 return;
             };
-            if chat.summary.is_empty() {
-                ui.heading("Editing Chat \"New Chat\"");
-            } else {
-                ui.heading(format!("Editing Chat \"{}\"", chat.summary));
-            }
+            ui.heading(format!("Editing Chat \"{}\"", chat.decorated_name()));
 
             ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
                 if ui
@@ -414,6 +1182,126 @@ impl Sessions {
             });
         });
 
+        let clear_modal = Modal::new(ui.ctx(), "clear_chat_modal");
+        clear_modal.show(|ui| {
+            clear_modal.title(ui, "Clear Messages");
+            clear_modal.frame(ui, |ui| {
+                clear_modal.body_and_icon(
+                    ui,
+                    "Remove all messages from this chat? \
+                    The name, model settings and attachments are kept.",
+                    Icon::Warning,
+                );
+                clear_modal.buttons(ui, |ui| {
+                    if clear_modal.button(ui, "No").clicked() {
+                        clear_modal.close();
+                    }
+                    if clear_modal.caution_button(ui, "Clear").clicked() {
+                        clear_modal.close();
+                        match self.chats.get_mut(chat_idx) {
+                            // an answer may have started while the dialog was open
+                            Some(chat) if chat.flower_active() => {
+                                self.toasts.add(Toast::warning(
+                                    "Stop the answer being generated to clear the messages",
+                                ));
+                            }
+                            Some(chat) => {
+                                chat.clear_messages();
+                                self.toasts.add(Toast::info(
+                                    "Messages cleared, you can undo this for now",
+                                ));
+                            }
+                            None => (),
+                        }
+                    }
+                });
+            });
+        });
+
+        if let Some(chat) = self.chats.get_mut(chat_idx) {
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                let mut title = chat.title.clone().unwrap_or_default();
+                if ui
+                    .add(egui::TextEdit::singleline(&mut title).hint_text(&chat.summary))
+                    .on_hover_text("Leave empty to use the automatic summary")
+                    .changed()
+                {
+                    chat.title = (!title.trim().is_empty()).then_some(title);
+                }
+            });
+            let words = chat.word_count();
+            ui.weak(format!(
+                "{words} words, about {} to read",
+                crate::analytics::reading_time(words)
+            ))
+            .on_hover_text("In the prompts and answers, without thoughts");
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !chat.messages.is_empty() && !chat.flower_active(),
+                        egui::Button::new("🗑 Clear messages"),
+                    )
+                    .on_hover_text("Remove the message history, keeping the chat settings")
+                    .clicked()
+                {
+                    clear_modal.open();
+                }
+                if chat.can_undo_clear()
+                    && !chat.flower_active()
+                    && ui
+                        .button("↩ Undo clear")
+                        .on_hover_text("Bring back the removed messages")
+                        .clicked()
+                {
+                    chat.undo_clear();
+                }
+            });
+        }
+
+        ui.collapsing("Appearance", |ui| {
+            let Some(chat) = self.chats.get_mut(chat_idx) else {
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.label("Icon");
+                let mut icon = chat.icon.clone().unwrap_or_default();
+                if ui
+                    .add(egui::TextEdit::singleline(&mut icon).desired_width(32.0))
+                    .changed()
+                {
+                    chat.icon = (!icon.trim().is_empty()).then_some(icon);
+                }
+                for emoji in CHAT_ICONS {
+                    if ui
+                        .add(egui::Button::new(*emoji).fill(Color32::TRANSPARENT))
+                        .clicked()
+                    {
+                        chat.icon = Some(emoji.to_string());
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Accent color");
+                let mut color = chat
+                    .accent_color
+                    .unwrap_or(ui.style().visuals.window_stroke.color);
+                if egui::color_picker::color_edit_button_srgba(
+                    ui,
+                    &mut color,
+                    egui::color_picker::Alpha::Opaque,
+                )
+                .changed()
+                {
+                    chat.accent_color = Some(color);
+                }
+            });
+            if ui.button("Reset").clicked() {
+                chat.icon = None;
+                chat.accent_color = None;
+            }
+        });
+
         egui::CollapsingHeader::new("Model")
             .default_open(true)
             .show(ui, |ui| {
@@ -421,12 +1309,71 @@ impl Sessions {
                     return;
                 };
 
-                chat.model_picker.show(ui, &mut |_| {});
+                chat.model_picker
+                    .show(ui, &mut self.settings.model_profiles, &mut |_| {});
 
                 if self.settings.inherit_chat_picker {
                     self.settings.model_picker.selected = chat.model_picker.selected.clone();
                 }
             });
+        ui.collapsing("Automation", |ui| {
+            let Some(chat) = self.chats.get_mut(chat_idx) else {
+                return;
+            };
+            let id = chat.sync_id().to_owned();
+            let mut hook = self
+                .settings
+                .chat_hooks
+                .get(&id)
+                .cloned()
+                .unwrap_or_default();
+            ui.checkbox(&mut chat.apply_filters, "Apply response filters")
+                .on_hover_text("Rewrite answers with the response filters in the settings");
+            ui.separator();
+            hook.show(ui);
+            if hook == crate::hooks::CompletionHook::default() {
+                self.settings.chat_hooks.remove(&id);
+            } else {
+                self.settings.chat_hooks.insert(id, hook);
+            }
+        });
+        ui.collapsing("Merge", |ui| {
+            ui.label(
+                "Move the messages of another chat into this one, sorted by time. \
+                The other chat is removed.",
+            );
+            ui.horizontal(|ui| {
+                let source = self
+                    .merge_source
+                    .filter(|&i| i != chat_idx && i < self.chats.len());
+                egui::ComboBox::from_id_salt("merge_source")
+                    .selected_text(source.map_or("Select a chat…".to_owned(), |i| {
+                        self.chats[i].decorated_name()
+                    }))
+                    .show_ui(ui, |ui| {
+                        for (i, chat) in self.chats.iter().enumerate() {
+                            if i != chat_idx {
+                                ui.selectable_value(
+                                    &mut self.merge_source,
+                                    Some(i),
+                                    chat.decorated_name(),
+                                );
+                            }
+                        }
+                    });
+                let is_busy = source.is_some_and(|i| self.chats[i].flower_active())
+                    || self.chats[chat_idx].flower_active();
+                if ui
+                    .add_enabled(source.is_some() && !is_busy, egui::Button::new("Merge"))
+                    .on_disabled_hover_text("Wait for both chats to finish generating")
+                    .clicked()
+                {
+                    if let Some(source) = source {
+                        self.merge_chats(source, chat_idx);
+                    }
+                }
+            });
+        });
         ui.collapsing("Export", |ui| {
             ui.label("Export chat history to a file");
             let format = self.chat_export_format;
@@ -449,9 +1396,10 @@ impl Sessions {
                     return;
                 };
                 let messages = chat.messages.clone();
+                let system_prompt = chat.model_picker.system_prompt.clone();
                 let handle = self.flower.handle();
                 tokio::spawn(async move {
-                    let toast = crate::chat::export_messages(messages, format, task)
+                    let toast = crate::chat::export_messages(messages, system_prompt, format, task)
                         .await
                         .map_err(|e| {
                             log::error!("failed to export messages: {e}");
@@ -466,6 +1414,30 @@ impl Sessions {
                     };
                 });
             }
+            if ui
+                .button("🗐 Copy as ShareGPT")
+                .on_hover_text("Copy the chat as ShareGPT JSON, for datasets and other LLM tools")
+                .clicked()
+            {
+                let Some(chat) = self.chats.get(chat_idx) else {
+                    return;
+                };
+                let value = crate::chat::sharegpt(
+                    &chat.messages,
+                    chat.model_picker.system_prompt.as_deref(),
+                );
+                match serde_json::to_string_pretty(&value) {
+                    Ok(json) => {
+                        ui.ctx().copy_text(json);
+                        self.toasts
+                            .add(Toast::success("Copied the chat as ShareGPT JSON"));
+                    }
+                    Err(e) => {
+                        log::error!("failed to serialize the chat as ShareGPT: {e}");
+                        self.toasts.add(Toast::error(e.to_string()));
+                    }
+                }
+            }
         });
     }
 
@@ -475,10 +1447,55 @@ impl Sessions {
             ui.selectable_value(&mut self.tab, SessionTab::Chats, "Chats");
             ui.with_layout(Layout::right_to_left(egui::Align::Max), |ui| {
                 ui.toggle_value(&mut self.settings_open, "⚙")
-                    .on_hover_text("Settings");
+                    .on_hover_text(format!(
+                        "Settings, press ? or {} for shortcuts",
+                        ui.ctx()
+                            .format_shortcut(&crate::cheatsheet::CHEAT_SHEET_SHORTCUT)
+                    ));
+                self.show_tools_menu(ui);
+                if cfg!(debug_assertions) {
+                    self.show_dev_menu(ui);
+                }
+                if ui
+                    .add(egui::Button::new("🗕").fill(Color32::TRANSPARENT))
+                    .on_hover_text("Mini mode: show only the chatbox and the last reply")
+                    .clicked()
+                {
+                    self.set_mini_mode(ui.ctx(), true);
+                }
+                if ui
+                    .add(egui::Button::new("📖").fill(Color32::TRANSPARENT))
+                    .on_hover_text(format!(
+                        "Focus mode: hide everything but the conversation ({})",
+                        ui.ctx().format_shortcut(&FOCUS_MODE_SHORTCUT)
+                    ))
+                    .clicked()
+                {
+                    self.set_focus_mode(ui.ctx(), true);
+                }
+                if ui
+                    .add(egui::Button::new("◀").fill(Color32::TRANSPARENT))
+                    .on_hover_text("Collapse sidebar")
+                    .clicked()
+                {
+                    self.sidebar_collapsed = true;
+                }
             });
         });
 
+        if let Some(profile) = self.profiles.active_name() {
+            if ui
+                .add(
+                    egui::Label::new(egui::RichText::new(format!("🗂 {profile}")).weak())
+                        .sense(egui::Sense::click()),
+                )
+                .on_hover_text("Settings profile in use, click to switch")
+                .clicked()
+            {
+                self.settings_open = true;
+            }
+        }
+
         ui.add_space(8.0);
 
         match self.tab {
@@ -492,6 +1509,174 @@ impl Sessions {
         }
     }
 
+    fn show_dnd_toggle(&mut self, ui: &mut egui::Ui, label: &str) {
+        let dnd = &mut self.settings.do_not_disturb;
+        let active = dnd.active();
+        let icon = if active.is_some() { "🔕" } else { "🔔" };
+        let text = if label.is_empty() {
+            icon.to_owned()
+        } else {
+            format!("{icon} {label}")
+        };
+        ui.toggle_value(&mut dnd.enabled, text)
+            .on_hover_text(match active {
+                Some(reason) => format!("{reason}, toasts and sounds are held back"),
+                None => "Hold back toasts and sounds".to_owned(),
+            });
+    }
+
+    fn show_tools_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("🧰", |ui| {
+            for tool in ToolView::ALL {
+                if ui
+                    .selectable_label(self.tool_view == Some(tool), tool.name())
+                    .clicked()
+                {
+                    self.tool_view = (self.tool_view != Some(tool)).then_some(tool);
+                    self.settings_open = false;
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Tools");
+    }
+
+    /// Debug builds only: tools for working on the UI without the API
+    fn show_dev_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("🛠", |ui| {
+            ui.label("Replay a recorded stream into the current chat");
+            let fixtures = crate::replay::list_fixtures();
+            if fixtures.is_empty() {
+                ui.weak(format!("No fixtures in `{}`", crate::replay::FIXTURES_DIR));
+            }
+            let is_generating = self
+                .chats
+                .get(self.selected_chat)
+                .is_some_and(|chat| chat.flower_active());
+            for fixture in fixtures {
+                let name = fixture
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                if ui
+                    .add_enabled(!is_generating, egui::Button::new(name))
+                    .clicked()
+                {
+                    if let Some(chat) = self.chats.get_mut(self.selected_chat) {
+                        chat.replay_fixture(fixture);
+                    }
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Developer tools");
+    }
+
+    fn set_mini_mode(&mut self, ctx: &egui::Context, enabled: bool) {
+        if self.mini_mode == enabled {
+            return;
+        }
+        self.mini_mode = enabled;
+        if enabled {
+            self.size_before_mini = ctx.input(|i| i.viewport().inner_rect.map(|r| r.size()));
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(vec2(420.0, 360.0)));
+        } else if let Some(size) = self.size_before_mini.take() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+        }
+    }
+
+    fn set_focus_mode(&mut self, ctx: &egui::Context, enabled: bool) {
+        self.focus_mode = enabled;
+        if enabled {
+            // the views that replace the chat can't be reached without the sidebar
+            self.settings_open = false;
+            self.tool_view = None;
+            self.edited_chat = None;
+            self.toasts.add(Toast::info(format!(
+                "Press {} to leave focus mode",
+                ctx.format_shortcut(&FOCUS_MODE_SHORTCUT)
+            )));
+        }
+    }
+
+    fn show_mini_mode_bar(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::top("mini_mode_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(chat) = self.chats.get(self.selected_chat) {
+                    EmojiLabel::new(chat.decorated_name())
+                        .selectable(false)
+                        .truncate()
+                        .show(ui);
+                }
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui
+                        .add(egui::Button::new("🗖").fill(Color32::TRANSPARENT))
+                        .on_hover_text("Exit mini mode")
+                        .clicked()
+                    {
+                        self.set_mini_mode(ui.ctx(), false);
+                    }
+                    ui.toggle_value(&mut self.settings.always_on_top, "📌")
+                        .on_hover_text("Always on top");
+                });
+            });
+        });
+    }
+
+    /// Collapsed sidebar: a narrow column of chat icons
+    fn show_sidebar_rail(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(ui.style().spacing.window_margin.top as _);
+        ui.vertical_centered(|ui| {
+            if self.settings.sidebar_density != SidebarDensity::Rail
+                && ui
+                    .add(egui::Button::new("▶").fill(Color32::TRANSPARENT))
+                    .on_hover_text("Expand sidebar")
+                    .clicked()
+            {
+                self.sidebar_collapsed = false;
+            }
+            ui.toggle_value(&mut self.settings_open, "⚙")
+                .on_hover_text("Settings");
+            self.show_tools_menu(ui);
+            self.show_dnd_toggle(ui, "");
+            if ui.button("➕").on_hover_text("Create a new chat").clicked() {
+                self.open_new_chat();
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, chat) in self.chats.iter().enumerate() {
+                    let icon = match &chat.icon {
+                        Some(icon) if !icon.is_empty() => icon.clone(),
+                        _ => chat
+                            .display_name()
+                            .chars()
+                            .next()
+                            .map(|c| c.to_uppercase().to_string())
+                            .unwrap_or_default(),
+                    };
+                    let mut text = egui::RichText::new(icon);
+                    if let Some(color) = chat.accent_color {
+                        text = text.color(color);
+                    }
+                    if ui
+                        .selectable_label(self.selected_chat == i, text)
+                        .on_hover_text(chat.display_name())
+                        .clicked()
+                    {
+                        self.selected_chat = i;
+                        self.settings_open = false;
+                        self.tool_view = None;
+                        self.edited_chat = None;
+                    }
+                }
+            });
+        });
+    }
+
     #[inline]
     pub fn model_picker(&self) -> &ModelPicker {
         &self.settings.model_picker
@@ -510,9 +1695,21 @@ impl Sessions {
                         chat.files.extend(files);
                     }
                 }
-                Ok(BackendResponse::Settings(settings)) => {
+                Ok(BackendResponse::Settings(mut settings)) => {
+                    settings.restore_secrets(&self.settings);
+                    if settings.keep_local_hooks(&self.settings) {
+                        self.toasts.add(Toast::warning(
+                            "Completion hooks aren't loaded from files, \
+                            set them up again in the chat settings",
+                        ));
+                    }
                     self.settings = *settings;
                 }
+                Ok(BackendResponse::Update(release)) => {
+                    if self.dismissed_update.as_ref() != Some(&release.tag_name) {
+                        self.available_update = Some(*release);
+                    }
+                }
                 Err(flowync::error::Compact::Suppose(e)) => {
                     modal
                         .dialog()
@@ -543,6 +1740,12 @@ impl Sessions {
 
     fn remove_chat(&mut self, idx: usize) {
         self.chats.remove(idx);
+        self.renaming_chat = None;
+        self.quick_ask_chat = match self.quick_ask_chat {
+            Some(chat) if chat == idx => None,
+            Some(chat) if chat > idx => Some(chat - 1),
+            other => other,
+        };
         if self.chats.is_empty() {
             self.add_default_chat();
             self.selected_chat = 0;
@@ -551,6 +1754,30 @@ impl Sessions {
         }
     }
 
+    /// Moves the messages of `source` into `target` and removes `source`
+    fn merge_chats(&mut self, source: usize, target: usize) {
+        let selected = self.selected_chat;
+        let source_chat = std::mem::take(&mut self.chats[source]);
+        let messages = source_chat.messages.len();
+        self.chats[target].merge(source_chat);
+        self.remove_chat(source);
+
+        let target = if source < target { target - 1 } else { target };
+        if self.edited_chat.is_some() {
+            self.edited_chat = Some(target);
+        }
+        self.selected_chat = if selected == source {
+            target
+        } else if selected > source {
+            selected - 1
+        } else {
+            selected
+        };
+        self.merge_source = None;
+        self.toasts
+            .add(Toast::success(format!("Merged {messages} messages")));
+    }
+
     /// Returns whether any chat was removed
     fn show_chat_frame(&mut self, ui: &mut egui::Ui, idx: usize, modal: &Modal) -> bool {
         let Some(chat) = &self.chats.get(idx) else {
@@ -558,20 +1785,57 @@ impl Sessions {
         };
         let mut ignore_click = false;
 
-        let last_message = chat
-            .last_message_contents()
-            .unwrap_or_else(|| "No recent messages".to_string());
+        let last_message = chat.last_message_contents();
+        let is_generating = chat.flower_active();
 
+        let name = chat.decorated_name();
         let summary = chat.summary.clone();
+        // chats without an icon show the avatar of their model, if it has one
+        let avatar_model = Some(chat.model_picker.selected).filter(|model| {
+            !matches!(&chat.icon, Some(icon) if !icon.is_empty())
+                && self.settings.avatars.has_model_avatar(*model)
+        });
 
         ui.horizontal(|ui| {
-            if summary.is_empty() {
-                ui.add(egui::Label::new("New Chat").selectable(false).truncate());
+            if self.renaming_chat == Some(idx) {
+                let resp = ui.add(
+                    egui::TextEdit::singleline(&mut self.rename_buf)
+                        .hint_text(summary)
+                        .desired_width(ui.available_width() - 48.0),
+                );
+                if resp.lost_focus() {
+                    if !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                        let title = self.rename_buf.trim();
+                        self.chats[idx].title = (!title.is_empty()).then(|| title.to_owned());
+                    }
+                    self.renaming_chat = None;
+                } else {
+                    resp.request_focus();
+                }
+                ignore_click = true;
             } else {
-                EmojiLabel::new(summary)
+                if let Some(model) = avatar_model {
+                    self.settings.avatars.show_model(ui, model);
+                }
+                let title_rect = EmojiLabel::new(name)
                     .selectable(false)
                     .truncate()
-                    .show(ui);
+                    .show(ui)
+                    .rect;
+
+                // double-click the title to rename the chat inline
+                if ui.input(|i| {
+                    i.pointer
+                        .button_double_clicked(egui::PointerButton::Primary)
+                        && i.pointer
+                            .interact_pos()
+                            .map(|p| title_rect.contains(p))
+                            .unwrap_or(false)
+                }) {
+                    self.rename_buf = self.chats[idx].title.clone().unwrap_or_default();
+                    self.renaming_chat = Some(idx);
+                    ignore_click = true;
+                }
             }
 
             ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
@@ -583,7 +1847,7 @@ impl Sessions {
                             .fill(Color32::TRANSPARENT)
                             .stroke(Stroke::NONE),
                     )
-                    .on_hover_text("Remove chat")
+                    .on_hover_text("Remove chat, hold Shift to skip the confirmation")
                     .clicked()
                 {
                     if self.chats[idx].messages.is_empty() || ui.input(|i| i.modifiers.shift) {
@@ -617,19 +1881,56 @@ impl Sessions {
             });
         });
 
-        ui.add_enabled(
-            false,
-            egui::Label::new(last_message).selectable(false).truncate(),
-        );
+        if self.settings.sidebar_density == SidebarDensity::Comfortable {
+            ui.horizontal(|ui| {
+                if is_generating {
+                    ui.add(egui::Spinner::new());
+                    ui.add_enabled(false, egui::Label::new("Generating…").selectable(false));
+                    return;
+                }
+                let Some((preview, time)) = last_message else {
+                    ui.add_enabled(
+                        false,
+                        egui::Label::new("No recent messages").selectable(false),
+                    );
+                    return;
+                };
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    let ago = timeago::Formatter::new().convert_chrono(time, chrono::Utc::now());
+                    ui.add_enabled(
+                        false,
+                        egui::Label::new(egui::RichText::new(ago).small()).selectable(false),
+                    )
+                    .on_disabled_hover_text(time.with_timezone(&chrono::Local).to_rfc2822());
+                    ui.with_layout(Layout::left_to_right(egui::Align::Center), |ui| {
+                        ui.add_enabled(
+                            false,
+                            egui::Label::new(preview).selectable(false).truncate(),
+                        );
+                    });
+                });
+            });
+        }
         ignore_click
     }
 
     /// Returns whether the chat should be selected as the current one
     fn show_chat_in_sidepanel(&mut self, ui: &mut egui::Ui, idx: usize, modal: &Modal) -> bool {
         let mut ignore_click = false;
+        let stroke_color = self
+            .chats
+            .get(idx)
+            .and_then(|c| c.accent_color)
+            .unwrap_or(ui.style().visuals.window_stroke.color);
+        let inner_margin = if self.settings.sidebar_density == SidebarDensity::Compact {
+            Margin::symmetric(6, 2)
+        } else {
+            Margin::same(6)
+        };
         let resp = Frame::group(ui.style())
+            .inner_margin(inner_margin)
             .corner_radius(CornerRadius::same(6))
-            .stroke(Stroke::new(2.0, ui.style().visuals.window_stroke.color))
+            .stroke(Stroke::new(2.0, stroke_color))
             .fill(if self.selected_chat == idx {
                 ui.style().visuals.faint_bg_color
             } else {
@@ -670,10 +1971,7 @@ impl Sessions {
                 .on_hover_text("Create a new chat")
                 .clicked()
             {
-                self.add_default_chat();
-                self.selected_chat = self.chats.len() - 1;
-                self.edited_chat = None;
-                self.settings_open = false;
+                self.open_new_chat();
             }
         });
 
@@ -687,6 +1985,7 @@ impl Sessions {
                     if self.show_chat_in_sidepanel(ui, i, modal) {
                         self.selected_chat = i;
                         self.settings_open = false;
+                        self.tool_view = None;
                         self.edited_chat = None;
                     }
                     ui.add_space(2.0);
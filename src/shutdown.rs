@@ -0,0 +1,143 @@
+//! Graceful shutdown: closing the window waits for the answers being
+//! generated and the files being written, so neither is cut off halfway.
+
+use eframe::egui;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Exports and backups being written
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Held while a file is written, closing waits until it's dropped
+pub struct WriteGuard(());
+
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        WRITES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn begin_write() -> WriteGuard {
+    WRITES.fetch_add(1, Ordering::SeqCst);
+    WriteGuard(())
+}
+
+fn pending_writes() -> usize {
+    WRITES.load(Ordering::SeqCst)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Running,
+    /// Minimized until the answers are done
+    FinishingInBackground,
+    /// Answers were stopped, waiting for them and for writes to wrap up
+    FinishingUp,
+    /// Closing with nothing left to wait for, or the user chose not to wait
+    Closing,
+}
+
+/// What to do with the answers being generated when the window is closed
+pub enum OnClose {
+    /// Let them finish while minimized
+    Finish,
+    /// Stop them and wait only until the text so far is kept
+    Stop,
+}
+
+#[derive(Default)]
+pub struct Shutdown {
+    state: State,
+}
+
+impl Shutdown {
+    /// Puts off closing the window while there's work left, call every frame
+    /// with the number of answers being generated. Returns true when those
+    /// answers should be stopped
+    pub fn update(&mut self, ctx: &egui::Context, generating: usize, on_close: OnClose) -> bool {
+        let writing = pending_writes();
+        let busy = generating > 0 || writing > 0;
+        let mut stop = false;
+
+        if ctx.input(|i| i.viewport().close_requested()) && self.state != State::Closing {
+            if !busy {
+                self.state = State::Closing;
+                return false;
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            if self.state == State::Running {
+                log::info!("closing after {generating} answers and {writing} writes are done");
+                self.state = match on_close {
+                    OnClose::Finish if generating > 0 => {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                        State::FinishingInBackground
+                    }
+                    _ => {
+                        stop = true;
+                        State::FinishingUp
+                    }
+                };
+            }
+        }
+
+        match self.state {
+            State::FinishingInBackground | State::FinishingUp if !busy => {
+                self.state = State::Closing;
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            State::FinishingUp => self.show(ctx, generating, writing),
+            _ => {}
+        }
+        if matches!(
+            self.state,
+            State::FinishingInBackground | State::FinishingUp
+        ) {
+            // writes don't repaint when they're done
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        stop
+    }
+
+    /// Stays open after all, when the window is brought back while waiting
+    pub fn cancel(&mut self) {
+        if self.state != State::Closing {
+            self.state = State::Running;
+        }
+    }
+
+    fn show(&mut self, ctx: &egui::Context, generating: usize, writing: usize) {
+        egui::Window::new("Finishing up…")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.vertical(|ui| {
+                        if generating > 0 {
+                            ui.label(format!("Saving {generating} stopped answers"));
+                        }
+                        if writing > 0 {
+                            ui.label(format!("Writing {writing} exports and backups"));
+                        }
+                    });
+                });
+                ui.weak("The app closes once they're done.");
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        log::info!("closing cancelled");
+                        self.state = State::Running;
+                    }
+                    if ui
+                        .button("Close now")
+                        .on_hover_text("Unfinished files may be left incomplete")
+                        .clicked()
+                    {
+                        log::warn!("closing without waiting for {writing} writes");
+                        self.state = State::Closing;
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                });
+            });
+    }
+}
@@ -0,0 +1,92 @@
+//! Short sounds played when a response finishes or fails. Needs the `audio`
+//! feature, without it the cues are silent.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    Completed,
+    Failed,
+}
+
+impl Cue {
+    /// Frequencies of the notes played one after another, in Hz
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    const fn notes(self) -> &'static [f32] {
+        match self {
+            Self::Completed => &[660.0, 880.0],
+            Self::Failed => &[440.0, 330.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SoundCues {
+    pub on_complete: bool,
+    pub on_error: bool,
+    /// From 0 to 1
+    pub volume: f32,
+    /// Stay quiet while the window has focus
+    pub only_in_background: bool,
+}
+
+impl Default for SoundCues {
+    fn default() -> Self {
+        Self {
+            on_complete: false,
+            on_error: false,
+            volume: 0.3,
+            only_in_background: true,
+        }
+    }
+}
+
+impl SoundCues {
+    /// Plays the cue if it's enabled
+    pub fn notify(&self, cue: Cue, window_focused: bool) {
+        let enabled = match cue {
+            Cue::Completed => self.on_complete,
+            Cue::Failed => self.on_error,
+        };
+        if enabled && !(self.only_in_background && window_focused) {
+            play(cue, self.volume);
+        }
+    }
+}
+
+/// Plays the cue on a background thread
+#[cfg(feature = "audio")]
+pub fn play(cue: Cue, volume: f32) {
+    use rodio::{source::SineWave, OutputStream, Sink, Source};
+    use std::time::Duration;
+
+    std::thread::spawn(move || {
+        let (_stream, handle) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("failed to open audio output for a sound cue: {e}");
+                return;
+            }
+        };
+        let sink = match Sink::try_new(&handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                log::error!("failed to play a sound cue: {e}");
+                return;
+            }
+        };
+        for &note in cue.notes() {
+            sink.append(
+                SineWave::new(note)
+                    .take_duration(Duration::from_millis(90))
+                    .fade_in(Duration::from_millis(10))
+                    .amplify(volume.clamp(0.0, 1.0)),
+            );
+        }
+        sink.sleep_until_end();
+    });
+}
+
+#[cfg(not(feature = "audio"))]
+pub fn play(_cue: Cue, _volume: f32) {
+    log::debug!("sound cues need the `audio` feature");
+}
@@ -0,0 +1,218 @@
+//! Saving JSON or CSV replies to files, validated against the response
+//! schema of the chat if JSON mode is on.
+
+use anyhow::Result;
+use serde_json::Value;
+
+pub enum StructuredOutput {
+    Json(Value),
+    /// Rows of fields
+    Csv(Vec<Vec<String>>),
+}
+
+/// Splits CSV text into rows of fields. Quoted fields may contain commas,
+/// quotes and line breaks, blank lines are skipped.
+pub(crate) fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    let mut end_row = |row: &mut Vec<String>, field: &mut String| {
+        row.push(std::mem::take(field));
+        if row.len() > 1 || !row[0].trim().is_empty() {
+            rows.push(std::mem::take(row));
+        } else {
+            row.clear();
+        }
+    };
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => (),
+            '\n' if !in_quotes => end_row(&mut row, &mut field),
+            _ => field.push(ch),
+        }
+    }
+    end_row(&mut row, &mut field);
+    rows
+}
+
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Returns the language and body of the first fenced code block
+fn first_code_block(content: &str) -> Option<(&str, &str)> {
+    let start = content.find("```")?;
+    let rest = &content[start + 3..];
+    let (lang, body) = rest.split_once('\n')?;
+    let end = body.find("```").unwrap_or(body.len());
+    Some((lang.trim(), &body[..end]))
+}
+
+impl StructuredOutput {
+    /// Finds JSON or CSV in a reply, either as the whole reply or in its first
+    /// code block
+    pub fn extract(content: &str) -> Option<Self> {
+        let (lang, body) = first_code_block(content).unwrap_or(("", content));
+        if lang.is_empty() || lang.eq_ignore_ascii_case("json") {
+            if let Ok(value @ (Value::Object(_) | Value::Array(_))) = serde_json::from_str(body) {
+                return Some(Self::Json(value));
+            }
+        }
+
+        let rows = parse_csv(body);
+        let is_table = rows.len() >= 2
+            && rows[0].len() >= 2
+            && rows.iter().all(|row| row.len() == rows[0].len());
+        (lang.eq_ignore_ascii_case("csv") || (lang.is_empty() && is_table))
+            .then_some(Self::Csv(rows))
+    }
+
+    #[inline]
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Json(_) => "json",
+            Self::Csv(_) => "csv",
+        }
+    }
+
+    /// Returns what's wrong with the output, empty if it's valid
+    pub fn validate(&self, schema: Option<&Value>) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self {
+            Self::Json(value) => {
+                if let Some(schema) = schema {
+                    validate_json(value, schema, "$", &mut errors);
+                }
+            }
+            Self::Csv(rows) => {
+                let columns = rows.first().map_or(0, Vec::len);
+                for (i, row) in rows.iter().enumerate().skip(1) {
+                    if row.len() != columns {
+                        errors.push(format!(
+                            "row {} has {} fields, expected {columns}",
+                            i + 1,
+                            row.len()
+                        ));
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    fn to_file_contents(&self) -> Result<String> {
+        Ok(match self {
+            Self::Json(value) => serde_json::to_string_pretty(value)?,
+            Self::Csv(rows) => rows
+                .iter()
+                .map(|row| {
+                    let fields: Vec<_> = row.iter().map(|f| csv_field(f)).collect();
+                    fields.join(",") + "\n"
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Checks `value` against the subset of OpenAPI schemas Gemini supports
+fn validate_json(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(ty) = schema.get("type").and_then(Value::as_str) {
+        let matches = match ty.to_ascii_lowercase().as_str() {
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            "null" => value.is_null(),
+            _ => true,
+        };
+        if !matches {
+            errors.push(format!("{path}: expected {}", ty.to_ascii_lowercase()));
+            return;
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            errors.push(format!("{path}: {value} is not one of the allowed values"));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    errors.push(format!("{path}: missing required property `{key}`"));
+                }
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (key, property) in properties {
+                if let Some(value) = object.get(key) {
+                    validate_json(value, property, &format!("{path}.{key}"), errors);
+                }
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, value) in array.iter().enumerate() {
+            validate_json(value, items, &format!("{path}[{i}]"), errors);
+        }
+    }
+}
+
+pub async fn save_structured_output(
+    output: StructuredOutput,
+    schema: Option<Value>,
+    task: impl std::future::Future<Output = Option<rfd::FileHandle>>,
+) -> Result<egui_notify::Toast> {
+    const MAX_LISTED_ERRORS: usize = 5;
+
+    let Some(file) = task.await else {
+        return Ok(egui_notify::Toast::info("Save cancelled"));
+    };
+    let _writing = crate::shutdown::begin_write();
+
+    let errors = output.validate(schema.as_ref());
+    std::fs::write(file.path(), output.to_file_contents()?)?;
+    log::info!(
+        "saved structured output to {file:?} ({} validation errors)",
+        errors.len()
+    );
+
+    if errors.is_empty() {
+        return Ok(egui_notify::Toast::success(format!(
+            "Saved to {}",
+            file.file_name()
+        )));
+    }
+    let mut listed = errors
+        .iter()
+        .take(MAX_LISTED_ERRORS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if errors.len() > MAX_LISTED_ERRORS {
+        listed.push_str(&format!("\n…and {} more", errors.len() - MAX_LISTED_ERRORS));
+    }
+    let against = if schema.is_some() && matches!(output, StructuredOutput::Json(_)) {
+        "the response schema"
+    } else {
+        "a consistent table"
+    };
+    Ok(egui_notify::Toast::warning(format!(
+        "Saved to {}, but it doesn't match {against}:\n{listed}",
+        file.file_name()
+    )))
+}
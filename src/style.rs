@@ -6,6 +6,12 @@ pub fn set_style(ctx: &egui::Context) {
         s.url_in_tooltip = true;
     });
 
+    ctx.set_zoom_factor(1.09);
+    ctx.set_fonts(font_definitions());
+}
+
+/// The bundled fonts, themes may put their own in front
+pub fn font_definitions() -> egui::FontDefinitions {
     let mut fonts = egui::FontDefinitions::empty();
 
     // install custom fonts
@@ -58,6 +64,5 @@ pub fn set_style(ctx: &egui::Context) {
         ],
     );
 
-    ctx.set_zoom_factor(1.09);
-    ctx.set_fonts(fonts);
+    fonts
 }
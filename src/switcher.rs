@@ -0,0 +1,170 @@
+//! Chat switcher: Ctrl+Tab shows the most recently used chats with the start
+//! of their last messages. Tab moves through them while Ctrl is held, and
+//! releasing Ctrl switches to the highlighted one.
+
+use crate::chat::Chat;
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+/// Ctrl on every platform, like the tab switchers of editors and browsers
+pub const SWITCHER_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Tab);
+
+/// Chats shown at once, the least recently used ones are left out
+const MAX_CARDS: usize = 12;
+const CARD_WIDTH: f32 = 200.0;
+const CARD_HEIGHT: f32 = 110.0;
+/// Messages in a card, and lines of each
+const THUMBNAIL_MESSAGES: usize = 2;
+const THUMBNAIL_LINES: usize = 3;
+
+#[derive(Default)]
+pub struct ChatSwitcher {
+    /// Ids of the chats shown, most recent first
+    recent: Vec<usize>,
+    /// Ids of the chats in the switcher while it's open
+    order: Vec<usize>,
+    /// Index in `order` of the chat released Ctrl switches to
+    highlighted: Option<usize>,
+}
+
+impl ChatSwitcher {
+    /// Moves the chat to the front of the most recently used list
+    pub fn visit(&mut self, id: usize) {
+        self.recent.retain(|&r| r != id);
+        self.recent.insert(0, id);
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.highlighted.is_some()
+    }
+
+    fn close(&mut self) {
+        self.highlighted = None;
+        self.order.clear();
+    }
+
+    /// Opens the switcher and moves through it on Ctrl+Tab, Shift goes
+    /// backwards. Returns the id of the chat to switch to once Ctrl is released
+    pub fn handle_input(&mut self, ctx: &egui::Context, chats: &[Chat]) -> Option<usize> {
+        let (pressed, backwards, ctrl_held, escape) = ctx.input_mut(|i| {
+            let pressed = i.consume_shortcut(&SWITCHER_SHORTCUT);
+            let escape = self.is_open() && i.consume_key(Modifiers::CTRL, Key::Escape);
+            (pressed, i.modifiers.shift, i.modifiers.ctrl, escape)
+        });
+
+        if escape {
+            self.close();
+            return None;
+        }
+        if pressed {
+            match self.highlighted {
+                Some(highlighted) => {
+                    let len = self.order.len();
+                    self.highlighted = Some(if backwards {
+                        (highlighted + len - 1) % len
+                    } else {
+                        (highlighted + 1) % len
+                    });
+                }
+                None => {
+                    let ids: Vec<usize> = chats.iter().map(Chat::id).collect();
+                    self.recent.retain(|id| ids.contains(id));
+                    self.order = self.recent.clone();
+                    // chats not opened since the app started come after the recent ones
+                    self.order
+                        .extend(ids.iter().filter(|id| !self.recent.contains(id)));
+                    self.order.truncate(MAX_CARDS);
+                    if self.order.len() < 2 {
+                        self.order.clear();
+                        return None;
+                    }
+                    self.highlighted = Some(if backwards { self.order.len() - 1 } else { 1 });
+                }
+            }
+            ctx.request_repaint();
+        }
+
+        let highlighted = self.highlighted?;
+        if ctrl_held {
+            return None;
+        }
+        let id = self.order.get(highlighted).copied();
+        self.close();
+        id
+    }
+
+    /// Shows the cards while the switcher is open, returns the id of a
+    /// clicked one
+    pub fn show(&mut self, ctx: &egui::Context, chats: &[Chat]) -> Option<usize> {
+        let highlighted = self.highlighted?;
+        let mut clicked = None;
+        egui::Area::new(egui::Id::new("chat_switcher"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width((CARD_WIDTH + 8.0) * 4.0);
+                    ui.weak("Tab for the next chat, Shift+Tab for the previous, release Ctrl to open it");
+                    ui.add_space(4.0);
+                    ui.horizontal_wrapped(|ui| {
+                        for (i, id) in self.order.iter().enumerate() {
+                            let Some(chat) = chats.iter().find(|c| c.id() == *id) else {
+                                continue;
+                            };
+                            if show_card(ui, chat, i == highlighted).clicked() {
+                                clicked = Some(*id);
+                            }
+                        }
+                    });
+                });
+            });
+        if clicked.is_some() {
+            self.close();
+        }
+        clicked
+    }
+}
+
+fn show_card(ui: &mut egui::Ui, chat: &Chat, highlighted: bool) -> egui::Response {
+    let visuals = ui.visuals();
+    let stroke = if highlighted {
+        visuals.selection.stroke
+    } else {
+        visuals.widgets.noninteractive.bg_stroke
+    };
+    let fill = if highlighted {
+        visuals.selection.bg_fill.gamma_multiply(0.3)
+    } else {
+        visuals.faint_bg_color
+    };
+    egui::Frame::group(ui.style())
+        .stroke(stroke)
+        .fill(fill)
+        .show(ui, |ui| {
+            ui.set_width(CARD_WIDTH);
+            ui.set_height(CARD_HEIGHT);
+            ui.vertical(|ui| {
+                ui.add(
+                    egui::Label::new(egui::RichText::new(chat.decorated_name()).strong())
+                        .truncate()
+                        .selectable(false),
+                );
+                if let Some((_, time)) = chat.last_message_contents() {
+                    let ago = timeago::Formatter::new().convert_chrono(time, chrono::Utc::now());
+                    ui.add(
+                        egui::Label::new(egui::RichText::new(ago).small().weak()).selectable(false),
+                    );
+                }
+                let thumbnail = chat.thumbnail(THUMBNAIL_MESSAGES, THUMBNAIL_LINES);
+                for line in thumbnail.iter().flat_map(|message| message.lines()) {
+                    ui.add(
+                        egui::Label::new(egui::RichText::new(line).small())
+                            .wrap_mode(egui::TextWrapMode::Truncate)
+                            .selectable(false),
+                    );
+                }
+            });
+        })
+        .response
+        .interact(egui::Sense::click())
+}
@@ -0,0 +1,295 @@
+//! User themes: colors, corner radii and fonts read from `theme.ron` in the
+//! data folder, reloaded when the file changes.
+
+use eframe::egui::{self, Color32, CornerRadius, FontFamily, TextStyle};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+const FILE_NAME: &str = "theme.ron";
+/// How often the file is checked for changes
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const CUSTOM_FONT: &str = "theme-font";
+
+/// Result of loading the theme, shown in the settings
+static STATUS: Mutex<Option<Result<String, String>>> = Mutex::new(None);
+
+pub fn theme_path() -> PathBuf {
+    eframe::storage_dir(crate::TITLE)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(FILE_NAME)
+}
+
+/// Colors are `#rrggbb` or `#rrggbbaa`, anything left out keeps the default
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Start from the dark or the light look
+    pub dark: Option<bool>,
+    pub accent: Option<String>,
+    pub text: Option<String>,
+    pub hyperlink: Option<String>,
+    pub window_fill: Option<String>,
+    pub panel_fill: Option<String>,
+    pub faint_bg: Option<String>,
+    pub extreme_bg: Option<String>,
+    pub code_bg: Option<String>,
+    pub warn: Option<String>,
+    pub error: Option<String>,
+    /// Of windows, menus and widgets, in points
+    pub corner_radius: Option<u8>,
+    pub font_size: Option<f32>,
+    pub monospace_size: Option<f32>,
+    /// A .ttf or .otf file used before the bundled font, relative to the data folder
+    pub font: Option<PathBuf>,
+}
+
+fn parse_color(name: &str, hex: &str) -> Result<Color32, String> {
+    let digits = hex.trim().trim_start_matches('#');
+    let byte = |i: usize| {
+        digits
+            .get(i..i + 2)
+            .and_then(|b| u8::from_str_radix(b, 16).ok())
+            .ok_or_else(|| format!("{name}: `{hex}` is not a #rrggbb color"))
+    };
+    match digits.len() {
+        6 => Ok(Color32::from_rgb(byte(0)?, byte(2)?, byte(4)?)),
+        8 => Ok(Color32::from_rgba_unmultiplied(
+            byte(0)?,
+            byte(2)?,
+            byte(4)?,
+            byte(6)?,
+        )),
+        _ => Err(format!("{name}: `{hex}` is not a #rrggbb color")),
+    }
+}
+
+impl Theme {
+    fn visuals(&self) -> Result<egui::Visuals, String> {
+        let mut visuals = if self.dark.unwrap_or(true) {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+        visuals.interact_cursor = Some(egui::CursorIcon::PointingHand);
+
+        let color = |name: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|hex| parse_color(name, hex))
+                .transpose()
+        };
+        if let Some(accent) = color("accent", &self.accent)? {
+            visuals.selection.bg_fill = accent;
+            visuals.selection.stroke.color = accent;
+            visuals.widgets.hovered.bg_stroke.color = accent;
+        }
+        if let Some(text) = color("text", &self.text)? {
+            visuals.override_text_color = Some(text);
+        }
+        if let Some(hyperlink) = color("hyperlink", &self.hyperlink)? {
+            visuals.hyperlink_color = hyperlink;
+        }
+        if let Some(fill) = color("window_fill", &self.window_fill)? {
+            visuals.window_fill = fill;
+        }
+        if let Some(fill) = color("panel_fill", &self.panel_fill)? {
+            visuals.panel_fill = fill;
+        }
+        if let Some(fill) = color("faint_bg", &self.faint_bg)? {
+            visuals.faint_bg_color = fill;
+        }
+        if let Some(fill) = color("extreme_bg", &self.extreme_bg)? {
+            visuals.extreme_bg_color = fill;
+        }
+        if let Some(fill) = color("code_bg", &self.code_bg)? {
+            visuals.code_bg_color = fill;
+        }
+        if let Some(warn) = color("warn", &self.warn)? {
+            visuals.warn_fg_color = warn;
+        }
+        if let Some(error) = color("error", &self.error)? {
+            visuals.error_fg_color = error;
+        }
+        if let Some(radius) = self.corner_radius {
+            let radius = CornerRadius::same(radius);
+            visuals.window_corner_radius = radius;
+            visuals.menu_corner_radius = radius;
+            for widget in [
+                &mut visuals.widgets.noninteractive,
+                &mut visuals.widgets.inactive,
+                &mut visuals.widgets.hovered,
+                &mut visuals.widgets.active,
+                &mut visuals.widgets.open,
+            ] {
+                widget.corner_radius = radius;
+            }
+        }
+        Ok(visuals)
+    }
+
+    fn fonts(&self) -> Result<egui::FontDefinitions, String> {
+        let mut fonts = crate::style::font_definitions();
+        if let Some(font) = &self.font {
+            let path = theme_path().with_file_name(font);
+            let data = std::fs::read(&path)
+                .map_err(|e| format!("failed to read font `{}`: {e}", path.display()))?;
+            fonts.font_data.insert(
+                CUSTOM_FONT.to_owned(),
+                egui::FontData::from_owned(data).into(),
+            );
+            if let Some(family) = fonts.families.get_mut(&FontFamily::Proportional) {
+                family.insert(0, CUSTOM_FONT.to_owned());
+            }
+        }
+        Ok(fonts)
+    }
+
+    /// Applies the theme on top of the default style
+    fn apply(&self, ctx: &egui::Context) -> Result<(), String> {
+        let visuals = self.visuals()?;
+        let fonts = self.fonts()?;
+        let defaults = ctx.theme().default_style().text_styles;
+        ctx.style_mut(|style| {
+            style.visuals = visuals;
+            style.text_styles = defaults;
+            for (text_style, font) in &mut style.text_styles {
+                match text_style {
+                    TextStyle::Monospace => {
+                        if let Some(size) = self.monospace_size {
+                            font.size = size;
+                        }
+                    }
+                    TextStyle::Body | TextStyle::Button => {
+                        if let Some(size) = self.font_size {
+                            font.size = size;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+        ctx.set_fonts(fonts);
+        Ok(())
+    }
+}
+
+fn set_status(status: Option<Result<String, String>>) {
+    if let Ok(mut current) = STATUS.lock() {
+        *current = status;
+    }
+}
+
+/// Watches the theme file and applies it when it changes
+#[derive(Default)]
+pub struct ThemeWatcher {
+    checked: Option<Instant>,
+    /// Modified time of the applied file, `None` when there's no file
+    modified: Option<SystemTime>,
+}
+
+impl ThemeWatcher {
+    /// Call every frame, the file is checked at most once a second
+    pub fn update(&mut self, ctx: &egui::Context) {
+        if self.checked.is_some_and(|c| c.elapsed() < CHECK_INTERVAL) {
+            return;
+        }
+        self.checked = Some(Instant::now());
+
+        let path = theme_path();
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return;
+        }
+        let had_theme = self.modified.is_some();
+        self.modified = modified;
+
+        if modified.is_none() {
+            if had_theme {
+                log::info!("theme file removed, back to the default look");
+                ctx.set_style(ctx.theme().default_style());
+                crate::style::set_style(ctx);
+            }
+            set_status(None);
+            return;
+        }
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| ron::from_str::<Theme>(&text).map_err(|e| e.to_string()))
+            .and_then(|theme| theme.apply(ctx));
+        match result {
+            Ok(()) => {
+                log::info!("applied theme from `{}`", path.display());
+                set_status(Some(Ok(format!("Applied {FILE_NAME}"))));
+            }
+            Err(e) => {
+                // the last good theme stays until the file is fixed
+                log::error!("failed to apply theme `{}`: {e}", path.display());
+                set_status(Some(Err(e)));
+            }
+        }
+    }
+}
+
+/// The theme row in the appearance settings
+pub fn show_settings(ui: &mut egui::Ui) {
+    let path = theme_path();
+    ui.horizontal(|ui| {
+        ui.label("Theme file");
+        if ui
+            .button("📂 Open folder")
+            .on_hover_text(path.display().to_string())
+            .clicked()
+        {
+            if let Some(dir) = path.parent() {
+                if let Err(e) = std::fs::create_dir_all(dir).and_then(|_| open::that(dir)) {
+                    log::error!("failed to open `{}`: {e}", dir.display());
+                }
+            }
+        }
+        if !path.exists()
+            && ui
+                .button("Create")
+                .on_hover_text(format!("Write a {FILE_NAME} to start from"))
+                .clicked()
+        {
+            let example = Theme {
+                dark: Some(true),
+                accent: Some("#8ab4f8".to_owned()),
+                corner_radius: Some(6),
+                font_size: Some(12.5),
+                ..Default::default()
+            };
+            let written = ron::ser::to_string_pretty(&example, ron::ser::PrettyConfig::default())
+                .map_err(|e| e.to_string())
+                .and_then(|text| {
+                    std::fs::create_dir_all(path.parent().unwrap_or(&path))
+                        .and_then(|_| std::fs::write(&path, text))
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(e) = written {
+                log::error!("failed to write `{}`: {e}", path.display());
+            }
+        }
+    });
+    match STATUS.lock().ok().and_then(|status| status.clone()) {
+        Some(Ok(status)) => {
+            ui.weak(status);
+        }
+        Some(Err(e)) => {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!("Theme not applied: {e}"),
+            );
+        }
+        None => {
+            ui.weak(format!(
+                "Put a {FILE_NAME} in the data folder to change colors, corner radii and fonts, \
+                it's reloaded when saved"
+            ));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Thought summaries split into steps: Gemini starts each part of its
+//! reasoning with a bold title line, which is shown as a step list with the
+//! time each step took.
+
+use eframe::egui;
+use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
+use std::time::Duration;
+
+/// A part of a thought summary
+pub struct Step<'a> {
+    /// `None` for text before the first title
+    pub title: Option<&'a str>,
+    pub body: &'a str,
+}
+
+/// The title of a step, if `line` is a markdown heading or entirely bold
+fn step_title(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if let Some(heading) = line.strip_prefix('#') {
+        let title = heading.trim_start_matches('#').trim();
+        return (!title.is_empty()).then_some(title);
+    }
+    let title = line
+        .strip_prefix("**")
+        .and_then(|l| l.strip_suffix("**"))
+        .or_else(|| line.strip_prefix("__").and_then(|l| l.strip_suffix("__")))?
+        .trim();
+    (!title.is_empty() && !title.contains("**")).then_some(title)
+}
+
+/// Splits thought text into steps at the title lines
+pub fn split_steps(text: &str) -> Vec<Step<'_>> {
+    let mut steps = Vec::new();
+    let mut title = None;
+    let mut body_start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if let Some(next) = step_title(line) {
+            let body = text[body_start..offset].trim();
+            if title.is_some() || !body.is_empty() {
+                steps.push(Step { title, body });
+            }
+            title = Some(next);
+            body_start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    let body = text[body_start..].trim();
+    if title.is_some() || !body.is_empty() {
+        steps.push(Step { title, body });
+    }
+    steps
+}
+
+/// Records when new steps of a streamed thought appear, `starts` holds the
+/// time each step started at since the request, `elapsed` is the time now
+pub fn track_steps(text: &str, starts: &mut Vec<Duration>, elapsed: Duration) {
+    let count = split_steps(text).len();
+    while starts.len() < count {
+        starts.push(if starts.is_empty() {
+            Duration::ZERO
+        } else {
+            elapsed
+        });
+    }
+}
+
+/// The time step `idx` took, `end` is when the thought ended, or the time
+/// now while it's being generated
+fn step_duration(starts: &[Duration], idx: usize, end: Duration) -> Option<Duration> {
+    let start = *starts.get(idx)?;
+    let next = starts.get(idx + 1).copied().unwrap_or(end);
+    Some(next.saturating_sub(start))
+}
+
+/// Title of the step being thought about, shown while thinking
+pub fn current_title(text: &str) -> Option<&str> {
+    split_steps(text).last()?.title
+}
+
+/// Shows the thought as a numbered step list, or as plain markdown when it
+/// has no titles
+pub fn show_steps(
+    ui: &mut egui::Ui,
+    cache: &mut CommonMarkCache,
+    id: impl std::hash::Hash,
+    text: &str,
+    starts: &[Duration],
+    end: Duration,
+    is_generating: bool,
+) {
+    let steps = split_steps(text);
+    if steps.iter().all(|step| step.title.is_none()) {
+        CommonMarkViewer::new().show(ui, cache, text);
+        return;
+    }
+    let id = egui::Id::new(id);
+    let last = steps.len() - 1;
+    for (i, step) in steps.iter().enumerate() {
+        let title = step.title.unwrap_or("Thinking");
+        let timing = match step_duration(starts, i, end) {
+            Some(duration) => format!("  {:.1}s", duration.as_secs_f32()),
+            None => String::new(),
+        };
+        egui::CollapsingHeader::new(format!("{}. {title}{timing}", i + 1))
+            .id_salt(id.with(i))
+            .open(if is_generating && i == last {
+                Some(true)
+            } else {
+                None
+            })
+            .default_open(false)
+            .show(ui, |ui| {
+                if step.body.is_empty() {
+                    ui.weak("…");
+                } else {
+                    CommonMarkViewer::new().show(ui, cache, step.body);
+                }
+            });
+    }
+}
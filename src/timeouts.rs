@@ -0,0 +1,51 @@
+//! Timeouts of completion requests, so a stalled connection ends in a clear
+//! error instead of a spinner that never stops.
+
+use std::{future::Future, time::Duration};
+
+/// Start of the error of a request that timed out, to tell it apart from other errors
+pub const TIMED_OUT: &str = "The request timed out";
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RequestTimeouts {
+    /// Time until the response starts, in seconds
+    pub connect_secs: u64,
+    /// Longest wait for the next part of a streamed response, or for the
+    /// whole response without streaming, in seconds
+    pub read_secs: u64,
+}
+
+impl Default for RequestTimeouts {
+    fn default() -> Self {
+        Self {
+            connect_secs: 20,
+            read_secs: 120,
+        }
+    }
+}
+
+impl RequestTimeouts {
+    pub fn connect(&self) -> Duration {
+        Duration::from_secs(self.connect_secs.max(1))
+    }
+
+    pub fn read(&self) -> Duration {
+        Duration::from_secs(self.read_secs.max(1))
+    }
+}
+
+/// Runs `future`, failing with a [`TIMED_OUT`] error after `duration`
+pub async fn timeout<T>(
+    duration: Duration,
+    waiting_for: &str,
+    future: impl Future<Output = T>,
+) -> Result<T, String> {
+    tokio::time::timeout(duration, future).await.map_err(|_| {
+        log::warn!("timed out after {duration:?} waiting for {waiting_for}");
+        format!(
+            "{TIMED_OUT}: no {waiting_for} after {} seconds.",
+            duration.as_secs()
+        )
+    })
+}
@@ -0,0 +1,254 @@
+//! Token diet: shows which messages and attachments take up most of a chat's
+//! context, with one click to exclude them or replace them with a summary.
+
+use crate::widgets::Settings;
+use eframe::egui;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Tokens of an image up to 384 pixels, and of every 768 pixel tile of a larger one
+const IMAGE_TILE_TOKENS: usize = 258;
+const IMAGE_TILE_SIZE: u32 = 768;
+const SMALL_IMAGE_SIZE: u32 = 384;
+const VIDEO_TOKENS_PER_SEC: f64 = 263.0;
+const AUDIO_TOKENS_PER_SEC: f64 = 32.0;
+/// Audio length is guessed from the file size, assuming about 128 kbps
+const AUDIO_BYTES_PER_SEC: f64 = 16_000.0;
+/// Only messages at least this long are worth summarizing
+const MIN_SUMMARIZE_TOKENS: usize = 300;
+/// Rows shown, the rest are too small to matter
+const MAX_ENTRIES: usize = 20;
+
+const SUMMARIZE_PROMPT: &str = "Summarize the following message from a conversation in a few \
+    sentences. Keep the key facts, decisions, names, numbers and code identifiers, and reply \
+    with the summary only.\n\n";
+/// Starts a summary that replaced a message
+pub const SUMMARY_PREFIX: &str = "*(Summary)* ";
+
+/// Estimated tokens of attachments, by path, `None` when unknown
+static FILE_TOKENS: Mutex<BTreeMap<PathBuf, Option<usize>>> = Mutex::new(BTreeMap::new());
+
+fn estimate_file_tokens(path: &Path) -> Option<usize> {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    match mime.type_().as_str() {
+        "image" => {
+            let (width, height) = image::image_dimensions(path).ok()?;
+            if width <= SMALL_IMAGE_SIZE && height <= SMALL_IMAGE_SIZE {
+                return Some(IMAGE_TILE_TOKENS);
+            }
+            let tiles = width.div_ceil(IMAGE_TILE_SIZE) * height.div_ceil(IMAGE_TILE_SIZE);
+            Some(tiles as usize * IMAGE_TILE_TOKENS)
+        }
+        "video" => {
+            let seconds = crate::video::duration(path).ok()?;
+            Some((seconds * VIDEO_TOKENS_PER_SEC) as usize)
+        }
+        "audio" => {
+            let bytes = std::fs::metadata(path).ok()?.len() as f64;
+            Some((bytes / AUDIO_BYTES_PER_SEC * AUDIO_TOKENS_PER_SEC) as usize)
+        }
+        // folders are bundled with a size limit, there's no telling without doing it
+        _ if path.is_dir() => None,
+        _ => {
+            let bytes = std::fs::metadata(path).ok()?.len() as usize;
+            Some(bytes.div_ceil(4))
+        }
+    }
+}
+
+/// Estimated tokens of an attachment, cached
+pub fn file_tokens(path: &Path) -> Option<usize> {
+    if let Some(tokens) = FILE_TOKENS.lock().ok()?.get(path) {
+        return *tokens;
+    }
+    let tokens = estimate_file_tokens(path);
+    if let Ok(mut cache) = FILE_TOKENS.lock() {
+        cache.insert(path.to_path_buf(), tokens);
+    }
+    tokens
+}
+
+/// A message's text or one of its attachments
+pub struct Entry {
+    pub message: usize,
+    /// Index of the attachment in the message, `None` for the text
+    pub file: Option<usize>,
+    pub label: String,
+    pub tokens: usize,
+}
+
+pub enum DietAction {
+    Exclude(usize),
+    RemoveFile { message: usize, file: usize },
+    Summarize(usize),
+}
+
+/// A finished summary of the message at `message`, which had `original_len`
+/// bytes when it was requested
+struct Summary {
+    message: usize,
+    original_len: usize,
+    result: Result<String, String>,
+}
+
+#[derive(Default)]
+struct SummaryState {
+    /// Messages being summarized
+    pending: Vec<usize>,
+    done: Vec<Summary>,
+}
+
+#[derive(Default)]
+pub struct TokenDiet {
+    pub open: bool,
+    summaries: Arc<Mutex<SummaryState>>,
+}
+
+impl TokenDiet {
+    /// Asks a fast model for a summary of the message at `message`
+    pub fn summarize(
+        &self,
+        ctx: &egui::Context,
+        settings: &Settings,
+        message: usize,
+        text: String,
+    ) {
+        let mut model_picker = settings.model_picker.clone();
+        model_picker.selected = crate::routing::FAST_MODEL;
+        model_picker.auto_route = false;
+        model_picker.system_prompt = None;
+        let settings = settings.clone();
+        let summaries = self.summaries.clone();
+        let ctx = ctx.clone();
+        if let Ok(mut summaries) = summaries.lock() {
+            summaries.pending.push(message);
+        }
+        log::info!("summarizing message {message} ({} bytes)", text.len());
+
+        tokio::spawn(async move {
+            let mut summary = String::new();
+            let result = crate::chat::ask_headless(
+                &settings,
+                model_picker,
+                format!("{SUMMARIZE_PROMPT}{text}"),
+                Vec::new(),
+                |text, is_thought| {
+                    if !is_thought {
+                        summary.push_str(text);
+                    }
+                },
+            )
+            .await;
+            if let Ok(mut summaries) = summaries.lock() {
+                summaries.pending.retain(|m| *m != message);
+                summaries.done.push(Summary {
+                    message,
+                    original_len: text.len(),
+                    result: result.map(|_| summary.trim().to_owned()),
+                });
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    /// Finished summaries as the message index, its length when the summary
+    /// was requested, and the summary or the error
+    pub fn take_summaries(&self) -> Vec<(usize, usize, Result<String, String>)> {
+        let Ok(mut summaries) = self.summaries.lock() else {
+            return Vec::new();
+        };
+        summaries
+            .done
+            .drain(..)
+            .map(|s| (s.message, s.original_len, s.result))
+            .collect()
+    }
+
+    /// Shows the window listing the biggest `entries`, `total` is the estimate
+    /// for the whole context
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        entries: &[Entry],
+        total: usize,
+    ) -> Option<DietAction> {
+        let mut action = None;
+        let pending = self
+            .summaries
+            .lock()
+            .map(|s| s.pending.clone())
+            .unwrap_or_default();
+        egui::Window::new("⚖ Token diet")
+            .open(&mut self.open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.label(format!("About {total} tokens are sent with every message."));
+                ui.weak("Estimated, attachments count as Gemini bills them.");
+                ui.separator();
+                if entries.is_empty() {
+                    ui.weak("Nothing in the context yet.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for entry in entries.iter().take(MAX_ENTRIES) {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::Label::new(&entry.label).truncate())
+                                    .on_hover_text(&entry.label);
+                            });
+                            ui.horizontal(|ui| {
+                                let fraction = entry.tokens as f32 / total.max(1) as f32;
+                                ui.add(egui::ProgressBar::new(fraction).desired_width(200.0).text(
+                                    format!("{} tokens, {:.0}%", entry.tokens, fraction * 100.0),
+                                ));
+                                match entry.file {
+                                    Some(file) => {
+                                        if ui
+                                            .small_button("Remove")
+                                            .on_hover_text("Remove the attachment from the message")
+                                            .clicked()
+                                        {
+                                            action = Some(DietAction::RemoveFile {
+                                                message: entry.message,
+                                                file,
+                                            });
+                                        }
+                                    }
+                                    None => {
+                                        if ui
+                                            .small_button("Exclude")
+                                            .on_hover_text(
+                                                "Keep the message in the chat, but don't send it",
+                                            )
+                                            .clicked()
+                                        {
+                                            action = Some(DietAction::Exclude(entry.message));
+                                        }
+                                        if pending.contains(&entry.message) {
+                                            ui.spinner();
+                                        } else if entry.tokens >= MIN_SUMMARIZE_TOKENS
+                                            && ui
+                                                .small_button("Summarize")
+                                                .on_hover_text(
+                                                    "Replace the message with a short summary",
+                                                )
+                                                .clicked()
+                                        {
+                                            action = Some(DietAction::Summarize(entry.message));
+                                        }
+                                    }
+                                }
+                            });
+                        }
+                        if entries.len() > MAX_ENTRIES {
+                            ui.weak(format!("And {} smaller ones", entries.len() - MAX_ENTRIES));
+                        }
+                    });
+            });
+        action
+    }
+}
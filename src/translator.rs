@@ -0,0 +1,331 @@
+//! Translator mode: every prompt is translated between two languages, and the
+//! chat is shown as source/translation pairs.
+
+use eframe::egui;
+
+/// Languages offered in the pickers, with the script they are written in
+const LANGUAGES: &[(&str, Script)] = &[
+    ("English", Script::Latin),
+    ("Spanish", Script::Latin),
+    ("French", Script::Latin),
+    ("German", Script::Latin),
+    ("Italian", Script::Latin),
+    ("Portuguese", Script::Latin),
+    ("Polish", Script::Latin),
+    ("Dutch", Script::Latin),
+    ("Turkish", Script::Latin),
+    ("Russian", Script::Cyrillic),
+    ("Ukrainian", Script::Cyrillic),
+    ("Greek", Script::Greek),
+    ("Arabic", Script::Arabic),
+    ("Hebrew", Script::Hebrew),
+    ("Hindi", Script::Devanagari),
+    ("Chinese", Script::Han),
+    ("Japanese", Script::Kana),
+    ("Korean", Script::Hangul),
+    ("Thai", Script::Thai),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Han,
+    Kana,
+    Hangul,
+    Thai,
+}
+
+impl Script {
+    fn of(c: char) -> Option<Self> {
+        Some(match c {
+            'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Self::Latin,
+            '\u{0400}'..='\u{04FF}' => Self::Cyrillic,
+            '\u{0370}'..='\u{03FF}' => Self::Greek,
+            '\u{0600}'..='\u{06FF}' => Self::Arabic,
+            '\u{0590}'..='\u{05FF}' => Self::Hebrew,
+            '\u{0900}'..='\u{097F}' => Self::Devanagari,
+            '\u{4E00}'..='\u{9FFF}' => Self::Han,
+            '\u{3040}'..='\u{30FF}' => Self::Kana,
+            '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' => Self::Hangul,
+            '\u{0E00}'..='\u{0E7F}' => Self::Thai,
+            _ => return None,
+        })
+    }
+
+    /// The script most letters of `text` are written in
+    fn detect(text: &str) -> Option<Self> {
+        let mut counts: Vec<(Self, usize)> = Vec::new();
+        for script in text.chars().filter_map(Self::of) {
+            match counts.iter_mut().find(|(s, _)| *s == script) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((script, 1)),
+            }
+        }
+        // Japanese mixes kanji with kana, so any kana wins over Han
+        if counts.iter().any(|(s, _)| *s == Script::Kana) {
+            return Some(Script::Kana);
+        }
+        counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(s, _)| s)
+    }
+}
+
+fn script_of(language: &str) -> Option<Script> {
+    LANGUAGES
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map(|(_, script)| *script)
+}
+
+/// Common short words of the languages written in the Latin script
+const COMMON_WORDS: &[(&str, &[&str])] = &[
+    (
+        "English",
+        &[
+            "the", "and", "is", "are", "of", "to", "you", "that", "with", "this", "it", "for",
+        ],
+    ),
+    (
+        "Spanish",
+        &[
+            "el", "la", "los", "las", "que", "y", "es", "por", "para", "con", "una", "del",
+        ],
+    ),
+    (
+        "French",
+        &[
+            "le", "les", "et", "est", "des", "une", "dans", "pour", "avec", "vous", "pas", "du",
+        ],
+    ),
+    (
+        "German",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "ich", "sie", "zu",
+        ],
+    ),
+    (
+        "Italian",
+        &[
+            "il", "che", "di", "è", "non", "per", "sono", "gli", "della", "anche", "come", "questo",
+        ],
+    ),
+    (
+        "Portuguese",
+        &[
+            "o", "os", "não", "é", "uma", "com", "em", "do", "da", "você", "mais", "isso",
+        ],
+    ),
+    (
+        "Polish",
+        &[
+            "i", "w", "nie", "się", "na", "jest", "to", "że", "z", "jak", "czy", "ale",
+        ],
+    ),
+    (
+        "Dutch",
+        &[
+            "het", "een", "en", "van", "niet", "dat", "op", "te", "zijn", "voor", "met", "ik",
+        ],
+    ),
+    (
+        "Turkish",
+        &[
+            "ve", "bir", "bu", "için", "ile", "ne", "çok", "olarak", "gibi", "var", "mi", "daha",
+        ],
+    ),
+];
+
+/// Guesses the language of `text` from its script, and from common words for
+/// languages written in the Latin script. Code blocks are skipped.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut in_code = false;
+    let prose: String = text
+        .lines()
+        .filter(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                return false;
+            }
+            !in_code
+        })
+        .flat_map(|line| [line, "\n"])
+        .collect();
+
+    let script = Script::detect(&prose)?;
+    if script == Script::Cyrillic {
+        let ukrainian = prose.chars().any(|c| "іїєґІЇЄҐ".contains(c));
+        return Some(if ukrainian { "Ukrainian" } else { "Russian" });
+    }
+    if script != Script::Latin {
+        return LANGUAGES
+            .iter()
+            .find(|(_, s)| *s == script)
+            .map(|(name, _)| *name);
+    }
+
+    let words: Vec<String> = prose
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(&'static str, usize)> = COMMON_WORDS
+        .iter()
+        .map(|(language, common)| {
+            let hits = words
+                .iter()
+                .filter(|word| common.contains(&word.as_str()))
+                .count();
+            (*language, hits)
+        })
+        .collect();
+    scores.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+    // a few hits that clearly stand out, short texts are too ambiguous
+    match scores[..] {
+        [(language, best), (_, second), ..] if best >= 3 && best * 2 > second * 3 => Some(language),
+        _ => None,
+    }
+}
+
+/// Appended to the system prompt when answers should be in `language`
+pub fn answer_instruction(language: &str) -> String {
+    format!(
+        "Always answer in {language}, whatever language the user writes in, \
+        unless they explicitly ask for another language."
+    )
+}
+
+/// Picker for the language answers should be in, `None` is shown as `none_label`
+pub fn response_language_picker(
+    ui: &mut egui::Ui,
+    id: &str,
+    language: &mut Option<String>,
+    none_label: &str,
+) -> egui::Response {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(format!("🗣 {}", language.as_deref().unwrap_or(none_label)))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(language, None, none_label);
+            for (name, _) in LANGUAGES {
+                ui.selectable_value(language, Some((*name).to_owned()), *name);
+            }
+        })
+        .response
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Translator {
+    pub source: String,
+    pub target: String,
+}
+
+impl Default for Translator {
+    fn default() -> Self {
+        Self {
+            source: "English".to_owned(),
+            target: "Spanish".to_owned(),
+        }
+    }
+}
+
+impl Translator {
+    /// Guesses the direction from the script of `text`. `None` when both
+    /// languages share a script, then the model decides.
+    pub fn direction(&self, text: &str) -> Option<(&str, &str)> {
+        let source = script_of(&self.source)?;
+        let target = script_of(&self.target)?;
+        if source == target {
+            return None;
+        }
+        match Script::detect(text)? {
+            s if s == source => Some((&self.source, &self.target)),
+            s if s == target => Some((&self.target, &self.source)),
+            _ => None,
+        }
+    }
+
+    /// Instruction appended to the prompt
+    pub fn instruction(&self, text: &str) -> String {
+        const REPLY_FORMAT: &str = "Reply with the translation only, keeping the formatting, \
+            without explanations or quotes.";
+        match self.direction(text) {
+            Some((from, to)) => {
+                format!("Translate the text above from {from} to {to}. {REPLY_FORMAT}")
+            }
+            None => format!(
+                "Translate the text above: if it is in {0}, translate it to {1}, \
+                otherwise translate it to {0}. {REPLY_FORMAT}",
+                self.target, self.source
+            ),
+        }
+    }
+
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.source, &mut self.target);
+    }
+
+    fn language_picker(ui: &mut egui::Ui, id: &str, language: &mut String) {
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(language.as_str())
+            .show_ui(ui, |ui| {
+                for (name, _) in LANGUAGES {
+                    ui.selectable_value(language, (*name).to_owned(), *name);
+                }
+            });
+    }
+
+    pub fn show_languages(&mut self, ui: &mut egui::Ui) {
+        Self::language_picker(ui, "translator_source", &mut self.source);
+        if ui
+            .button("⇄")
+            .on_hover_text("Swap languages, the direction is also detected automatically")
+            .clicked()
+        {
+            self.swap();
+        }
+        Self::language_picker(ui, "translator_target", &mut self.target);
+    }
+}
+
+fn copy_button(ui: &mut egui::Ui, text: &str, hover: &str) {
+    if ui.small_button("🗐").on_hover_text(hover).clicked() {
+        ui.ctx().copy_text(text.to_owned());
+    }
+}
+
+/// Shows one source text and its translation
+pub fn show_pair(ui: &mut egui::Ui, translator: &Translator, source: &str, translation: &str) {
+    let (from, to) = translator
+        .direction(source)
+        .unwrap_or(("Source", "Translation"));
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width());
+        ui.horizontal(|ui| {
+            ui.weak(from);
+            copy_button(ui, source, "Copy the source text");
+        });
+        ui.label(source);
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.weak(to);
+            copy_button(ui, translation, "Copy the translation");
+            copy_button(
+                ui,
+                &format!("{source}\n\n{translation}"),
+                "Copy the source and the translation",
+            );
+        });
+        if translation.is_empty() {
+            ui.add(egui::Spinner::new());
+        } else {
+            ui.label(egui::RichText::new(translation).strong());
+        }
+    });
+}
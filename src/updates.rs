@@ -0,0 +1,53 @@
+//! Checking GitHub releases for a newer version of the app.
+
+use anyhow::{Context, Result};
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/LaVashikk/Gemini-eGUI/releases/latest";
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub html_url: String,
+    /// Release notes in markdown
+    #[serde(default)]
+    pub body: String,
+}
+
+/// Parses `v1.2.3` or `1.2.3-beta` into comparable numeric components
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// Returns the latest release if it is newer than the running version
+pub async fn check_for_update() -> Result<Option<Release>> {
+    log::info!("checking for updates...");
+    let release: Release = reqwest::Client::new()
+        .get(LATEST_RELEASE_URL)
+        .header(reqwest::header::USER_AGENT, crate::TITLE)
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .context("failed to reach GitHub")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("failed to parse the latest release")?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    if parse_version(&release.tag_name) > parse_version(current) {
+        log::info!("update available: {current} -> {}", release.tag_name);
+        Ok(Some(release))
+    } else {
+        log::info!(
+            "no updates available (latest release is {})",
+            release.tag_name
+        );
+        Ok(None)
+    }
+}
@@ -0,0 +1,251 @@
+//! Video attachments: poster frames and trimming before upload. Uses the
+//! `ffmpeg` and `ffprobe` command line tools, which have to be installed.
+
+use anyhow::{anyhow, Context, Result};
+use eframe::egui;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+/// Extracts a representative frame of the video
+pub fn poster_frame(path: &Path) -> Result<image::DynamicImage> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args([
+            "-vf",
+            "thumbnail,scale=512:-2",
+            "-frames:v",
+            "1",
+            "-f",
+            "image2pipe",
+            "-vcodec",
+            "png",
+            "-",
+        ])
+        .output()
+        .context("failed to run `ffmpeg`, is it installed?")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`ffmpeg` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(image::load_from_memory(&output.stdout)?)
+}
+
+/// Length of the video in seconds
+pub fn duration(path: &Path) -> Result<f64> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .context("failed to run `ffprobe`, is it installed?")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`ffprobe` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|e| anyhow!("invalid video duration: {e}"))
+}
+
+/// Cuts out the segment between `start` and `end` seconds without re-encoding
+fn trim(path: &Path, start: f64, end: f64) -> Result<PathBuf> {
+    let dir = crate::file_handler::attachments_dir();
+    std::fs::create_dir_all(&dir)?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let output_path = dir.join(format!("{stem}-{start:.1}s-{end:.1}s.{ext}"));
+
+    let output = Command::new("ffmpeg")
+        .args([
+            "-v",
+            "error",
+            "-y",
+            "-ss",
+            &start.to_string(),
+            "-to",
+            &end.to_string(),
+            "-i",
+        ])
+        .arg(path)
+        .args(["-c", "copy"])
+        .arg(&output_path)
+        .output()
+        .context("failed to run `ffmpeg`, is it installed?")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`ffmpeg` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    log::info!(
+        "trimmed {} to {start:.1}s-{end:.1}s: `{}`",
+        path.display(),
+        output_path.display()
+    );
+    Ok(output_path)
+}
+
+enum TrimStatus {
+    Editing,
+    Trimming,
+    Done(PathBuf),
+    Failed(String),
+}
+
+struct TrimState {
+    source: PathBuf,
+    /// `None` while it's being read
+    duration: Option<Result<f64, String>>,
+    start: f64,
+    end: f64,
+    status: TrimStatus,
+}
+
+/// The trim dialog, only one video can be trimmed at a time
+static TRIM: Mutex<Option<TrimState>> = Mutex::new(None);
+
+/// Opens the trim dialog for a video attachment
+pub fn open_trim_dialog(ctx: &egui::Context, path: &Path) {
+    let Ok(mut trim) = TRIM.lock() else {
+        return;
+    };
+    *trim = Some(TrimState {
+        source: path.to_path_buf(),
+        duration: None,
+        start: 0.0,
+        end: 0.0,
+        status: TrimStatus::Editing,
+    });
+
+    let ctx = ctx.clone();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let duration = duration(&path).map_err(|e| e.to_string());
+        if let Ok(mut trim) = TRIM.lock() {
+            if let Some(state) = trim.as_mut().filter(|s| s.source == path) {
+                state.end = *duration.as_ref().unwrap_or(&0.0);
+                state.duration = Some(duration);
+            }
+        }
+        ctx.request_repaint();
+    });
+}
+
+/// Shows the trim dialog if it's open for `file_path`, and swaps the
+/// attachment for the trimmed video once it's done
+pub fn show_trim_dialog(ctx: &egui::Context, file_path: &mut PathBuf) {
+    let Ok(mut trim) = TRIM.lock() else {
+        return;
+    };
+    let Some(state) = trim.as_mut().filter(|s| s.source == *file_path) else {
+        return;
+    };
+    if let TrimStatus::Done(trimmed) = &state.status {
+        *file_path = trimmed.clone();
+        *trim = None;
+        return;
+    }
+
+    let mut open = true;
+    let mut start_trim = false;
+    egui::Window::new("Trim video")
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.label(
+                state
+                    .source
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+            );
+            match &state.duration {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Reading video…");
+                    });
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                Some(Ok(duration)) => {
+                    ui.add(
+                        egui::Slider::new(&mut state.start, 0.0..=*duration)
+                            .text("Start (s)")
+                            .fixed_decimals(1),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut state.end, 0.0..=*duration)
+                            .text("End (s)")
+                            .fixed_decimals(1),
+                    );
+                    state.end = state.end.max(state.start);
+                    ui.weak(format!(
+                        "Sending {:.1}s of {:.1}s",
+                        state.end - state.start,
+                        duration
+                    ));
+                }
+            }
+
+            match &state.status {
+                TrimStatus::Trimming => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Trimming…");
+                    });
+                }
+                TrimStatus::Failed(e) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                _ => (),
+            }
+
+            let can_trim = matches!(state.duration, Some(Ok(_)))
+                && state.end > state.start
+                && !matches!(state.status, TrimStatus::Trimming);
+            if ui
+                .add_enabled(can_trim, egui::Button::new("✂ Trim"))
+                .on_hover_text("The trimmed copy replaces the attachment, the original is kept")
+                .clicked()
+            {
+                start_trim = true;
+            }
+        });
+
+    if !open {
+        *trim = None;
+        return;
+    }
+    if start_trim {
+        state.status = TrimStatus::Trimming;
+        let (source, start, end) = (state.source.clone(), state.start, state.end);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let status = match trim(&source, start, end) {
+                Ok(trimmed) => TrimStatus::Done(trimmed),
+                Err(e) => TrimStatus::Failed(e.to_string()),
+            };
+            if let Ok(mut trim) = TRIM.lock() {
+                if let Some(state) = trim.as_mut().filter(|s| s.source == source) {
+                    state.status = status;
+                }
+            }
+            ctx.request_repaint();
+        });
+    }
+}
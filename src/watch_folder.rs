@@ -0,0 +1,202 @@
+//! A watched folder, like the screenshots directory: files that appear in it
+//! show up in a tray above the chatbox, one click away from being attached.
+
+use eframe::egui::{self, vec2};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Files modified more recently may still be being written
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+/// Only the newest files are kept in the tray
+const MAX_TRAY_FILES: usize = 5;
+const THUMBNAIL_SIZE: f32 = 40.0;
+
+/// Folder picked in the settings, applied on the next frame
+static PICKED_FOLDER: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn pick_folder() {
+    let task = rfd::AsyncFileDialog::new()
+        .set_title("Pick a folder to watch for new files")
+        .pick_folder();
+    tokio::spawn(async move {
+        let Some(folder) = task.await else {
+            return;
+        };
+        if let Ok(mut picked) = PICKED_FOLDER.lock() {
+            *picked = Some(folder.path().to_path_buf());
+        }
+    });
+}
+
+pub fn take_picked_folder() -> Option<PathBuf> {
+    PICKED_FOLDER.lock().ok()?.take()
+}
+
+fn is_attachable(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    let ext = ext.to_lowercase();
+    [
+        crate::IMAGE_FORMATS,
+        crate::VIDEO_FORMATS,
+        crate::TEXT_FORMATS,
+        crate::MUSIC_FORMATS,
+    ]
+    .iter()
+    .any(|formats| formats.contains(&ext.as_str()))
+}
+
+/// Attachable files in the folder, with their modification times
+fn scan(folder: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let path = entry.path();
+            (metadata.is_file() && is_attachable(&path))
+                .then_some((path, metadata.modified().ok()?))
+        })
+        .collect()
+}
+
+/// Polls `folder` until `stop` is set, adding files that appear to `tray`.
+/// Files already there when it starts are ignored.
+fn spawn_watcher(
+    ctx: egui::Context,
+    folder: PathBuf,
+    tray: Arc<Mutex<Vec<PathBuf>>>,
+    stop: Arc<AtomicBool>,
+) {
+    std::thread::spawn(move || {
+        let mut seen: HashSet<PathBuf> = scan(&folder).into_iter().map(|(path, _)| path).collect();
+        log::info!("watching `{}` for new files", folder.display());
+        while !stop.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL_INTERVAL);
+            let mut new_files: Vec<(PathBuf, SystemTime)> = scan(&folder)
+                .into_iter()
+                .filter(|(path, modified)| {
+                    !seen.contains(path) && modified.elapsed().is_ok_and(|age| age >= SETTLE_TIME)
+                })
+                .collect();
+            if new_files.is_empty() {
+                continue;
+            }
+            new_files.sort_by_key(|(_, modified)| *modified);
+            let Ok(mut tray) = tray.lock() else {
+                return;
+            };
+            for (path, _) in new_files {
+                log::debug!("new file in the watched folder: `{}`", path.display());
+                seen.insert(path.clone());
+                tray.push(path);
+            }
+            let excess = tray.len().saturating_sub(MAX_TRAY_FILES);
+            tray.drain(..excess);
+            ctx.request_repaint();
+        }
+        log::info!("stopped watching `{}`", folder.display());
+    });
+}
+
+#[derive(Default)]
+pub struct WatchFolder {
+    /// Folder the running watcher polls
+    watching: Option<PathBuf>,
+    /// New files, oldest first
+    tray: Arc<Mutex<Vec<PathBuf>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl WatchFolder {
+    /// Starts, restarts or stops the watcher to match the settings, call every frame
+    pub fn update(&mut self, ctx: &egui::Context, folder: Option<&Path>) {
+        let folder = folder.filter(|folder| folder.is_dir());
+        if self.watching.as_deref() == folder {
+            return;
+        }
+        self.stop.store(true, Ordering::Relaxed);
+        self.stop = Arc::default();
+        self.tray = Arc::default();
+        self.watching = folder.map(Path::to_path_buf);
+        if let Some(folder) = &self.watching {
+            spawn_watcher(
+                ctx.clone(),
+                folder.clone(),
+                self.tray.clone(),
+                self.stop.clone(),
+            );
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tray.lock().is_ok_and(|tray| tray.is_empty())
+    }
+
+    /// Shows the new files, returns the ones clicked to be attached
+    pub fn show_tray(&self, ui: &mut egui::Ui) -> Vec<PathBuf> {
+        let Ok(mut tray) = self.tray.lock() else {
+            return Vec::new();
+        };
+        let mut attach = Vec::new();
+        ui.horizontal(|ui| {
+            let folder_name = self
+                .watching
+                .as_deref()
+                .and_then(Path::file_name)
+                .unwrap_or_default()
+                .to_string_lossy();
+            ui.weak(format!("📥 New in {folder_name}:"));
+            for path in tray.iter().rev() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let is_image = path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| crate::IMAGE_FORMATS.contains(&ext.to_lowercase().as_str()));
+                let thumbnail = is_image
+                    .then(|| crate::file_handler::thumbnail(ui.ctx(), path))
+                    .flatten();
+                let clicked = match thumbnail {
+                    Some(Ok(texture)) => ui.add(
+                        egui::ImageButton::new(
+                            egui::Image::new(&texture)
+                                .fit_to_exact_size(vec2(THUMBNAIL_SIZE, THUMBNAIL_SIZE)),
+                        )
+                        .frame(false),
+                    ),
+                    _ => ui.small_button(name.as_ref()),
+                }
+                .on_hover_text(format!("Attach {name}"))
+                .clicked();
+                if clicked {
+                    attach.push(path.clone());
+                }
+            }
+            if let Some(latest) = tray.last() {
+                if ui.button("Attach latest").clicked() {
+                    attach.push(latest.clone());
+                }
+            }
+            if ui
+                .small_button("✖")
+                .on_hover_text("Dismiss these files")
+                .clicked()
+            {
+                tray.clear();
+            }
+        });
+        tray.retain(|path| !attach.contains(path));
+        attach
+    }
+}
@@ -1,4 +1,7 @@
-use std::fmt;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
 
 use eframe::{
     egui::{self, collapsing_header::CollapsingState, CornerRadius, Frame, Layout, Stroke, Vec2},
@@ -14,10 +17,132 @@ pub struct ModelPicker {
     pub selected: GeminiModel,
     settings: ModelSettings,
     pub system_prompt: Option<String>,
+    /// Name of the preset the system prompt comes from, cleared when it's edited
+    #[serde(default)]
+    pub system_preset: Option<String>,
+    /// Pick the model for each prompt instead of using `selected`
+    #[serde(default)]
+    pub auto_route: bool,
+    /// Let the model run Python code with Gemini's built-in code execution tool
+    #[serde(default)]
+    pub code_execution: bool,
+    /// Let the model fetch the pages linked in the prompt with the URL context tool
+    #[serde(default)]
+    pub url_context: bool,
 }
 
 pub enum RequestInfoType {
     LoadSettings,
+    ExportUsageReport(crate::analytics::UsageReportFormat),
+    /// Copy the preset to every chat using it
+    PropagateSystemPreset(SystemPreset),
+    /// Make the app the handler of `egeminui://` links
+    RegisterLinkHandler,
+}
+
+/// How long the answer to a prompt should be
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ResponseStyle {
+    Concise,
+    #[default]
+    Normal,
+    Detailed,
+}
+
+impl fmt::Display for ResponseStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl ResponseStyle {
+    pub const ALL: [Self; 3] = [Self::Concise, Self::Normal, Self::Detailed];
+
+    /// `maxOutputTokens` for the request, thinking models count their thoughts too
+    #[inline]
+    pub const fn max_output_tokens(self) -> Option<i32> {
+        match self {
+            Self::Concise => Some(2048),
+            Self::Normal => None,
+            Self::Detailed => Some(16384),
+        }
+    }
+
+    /// Instruction appended to the prompt
+    #[inline]
+    pub const fn instruction(self) -> Option<&'static str> {
+        match self {
+            Self::Concise => Some("Answer concisely, in a few sentences at most."),
+            Self::Normal => None,
+            Self::Detailed => {
+                Some("Give a thorough, detailed answer with explanations and examples.")
+            }
+        }
+    }
+}
+
+/// A system instruction from the library, shared between chats
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SystemPreset {
+    pub name: String,
+    pub text: String,
+}
+
+/// A card on the empty chat screen that sends a prompt
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SuggestionCard {
+    pub title: String,
+    pub subtitle: String,
+    /// Sent when the card is clicked, the title and subtitle if empty
+    pub prompt: String,
+    /// Model the chat switches to before sending
+    pub model: Option<GeminiModel>,
+}
+
+impl SuggestionCard {
+    fn new(title: &str, subtitle: &str) -> Self {
+        Self {
+            title: title.to_owned(),
+            subtitle: subtitle.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("Tell me a fun fact", "about the Roman empire"),
+            Self::new("Show me a code snippet", "of a web server in Rust"),
+            Self::new("Tell me a joke", "about crabs"),
+            Self::new("Give me ideas", "for a birthday present"),
+        ]
+    }
+
+    pub fn prompt(&self) -> String {
+        if self.prompt.trim().is_empty() {
+            format!("{} {}", self.title, self.subtitle)
+                .trim()
+                .to_owned()
+        } else {
+            self.prompt.clone()
+        }
+    }
+}
+
+const PRESET_VARIABLES_HELP: &str =
+    "Variables: {{date}}, {{time}}, {{weekday}}, {{os}} and {{model}} are filled in when sending";
+
+/// Fills in the variables of a system instruction
+pub fn expand_variables(text: &str, model: GeminiModel) -> String {
+    if !text.contains("{{") {
+        return text.to_owned();
+    }
+    let now = chrono::Local::now();
+    text.replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{weekday}}", &now.format("%A").to_string())
+        .replace("{{os}}", std::env::consts::OS)
+        .replace("{{model}}", &model.to_string())
 }
 
 /// Represents the available Gemini models.
@@ -102,6 +227,16 @@ impl fmt::Display for GeminiModel {
     }
 }
 
+impl GeminiModel {
+    /// Finds a model by its exact name, or else the first one containing `query`
+    pub fn from_query(query: &str) -> Option<Self> {
+        let query = query.to_lowercase();
+        enum_iterator::all::<Self>()
+            .find(|m| m.to_string() == query)
+            .or_else(|| enum_iterator::all::<Self>().find(|m| m.to_string().contains(&query)))
+    }
+}
+
 fn collapsing_frame<R>(
     ui: &mut egui::Ui,
     heading: &str,
@@ -150,22 +285,31 @@ const TEMPLATE_HINT_TEXT: &str =
 
 impl ModelPicker {
     pub fn create_client(&self, api_key: &str, proxy_path: Option<String>) -> Gemini {
-        let sys_prompt = if let Some(sys_prompt) = &self.system_prompt {
-            if !sys_prompt.is_empty() {
-                Some(SystemInstruction::from_str(sys_prompt.clone()))
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        self.create_client_with_timeout(api_key, proxy_path, std::time::Duration::from_secs(60))
+    }
+
+    /// The system prompt with its variables filled in, `None` if it's empty
+    pub fn system_instruction(&self) -> Option<String> {
+        self.system_prompt
+            .as_deref()
+            .filter(|prompt| !prompt.is_empty())
+            .map(|prompt| expand_variables(prompt, self.selected))
+    }
+
+    pub fn create_client_with_timeout(
+        &self,
+        api_key: &str,
+        proxy_path: Option<String>,
+        timeout: std::time::Duration,
+    ) -> Gemini {
+        let sys_prompt = self.system_instruction().map(SystemInstruction::from_str);
 
         let mut client = Gemini::new_with_timeout(
             api_key.to_string(),
             self.selected.to_string(),
             sys_prompt,
             proxy_path,
-            std::time::Duration::from_secs(60),
+            timeout,
         );
 
         let val = client.set_generation_config();
@@ -174,25 +318,84 @@ impl ModelPicker {
         client
     }
 
-    pub fn show<R>(&mut self, ui: &mut egui::Ui, _request_info: &mut R)
-    where
+    pub fn show<R>(
+        &mut self,
+        ui: &mut egui::Ui,
+        profiles: &mut ModelProfiles,
+        _request_info: &mut R,
+    ) where
         R: FnMut(RequestInfoType),
     {
-        egui::ComboBox::from_id_salt("model_selector_combobox")
-            .selected_text(self.selected.to_string())
-            .show_ui(ui, |ui| {
-                for model in enum_iterator::all::<GeminiModel>() {
-                    if ui
-                        .selectable_label(self.selected == model, model.to_string())
-                        .clicked()
-                    {
-                        self.selected = model;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("model_selector_combobox")
+                .selected_text(self.selected.to_string())
+                .show_ui(ui, |ui| {
+                    for model in enum_iterator::all::<GeminiModel>() {
+                        ui.horizontal(|ui| {
+                            let mut resp =
+                                ui.selectable_label(self.selected == model, model.to_string());
+                            if let Some(profile) = profiles.get(model) {
+                                resp =
+                                    resp.on_hover_text(format!("Defaults: {}", profile.summary()));
+                            }
+                            if resp.clicked() && self.selected != model {
+                                profiles.select(self, model);
+                            }
+                            crate::capabilities::show_badges(ui, model);
+                        });
                     }
-                }
-            });
+                });
+            crate::capabilities::show_badges(ui, self.selected);
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.auto_route));
+            help(
+                ui,
+                "Use a fast model for short, simple prompts and a thinking model for long prompts, code and attachments",
+                |ui| {
+                    ui.label("Auto-pick model");
+                },
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.code_execution));
+            help(
+                ui,
+                "Let the model write and run Python code on Google's servers, the code and its output are shown in the reply",
+                |ui| {
+                    ui.label("Code execution");
+                },
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.url_context));
+            help(
+                ui,
+                "Let the model read the web pages linked in your messages, the pages it read are listed under the reply",
+                |ui| {
+                    ui.label("Read linked pages");
+                },
+            );
+        });
 
         ui.collapsing("Inference Settings", |ui| {
             self.settings.show(ui);
+            ui.add_space(2.0);
+            ui.horizontal_wrapped(|ui| {
+                if ui
+                    .button(format!("Save as defaults for {}", self.selected))
+                    .on_hover_text("Apply these settings whenever this model is picked")
+                    .clicked()
+                {
+                    profiles.save(self.selected, &self.settings);
+                }
+                if profiles.get(self.selected).is_some() && ui.button("Forget defaults").clicked() {
+                    profiles.remove(self.selected);
+                }
+            });
         });
 
         collapsing_frame(ui, "System Prompt", |ui| {
@@ -211,25 +414,133 @@ impl ModelPicker {
                 self.system_prompt = Some(String::new());
             }
 
+            if let Some(preset) = &self.system_preset {
+                ui.weak(format!("From preset \"{preset}\""));
+            }
             ui.add_enabled_ui(self.system_prompt.is_some(), |ui| {
                 if let Some(ref mut template) = self.system_prompt {
-                    ui.add(
-                        egui::TextEdit::multiline(template)
-                            .hint_text(TEMPLATE_HINT_TEXT)
-                            .desired_rows(3),
-                    );
+                    if ui
+                        .add(
+                            egui::TextEdit::multiline(template)
+                                .hint_text(TEMPLATE_HINT_TEXT)
+                                .desired_rows(3),
+                        )
+                        .on_hover_text(PRESET_VARIABLES_HELP)
+                        .changed()
+                    {
+                        self.system_preset = None;
+                    }
                 }
             });
+            if self.system_prompt.is_none() {
+                self.system_preset = None;
+            }
         });
     }
 
+    pub fn apply_preset(&mut self, preset: &SystemPreset) {
+        self.system_prompt = Some(preset.text.clone());
+        self.system_preset = Some(preset.name.clone());
+    }
+
+    /// Quick preset switcher for the chat header
+    pub fn show_preset_picker(&mut self, ui: &mut egui::Ui, presets: &[SystemPreset]) {
+        let selected = match (&self.system_preset, &self.system_prompt) {
+            (Some(preset), _) => preset.as_str(),
+            (None, Some(prompt)) if !prompt.is_empty() => "Custom",
+            _ => "None",
+        };
+        egui::ComboBox::from_id_salt("system_preset_picker")
+            .selected_text(format!("System: {selected}"))
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(self.system_prompt.is_none(), "None")
+                    .clicked()
+                {
+                    self.system_prompt = None;
+                    self.system_preset = None;
+                }
+                for preset in presets {
+                    let is_selected = self.system_preset.as_ref() == Some(&preset.name);
+                    if ui
+                        .selectable_label(is_selected, &preset.name)
+                        .on_hover_text(&preset.text)
+                        .clicked()
+                    {
+                        self.apply_preset(preset);
+                    }
+                }
+                if presets.is_empty() {
+                    ui.weak("Add presets in the settings");
+                }
+            });
+    }
+
+    /// Caps the response length, overriding the inference settings
+    #[inline]
+    pub fn set_max_output_tokens(&mut self, tokens: i32) {
+        self.settings.num_predict = Some(tokens);
+    }
+
+    /// The response schema, if JSON mode is on and it's valid
+    #[inline]
+    pub fn response_schema(&self) -> Option<serde_json::Value> {
+        self.settings.parsed_schema()
+    }
+
     #[inline]
     pub fn get_generation_config(&self) -> serde_json::Value {
         self.settings.clone().into()
     }
 }
 
+/// Inference settings saved per model, applied when the model is picked
+#[derive(Default, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ModelProfiles(BTreeMap<String, ModelSettings>);
+
+impl ModelProfiles {
+    fn get(&self, model: GeminiModel) -> Option<&ModelSettings> {
+        self.0.get(&model.to_string())
+    }
+
+    fn save(&mut self, model: GeminiModel, settings: &ModelSettings) {
+        log::info!("saved default inference settings for {model}");
+        self.0.insert(model.to_string(), settings.clone());
+    }
+
+    fn remove(&mut self, model: GeminiModel) {
+        self.0.remove(&model.to_string());
+    }
+
+    /// Switches `picker` to `model`, with its saved settings if it has any
+    pub fn select(&self, picker: &mut ModelPicker, model: GeminiModel) {
+        picker.selected = model;
+        if let Some(profile) = self.get(model) {
+            picker.settings.apply_profile(profile);
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if self.0.is_empty() {
+            return;
+        }
+        ui.label("Saved model defaults");
+        egui::Grid::new("model_profiles_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                self.0.retain(|model, profile| {
+                    ui.label(model.as_str()).on_hover_text(profile.summary());
+                    let keep = !ui.button("Forget").clicked();
+                    ui.end_row();
+                    keep
+                });
+            });
+    }
+}
+
 #[derive(Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
 struct ModelSettings {
     pub temperature: Option<f32>,
     pub top_p: Option<f32>,
@@ -238,6 +549,10 @@ struct ModelSettings {
     pub stop: Option<Vec<String>>,
     pub include_thoughts: bool,
     pub thinking_budget: Option<i32>,
+    /// Ask for a JSON response (`responseMimeType: application/json`)
+    pub json_mode: bool,
+    /// `responseSchema` for JSON mode, as JSON text
+    pub response_schema: Option<String>,
 }
 
 impl From<ModelSettings> for serde_json::Value {
@@ -272,11 +587,60 @@ impl From<ModelSettings> for serde_json::Value {
             }
             map.insert("thinkingConfig".to_string(), json!(thinking_config));
         }
+        if value.json_mode {
+            map.insert("responseMimeType".to_string(), json!("application/json"));
+            if let Some(schema) = value.parsed_schema() {
+                map.insert("responseSchema".to_string(), schema);
+            }
+        }
         serde_json::Value::Object(map)
     }
 }
 
 impl ModelSettings {
+    /// Takes the sampling and thinking settings of `profile`, JSON mode is
+    /// left alone since it's about the task, not the model
+    fn apply_profile(&mut self, profile: &Self) {
+        self.temperature = profile.temperature;
+        self.top_p = profile.top_p;
+        self.top_k = profile.top_k;
+        self.num_predict = profile.num_predict;
+        self.stop = profile.stop.clone();
+        self.include_thoughts = profile.include_thoughts;
+        self.thinking_budget = profile.thinking_budget;
+    }
+
+    fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(temperature) = self.temperature {
+            parts.push(format!("temperature {temperature:.2}"));
+        }
+        if let Some(top_p) = self.top_p {
+            parts.push(format!("top-p {top_p:.2}"));
+        }
+        if let Some(top_k) = self.top_k {
+            parts.push(format!("top-k {top_k}"));
+        }
+        if let Some(max) = self.num_predict {
+            parts.push(format!("max {max} tokens"));
+        }
+        if self.include_thoughts {
+            parts.push("thoughts".to_owned());
+        }
+        if parts.is_empty() {
+            "model defaults".to_owned()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    fn parsed_schema(&self) -> Option<serde_json::Value> {
+        self.response_schema
+            .as_deref()
+            .filter(|_| self.json_mode)
+            .and_then(|schema| serde_json::from_str(schema).ok())
+    }
+
     fn edit_numeric<N: Numeric>(
         ui: &mut egui::Ui,
         val: &mut Option<N>,
@@ -365,6 +729,38 @@ impl ModelSettings {
         Self::edit_numeric(ui, &mut self.top_k, 40, 1.0, 1..=100, "Top-K", "Changes how the model selects tokens for output. A lower value limits the sampling to a smaller set of the most likely tokens.");
         Self::edit_numeric(ui, &mut self.top_p, 0.95, 0.01, 0.0..=1.0, "Top-P", "Changes how the model selects tokens for output, sampling from a cumulative probability distribution. Use either Top-K or Top-P, not both.");
 
+        collapsing_frame(ui, "JSON Output", |ui| {
+            ui.label("Make the model answer with JSON, optionally following a schema.");
+            ui.checkbox(&mut self.json_mode, "JSON mode");
+
+            ui.add_enabled_ui(self.json_mode, |ui| {
+                let mut schema_enabled = self.response_schema.is_some();
+                ui.horizontal(|ui| {
+                    ui.add(toggle(&mut schema_enabled));
+                    ui.label("Response schema");
+                });
+                if !schema_enabled {
+                    self.response_schema = None;
+                } else if self.response_schema.is_none() {
+                    self.response_schema = Some(String::new());
+                }
+
+                if let Some(ref mut schema) = self.response_schema {
+                    ui.add(
+                        egui::TextEdit::multiline(schema)
+                            .code_editor()
+                            .hint_text(
+                                r#"{"type": "OBJECT", "properties": {"name": {"type": "STRING"}}}"#,
+                            )
+                            .desired_rows(4),
+                    );
+                    if let Err(e) = serde_json::from_str::<serde_json::Value>(schema) {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("Invalid JSON: {e}"));
+                    }
+                }
+            });
+        });
+
         collapsing_frame(ui, "Stop Sequence", |ui| {
             ui.label("A set of up to 5 character sequences that will stop output generation.");
             let mut enabled = self.stop.is_some();
@@ -522,8 +918,35 @@ fn help(ui: &mut egui::Ui, text: &str, add_contents: impl FnOnce(&mut egui::Ui))
     });
 }
 
+/// How much space each chat takes in the sidebar.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SidebarDensity {
+    #[default]
+    Comfortable,
+    Compact,
+    Rail,
+}
+
+impl fmt::Display for SidebarDensity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Comfortable => "Comfortable",
+            Self::Compact => "Compact",
+            Self::Rail => "Icon rail",
+        })
+    }
+}
+
+impl SidebarDensity {
+    pub const ALL: [Self; 3] = [Self::Comfortable, Self::Compact, Self::Rail];
+}
+
+/// Stands in for secrets left out of exported settings
+const REDACTED: &str = "<redacted>";
+
 // This is the main settings struct.
 #[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct Settings {
     pub api_key: String,
     pub model_picker: ModelPicker,
@@ -531,6 +954,68 @@ pub struct Settings {
     pub use_streaming: bool,
     pub include_thoughts_in_history: bool,
     pub proxy_path: Option<String>,
+    /// Base URL and headers for gateways and API-compatible mirrors
+    pub endpoint: crate::endpoint::Endpoint,
+    pub sidebar_density: SidebarDensity,
+    pub always_on_top: bool,
+    pub autostart: bool,
+    pub start_minimized: bool,
+    pub quick_ask_enabled: bool,
+    /// Global shortcut that sends the clipboard to quick ask, e.g. `ctrl+alt+KeyG`
+    pub quick_ask_hotkey: String,
+    pub quick_ask_instruction: String,
+    pub check_for_updates: bool,
+    pub usage_report_format: crate::analytics::UsageReportFormat,
+    pub system_presets: Vec<SystemPreset>,
+    /// Shown on the empty chat screen
+    pub suggestion_cards: Vec<SuggestionCard>,
+    pub http_tools: Vec<crate::http_tools::HttpTool>,
+    pub ocr: crate::ocr::OcrSettings,
+    pub strip_image_metadata: bool,
+    pub compression: crate::file_handler::Compression,
+    pub sound_cues: crate::sound::SoundCues,
+    /// Rewrite answers once they're done
+    pub response_filters: Vec<crate::filters::ResponseFilter>,
+    pub do_not_disturb: crate::dnd::DoNotDisturb,
+    /// The conversation is centered in a column this wide, `None` to fill the window
+    pub max_message_width: Option<f32>,
+    pub line_spacing: f32,
+    pub avatars: crate::avatar::Avatars,
+    /// Short names shown in message headers instead of the derived ones, keyed by model
+    pub model_aliases: BTreeMap<String, String>,
+    pub model_profiles: ModelProfiles,
+    /// Show consecutive messages from the same author under one header
+    pub group_messages: bool,
+    pub plain_paste: crate::plain_text::PlainPaste,
+    /// Folder shared with a file-sync tool that chats are kept in
+    pub sync_folder: Option<std::path::PathBuf>,
+    pub timeouts: crate::timeouts::RequestTimeouts,
+    /// Called when an answer is done, keyed by the sync id of the chat. Kept
+    /// here rather than in the chat, so synced chats can't bring commands along
+    pub chat_hooks: BTreeMap<String, crate::hooks::CompletionHook>,
+    /// Script plugins that are turned off, by file name
+    pub disabled_plugins: BTreeSet<String>,
+    /// Give new chats an emoji matching their first prompt
+    pub title_emoji: bool,
+    /// Show what a request includes and confirm it before it's sent
+    pub preview_requests: bool,
+    /// Ask before sending requests estimated to cost more than this many dollars
+    pub cost_warning: Option<f64>,
+    /// Stop the answer of a chat when switching to another one
+    pub stop_on_chat_switch: bool,
+    /// Keep the app running minimized until answers are done when it's closed
+    pub finish_before_close: bool,
+    /// Probe the API host and block sending while it can't be reached
+    pub check_connection: bool,
+    /// Language answers should be in, chats can override it
+    pub response_language: Option<String>,
+    /// New files in this folder are offered for attaching above the chatbox
+    pub watch_folder: Option<std::path::PathBuf>,
+    pub backup: crate::backup::BackupSettings,
+    pub frame_pacing: crate::frame_pacing::FramePacing,
+    /// Secrets left out of imported settings, by label, until the user fills them in
+    #[serde(skip)]
+    missing_secrets: Vec<String>,
 }
 
 impl Default for Settings {
@@ -542,6 +1027,47 @@ impl Default for Settings {
             use_streaming: true,
             include_thoughts_in_history: false,
             proxy_path: None,
+            endpoint: crate::endpoint::Endpoint::default(),
+            sidebar_density: SidebarDensity::default(),
+            always_on_top: false,
+            autostart: false,
+            start_minimized: false,
+            quick_ask_enabled: false,
+            quick_ask_hotkey: crate::quick_ask::DEFAULT_SHORTCUT.to_owned(),
+            quick_ask_instruction: "Explain this:".to_owned(),
+            check_for_updates: false,
+            usage_report_format: crate::analytics::UsageReportFormat::default(),
+            system_presets: Vec::new(),
+            suggestion_cards: SuggestionCard::defaults(),
+            http_tools: Vec::new(),
+            ocr: crate::ocr::OcrSettings::default(),
+            strip_image_metadata: false,
+            compression: crate::file_handler::Compression::default(),
+            sound_cues: crate::sound::SoundCues::default(),
+            response_filters: Vec::new(),
+            do_not_disturb: crate::dnd::DoNotDisturb::default(),
+            max_message_width: None,
+            line_spacing: 1.0,
+            avatars: crate::avatar::Avatars::default(),
+            model_aliases: BTreeMap::new(),
+            model_profiles: ModelProfiles::default(),
+            group_messages: true,
+            plain_paste: crate::plain_text::PlainPaste::default(),
+            sync_folder: None,
+            timeouts: crate::timeouts::RequestTimeouts::default(),
+            chat_hooks: BTreeMap::new(),
+            disabled_plugins: BTreeSet::new(),
+            title_emoji: true,
+            preview_requests: false,
+            cost_warning: None,
+            stop_on_chat_switch: false,
+            finish_before_close: false,
+            check_connection: true,
+            response_language: None,
+            watch_folder: None,
+            backup: crate::backup::BackupSettings::default(),
+            frame_pacing: crate::frame_pacing::FramePacing::default(),
+            missing_secrets: Vec::new(),
         }
     }
 }
@@ -570,6 +1096,118 @@ impl Settings {
         });
     }
 
+    pub fn conversion_options(&self) -> crate::file_handler::ConversionOptions {
+        crate::file_handler::ConversionOptions {
+            strip_metadata: self.strip_image_metadata,
+            compression: self.compression,
+        }
+    }
+
+    /// Labeled fields that may hold credentials: the API key, a proxy URL with
+    /// a password, and API and HTTP tool headers like `Authorization`
+    fn secrets_mut(&mut self) -> Vec<(String, &mut String)> {
+        let mut secrets = vec![("API key".to_owned(), &mut self.api_key)];
+        if let Some(proxy) = self
+            .proxy_path
+            .as_mut()
+            .filter(|proxy| proxy.contains('@') || proxy.as_str() == REDACTED)
+        {
+            secrets.push(("Proxy URL".to_owned(), proxy));
+        }
+        for (name, value) in &mut self.endpoint.headers {
+            if crate::http_tools::is_secret_header(name) {
+                secrets.push((format!("{name} API header"), value));
+            }
+        }
+        for tool in &mut self.http_tools {
+            for (name, value) in &mut tool.headers {
+                if crate::http_tools::is_secret_header(name) {
+                    secrets.push((format!("{} header of the {} tool", name, tool.name), value));
+                }
+            }
+        }
+        for (label, value) in self.backup.secrets_mut() {
+            secrets.push((label.to_owned(), value));
+        }
+        secrets
+    }
+
+    /// Copy for exporting, with secrets replaced by a placeholder
+    fn redacted(&self) -> Self {
+        let mut settings = self.clone();
+        for (_, value) in settings.secrets_mut() {
+            if !value.is_empty() {
+                *value = REDACTED.to_owned();
+            }
+        }
+        settings
+    }
+
+    /// Fills the secrets left out of imported settings from `current`, the
+    /// ones it doesn't have are asked for on the settings page
+    pub fn restore_secrets(&mut self, current: &Self) {
+        let mut current = current.clone();
+        let known: BTreeMap<String, String> = current
+            .secrets_mut()
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty() && value.as_str() != REDACTED)
+            .map(|(label, value)| (label, value.clone()))
+            .collect();
+        let mut missing = Vec::new();
+        for (label, value) in self.secrets_mut() {
+            if value.as_str() != REDACTED {
+                continue;
+            }
+            match known.get(&label) {
+                Some(known) => *value = known.clone(),
+                None => {
+                    value.clear();
+                    missing.push(label);
+                }
+            }
+        }
+        self.missing_secrets = missing;
+    }
+
+    /// Keeps the completion hooks of `current` in place of imported ones, a
+    /// settings file from someone else must not bring commands to run.
+    /// Returns whether imported hooks were dropped.
+    pub fn keep_local_hooks(&mut self, current: &Self) -> bool {
+        let dropped = self.chat_hooks != current.chat_hooks;
+        self.chat_hooks = current.chat_hooks.clone();
+        dropped
+    }
+
+    fn show_missing_secrets(&mut self, ui: &mut egui::Ui) {
+        if self.missing_secrets.is_empty() {
+            return;
+        }
+        let missing = self.missing_secrets.clone();
+        let mut done = false;
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "⚠ The imported settings were saved without secrets, enter them again",
+            );
+            egui::Grid::new("missing_secrets_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    for (label, value) in self.secrets_mut() {
+                        if missing.contains(&label) {
+                            ui.label(label);
+                            ui.add(egui::TextEdit::singleline(value).password(true));
+                            ui.end_row();
+                        }
+                    }
+                });
+            done = ui.button("Done").clicked();
+        });
+        if done {
+            self.missing_secrets.clear();
+        }
+        ui.add_space(8.0);
+    }
+
     async fn ask_save_settings(settings: Self) {
         let Some(file) = rfd::AsyncFileDialog::new()
             .add_filter("JSON file", &["json"])
@@ -590,10 +1228,239 @@ impl Settings {
             .map_err(|e| log::error!("failed to save settings: {e}"));
     }
 
+    fn show_system_presets<R>(&mut self, ui: &mut egui::Ui, request_info: &mut R)
+    where
+        R: FnMut(RequestInfoType),
+    {
+        ui.heading("System Instructions");
+        ui.label("Presets that can be applied to any chat from its header");
+        ui.weak(PRESET_VARIABLES_HELP);
+        let mut to_remove = None;
+        for (i, preset) in self.system_presets.iter_mut().enumerate() {
+            let title = if preset.name.is_empty() {
+                "Untitled"
+            } else {
+                preset.name.as_str()
+            };
+            egui::CollapsingHeader::new(title)
+                .id_salt(("system_preset", i))
+                .show(ui, |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut preset.name).hint_text("Name"));
+                    ui.add(
+                        egui::TextEdit::multiline(&mut preset.text)
+                            .hint_text(TEMPLATE_HINT_TEXT)
+                            .desired_rows(3),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Update chats")
+                            .on_hover_text(
+                                "Replace the system prompt of every chat using this preset",
+                            )
+                            .clicked()
+                        {
+                            request_info(RequestInfoType::PropagateSystemPreset(preset.clone()));
+                        }
+                        if ui.button("🗑 Remove").clicked() {
+                            to_remove = Some(i);
+                        }
+                    });
+                });
+        }
+        if let Some(i) = to_remove {
+            self.system_presets.remove(i);
+        }
+        if ui.button("➕ Add preset").clicked() {
+            self.system_presets.push(SystemPreset {
+                name: format!("Preset {}", self.system_presets.len() + 1),
+                text: String::new(),
+            });
+        }
+    }
+
+    fn show_suggestion_cards(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Suggestions");
+        ui.label("Cards on the empty chat screen, clicking one sends its prompt");
+        let mut to_remove = None;
+        for (i, card) in self.suggestion_cards.iter_mut().enumerate() {
+            let title = if card.title.is_empty() {
+                "Untitled"
+            } else {
+                card.title.as_str()
+            };
+            egui::CollapsingHeader::new(title)
+                .id_salt(("suggestion_card", i))
+                .show(ui, |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut card.title).hint_text("Title"));
+                    ui.add(egui::TextEdit::singleline(&mut card.subtitle).hint_text("Subtitle"));
+                    ui.add(
+                        egui::TextEdit::multiline(&mut card.prompt)
+                            .hint_text("Prompt, the title and subtitle if empty")
+                            .desired_rows(2),
+                    );
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt(("suggestion_card_model", i))
+                            .selected_text(
+                                card.model
+                                    .map_or("Chat's model".to_owned(), |m| m.to_string()),
+                            )
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut card.model, None, "Chat's model");
+                                for model in enum_iterator::all::<GeminiModel>() {
+                                    ui.selectable_value(
+                                        &mut card.model,
+                                        Some(model),
+                                        model.to_string(),
+                                    );
+                                }
+                            });
+                        if ui.button("🗑 Remove").clicked() {
+                            to_remove = Some(i);
+                        }
+                    });
+                });
+        }
+        if let Some(i) = to_remove {
+            self.suggestion_cards.remove(i);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("➕ Add card").clicked() {
+                self.suggestion_cards.push(SuggestionCard {
+                    title: format!("Card {}", self.suggestion_cards.len() + 1),
+                    ..Default::default()
+                });
+            }
+            if ui.button("Reset").clicked() {
+                self.suggestion_cards = SuggestionCard::defaults();
+            }
+        });
+    }
+
+    fn show_http_tools(&mut self, ui: &mut egui::Ui) {
+        use crate::http_tools::{is_valid_name, HttpMethod, HttpTool};
+
+        ui.heading("Tools");
+        ui.label(
+            "REST endpoints the model can call. Use {argument} in the URL and headers, \
+            other arguments are sent as the query (GET, DELETE) or a JSON body.",
+        );
+        let mut to_remove = None;
+        for (i, tool) in self.http_tools.iter_mut().enumerate() {
+            let title = if tool.name.is_empty() {
+                "Unnamed tool"
+            } else {
+                tool.name.as_str()
+            };
+            egui::CollapsingHeader::new(title)
+                .id_salt(("http_tool", i))
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(toggle(&mut tool.enabled));
+                        ui.label("Enabled");
+                    });
+                    ui.horizontal(|ui| {
+                        let mut ask = tool.asks_before_calling();
+                        if ui.add(toggle(&mut ask)).changed() {
+                            tool.ask_before_calling = Some(ask);
+                        }
+                        help(ui, "Show the arguments the model calls the tool with, and run it only once you allow it. Turning it off lets the model send anything from the chat to the tool without asking", |ui| {
+                            ui.label("Ask before calling");
+                        });
+                    });
+                    egui::Grid::new(("http_tool_grid", i))
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Name");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut tool.name).hint_text("get_weather"),
+                            );
+                            ui.end_row();
+
+                            ui.label("Description");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut tool.description)
+                                    .hint_text("What it does and when to use it"),
+                            );
+                            ui.end_row();
+
+                            ui.label("Request");
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_salt(("http_tool_method", i))
+                                    .width(72.0)
+                                    .selected_text(tool.method.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for method in HttpMethod::ALL {
+                                            ui.selectable_value(
+                                                &mut tool.method,
+                                                method,
+                                                method.to_string(),
+                                            );
+                                        }
+                                    });
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut tool.url)
+                                        .hint_text("https://example.com/weather/{city}"),
+                                );
+                            });
+                            ui.end_row();
+                        });
+
+                    ui.label("Headers");
+                    let mut header_to_remove = None;
+                    for (j, (key, value)) in tool.headers.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(key)
+                                    .desired_width(120.0)
+                                    .hint_text("Header"),
+                            );
+                            ui.add(egui::TextEdit::singleline(value).hint_text("Value"));
+                            if ui.small_button("🗑").clicked() {
+                                header_to_remove = Some(j);
+                            }
+                        });
+                    }
+                    if let Some(j) = header_to_remove {
+                        tool.headers.remove(j);
+                    }
+                    if ui.small_button("➕ Add header").clicked() {
+                        tool.headers.push(Default::default());
+                    }
+
+                    ui.label("Arguments (JSON schema)");
+                    ui.add(
+                        egui::TextEdit::multiline(&mut tool.parameters)
+                            .code_editor()
+                            .desired_rows(4),
+                    );
+                    if let Err(e) = tool.parsed_parameters() {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("{e:#}"));
+                    }
+                    if !is_valid_name(&tool.name) {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            "The name may only contain letters, digits, _ and -",
+                        );
+                    }
+                    if ui.button("🗑 Remove tool").clicked() {
+                        to_remove = Some(i);
+                    }
+                });
+        }
+        if let Some(i) = to_remove {
+            self.http_tools.remove(i);
+        }
+        if ui.button("➕ Add tool").clicked() {
+            self.http_tools.push(HttpTool::default());
+        }
+    }
+
     pub fn show<R>(&mut self, ui: &mut egui::Ui, request_info: &mut R, modal: &Modal)
     where
         R: FnMut(RequestInfoType),
     {
+        self.show_missing_secrets(ui);
+
         ui.heading("Gemini API");
         ui.label("Connection settings");
         egui::Grid::new("settings_grid")
@@ -621,7 +1488,15 @@ impl Settings {
             });
         });
         ui.add_space(2.0);
-        self.model_picker.show(ui, request_info);
+        self.model_picker
+            .show(ui, &mut self.model_profiles, request_info);
+        self.model_profiles.show(ui);
+
+        ui.separator();
+        self.show_system_presets(ui, request_info);
+
+        ui.separator();
+        self.show_suggestion_cards(ui);
 
         ui.separator();
         ui.heading("Behavior");
@@ -631,12 +1506,481 @@ impl Settings {
                 ui.label("Stream response");
             });
         });
+        ui.horizontal(|ui| {
+            help(ui, "How long to wait for the response to start, and then for each next part of it. Requests that take longer fail with a timeout error", |ui| {
+                ui.label("Timeouts");
+            });
+            ui.add(
+                egui::DragValue::new(&mut self.timeouts.connect_secs)
+                    .range(1..=600)
+                    .suffix(" s to connect"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.timeouts.read_secs)
+                    .range(1..=3600)
+                    .suffix(" s to read"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.title_emoji));
+            help(ui, "When a chat gets its title from the first prompt, it also gets an emoji picked from the words in it. Chats with an icon set keep it", |ui| {
+                ui.label("Emoji in chat titles");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.stop_on_chat_switch));
+            help(ui, "A chat keeps generating in the background after switching to another one, unless this is on. The text received before stopping is kept", |ui| {
+                ui.label("Stop answers when switching chats");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.finish_before_close));
+            help(ui, "Closing the app stops the answers still being generated, unless this is on. Then the app stays open minimized and closes once they're done", |ui| {
+                ui.label("Finish answers before closing");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.preview_requests));
+            help(ui, "Before a request is sent, show the system prompt, messages and attachments it includes, and leave some out with a click", |ui| {
+                ui.label("Preview requests");
+            });
+        });
+        let mut warn_cost = self.cost_warning.is_some();
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut warn_cost));
+            help(ui, "The estimate next to the chatbox uses rough token counts and the paid tier prices. Free models are never held back", |ui| {
+                ui.label("Ask before expensive requests");
+            });
+            if warn_cost {
+                let limit = self.cost_warning.get_or_insert(0.10);
+                ui.add(
+                    egui::DragValue::new(limit)
+                        .speed(0.01)
+                        .range(0.001..=100.0)
+                        .prefix("over $"),
+                );
+            } else {
+                self.cost_warning = None;
+            }
+        });
         ui.horizontal(|ui| {
             ui.add(toggle(&mut self.include_thoughts_in_history));
             help(ui, "When enabled, the model's 'thought' parts are appended to the session context for subsequent requests. Warning: This will rapidly increase token consumption", |ui| {
                 ui.label("Persist Thoughts in Context");
             });
         });
+        ui.horizontal(|ui| {
+            help(ui, "Ask the model to always answer in this language, whatever language you write in. Chats can pick their own language in the header", |ui| {
+                ui.label("Answer in");
+            });
+            crate::translator::response_language_picker(
+                ui,
+                "response_language",
+                &mut self.response_language,
+                "Any language",
+            );
+        });
+
+        ui.separator();
+        ui.heading("Response Filters");
+        crate::filters::show(ui, &mut self.response_filters);
+
+        ui.separator();
+        ui.heading("Privacy");
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.strip_image_metadata));
+            help(ui, "Remove EXIF data like GPS location and camera details from attached images before they are uploaded. Parts of an image can also be masked with the ▩ button on the attachment", |ui| {
+                ui.label("Strip image metadata");
+            });
+        });
+
+        ui.separator();
+        ui.heading("Pasting");
+        ui.label(format!(
+            "{} pastes as plain text",
+            ui.ctx()
+                .format_shortcut(&crate::plain_text::PASTE_PLAIN_SHORTCUT)
+        ));
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.plain_paste.always));
+            help(
+                ui,
+                "Clean up every paste into the chat box, not only with the shortcut",
+                |ui| {
+                    ui.label("Always paste as plain text");
+                },
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.plain_paste.strip_markdown));
+            help(ui, "Remove headings, emphasis, links and other markdown, keeping the text. Code blocks are kept", |ui| {
+                ui.label("Strip formatting");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.plain_paste.normalize_punctuation));
+            help(ui, "Replace smart quotes, long dashes, ellipses and non-breaking spaces with plain characters. Invisible characters like zero-width spaces are always removed", |ui| {
+                ui.label("Normalize punctuation");
+            });
+        });
+
+        #[cfg(feature = "scripting")]
+        {
+            ui.separator();
+            ui.heading("Plugins");
+            crate::plugins::show_manager(ui, &mut self.disabled_plugins);
+        }
+
+        ui.separator();
+        ui.heading("Watched Folder");
+        if let Some(folder) = crate::watch_folder::take_picked_folder() {
+            self.watch_folder = Some(folder);
+        }
+        let mut watch = self.watch_folder.is_some();
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut watch));
+            help(ui, "Files that appear in this folder, like new screenshots, are shown above the chatbox to be attached with one click", |ui| {
+                ui.label("Watch a folder for new files");
+            });
+        });
+        if !watch {
+            self.watch_folder = None;
+        } else if let Some(folder) = &mut self.watch_folder {
+            ui.horizontal(|ui| {
+                let mut path = folder.display().to_string();
+                if ui.text_edit_singleline(&mut path).changed() {
+                    *folder = path.into();
+                }
+                if ui.button("📁 Browse…").clicked() {
+                    crate::watch_folder::pick_folder();
+                }
+            });
+        } else {
+            self.watch_folder = Some(Default::default());
+            crate::watch_folder::pick_folder();
+        }
+
+        ui.separator();
+        ui.heading("Attachment Compression");
+        ui.label("Shrink attachments before upload, the savings are shown on each attachment");
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.compression.compress_images));
+            help(ui, "Downscale large images and send them as JPEG (PNG if they have transparency). Without this, images are sent at their original resolution", |ui| {
+                ui.label("Compress images");
+            });
+        });
+        ui.add_enabled_ui(self.compression.compress_images, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.compression.max_image_dimension, 256..=4096)
+                    .text("Max size (px)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.compression.jpeg_quality, 30..=100)
+                    .text("JPEG quality"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.compression.reencode_audio));
+            help(
+                ui,
+                "Convert audio to mono Opus with ffmpeg (must be installed)",
+                |ui| {
+                    ui.label("Re-encode audio");
+                },
+            );
+        });
+        ui.add_enabled(
+            self.compression.reencode_audio,
+            egui::Slider::new(&mut self.compression.audio_bitrate_kbps, 16..=128)
+                .text("Bitrate (kbps)"),
+        );
+
+        ui.separator();
+        ui.heading("Text Recognition");
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.ocr.enabled));
+            help(ui, "Read the text in attached images locally with Tesseract (must be installed) and send it along with the image", |ui| {
+                ui.label("Recognize text in images");
+            });
+        });
+        ui.add_enabled_ui(self.ocr.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Languages");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.ocr.languages)
+                        .desired_width(120.0)
+                        .hint_text("eng+deu"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.add(toggle(&mut self.ocr.text_only));
+                help(ui, "Send only the recognized text instead of the image, which uses fewer tokens. Images without text are still sent", |ui| {
+                    ui.label("Send text only");
+                });
+            });
+        });
+
+        ui.separator();
+        ui.heading("Sounds");
+        if !cfg!(feature = "audio") {
+            ui.weak("This build has no audio support, rebuild with `--features audio`");
+        }
+        ui.add_enabled_ui(cfg!(feature = "audio"), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(toggle(&mut self.sound_cues.on_complete));
+                ui.label("Play a sound when a response is ready");
+                if ui.small_button("▶").on_hover_text("Test").clicked() {
+                    crate::sound::play(crate::sound::Cue::Completed, self.sound_cues.volume);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(toggle(&mut self.sound_cues.on_error));
+                ui.label("Play a sound when a request fails");
+                if ui.small_button("▶").on_hover_text("Test").clicked() {
+                    crate::sound::play(crate::sound::Cue::Failed, self.sound_cues.volume);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(toggle(&mut self.sound_cues.only_in_background));
+                help(ui, "Stay quiet while the window is focused", |ui| {
+                    ui.label("Only in the background");
+                });
+            });
+            ui.add(
+                egui::Slider::new(&mut self.sound_cues.volume, 0.0..=1.0)
+                    .text("Volume")
+                    .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+            );
+        });
+
+        ui.separator();
+        ui.heading("Do Not Disturb");
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.do_not_disturb.enabled));
+            help(ui, "Hold back toasts and sounds until it's turned off, the held toasts show up then. Also in the sidebar footer", |ui| {
+                ui.label("Do not disturb");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.do_not_disturb.quiet_hours));
+            ui.label("Quiet hours from");
+            ui.add(
+                egui::DragValue::new(&mut self.do_not_disturb.start_hour)
+                    .range(0..=23)
+                    .suffix(":00"),
+            );
+            ui.label("to");
+            ui.add(
+                egui::DragValue::new(&mut self.do_not_disturb.end_hour)
+                    .range(0..=23)
+                    .suffix(":00"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.do_not_disturb.when_fullscreen));
+            help(ui, "Stay quiet while a game, a video or a presentation is fullscreen. Works on Windows and on X11", |ui| {
+                ui.label("While another app is fullscreen");
+            });
+        });
+
+        ui.separator();
+        ui.heading("Appearance");
+        crate::theme::show_settings(ui);
+        egui::ComboBox::from_label("Sidebar density")
+            .selected_text(self.sidebar_density.to_string())
+            .show_ui(ui, |ui| {
+                for density in SidebarDensity::ALL {
+                    ui.selectable_value(&mut self.sidebar_density, density, density.to_string());
+                }
+            });
+        let mut limit_width = self.max_message_width.is_some();
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut limit_width));
+            help(
+                ui,
+                "Center the conversation in a column instead of stretching it across wide windows",
+                |ui| {
+                    ui.label("Limit message width");
+                },
+            );
+        });
+        if limit_width {
+            let width = self.max_message_width.get_or_insert(900.0);
+            ui.add(egui::Slider::new(width, 400.0..=1600.0).text("Width (px)"));
+        } else {
+            self.max_message_width = None;
+        }
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.group_messages));
+            help(ui, "Show consecutive messages from the same author, like thoughts and the answer after them, under a single header", |ui| {
+                ui.label("Group consecutive messages");
+            });
+        });
+        ui.add(
+            egui::Slider::new(&mut self.line_spacing, 0.5..=4.0)
+                .text("Line spacing")
+                .fixed_decimals(1),
+        );
+        ui.add_space(4.0);
+        ui.label("Avatars");
+        self.avatars.show(ui);
+        ui.add_space(4.0);
+        egui::CollapsingHeader::new("Model names").show(ui, |ui| {
+            ui.weak("Names shown in message headers, leave empty to use the default");
+            egui::Grid::new("model_aliases_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    for model in enum_iterator::all::<GeminiModel>() {
+                        let name = model.to_string();
+                        ui.label(&name);
+                        let mut alias = self.model_aliases.get(&name).cloned().unwrap_or_default();
+                        let hint = crate::chat::make_short_name(&name);
+                        if ui
+                            .add(egui::TextEdit::singleline(&mut alias).hint_text(hint))
+                            .changed()
+                        {
+                            if alias.is_empty() {
+                                self.model_aliases.remove(&name);
+                            } else {
+                                self.model_aliases.insert(name, alias);
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+        ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.always_on_top));
+            help(ui, "Keep the window above all other windows", |ui| {
+                ui.label("Always on top");
+            });
+        });
+
+        ui.separator();
+        ui.heading("Sync");
+        if let Some(folder) = crate::folder_sync::take_picked_folder() {
+            self.sync_folder = Some(folder);
+        }
+        let mut sync = self.sync_folder.is_some();
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut sync));
+            help(ui, "Write every chat to its own file in a folder shared with Syncthing, Dropbox or a similar tool. Chats changed on other machines are merged when the app starts, and if a chat was changed on two machines both versions are kept. Attachments are not synced", |ui| {
+                ui.label("Sync chats through a folder");
+            });
+        });
+        if !sync {
+            self.sync_folder = None;
+        } else if let Some(folder) = &mut self.sync_folder {
+            ui.horizontal(|ui| {
+                let mut path = folder.display().to_string();
+                if ui.text_edit_singleline(&mut path).changed() {
+                    *folder = path.into();
+                }
+                if ui.button("📁 Browse…").clicked() {
+                    crate::folder_sync::pick_folder();
+                }
+            });
+        } else {
+            self.sync_folder = Some(Default::default());
+            crate::folder_sync::pick_folder();
+        }
+
+        ui.separator();
+        ui.heading("Remote Backup");
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.backup.scheduled));
+            help(ui, "Upload an encrypted copy of all chats to a WebDAV server or an S3-compatible bucket on a schedule. Backups can be made by hand and restored in the Backups tool. Without the passphrase they can't be read, not even by the server", |ui| {
+                ui.label("Scheduled backups");
+            });
+        });
+        self.backup.show(ui);
+
+        ui.separator();
+        ui.heading("Performance");
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.frame_pacing.power_saver));
+            help(ui, "Redraw less often while a response is streamed and when the window is in the background. Saves battery on laptops, at the cost of choppier animations", |ui| {
+                ui.label("Power saver");
+            });
+        });
+        ui.add(
+            egui::Slider::new(&mut self.frame_pacing.max_fps, 5..=144)
+                .text("Frame rate limit while busy"),
+        );
+
+        ui.separator();
+        ui.heading("Startup");
+        ui.horizontal(|ui| {
+            if ui.add(toggle(&mut self.autostart)).changed() {
+                if let Err(e) = crate::autostart::set_enabled(self.autostart) {
+                    log::error!("failed to update autostart entry: {e:#}");
+                    self.autostart = !self.autostart;
+                }
+            }
+            help(ui, "Launch the app automatically when you log in", |ui| {
+                ui.label("Start with the system");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.check_for_updates));
+            help(ui, "Look for a newer release on GitHub when the app starts. Nothing is sent except the request itself", |ui| {
+                ui.label("Check for updates");
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.start_minimized));
+            help(
+                ui,
+                "Minimize the window to the taskbar right after launch",
+                |ui| {
+                    ui.label("Start minimized");
+                },
+            );
+        });
+
+        ui.separator();
+        ui.heading("Quick Ask");
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.quick_ask_enabled));
+            help(ui, "Press the shortcut below anywhere to send the text on the clipboard to the \"Quick ask\" chat with the instruction below. The first line of the answer is shown as a notification. The clipboard is only read when the shortcut is pressed", |ui| {
+                ui.label("Ask about copied text");
+            });
+        });
+        ui.add_enabled_ui(self.quick_ask_enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Shortcut");
+                // applied once editing is done, half-typed shortcuts would fail to register
+                let id = egui::Id::new("quick_ask_hotkey");
+                let mut shortcut = ui
+                    .data(|d| d.get_temp::<String>(id))
+                    .unwrap_or_else(|| self.quick_ask_hotkey.clone());
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut shortcut)
+                        .hint_text(crate::quick_ask::DEFAULT_SHORTCUT)
+                        .desired_width(140.0),
+                );
+                if response.lost_focus() {
+                    self.quick_ask_hotkey = shortcut;
+                    ui.data_mut(|d| d.remove::<String>(id));
+                } else if response.has_focus() {
+                    ui.data_mut(|d| d.insert_temp(id, shortcut));
+                }
+            })
+            .response
+            .on_hover_text(
+                "Modifiers and a key joined with +, like ctrl+alt+KeyG or shift+super+Space",
+            );
+        });
+        ui.add_enabled(
+            self.quick_ask_enabled,
+            egui::TextEdit::singleline(&mut self.quick_ask_instruction)
+                .hint_text("Instruction sent before the copied text"),
+        );
+
+        ui.separator();
+        self.show_http_tools(ui);
 
         // ui.end_row();
         ui.separator();
@@ -662,6 +2006,60 @@ impl Settings {
             );
         }
 
+        ui.collapsing("API endpoint", |ui| {
+            ui.label(
+                "For corporate gateways and API-compatible mirrors. Requests go to the base URL \
+                instead of the Gemini API, with the headers below added.",
+            );
+            self.endpoint.show(ui);
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(toggle(&mut self.check_connection));
+            help(ui, "Check in the background whether the Gemini API (or the proxy) can be reached, and show a banner instead of sending while it can't", |ui| {
+                ui.label("Detect when offline");
+            });
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Register").clicked() {
+                request_info(RequestInfoType::RegisterLinkHandler);
+            }
+            help(
+                ui,
+                "Open `egeminui://new?prompt=...` links from browsers and scripts in this app. \
+                The prompt is only put into a new chat, it's sent when you press Send",
+                |ui| {
+                    ui.label("Link handler");
+                },
+            );
+        });
+
+        ui.label("Export a local usage report (models, estimated tokens, response times)");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("usage_report_format")
+                .selected_text(self.usage_report_format.to_string())
+                .show_ui(ui, |ui| {
+                    for format in crate::analytics::UsageReportFormat::ALL {
+                        ui.selectable_value(
+                            &mut self.usage_report_format,
+                            format,
+                            format.to_string(),
+                        );
+                    }
+                });
+            if ui.button("Export…").clicked() {
+                request_info(RequestInfoType::ExportUsageReport(self.usage_report_format));
+            }
+            help(
+                ui,
+                "The report is only written to the file you pick, nothing is sent anywhere",
+                |ui| {
+                    ui.label("Usage report");
+                },
+            );
+        });
+
         ui.label("Reset global settings to defaults");
         if ui.button("Reset").clicked() {
             modal.open();
@@ -669,8 +2067,14 @@ impl Settings {
 
         ui.label("Save and load settings as JSON");
         ui.horizontal(|ui| {
+            let include_secrets_id = ui.id().with("export_secrets");
+            let mut include_secrets = ui.data(|d| d.get_temp(include_secrets_id).unwrap_or(false));
             if ui.button("Save").clicked() {
-                let settings = self.clone();
+                let settings = if include_secrets {
+                    self.clone()
+                } else {
+                    self.redacted()
+                };
                 tokio::spawn(async move {
                     Self::ask_save_settings(settings).await;
                 });
@@ -678,6 +2082,9 @@ impl Settings {
             if ui.button("Load").clicked() {
                 request_info(RequestInfoType::LoadSettings);
             }
+            ui.checkbox(&mut include_secrets, "Include secrets")
+                .on_hover_text("Save the API key, proxy password and HTTP tool credentials in plain text. Without this, they are asked for when the file is loaded");
+            ui.data_mut(|d| d.insert_temp(include_secrets_id, include_secrets));
         });
     }
 }